@@ -0,0 +1,84 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::sync::Arc;
+
+use crate::{
+    AppService, ArcAssembleSourcesUseCase, ArcCompleteUploadUseCase,
+    ArcConfigureAppUseCase, ArcCreateAppUseCase, ArcDeployAppUseCase,
+    ArcExpireUploadsUseCase, ArcInitiateUploadUseCase,
+    ArcMissingSourcesUseCase, ArcShowAppUseCase, ArcShowStageUseCase,
+    ArcShowUploadUseCase, ArcStageAppUseCase, ArcStageLogsUseCase,
+    ArcUploadAppUseCase, ArcUploadPartUseCase, ArcUploadSourceUseCase,
+    OutArchivePort, OutBlobStorePort, OutKubernetesPort, OutUploadStorePort,
+};
+
+#[derive(Clone)]
+pub struct AppApplication {
+    pub create_app: ArcCreateAppUseCase,
+    pub configure_app: ArcConfigureAppUseCase,
+    pub show_app: ArcShowAppUseCase,
+    pub deploy_app: ArcDeployAppUseCase,
+    pub upload_app: ArcUploadAppUseCase,
+    pub initiate_upload: ArcInitiateUploadUseCase,
+    pub upload_part: ArcUploadPartUseCase,
+    pub show_upload: ArcShowUploadUseCase,
+    pub complete_upload: ArcCompleteUploadUseCase,
+    pub expire_uploads: ArcExpireUploadsUseCase,
+    pub missing_sources: ArcMissingSourcesUseCase,
+    pub upload_source: ArcUploadSourceUseCase,
+    pub assemble_sources: ArcAssembleSourcesUseCase,
+    pub stage_app: ArcStageAppUseCase,
+    pub show_stage: ArcShowStageUseCase,
+    pub stage_logs: ArcStageLogsUseCase,
+}
+
+impl AppApplication {
+    /// Staged images are pushed to registry
+    pub fn new(
+        kubernetes_port: OutKubernetesPort,
+        blob_store_port: OutBlobStorePort,
+        archive_port: OutArchivePort,
+        upload_store_port: OutUploadStorePort,
+        registry: impl Into<String>,
+    ) -> Self {
+        let app_service = Arc::new(
+            AppService::new(
+                kubernetes_port,
+                blob_store_port,
+                archive_port,
+                upload_store_port,
+            )
+            .with_registry(registry),
+        );
+        Self {
+            create_app: app_service.clone(),
+            configure_app: app_service.clone(),
+            show_app: app_service.clone(),
+            deploy_app: app_service.clone(),
+            upload_app: app_service.clone(),
+            initiate_upload: app_service.clone(),
+            upload_part: app_service.clone(),
+            show_upload: app_service.clone(),
+            complete_upload: app_service.clone(),
+            expire_uploads: app_service.clone(),
+            missing_sources: app_service.clone(),
+            upload_source: app_service.clone(),
+            assemble_sources: app_service.clone(),
+            stage_app: app_service.clone(),
+            show_stage: app_service.clone(),
+            stage_logs: app_service,
+        }
+    }
+}
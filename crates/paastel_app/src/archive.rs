@@ -0,0 +1,167 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{fmt::Display, str::FromStr};
+
+use derive_new::new;
+
+use crate::Error;
+
+/// Format of uploaded source archive
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArchiveFormat {
+    #[default]
+    Zip,
+    /// Tar compressed with gzip
+    TarGz,
+    /// Tar compressed with zstandard
+    TarZst,
+}
+
+impl ArchiveFormat {
+    /// Every format accepted, preferred first
+    pub const ALL: [ArchiveFormat; 3] = [Self::TarZst, Self::TarGz, Self::Zip];
+
+    /// Media type sent as `Content-Type` of upload
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Self::Zip => "application/zip",
+            Self::TarGz => "application/gzip",
+            Self::TarZst => "application/zstd",
+        }
+    }
+
+    pub fn from_media_type(media_type: &str) -> crate::Result<Self> {
+        let essence = media_type.split(';').next().unwrap_or_default().trim();
+        Self::ALL
+            .into_iter()
+            .find(|format| format.media_type().eq_ignore_ascii_case(essence))
+            .ok_or_else(|| {
+                Error::DomainError(format!(
+                    "`content-type` {media_type} is not a supported archive"
+                ))
+            })
+    }
+
+    /// Format from leading bytes of archive, ex: `PK\x03\x04` for zip
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        match magic {
+            [b'P', b'K', 0x03, 0x04, ..] | [b'P', b'K', 0x05, 0x06, ..] => {
+                Some(Self::Zip)
+            }
+            [0x1f, 0x8b, ..] => Some(Self::TarGz),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Self::TarZst),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = Error;
+
+    fn from_str(value: &str) -> crate::Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "zip" => Ok(Self::Zip),
+            "tar.gz" | "tgz" => Ok(Self::TarGz),
+            "tar.zst" => Ok(Self::TarZst),
+            _ => Err(Error::DomainError(format!(
+                "`format` {value} is invalid, use zip, tar.gz or tar.zst"
+            ))),
+        }
+    }
+}
+
+impl AsRef<str> for ArchiveFormat {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Zip => "zip",
+            Self::TarGz => "tar.gz",
+            Self::TarZst => "tar.zst",
+        }
+    }
+}
+
+impl Display for ArchiveFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+/// Content of a validated archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, new)]
+pub struct ArchiveInfo {
+    format: ArchiveFormat,
+    /// Number of entries, directories included
+    entries: u64,
+    /// Size of content once unpacked
+    unpacked_size: u64,
+}
+
+impl ArchiveInfo {
+    pub fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    pub fn unpacked_size(&self) -> u64 {
+        self.unpacked_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archive_format() {
+        for format in ArchiveFormat::ALL {
+            assert_eq!(
+                format.as_ref().parse::<ArchiveFormat>().ok(),
+                Some(format)
+            );
+            assert_eq!(
+                ArchiveFormat::from_media_type(format.media_type()).ok(),
+                Some(format)
+            );
+        }
+        assert!("rar".parse::<ArchiveFormat>().is_err());
+        assert!(ArchiveFormat::from_media_type("text/plain").is_err());
+        assert_eq!(
+            ArchiveFormat::from_media_type("application/zip; charset=binary")
+                .ok(),
+            Some(ArchiveFormat::Zip)
+        );
+    }
+
+    #[test]
+    fn detect_archive_format() {
+        assert_eq!(
+            ArchiveFormat::detect(b"PK\x03\x04rest"),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(
+            ArchiveFormat::detect(&[0x1f, 0x8b, 0x08]),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(
+            ArchiveFormat::detect(&[0x28, 0xb5, 0x2f, 0xfd]),
+            Some(ArchiveFormat::TarZst)
+        );
+        assert_eq!(ArchiveFormat::detect(b"PK"), None);
+        assert_eq!(ArchiveFormat::detect(b"hello"), None);
+    }
+}
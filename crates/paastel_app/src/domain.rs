@@ -0,0 +1,504 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use derive_new::new;
+
+use crate::{ArchiveFormat, Builder, Error};
+
+/// Length of hex encoded SHA-256 digest
+const SHA256_HEX_LENGTH: usize = 64;
+
+/// Check value is a DNS-1123 label, see [`paastel_manifest::check_dns_label`]
+fn check_dns_label(field: &str, value: &str) -> crate::Result<()> {
+    paastel_manifest::check_dns_label(field, value)
+        .map_err(|err| Error::DomainError(err.to_string()))
+}
+
+/// Name of application
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AppName(String);
+
+impl FromStr for AppName {
+    type Err = Error;
+
+    fn from_str(value: &str) -> crate::Result<Self> {
+        check_dns_label("name", value)?;
+        Ok(Self(value.to_string()))
+    }
+}
+
+impl TryFrom<String> for AppName {
+    type Error = Error;
+
+    fn try_from(value: String) -> crate::Result<Self> {
+        value.as_str().parse()
+    }
+}
+
+impl AsRef<str> for AppName {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Display for AppName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Kubernetes namespace where application live
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Namespace(String);
+
+impl FromStr for Namespace {
+    type Err = Error;
+
+    fn from_str(value: &str) -> crate::Result<Self> {
+        check_dns_label("namespace", value)?;
+        Ok(Self(value.to_string()))
+    }
+}
+
+impl TryFrom<String> for Namespace {
+    type Error = Error;
+
+    fn try_from(value: String) -> crate::Result<Self> {
+        value.as_str().parse()
+    }
+}
+
+impl AsRef<str> for Namespace {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Display for Namespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Request to create an application
+#[derive(Debug, Clone, new)]
+pub struct NewApp {
+    name: AppName,
+    namespace: Namespace,
+    /// Username of user creating application
+    created_by: String,
+}
+
+impl NewApp {
+    pub fn name(&self) -> &AppName {
+        &self.name
+    }
+
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    pub fn created_by(&self) -> &str {
+        self.created_by.as_str()
+    }
+}
+
+/// Application registered on cluster
+#[derive(Debug, Clone, PartialEq, Eq, new)]
+pub struct Application {
+    name: AppName,
+    namespace: Namespace,
+    created_by: Option<String>,
+    /// Image deployed, none until a stage is deployed
+    #[new(default)]
+    image: Option<String>,
+    #[new(default)]
+    config: AppConfig,
+    #[new(default)]
+    status: AppStatus,
+}
+
+impl Application {
+    pub fn name(&self) -> &AppName {
+        &self.name
+    }
+
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    pub fn created_by(&self) -> Option<&str> {
+        self.created_by.as_deref()
+    }
+
+    pub fn image(&self) -> Option<&str> {
+        self.image.as_deref()
+    }
+
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    pub fn status(&self) -> &AppStatus {
+        &self.status
+    }
+
+    pub fn with_config(mut self, config: AppConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_image(mut self, image: impl Into<String>) -> Self {
+        self.image = Some(image.into());
+        self
+    }
+
+    pub fn with_status(mut self, status: AppStatus) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+/// How application runs, usually declared on a manifest
+#[derive(Debug, Clone, Default, PartialEq, Eq, new)]
+pub struct AppConfig {
+    /// Number of replicas, default 1
+    instances: Option<i32>,
+    /// Hosts routed to application, default `<app>.<namespace>.<domain>`,
+    /// either `<label>.<domain>` or below `<namespace>.<domain>`
+    routes: Vec<String>,
+    env: BTreeMap<String, String>,
+    /// Configurations bound to application
+    configurations: Vec<String>,
+    /// Builder of stages requested without one, default buildpack
+    #[new(default)]
+    builder: Option<Builder>,
+}
+
+impl AppConfig {
+    pub fn instances(&self) -> Option<i32> {
+        self.instances
+    }
+
+    pub fn routes(&self) -> &[String] {
+        &self.routes
+    }
+
+    pub fn env(&self) -> &BTreeMap<String, String> {
+        &self.env
+    }
+
+    pub fn configurations(&self) -> &[String] {
+        &self.configurations
+    }
+
+    pub fn builder(&self) -> Option<Builder> {
+        self.builder
+    }
+
+    pub fn with_builder(mut self, builder: Option<Builder>) -> Self {
+        self.builder = builder;
+        self
+    }
+}
+
+/// Request to create or update how an application runs
+#[derive(Debug, Clone, new)]
+pub struct ConfigureApp {
+    name: AppName,
+    namespace: Namespace,
+    /// Username of user, recorded when application is created
+    created_by: String,
+    config: AppConfig,
+}
+
+impl ConfigureApp {
+    pub fn name(&self) -> &AppName {
+        &self.name
+    }
+
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    pub fn created_by(&self) -> &str {
+        self.created_by.as_str()
+    }
+
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+}
+
+/// Observed state of application workload
+#[derive(Debug, Clone, Default, PartialEq, Eq, new)]
+pub struct AppStatus {
+    /// All replicas of deployed image are ready
+    ready: bool,
+    ready_replicas: i32,
+    /// Route application is reachable from
+    url: Option<String>,
+    /// Why application is not ready
+    message: Option<String>,
+}
+
+impl AppStatus {
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    pub fn ready_replicas(&self) -> i32 {
+        self.ready_replicas
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+/// Identifier of a blob on blob store
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlobId(String);
+
+/// Separator of owner of application blobs, never part of a name
+const BLOB_OWNER_SEPARATOR: char = '_';
+
+impl BlobId {
+    /// Generate a random blob id
+    pub fn generate() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+
+    /// Generate a random blob id owned by application, ex: archive of its
+    /// sources
+    pub fn of_app(namespace: &Namespace, name: &AppName) -> Self {
+        let sep = BLOB_OWNER_SEPARATOR;
+        Self(format!(
+            "{namespace}{sep}{name}{sep}{}",
+            uuid::Uuid::new_v4()
+        ))
+    }
+
+    /// Blob was generated for application
+    pub fn is_owned_by(&self, namespace: &Namespace, name: &AppName) -> bool {
+        let sep = BLOB_OWNER_SEPARATOR;
+        self.0.starts_with(&format!("{namespace}{sep}{name}{sep}"))
+    }
+}
+
+impl FromStr for BlobId {
+    type Err = Error;
+
+    fn from_str(value: &str) -> crate::Result<Self> {
+        let valid = !value.is_empty()
+            && value.chars().all(|c| {
+                c.is_ascii_alphanumeric()
+                    || c == '-'
+                    || c == BLOB_OWNER_SEPARATOR
+            });
+        if !valid {
+            return Err(Error::DomainError(
+                "`blob id` must consist of alphanumeric characters, '-' or '_'"
+                    .to_string(),
+            ));
+        }
+        Ok(Self(value.to_string()))
+    }
+}
+
+impl AsRef<str> for BlobId {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Display for BlobId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Hex encoded SHA-256 digest of content
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Digest(String);
+
+impl FromStr for Digest {
+    type Err = Error;
+
+    fn from_str(value: &str) -> crate::Result<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        let valid = value.len() == SHA256_HEX_LENGTH
+            && value.chars().all(|c| c.is_ascii_hexdigit());
+        if !valid {
+            return Err(Error::DomainError(format!(
+                "`digest` must be {SHA256_HEX_LENGTH} hex characters of SHA-256"
+            )));
+        }
+        Ok(Self(value))
+    }
+}
+
+impl AsRef<str> for Digest {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Display for Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Metadata of a blob on blob store
+#[derive(Debug, Clone, PartialEq, Eq, new)]
+pub struct BlobInfo {
+    id: BlobId,
+    size: u64,
+}
+
+impl BlobInfo {
+    pub fn id(&self) -> &BlobId {
+        &self.id
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// Source archive uploaded to application
+#[derive(Debug, Clone, new)]
+pub struct AppUpload {
+    name: AppName,
+    namespace: Namespace,
+    /// Digest computed by client
+    digest: Digest,
+    /// Format declared by client, checked once stored
+    format: ArchiveFormat,
+}
+
+impl AppUpload {
+    pub fn name(&self) -> &AppName {
+        &self.name
+    }
+
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    pub fn digest(&self) -> &Digest {
+        &self.digest
+    }
+
+    pub fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+}
+
+/// Blob stored on blob store
+#[derive(Debug, Clone, PartialEq, Eq, new)]
+pub struct Blob {
+    id: BlobId,
+    digest: Digest,
+    size: u64,
+}
+
+impl Blob {
+    pub fn id(&self) -> &BlobId {
+        &self.id
+    }
+
+    pub fn digest(&self) -> &Digest {
+        &self.digest
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn app_name_ok() {
+        assert!("my-app".parse::<AppName>().is_ok());
+        assert!("app1".parse::<AppName>().is_ok());
+        assert!("a".parse::<AppName>().is_ok());
+    }
+
+    #[test]
+    fn app_name_invalid() {
+        assert!("".parse::<AppName>().is_err());
+        assert!("My-App".parse::<AppName>().is_err());
+        assert!("-app".parse::<AppName>().is_err());
+        assert!("app-".parse::<AppName>().is_err());
+        assert!("my_app".parse::<AppName>().is_err());
+        assert!("a".repeat(64).parse::<AppName>().is_err());
+    }
+
+    #[test]
+    fn namespace_invalid() {
+        assert!("paastel-space".parse::<Namespace>().is_ok());
+        assert!("paastel.space".parse::<Namespace>().is_err());
+    }
+
+    #[test]
+    fn digest_ok() {
+        let digest =
+            "E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855"
+                .parse::<Digest>();
+        assert_eq!(
+            digest.unwrap().as_ref(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn digest_invalid() {
+        assert!("".parse::<Digest>().is_err());
+        assert!("e3b0c442".parse::<Digest>().is_err());
+        assert!("z".repeat(64).parse::<Digest>().is_err());
+    }
+
+    #[test]
+    fn blob_id() {
+        let id = BlobId::generate();
+        assert_eq!(id.to_string().parse::<BlobId>().unwrap(), id);
+        assert!("../etc/passwd".parse::<BlobId>().is_err());
+    }
+
+    #[test]
+    fn blob_id_owned_by_app() {
+        let team_a: Namespace = "team-a".parse().unwrap();
+        let team: Namespace = "team".parse().unwrap();
+        let b: AppName = "b".parse().unwrap();
+        let a_b: AppName = "a-b".parse().unwrap();
+
+        let id = BlobId::of_app(&team_a, &b);
+        assert_eq!(id.to_string().parse::<BlobId>().unwrap(), id);
+        assert!(id.is_owned_by(&team_a, &b));
+        // NOTE: names with `-` never match across namespace and name
+        assert!(!id.is_owned_by(&team, &a_b));
+        assert!(!BlobId::generate().is_owned_by(&team_a, &b));
+    }
+}
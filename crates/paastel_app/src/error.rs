@@ -0,0 +1,51 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("domain error {0}")]
+    DomainError(String),
+    #[error("application already exists")]
+    AppAlreadyExists,
+    #[error("application changed concurrently")]
+    AppConflict,
+    #[error("not found application")]
+    AppNotFound,
+    #[error("not found blob")]
+    BlobNotFound,
+    #[error("not found stage")]
+    StageNotFound,
+    #[error("not found namespace")]
+    NamespaceNotFound,
+    #[error("stage not succeeded")]
+    StageNotSucceeded,
+    #[error("not found upload")]
+    UploadNotFound,
+    #[error("upload offset mismatch, expected {expected} found {found}")]
+    UploadOffsetMismatch { expected: u64, found: u64 },
+    #[error("upload incomplete, received {received} of {size} bytes")]
+    UploadIncomplete { received: u64, size: u64 },
+    #[error("missing {0} source blobs")]
+    SourcesMissing(usize),
+    #[error("digest mismatch, expected {expected} found {found}")]
+    DigestMismatch { expected: String, found: String },
+    #[error("invalid archive {0}")]
+    InvalidArchive(String),
+    #[error("kubernetes error {0}")]
+    Kubernetes(String),
+    #[error("storage error {0}")]
+    Storage(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,43 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+pub mod error;
+pub use error::*;
+
+pub mod domain;
+pub use domain::*;
+
+pub mod stage;
+pub use stage::*;
+
+pub mod archive;
+pub use archive::*;
+
+pub mod upload;
+pub use upload::*;
+
+pub mod source;
+pub use source::*;
+
+pub mod service;
+pub use service::*;
+
+pub mod port;
+pub use port::*;
+
+pub mod application;
+pub use application::*;
+
+pub mod memory;
+pub use memory::*;
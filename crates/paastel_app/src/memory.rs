@@ -0,0 +1,182 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    AppConfig, AppName, AppStatus, Application, Error, Namespace, NewApp,
+    OutgoingKubernetesPort, Stage, StageId, StagePhase,
+};
+
+/// Applications and stages kept in memory, for tests and local runs
+/// without a cluster, deployed applications are ready at once and stages
+/// end on phase of cluster, `Succeeded` by default
+#[derive(Clone)]
+pub struct MemoryCluster {
+    apps: Arc<Mutex<BTreeMap<(Namespace, AppName), Application>>>,
+    stages: Arc<Mutex<BTreeMap<StageId, Stage>>>,
+    stage_phase: (StagePhase, Option<String>),
+}
+
+impl Default for MemoryCluster {
+    fn default() -> Self {
+        Self {
+            apps: Arc::default(),
+            stages: Arc::default(),
+            stage_phase: (StagePhase::Succeeded, None),
+        }
+    }
+}
+
+impl MemoryCluster {
+    /// Phase and reason stages are in once created
+    pub fn with_stage_phase(
+        mut self,
+        phase: StagePhase,
+        reason: Option<String>,
+    ) -> Self {
+        self.stage_phase = (phase, reason);
+        self
+    }
+
+    /// Stage as stored, without phase of its build
+    pub fn stage(&self, id: &StageId) -> Option<Stage> {
+        lock(&self.stages).get(id).cloned()
+    }
+
+    fn update_app(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+        update: impl FnOnce(Application) -> Application,
+    ) -> crate::Result<Application> {
+        let mut apps = lock(&self.apps);
+        let key = (namespace.clone(), name.clone());
+        let app = apps.remove(&key).ok_or(Error::AppNotFound)?;
+        let app = update(app);
+        apps.insert(key, app.clone());
+        Ok(app)
+    }
+}
+
+#[async_trait]
+impl OutgoingKubernetesPort for MemoryCluster {
+    async fn find_app(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+    ) -> crate::Result<Option<Application>> {
+        let apps = lock(&self.apps);
+        Ok(apps.get(&(namespace.clone(), name.clone())).cloned())
+    }
+
+    async fn create_app(&self, new_app: &NewApp) -> crate::Result<Application> {
+        let mut apps = lock(&self.apps);
+        let key = (new_app.namespace().clone(), new_app.name().clone());
+        if apps.contains_key(&key) {
+            return Err(Error::AppAlreadyExists);
+        }
+        let app = Application::new(
+            key.1.clone(),
+            key.0.clone(),
+            Some(new_app.created_by().to_string()),
+        );
+        apps.insert(key, app.clone());
+        Ok(app)
+    }
+
+    async fn configure_app(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+        config: &AppConfig,
+    ) -> crate::Result<Application> {
+        self.update_app(namespace, name, |app| app.with_config(config.clone()))
+    }
+
+    async fn deploy_app(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+        image: &str,
+    ) -> crate::Result<Application> {
+        let url = format!("http://{name}.paastel.local");
+        self.update_app(namespace, name, |app| {
+            app.with_image(image).with_status(AppStatus::new(
+                true,
+                1,
+                Some(url),
+                None,
+            ))
+        })
+    }
+
+    async fn create_stage(
+        &self,
+        stage: &Stage,
+        _source_url: &str,
+    ) -> crate::Result<()> {
+        lock(&self.stages).insert(stage.id().clone(), stage.clone());
+        Ok(())
+    }
+
+    /// Stages not ended yet are in phase of cluster, like a finished build
+    async fn find_stage(
+        &self,
+        namespace: &Namespace,
+        id: &StageId,
+    ) -> crate::Result<Option<Stage>> {
+        let stages = lock(&self.stages);
+        Ok(stages
+            .get(id)
+            .filter(|stage| stage.namespace() == namespace)
+            .cloned()
+            .map(|stage| {
+                if stage.phase().is_terminal() {
+                    return stage;
+                }
+                let (phase, reason) = self.stage_phase.clone();
+                stage.with_phase(phase, reason)
+            }))
+    }
+
+    async fn update_stage(&self, stage: &Stage) -> crate::Result<()> {
+        let mut stages = lock(&self.stages);
+        let stored = stages.get_mut(stage.id()).ok_or(Error::StageNotFound)?;
+        *stored = stage.clone();
+        Ok(())
+    }
+
+    async fn stage_logs(
+        &self,
+        namespace: &Namespace,
+        id: &StageId,
+    ) -> crate::Result<String> {
+        let stage = self
+            .find_stage(namespace, id)
+            .await?
+            .ok_or(Error::StageNotFound)?;
+        Ok(format!("stage {id} {}", stage.phase()))
+    }
+}
+
+/// Lock of state, a panic while held leaves it usable
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
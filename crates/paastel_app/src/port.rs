@@ -0,0 +1,374 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{
+    pin::Pin,
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::Stream;
+#[cfg(test)]
+use mockall::automock;
+
+use crate::{
+    AppConfig, AppName, AppUpload, Application, ArchiveFormat, ArchiveInfo,
+    Blob, BlobId, BlobInfo, ConfigureApp, Deploy, Digest, Namespace, NewApp,
+    NewSources, NewStage, NewUpload, SourcePath, Stage, StageId, Upload,
+    UploadId, UploadPart,
+};
+
+/// Body of blob, read chunk by chunk to avoid buffering whole content
+pub type BlobStream =
+    Pin<Box<dyn Stream<Item = std::io::Result<Bytes>> + Send>>;
+
+///////////////////////////////////////////////////////////////////////////////
+// Ports Incoming
+///////////////////////////////////////////////////////////////////////////////
+
+/// # Create application use case
+///
+/// Incoming port
+#[async_trait]
+pub trait CreateAppUseCase {
+    async fn create_app(&self, new_app: &NewApp) -> crate::Result<Application>;
+}
+
+/// # Configure application use case
+///
+/// Incoming port, create application when missing and replace how it runs
+#[async_trait]
+pub trait ConfigureAppUseCase {
+    async fn configure_app(
+        &self,
+        configure: &ConfigureApp,
+    ) -> crate::Result<Application>;
+}
+
+/// # Show application use case
+///
+/// Incoming port, application with observed state of its workload
+#[async_trait]
+pub trait ShowAppUseCase {
+    async fn show_app(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+    ) -> crate::Result<Application>;
+}
+
+/// # Deploy application use case
+///
+/// Incoming port, run image built by a succeeded stage
+#[async_trait]
+pub trait DeployAppUseCase {
+    async fn deploy_app(&self, deploy: &Deploy) -> crate::Result<Application>;
+}
+
+/// # Upload application sources use case
+///
+/// Incoming port, store archive and check digest sent by client
+#[async_trait]
+pub trait UploadAppUseCase {
+    async fn upload_app(
+        &self,
+        upload: &AppUpload,
+        body: BlobStream,
+    ) -> crate::Result<Blob>;
+}
+
+/// # Initiate upload use case
+///
+/// Incoming port, start a resumable upload of application sources
+#[async_trait]
+pub trait InitiateUploadUseCase {
+    async fn initiate_upload(
+        &self,
+        new_upload: &NewUpload,
+    ) -> crate::Result<Upload>;
+}
+
+/// # Upload part use case
+///
+/// Incoming port, append a part at offset of bytes received so far
+#[async_trait]
+pub trait UploadPartUseCase {
+    async fn upload_part(
+        &self,
+        part: &UploadPart,
+        body: BlobStream,
+    ) -> crate::Result<Upload>;
+}
+
+/// # Show upload use case
+///
+/// Incoming port, progress of upload to resume it
+#[async_trait]
+pub trait ShowUploadUseCase {
+    async fn show_upload(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+        id: &UploadId,
+    ) -> crate::Result<Upload>;
+}
+
+/// # Complete upload use case
+///
+/// Incoming port, assemble parts into a blob checked like a single upload
+#[async_trait]
+pub trait CompleteUploadUseCase {
+    async fn complete_upload(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+        id: &UploadId,
+    ) -> crate::Result<Blob>;
+}
+
+/// # Expire uploads use case
+///
+/// Incoming port, delete uploads abandoned for longer than ttl
+#[async_trait]
+pub trait ExpireUploadsUseCase {
+    /// Number of uploads deleted
+    async fn expire_uploads(&self, ttl: Duration) -> crate::Result<usize>;
+}
+
+/// # Missing sources use case
+///
+/// Incoming port, digests of source files not yet on blob store
+#[async_trait]
+pub trait MissingSourcesUseCase {
+    async fn missing_sources(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+        digests: &[Digest],
+    ) -> crate::Result<Vec<Digest>>;
+}
+
+/// # Upload source use case
+///
+/// Incoming port, store a single source file addressed by its digest
+#[async_trait]
+pub trait UploadSourceUseCase {
+    async fn upload_source(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+        digest: &Digest,
+        body: BlobStream,
+    ) -> crate::Result<Blob>;
+}
+
+/// # Assemble sources use case
+///
+/// Incoming port, build source archive from files on blob store
+#[async_trait]
+pub trait AssembleSourcesUseCase {
+    async fn assemble_sources(
+        &self,
+        sources: &NewSources,
+    ) -> crate::Result<Blob>;
+}
+
+/// # Stage application use case
+///
+/// Incoming port, start build of an uploaded source archive
+#[async_trait]
+pub trait StageAppUseCase {
+    async fn stage_app(&self, new_stage: &NewStage) -> crate::Result<Stage>;
+}
+
+/// # Show stage use case
+///
+/// Incoming port, current phase of stage, logs are recorded once finished
+#[async_trait]
+pub trait ShowStageUseCase {
+    async fn show_stage(
+        &self,
+        namespace: &Namespace,
+        id: &StageId,
+    ) -> crate::Result<Stage>;
+}
+
+/// # Stage logs use case
+///
+/// Incoming port
+#[async_trait]
+pub trait StageLogsUseCase {
+    async fn stage_logs(
+        &self,
+        namespace: &Namespace,
+        id: &StageId,
+    ) -> crate::Result<BlobStream>;
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// Ports Outgoing
+///////////////////////////////////////////////////////////////////////////////
+
+/// Outgoing port to store applications on cluster
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait OutgoingKubernetesPort {
+    async fn find_app(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+    ) -> crate::Result<Option<Application>>;
+
+    async fn create_app(&self, new_app: &NewApp) -> crate::Result<Application>;
+
+    /// Replace how application runs
+    async fn configure_app(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+        config: &AppConfig,
+    ) -> crate::Result<Application>;
+
+    /// Set image run by application
+    async fn deploy_app(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+        image: &str,
+    ) -> crate::Result<Application>;
+
+    /// Record stage and start build job fetching sources from url
+    async fn create_stage(
+        &self,
+        stage: &Stage,
+        source_url: &str,
+    ) -> crate::Result<()>;
+
+    /// Stage with phase of build job while it is not finished
+    async fn find_stage(
+        &self,
+        namespace: &Namespace,
+        id: &StageId,
+    ) -> crate::Result<Option<Stage>>;
+
+    /// Record phase, reason and logs of stage
+    async fn update_stage(&self, stage: &Stage) -> crate::Result<()>;
+
+    /// Logs of build job
+    async fn stage_logs(
+        &self,
+        namespace: &Namespace,
+        id: &StageId,
+    ) -> crate::Result<String>;
+}
+
+pub type OutKubernetesPort = Box<dyn OutgoingKubernetesPort + Send + Sync>;
+
+/// Outgoing port to store blobs, ex: uploaded source archives
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait OutgoingBlobStorePort {
+    /// Store body and return number of bytes written
+    async fn put(&self, id: &BlobId, body: BlobStream) -> crate::Result<u64>;
+
+    /// Stream blob content, `BlobNotFound` when missing
+    async fn get(&self, id: &BlobId) -> crate::Result<BlobStream>;
+
+    /// Blob metadata without reading content, `BlobNotFound` when missing
+    async fn head(&self, id: &BlobId) -> crate::Result<BlobInfo>;
+
+    async fn delete(&self, id: &BlobId) -> crate::Result<()>;
+
+    async fn list(&self) -> crate::Result<Vec<BlobInfo>>;
+
+    /// Url where staging jobs download blob from
+    async fn source_url(&self, id: &BlobId) -> crate::Result<String>;
+}
+
+pub type OutBlobStorePort = Box<dyn OutgoingBlobStorePort + Send + Sync>;
+
+/// Outgoing port to keep resumable uploads and their parts until complete
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait OutgoingUploadStorePort {
+    async fn create_upload(&self, upload: &Upload) -> crate::Result<()>;
+
+    /// Upload with bytes received so far, `UploadNotFound` when missing
+    async fn find_upload(&self, id: &UploadId) -> crate::Result<Upload>;
+
+    /// Store part starting at offset and return number of bytes written,
+    /// part is discarded when body fails
+    async fn put_part(
+        &self,
+        id: &UploadId,
+        offset: u64,
+        body: BlobStream,
+    ) -> crate::Result<u64>;
+
+    /// Stream parts one after another
+    async fn read_parts(&self, id: &UploadId) -> crate::Result<BlobStream>;
+
+    async fn delete_upload(&self, id: &UploadId) -> crate::Result<()>;
+
+    /// Uploads without metadata or part written since before
+    async fn stale_uploads(
+        &self,
+        before: SystemTime,
+    ) -> crate::Result<Vec<UploadId>>;
+}
+
+pub type OutUploadStorePort = Box<dyn OutgoingUploadStorePort + Send + Sync>;
+
+/// Outgoing port to check uploaded archives before they are staged
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait OutgoingArchivePort {
+    /// Read whole archive, `InvalidArchive` when it is not of format, has
+    /// entries escaping root or unpacks beyond limits
+    async fn inspect(
+        &self,
+        format: ArchiveFormat,
+        body: BlobStream,
+    ) -> crate::Result<ArchiveInfo>;
+
+    /// Start an archive whose entries are appended one after another
+    async fn create_writer(&self) -> crate::Result<Box<dyn ArchiveWriter>>;
+}
+
+/// Archive being written by [`OutgoingArchivePort::create_writer`]
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait ArchiveWriter: Send {
+    fn format(&self) -> ArchiveFormat;
+
+    async fn append_file(
+        &mut self,
+        path: &SourcePath,
+        executable: bool,
+        size: u64,
+        body: BlobStream,
+    ) -> crate::Result<()>;
+
+    async fn append_symlink(
+        &mut self,
+        path: &SourcePath,
+        target: &str,
+    ) -> crate::Result<()>;
+
+    /// Content of finished archive
+    async fn finish(&mut self) -> crate::Result<BlobStream>;
+}
+
+pub type OutArchivePort = Box<dyn OutgoingArchivePort + Send + Sync>;
@@ -0,0 +1,1896 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+use derive_new::new;
+use futures::TryStreamExt;
+use sha2::{Digest as _, Sha256};
+
+use crate::{
+    AppName, AppUpload, Application, ArchiveFormat, AssembleSourcesUseCase,
+    Blob, BlobId, BlobStream, CompleteUploadUseCase, ConfigureApp,
+    ConfigureAppUseCase, CreateAppUseCase, Deploy, DeployAppUseCase, Digest,
+    Error, ExpireUploadsUseCase, InitiateUploadUseCase, MissingSourcesUseCase,
+    Namespace, NewApp, NewSources, NewStage, NewUpload, OutArchivePort,
+    OutBlobStorePort, OutKubernetesPort, OutUploadStorePort, ShowAppUseCase,
+    ShowStageUseCase, ShowUploadUseCase, SourceKind, Stage, StageAppUseCase,
+    StageId, StageLogsUseCase, Upload, UploadAppUseCase, UploadId, UploadPart,
+    UploadPartUseCase, UploadSourceUseCase,
+};
+
+/// Default registry where staged images are pushed
+pub const DEFAULT_REGISTRY: &str = "registry.paastel.svc.cluster.local:5000";
+
+/// Bytes read to detect format of archive
+const ARCHIVE_MAGIC_LENGTH: usize = 4;
+
+/// Source blobs checked at once for existence
+const SOURCE_HEAD_CONCURRENCY: usize = 16;
+
+/// # AppService
+///
+/// This service implement use cases from applications management
+#[derive(new)]
+pub struct AppService {
+    kubernetes_port: OutKubernetesPort,
+    blob_store_port: OutBlobStorePort,
+    archive_port: OutArchivePort,
+    upload_store_port: OutUploadStorePort,
+    #[new(value = "DEFAULT_REGISTRY.to_string()")]
+    registry: String,
+}
+
+impl AppService {
+    pub fn with_registry(mut self, registry: impl Into<String>) -> Self {
+        self.registry = registry.into();
+        self
+    }
+
+    async fn check_app_exists(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+    ) -> crate::Result<()> {
+        self.show_app(namespace, name).await.map(|_| ())
+    }
+
+    /// Inspect stored archive, blob is removed when invalid
+    async fn check_archive(
+        &self,
+        id: &BlobId,
+        format: ArchiveFormat,
+    ) -> crate::Result<()> {
+        let result = match self.blob_store_port.get(id).await {
+            Ok(body) => self.archive_port.inspect(format, body).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(info) => {
+                tracing::debug!(
+                    %id,
+                    entries = info.entries(),
+                    unpacked_size = info.unpacked_size(),
+                    "valid archive"
+                );
+                Ok(())
+            }
+            Err(e) => {
+                tracing::warn!(%id, "invalid archive {e}");
+                if let Err(e) = self.blob_store_port.delete(id).await {
+                    tracing::warn!(%id, "failed delete blob {e}");
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Stream body to blob store, returns size and digest of content
+    async fn put_hashed(
+        &self,
+        id: &BlobId,
+        body: BlobStream,
+    ) -> crate::Result<(u64, Digest)> {
+        // hash chunks while they are streamed to blob store
+        let hasher = Arc::new(Mutex::new(Sha256::new()));
+        let body = {
+            let hasher = hasher.clone();
+            body.inspect_ok(move |chunk| {
+                if let Ok(mut hasher) = hasher.lock() {
+                    hasher.update(chunk);
+                }
+            })
+        };
+
+        let size = self.blob_store_port.put(id, Box::pin(body)).await?;
+
+        let found = hasher
+            .lock()
+            .map(|hasher| format!("{:x}", hasher.clone().finalize()))
+            .map_err(|e| Error::Storage(e.to_string()))?
+            .parse::<Digest>()?;
+        Ok((size, found))
+    }
+
+    /// Store body as blob, blob is removed when its digest is not expected
+    async fn put_verified(
+        &self,
+        id: &BlobId,
+        expected: &Digest,
+        body: BlobStream,
+    ) -> crate::Result<Blob> {
+        let (size, found) = self.put_hashed(id, body).await?;
+        if &found != expected {
+            tracing::warn!(%id, "uploaded sources digest mismatch");
+            if let Err(e) = self.blob_store_port.delete(id).await {
+                tracing::warn!(%id, "failed delete blob {e}");
+            }
+            return Err(Error::DigestMismatch {
+                expected: expected.to_string(),
+                found: found.to_string(),
+            });
+        }
+        Ok(Blob::new(id.clone(), found, size))
+    }
+
+    /// Store archive as a new blob, removed when digest or content is
+    /// invalid
+    async fn store_blob(
+        &self,
+        upload: &AppUpload,
+        body: BlobStream,
+    ) -> crate::Result<Blob> {
+        let id = BlobId::of_app(upload.namespace(), upload.name());
+        let blob = self.put_verified(&id, upload.digest(), body).await?;
+
+        self.check_archive(&id, upload.format()).await?;
+
+        tracing::debug!(%id, size = blob.size(), "stored application sources");
+        Ok(blob)
+    }
+
+    /// Size of source blob of namespace, `None` when missing
+    async fn source_size(
+        &self,
+        namespace: &Namespace,
+        digest: &Digest,
+    ) -> crate::Result<(Digest, Option<u64>)> {
+        let id = BlobId::of_source(namespace, digest)?;
+        match self.blob_store_port.head(&id).await {
+            Ok(info) => Ok((digest.clone(), Some(info.size()))),
+            Err(Error::BlobNotFound) => Ok((digest.clone(), None)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Size of source blobs of namespace by digest, missing ones are `None`
+    async fn source_sizes(
+        &self,
+        namespace: &Namespace,
+        digests: &[Digest],
+    ) -> crate::Result<Vec<(Digest, Option<u64>)>> {
+        let mut sizes = Vec::with_capacity(digests.len());
+        for chunk in digests.chunks(SOURCE_HEAD_CONCURRENCY) {
+            let heads = chunk
+                .iter()
+                .map(|digest| self.source_size(namespace, digest));
+            for size in futures::future::join_all(heads).await {
+                sizes.push(size?);
+            }
+        }
+        Ok(sizes)
+    }
+
+    /// Upload of application, uploads of other applications are not found
+    async fn find_upload(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+        id: &UploadId,
+    ) -> crate::Result<Upload> {
+        let upload = self.upload_store_port.find_upload(id).await?;
+        if upload.namespace() != namespace || upload.name() != name {
+            return Err(Error::UploadNotFound);
+        }
+        Ok(upload)
+    }
+
+    /// Format from leading bytes of stored archive
+    async fn detect_format(&self, id: &BlobId) -> crate::Result<ArchiveFormat> {
+        let mut body = self.blob_store_port.get(id).await?;
+        let mut magic = Vec::new();
+        while magic.len() < ARCHIVE_MAGIC_LENGTH {
+            match body
+                .try_next()
+                .await
+                .map_err(|e| Error::Storage(e.to_string()))?
+            {
+                Some(chunk) => magic.extend_from_slice(&chunk),
+                None => break,
+            }
+        }
+        ArchiveFormat::detect(&magic).ok_or_else(|| {
+            Error::InvalidArchive("unknown archive format".to_string())
+        })
+    }
+}
+
+pub type ArcCreateAppUseCase = Arc<dyn CreateAppUseCase + Send + Sync>;
+
+pub type ArcConfigureAppUseCase = Arc<dyn ConfigureAppUseCase + Send + Sync>;
+
+pub type ArcShowAppUseCase = Arc<dyn ShowAppUseCase + Send + Sync>;
+
+pub type ArcDeployAppUseCase = Arc<dyn DeployAppUseCase + Send + Sync>;
+
+pub type ArcUploadAppUseCase = Arc<dyn UploadAppUseCase + Send + Sync>;
+
+pub type ArcInitiateUploadUseCase =
+    Arc<dyn InitiateUploadUseCase + Send + Sync>;
+
+pub type ArcUploadPartUseCase = Arc<dyn UploadPartUseCase + Send + Sync>;
+
+pub type ArcShowUploadUseCase = Arc<dyn ShowUploadUseCase + Send + Sync>;
+
+pub type ArcCompleteUploadUseCase =
+    Arc<dyn CompleteUploadUseCase + Send + Sync>;
+
+pub type ArcExpireUploadsUseCase = Arc<dyn ExpireUploadsUseCase + Send + Sync>;
+
+pub type ArcMissingSourcesUseCase =
+    Arc<dyn MissingSourcesUseCase + Send + Sync>;
+
+pub type ArcUploadSourceUseCase = Arc<dyn UploadSourceUseCase + Send + Sync>;
+
+pub type ArcAssembleSourcesUseCase =
+    Arc<dyn AssembleSourcesUseCase + Send + Sync>;
+
+pub type ArcStageAppUseCase = Arc<dyn StageAppUseCase + Send + Sync>;
+
+pub type ArcShowStageUseCase = Arc<dyn ShowStageUseCase + Send + Sync>;
+
+pub type ArcStageLogsUseCase = Arc<dyn StageLogsUseCase + Send + Sync>;
+
+#[async_trait]
+impl CreateAppUseCase for AppService {
+    async fn create_app(&self, new_app: &NewApp) -> crate::Result<Application> {
+        let name = new_app.name();
+        let namespace = new_app.namespace();
+
+        tracing::info!(%name, %namespace, "create application");
+
+        if self
+            .kubernetes_port
+            .find_app(namespace, name)
+            .await?
+            .is_some()
+        {
+            return Err(Error::AppAlreadyExists);
+        }
+
+        self.kubernetes_port.create_app(new_app).await
+    }
+}
+
+#[async_trait]
+impl ConfigureAppUseCase for AppService {
+    async fn configure_app(
+        &self,
+        configure: &ConfigureApp,
+    ) -> crate::Result<Application> {
+        let name = configure.name();
+        let namespace = configure.namespace();
+
+        tracing::info!(%name, %namespace, "configure application");
+
+        if self
+            .kubernetes_port
+            .find_app(namespace, name)
+            .await?
+            .is_none()
+        {
+            let new_app = NewApp::new(
+                name.clone(),
+                namespace.clone(),
+                configure.created_by().to_string(),
+            );
+            self.kubernetes_port.create_app(&new_app).await?;
+        }
+
+        self.kubernetes_port
+            .configure_app(namespace, name, configure.config())
+            .await
+    }
+}
+
+#[async_trait]
+impl ShowAppUseCase for AppService {
+    async fn show_app(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+    ) -> crate::Result<Application> {
+        self.kubernetes_port
+            .find_app(namespace, name)
+            .await?
+            .ok_or(Error::AppNotFound)
+    }
+}
+
+#[async_trait]
+impl DeployAppUseCase for AppService {
+    async fn deploy_app(&self, deploy: &Deploy) -> crate::Result<Application> {
+        let name = deploy.name();
+        let namespace = deploy.namespace();
+        let stage_id = deploy.stage_id();
+
+        tracing::info!(%name, %namespace, %stage_id, "deploy application");
+
+        self.check_app_exists(namespace, name).await?;
+        let stage = self.show_stage(namespace, stage_id).await?;
+        // NOTE: stage of another application is not visible from this one
+        if stage.name() != name {
+            return Err(Error::StageNotFound);
+        }
+        let image = stage.built_image().ok_or(Error::StageNotSucceeded)?;
+
+        self.kubernetes_port
+            .deploy_app(namespace, name, image)
+            .await
+    }
+}
+
+#[async_trait]
+impl UploadAppUseCase for AppService {
+    async fn upload_app(
+        &self,
+        upload: &AppUpload,
+        body: BlobStream,
+    ) -> crate::Result<Blob> {
+        let name = upload.name();
+        let namespace = upload.namespace();
+
+        tracing::info!(%name, %namespace, "upload application sources");
+
+        self.check_app_exists(namespace, name).await?;
+        self.store_blob(upload, body).await
+    }
+}
+
+#[async_trait]
+impl InitiateUploadUseCase for AppService {
+    async fn initiate_upload(
+        &self,
+        new_upload: &NewUpload,
+    ) -> crate::Result<Upload> {
+        let name = new_upload.name();
+        let namespace = new_upload.namespace();
+
+        tracing::info!(%name, %namespace, "initiate upload of sources");
+
+        self.check_app_exists(namespace, name).await?;
+        let upload = Upload::new(
+            UploadId::generate(),
+            name.clone(),
+            namespace.clone(),
+            new_upload.digest().clone(),
+            new_upload.format(),
+            new_upload.size(),
+        );
+        self.upload_store_port.create_upload(&upload).await?;
+        Ok(upload)
+    }
+}
+
+#[async_trait]
+impl UploadPartUseCase for AppService {
+    async fn upload_part(
+        &self,
+        part: &UploadPart,
+        body: BlobStream,
+    ) -> crate::Result<Upload> {
+        let id = part.id();
+        let upload =
+            self.find_upload(part.namespace(), part.name(), id).await?;
+
+        tracing::debug!(%id, offset = part.offset(), "upload part");
+
+        // NOTE: parts are appended, a part sent twice is rejected and
+        // client resumes from progress
+        if part.offset() != upload.received() {
+            return Err(Error::UploadOffsetMismatch {
+                expected: upload.received(),
+                found: part.offset(),
+            });
+        }
+
+        let failure = Arc::new(Mutex::new(None));
+        let body = checked_part(
+            body,
+            part.digest().clone(),
+            upload.size() - upload.received(),
+            failure.clone(),
+        );
+        match self
+            .upload_store_port
+            .put_part(id, part.offset(), body)
+            .await
+        {
+            Ok(size) => {
+                let received = upload.received() + size;
+                Ok(upload.with_received(received))
+            }
+            Err(e) => Err(failure
+                .lock()
+                .ok()
+                .and_then(|mut failure| failure.take())
+                .unwrap_or(e)),
+        }
+    }
+}
+
+#[async_trait]
+impl ShowUploadUseCase for AppService {
+    async fn show_upload(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+        id: &UploadId,
+    ) -> crate::Result<Upload> {
+        self.find_upload(namespace, name, id).await
+    }
+}
+
+#[async_trait]
+impl CompleteUploadUseCase for AppService {
+    async fn complete_upload(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+        id: &UploadId,
+    ) -> crate::Result<Blob> {
+        tracing::info!(%name, %namespace, %id, "complete upload of sources");
+
+        let upload = self.find_upload(namespace, name, id).await?;
+        if !upload.is_complete() {
+            return Err(Error::UploadIncomplete {
+                received: upload.received(),
+                size: upload.size(),
+            });
+        }
+        self.check_app_exists(namespace, name).await?;
+
+        let body = self.upload_store_port.read_parts(id).await?;
+        let result = self.store_blob(&upload.to_app_upload(), body).await;
+
+        // NOTE: parts are kept on storage failures so completion can retry
+        if matches!(
+            result,
+            Ok(_)
+                | Err(Error::DigestMismatch { .. } | Error::InvalidArchive(_))
+        ) {
+            if let Err(e) = self.upload_store_port.delete_upload(id).await {
+                tracing::warn!(%id, "failed delete upload {e}");
+            }
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl ExpireUploadsUseCase for AppService {
+    async fn expire_uploads(&self, ttl: Duration) -> crate::Result<usize> {
+        let before = SystemTime::now()
+            .checked_sub(ttl)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let mut expired = 0;
+        for id in self.upload_store_port.stale_uploads(before).await? {
+            // NOTE: a client may complete it in between
+            match self.upload_store_port.delete_upload(&id).await {
+                Ok(()) => expired += 1,
+                Err(Error::UploadNotFound) => {}
+                Err(e) => tracing::warn!(%id, "failed expire upload {e}"),
+            }
+        }
+        if expired > 0 {
+            tracing::info!(expired, "expired abandoned uploads");
+        }
+        Ok(expired)
+    }
+}
+
+#[async_trait]
+impl MissingSourcesUseCase for AppService {
+    async fn missing_sources(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+        digests: &[Digest],
+    ) -> crate::Result<Vec<Digest>> {
+        tracing::info!(%name, %namespace, "missing source files");
+
+        self.check_app_exists(namespace, name).await?;
+        let mut digests = digests.to_vec();
+        digests.sort();
+        digests.dedup();
+        let missing = self
+            .source_sizes(namespace, &digests)
+            .await?
+            .into_iter()
+            .filter(|(_, size)| size.is_none())
+            .map(|(digest, _)| digest)
+            .collect();
+        Ok(missing)
+    }
+}
+
+#[async_trait]
+impl UploadSourceUseCase for AppService {
+    async fn upload_source(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+        digest: &Digest,
+        body: BlobStream,
+    ) -> crate::Result<Blob> {
+        self.check_app_exists(namespace, name).await?;
+
+        // NOTE: content addressed by namespace, a file stored before is
+        // kept as is
+        let id = BlobId::of_source(namespace, digest)?;
+        match self.blob_store_port.head(&id).await {
+            Ok(info) => Ok(Blob::new(id, digest.clone(), info.size())),
+            Err(Error::BlobNotFound) => {
+                self.put_verified(&id, digest, body).await
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[async_trait]
+impl AssembleSourcesUseCase for AppService {
+    async fn assemble_sources(
+        &self,
+        sources: &NewSources,
+    ) -> crate::Result<Blob> {
+        let name = sources.name();
+        let namespace = sources.namespace();
+
+        tracing::info!(%name, %namespace, "assemble application sources");
+
+        self.check_app_exists(namespace, name).await?;
+        let mut sizes = BTreeMap::new();
+        for (digest, size) in
+            self.source_sizes(namespace, &sources.digests()).await?
+        {
+            sizes.insert(digest, size);
+        }
+        let missing = sizes.values().filter(|size| size.is_none()).count();
+        if missing > 0 {
+            return Err(Error::SourcesMissing(missing));
+        }
+
+        let mut writer = self.archive_port.create_writer().await?;
+        for entry in sources.entries() {
+            match entry.kind() {
+                SourceKind::File { digest, executable } => {
+                    let size = sizes
+                        .get(digest)
+                        .copied()
+                        .flatten()
+                        .ok_or(Error::SourcesMissing(1))?;
+                    let body = self
+                        .blob_store_port
+                        .get(&BlobId::of_source(namespace, digest)?)
+                        .await?;
+                    writer
+                        .append_file(entry.path(), *executable, size, body)
+                        .await?;
+                }
+                SourceKind::Symlink { target } => {
+                    writer.append_symlink(entry.path(), target).await?;
+                }
+            }
+        }
+        let format = writer.format();
+        let body = writer.finish().await?;
+
+        let id = BlobId::of_app(namespace, name);
+        let (size, digest) = self.put_hashed(&id, body).await?;
+        // NOTE: entries come from client, archive is checked as uploads
+        self.check_archive(&id, format).await?;
+
+        tracing::debug!(%id, size, "assembled application sources");
+        Ok(Blob::new(id, digest, size))
+    }
+}
+
+/// Part body checked while streamed, it fails before its end when digest
+/// differs or part goes beyond `max_size`, so store discards it
+fn checked_part(
+    body: BlobStream,
+    digest: Digest,
+    max_size: u64,
+    failure: Arc<Mutex<Option<Error>>>,
+) -> BlobStream {
+    let fail = move |e: Error| {
+        let io_error = std::io::Error::other(e.to_string());
+        if let Ok(mut failure) = failure.lock() {
+            *failure = Some(e);
+        }
+        io_error
+    };
+    Box::pin(futures::stream::try_unfold(
+        (body, Sha256::new(), 0, digest, fail),
+        move |(mut body, mut hasher, mut size, digest, fail)| async move {
+            match body.try_next().await? {
+                Some(chunk) => {
+                    size += chunk.len() as u64;
+                    if size > max_size {
+                        return Err(fail(Error::DomainError(format!(
+                            "part beyond upload size, {max_size} bytes left"
+                        ))));
+                    }
+                    hasher.update(&chunk);
+                    Ok(Some((chunk, (body, hasher, size, digest, fail))))
+                }
+                None => {
+                    let found = format!("{:x}", hasher.finalize());
+                    if found != digest.as_ref() {
+                        return Err(fail(Error::DigestMismatch {
+                            expected: digest.to_string(),
+                            found,
+                        }));
+                    }
+                    Ok(None)
+                }
+            }
+        },
+    ))
+}
+
+#[async_trait]
+impl StageAppUseCase for AppService {
+    async fn stage_app(&self, new_stage: &NewStage) -> crate::Result<Stage> {
+        let name = new_stage.name();
+        let namespace = new_stage.namespace();
+
+        tracing::info!(%name, %namespace, "stage application sources");
+
+        let app = self.show_app(namespace, name).await?;
+        // NOTE: blobs of other applications are never built, ids of other
+        // tenants may leak
+        if !new_stage.blob_id().is_owned_by(namespace, name) {
+            return Err(Error::BlobNotFound);
+        }
+        let builder = new_stage
+            .builder()
+            .or(app.config().builder())
+            .unwrap_or_default();
+        self.blob_store_port.head(new_stage.blob_id()).await?;
+        let format = self.detect_format(new_stage.blob_id()).await?;
+        let source_url =
+            self.blob_store_port.source_url(new_stage.blob_id()).await?;
+
+        let id = StageId::generate();
+        let image = format!("{}/{namespace}/{name}:{id}", self.registry);
+        let stage = Stage::new(
+            id,
+            name.clone(),
+            namespace.clone(),
+            new_stage.blob_id().clone(),
+            builder,
+            image,
+        )
+        .with_format(format);
+        self.kubernetes_port
+            .create_stage(&stage, &source_url)
+            .await?;
+
+        Ok(stage)
+    }
+}
+
+#[async_trait]
+impl ShowStageUseCase for AppService {
+    async fn show_stage(
+        &self,
+        namespace: &Namespace,
+        id: &StageId,
+    ) -> crate::Result<Stage> {
+        let stage = self
+            .kubernetes_port
+            .find_stage(namespace, id)
+            .await?
+            .ok_or(Error::StageNotFound)?;
+
+        if !stage.phase().is_terminal() || stage.logs().is_some() {
+            return Ok(stage);
+        }
+
+        // NOTE: record logs once, build pods are removed after some time
+        tracing::info!(%id, phase = %stage.phase(), "record stage logs");
+        let logs = self
+            .kubernetes_port
+            .stage_logs(namespace, id)
+            .await
+            .unwrap_or_else(|e| format!("failed retrieve logs {e}"));
+        let logs_id = id.logs_blob_id();
+        self.blob_store_port
+            .put(&logs_id, once(logs.into_bytes()))
+            .await?;
+
+        let stage = stage.with_logs(logs_id);
+        self.kubernetes_port.update_stage(&stage).await?;
+        Ok(stage)
+    }
+}
+
+#[async_trait]
+impl StageLogsUseCase for AppService {
+    async fn stage_logs(
+        &self,
+        namespace: &Namespace,
+        id: &StageId,
+    ) -> crate::Result<BlobStream> {
+        let stage = self.show_stage(namespace, id).await?;
+        match stage.logs() {
+            Some(logs_id) => self.blob_store_port.get(logs_id).await,
+            None => {
+                let logs =
+                    self.kubernetes_port.stage_logs(namespace, id).await?;
+                Ok(once(logs.into_bytes()))
+            }
+        }
+    }
+}
+
+/// Body with a single chunk
+fn once(content: Vec<u8>) -> BlobStream {
+    Box::pin(futures::stream::once(async move {
+        Ok(bytes::Bytes::from(content))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use bytes::Bytes;
+    use futures::{stream, TryStreamExt};
+    use mockall::predicate::eq;
+
+    use crate::{
+        AppConfig, AppName, AppService, AppUpload, Application, ArchiveFormat,
+        ArchiveInfo, AssembleSourcesUseCase, BlobId, BlobInfo, BlobStream,
+        Builder, CompleteUploadUseCase, ConfigureApp, ConfigureAppUseCase,
+        CreateAppUseCase, Deploy, DeployAppUseCase, Digest, Error,
+        ExpireUploadsUseCase, InitiateUploadUseCase, MemoryCluster,
+        MissingSourcesUseCase, MockArchiveWriter, MockOutgoingArchivePort,
+        MockOutgoingBlobStorePort, MockOutgoingKubernetesPort,
+        MockOutgoingUploadStorePort, Namespace, NewApp, NewSources, NewStage,
+        NewUpload, OutgoingKubernetesPort, ShowStageUseCase, SourceEntry,
+        SourceKind, Stage, StageAppUseCase, StageId, StageLogsUseCase,
+        StagePhase, Upload, UploadAppUseCase, UploadId, UploadPart,
+        UploadPartUseCase, UploadSourceUseCase,
+    };
+
+    /// SHA-256 of `hello world`
+    const HELLO_WORLD_DIGEST: &str =
+        "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+    fn new_app() -> crate::Result<NewApp> {
+        Ok(NewApp::new(
+            "my-app".parse::<AppName>()?,
+            "paastel-space".parse::<Namespace>()?,
+            "admin".to_string(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn create_app_ok() -> crate::Result<()> {
+        let new_app = new_app()?;
+        let mut kube_port = MockOutgoingKubernetesPort::new();
+        kube_port
+            .expect_find_app()
+            .with(eq(new_app.namespace().clone()), eq(new_app.name().clone()))
+            .times(1)
+            .returning(|_, _| Ok(None));
+        kube_port.expect_create_app().times(1).returning(|new_app| {
+            Ok(Application::new(
+                new_app.name().clone(),
+                new_app.namespace().clone(),
+                Some(new_app.created_by().to_string()),
+            ))
+        });
+
+        let app_service = AppService::new(
+            Box::new(kube_port),
+            Box::new(MockOutgoingBlobStorePort::new()),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let app = app_service.create_app(&new_app).await?;
+        assert_eq!(app.name(), new_app.name());
+        assert_eq!(app.created_by(), Some("admin"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn configure_app_create_missing() -> crate::Result<()> {
+        let new_app = new_app()?;
+        let config =
+            AppConfig::new(Some(2), vec![], Default::default(), vec![]);
+        let configure = ConfigureApp::new(
+            new_app.name().clone(),
+            new_app.namespace().clone(),
+            "admin".to_string(),
+            config.clone(),
+        );
+        let mut kube_port = MockOutgoingKubernetesPort::new();
+        kube_port
+            .expect_find_app()
+            .times(1)
+            .returning(|_, _| Ok(None));
+        kube_port
+            .expect_create_app()
+            .withf(|new_app| new_app.created_by() == "admin")
+            .times(1)
+            .returning(|new_app| {
+                Ok(Application::new(
+                    new_app.name().clone(),
+                    new_app.namespace().clone(),
+                    None,
+                ))
+            });
+        kube_port
+            .expect_configure_app()
+            .with(
+                eq(new_app.namespace().clone()),
+                eq(new_app.name().clone()),
+                eq(config),
+            )
+            .times(1)
+            .returning(|namespace, name, config| {
+                Ok(Application::new(name.clone(), namespace.clone(), None)
+                    .with_config(config.clone()))
+            });
+
+        let app_service = AppService::new(
+            Box::new(kube_port),
+            Box::new(MockOutgoingBlobStorePort::new()),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let app = app_service.configure_app(&configure).await?;
+        assert_eq!(app.config().instances(), Some(2));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_app_already_exists() -> crate::Result<()> {
+        let new_app = new_app()?;
+        let mut kube_port = MockOutgoingKubernetesPort::new();
+        kube_port
+            .expect_find_app()
+            .times(1)
+            .returning(|namespace, name| {
+                Ok(Some(Application::new(
+                    name.clone(),
+                    namespace.clone(),
+                    None,
+                )))
+            });
+        kube_port.expect_create_app().never();
+
+        let app_service = AppService::new(
+            Box::new(kube_port),
+            Box::new(MockOutgoingBlobStorePort::new()),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let result = app_service.create_app(&new_app).await;
+        assert!(matches!(result, Err(Error::AppAlreadyExists)));
+
+        Ok(())
+    }
+
+    fn body() -> BlobStream {
+        Box::pin(stream::iter(vec![
+            Ok(Bytes::from_static(b"hello ")),
+            Ok(Bytes::from_static(b"world")),
+        ]))
+    }
+
+    fn kube_port_with_app() -> MockOutgoingKubernetesPort {
+        let mut kube_port = MockOutgoingKubernetesPort::new();
+        kube_port
+            .expect_find_app()
+            .times(1)
+            .returning(|namespace, name| {
+                Ok(Some(Application::new(
+                    name.clone(),
+                    namespace.clone(),
+                    None,
+                )))
+            });
+        kube_port
+    }
+
+    fn blob_store_port() -> MockOutgoingBlobStorePort {
+        let mut blob_store_port = MockOutgoingBlobStorePort::new();
+        blob_store_port.expect_put().times(1).returning(|_, body| {
+            let chunks: Vec<Bytes> =
+                futures::executor::block_on(body.try_collect())
+                    .map_err(|e| Error::Storage(e.to_string()))?;
+            Ok(chunks.iter().map(|c| c.len() as u64).sum())
+        });
+        blob_store_port
+    }
+
+    fn upload(digest: &str) -> crate::Result<AppUpload> {
+        Ok(AppUpload::new(
+            "my-app".parse::<AppName>()?,
+            "paastel-space".parse::<Namespace>()?,
+            digest.parse()?,
+            ArchiveFormat::TarGz,
+        ))
+    }
+
+    #[tokio::test]
+    async fn upload_app_ok() -> crate::Result<()> {
+        let mut blob_store_port = blob_store_port();
+        blob_store_port
+            .expect_get()
+            .times(1)
+            .returning(|_| Ok(body()));
+        blob_store_port.expect_delete().never();
+        let mut archive_port = MockOutgoingArchivePort::new();
+        archive_port
+            .expect_inspect()
+            .withf(|format, _| *format == ArchiveFormat::TarGz)
+            .times(1)
+            .returning(|format, _| Ok(ArchiveInfo::new(format, 2, 11)));
+
+        let app_service = AppService::new(
+            Box::new(kube_port_with_app()),
+            Box::new(blob_store_port),
+            Box::new(archive_port),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let blob = app_service
+            .upload_app(&upload(HELLO_WORLD_DIGEST)?, body())
+            .await?;
+        assert_eq!(blob.digest().as_ref(), HELLO_WORLD_DIGEST);
+        assert_eq!(blob.size(), 11);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_app_digest_mismatch() -> crate::Result<()> {
+        let mut blob_store_port = blob_store_port();
+        blob_store_port
+            .expect_delete()
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let app_service = AppService::new(
+            Box::new(kube_port_with_app()),
+            Box::new(blob_store_port),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let result = app_service
+            .upload_app(&upload(&"0".repeat(64))?, body())
+            .await;
+        assert!(matches!(result, Err(Error::DigestMismatch { .. })));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_app_invalid_archive() -> crate::Result<()> {
+        let mut blob_store_port = blob_store_port();
+        blob_store_port
+            .expect_get()
+            .times(1)
+            .returning(|_| Ok(body()));
+        blob_store_port
+            .expect_delete()
+            .times(1)
+            .returning(|_| Ok(()));
+        let mut archive_port = MockOutgoingArchivePort::new();
+        archive_port.expect_inspect().times(1).returning(|_, _| {
+            Err(Error::InvalidArchive("entry ../etc/passwd".to_string()))
+        });
+
+        let app_service = AppService::new(
+            Box::new(kube_port_with_app()),
+            Box::new(blob_store_port),
+            Box::new(archive_port),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let result = app_service
+            .upload_app(&upload(HELLO_WORLD_DIGEST)?, body())
+            .await;
+        assert!(matches!(result, Err(Error::InvalidArchive(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_app_not_found() -> crate::Result<()> {
+        let mut kube_port = MockOutgoingKubernetesPort::new();
+        kube_port
+            .expect_find_app()
+            .times(1)
+            .returning(|_, _| Ok(None));
+        let mut blob_store_port = MockOutgoingBlobStorePort::new();
+        blob_store_port.expect_put().never();
+
+        let app_service = AppService::new(
+            Box::new(kube_port),
+            Box::new(blob_store_port),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let result = app_service
+            .upload_app(&upload(HELLO_WORLD_DIGEST)?, body())
+            .await;
+        assert!(matches!(result, Err(Error::AppNotFound)));
+
+        Ok(())
+    }
+
+    fn stored_upload(id: &UploadId, received: u64) -> crate::Result<Upload> {
+        Ok(Upload::new(
+            id.clone(),
+            "my-app".parse::<AppName>()?,
+            "paastel-space".parse::<Namespace>()?,
+            HELLO_WORLD_DIGEST.parse()?,
+            ArchiveFormat::TarGz,
+            11,
+        )
+        .with_received(received))
+    }
+
+    fn upload_store_port(received: u64) -> MockOutgoingUploadStorePort {
+        let mut upload_store_port = MockOutgoingUploadStorePort::new();
+        upload_store_port
+            .expect_find_upload()
+            .returning(move |id| stored_upload(id, received));
+        upload_store_port
+    }
+
+    fn part(
+        id: &UploadId,
+        offset: u64,
+        digest: &str,
+    ) -> crate::Result<UploadPart> {
+        Ok(UploadPart::new(
+            "my-app".parse::<AppName>()?,
+            "paastel-space".parse::<Namespace>()?,
+            id.clone(),
+            offset,
+            digest.parse()?,
+        ))
+    }
+
+    #[tokio::test]
+    async fn initiate_upload_ok() -> crate::Result<()> {
+        let mut upload_store_port = MockOutgoingUploadStorePort::new();
+        upload_store_port
+            .expect_create_upload()
+            .withf(|upload| upload.size() == 11 && upload.received() == 0)
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let app_service = AppService::new(
+            Box::new(kube_port_with_app()),
+            Box::new(MockOutgoingBlobStorePort::new()),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(upload_store_port),
+        );
+        let new_upload = NewUpload::new(
+            "my-app".parse::<AppName>()?,
+            "paastel-space".parse::<Namespace>()?,
+            HELLO_WORLD_DIGEST.parse()?,
+            ArchiveFormat::TarGz,
+            11,
+        );
+        let upload = app_service.initiate_upload(&new_upload).await?;
+        assert_eq!(upload.size(), 11);
+        assert!(!upload.is_complete());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_part_ok() -> crate::Result<()> {
+        let id = UploadId::generate();
+        let mut upload_store_port = upload_store_port(0);
+        upload_store_port
+            .expect_put_part()
+            .withf(|_, offset, _| *offset == 0)
+            .times(1)
+            .returning(|_, _, body| {
+                let chunks: Vec<Bytes> =
+                    futures::executor::block_on(body.try_collect())
+                        .map_err(|e| Error::Storage(e.to_string()))?;
+                Ok(chunks.iter().map(|c| c.len() as u64).sum())
+            });
+
+        let app_service = AppService::new(
+            Box::new(MockOutgoingKubernetesPort::new()),
+            Box::new(MockOutgoingBlobStorePort::new()),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(upload_store_port),
+        );
+        let upload = app_service
+            .upload_part(&part(&id, 0, HELLO_WORLD_DIGEST)?, body())
+            .await?;
+        assert!(upload.is_complete());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_part_digest_mismatch() -> crate::Result<()> {
+        let id = UploadId::generate();
+        let mut upload_store_port = upload_store_port(0);
+        upload_store_port
+            .expect_put_part()
+            .times(1)
+            .returning(|_, _, body| {
+                futures::executor::block_on(body.try_collect::<Vec<_>>())
+                    .map_err(|e| Error::Storage(e.to_string()))?;
+                Ok(11)
+            });
+
+        let app_service = AppService::new(
+            Box::new(MockOutgoingKubernetesPort::new()),
+            Box::new(MockOutgoingBlobStorePort::new()),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(upload_store_port),
+        );
+        let result = app_service
+            .upload_part(&part(&id, 0, &"0".repeat(64))?, body())
+            .await;
+        assert!(matches!(result, Err(Error::DigestMismatch { .. })));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_part_offset_mismatch() -> crate::Result<()> {
+        let id = UploadId::generate();
+        let mut upload_store_port = upload_store_port(6);
+        upload_store_port.expect_put_part().never();
+
+        let app_service = AppService::new(
+            Box::new(MockOutgoingKubernetesPort::new()),
+            Box::new(MockOutgoingBlobStorePort::new()),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(upload_store_port),
+        );
+        let result = app_service
+            .upload_part(&part(&id, 0, HELLO_WORLD_DIGEST)?, body())
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::UploadOffsetMismatch {
+                expected: 6,
+                found: 0
+            })
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn complete_upload_incomplete() -> crate::Result<()> {
+        let id = UploadId::generate();
+        let mut upload_store_port = upload_store_port(6);
+        upload_store_port.expect_read_parts().never();
+
+        let app_service = AppService::new(
+            Box::new(MockOutgoingKubernetesPort::new()),
+            Box::new(MockOutgoingBlobStorePort::new()),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(upload_store_port),
+        );
+        let result = app_service
+            .complete_upload(&"paastel-space".parse()?, &"my-app".parse()?, &id)
+            .await;
+        assert!(matches!(result, Err(Error::UploadIncomplete { .. })));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn complete_upload_ok() -> crate::Result<()> {
+        let id = UploadId::generate();
+        let mut upload_store_port = upload_store_port(11);
+        upload_store_port
+            .expect_read_parts()
+            .times(1)
+            .returning(|_| Ok(body()));
+        upload_store_port
+            .expect_delete_upload()
+            .with(eq(id.clone()))
+            .times(1)
+            .returning(|_| Ok(()));
+        let mut blob_store_port = blob_store_port();
+        blob_store_port
+            .expect_get()
+            .times(1)
+            .returning(|_| Ok(body()));
+        let mut archive_port = MockOutgoingArchivePort::new();
+        archive_port
+            .expect_inspect()
+            .times(1)
+            .returning(|format, _| Ok(ArchiveInfo::new(format, 2, 11)));
+
+        let app_service = AppService::new(
+            Box::new(kube_port_with_app()),
+            Box::new(blob_store_port),
+            Box::new(archive_port),
+            Box::new(upload_store_port),
+        );
+        let blob = app_service
+            .complete_upload(&"paastel-space".parse()?, &"my-app".parse()?, &id)
+            .await?;
+        assert_eq!(blob.digest().as_ref(), HELLO_WORLD_DIGEST);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn expire_uploads_skips_completed() -> crate::Result<()> {
+        let (stale, completed) = (UploadId::generate(), UploadId::generate());
+        let mut upload_store_port = MockOutgoingUploadStorePort::new();
+        let ids = vec![stale.clone(), completed.clone()];
+        upload_store_port
+            .expect_stale_uploads()
+            .withf(|before| *before < SystemTime::now())
+            .times(1)
+            .returning(move |_| Ok(ids.clone()));
+        upload_store_port
+            .expect_delete_upload()
+            .with(eq(stale))
+            .times(1)
+            .returning(|_| Ok(()));
+        upload_store_port
+            .expect_delete_upload()
+            .with(eq(completed))
+            .times(1)
+            .returning(|_| Err(Error::UploadNotFound));
+
+        let app_service = AppService::new(
+            Box::new(MockOutgoingKubernetesPort::new()),
+            Box::new(MockOutgoingBlobStorePort::new()),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(upload_store_port),
+        );
+        let expired =
+            app_service.expire_uploads(Duration::from_secs(60)).await?;
+        assert_eq!(expired, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_of_other_app_not_found() -> crate::Result<()> {
+        let id = UploadId::generate();
+        let app_service = AppService::new(
+            Box::new(MockOutgoingKubernetesPort::new()),
+            Box::new(MockOutgoingBlobStorePort::new()),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(upload_store_port(0)),
+        );
+        let result = app_service
+            .complete_upload(
+                &"paastel-space".parse()?,
+                &"other-app".parse()?,
+                &id,
+            )
+            .await;
+        assert!(matches!(result, Err(Error::UploadNotFound)));
+
+        Ok(())
+    }
+
+    fn source_id(digest: &Digest) -> BlobId {
+        BlobId::of_source(&"paastel-space".parse().unwrap(), digest).unwrap()
+    }
+
+    fn sources_blob_store_port(
+        stored: Vec<Digest>,
+    ) -> MockOutgoingBlobStorePort {
+        let mut blob_store_port = MockOutgoingBlobStorePort::new();
+        blob_store_port.expect_head().returning(move |id| {
+            stored
+                .iter()
+                .find(|digest| &source_id(digest) == id)
+                .map(|_| BlobInfo::new(id.clone(), 11))
+                .ok_or(Error::BlobNotFound)
+        });
+        blob_store_port
+    }
+
+    fn digest(c: char) -> Digest {
+        c.to_string().repeat(64).parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn missing_sources_ok() -> crate::Result<()> {
+        let app_service = AppService::new(
+            Box::new(kube_port_with_app()),
+            Box::new(sources_blob_store_port(vec![digest('a')])),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let missing = app_service
+            .missing_sources(
+                &"paastel-space".parse()?,
+                &"my-app".parse()?,
+                &[digest('b'), digest('a'), digest('b')],
+            )
+            .await?;
+        assert_eq!(missing, [digest('b')]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn missing_sources_of_other_namespace() -> crate::Result<()> {
+        let app_service = AppService::new(
+            Box::new(kube_port_with_app()),
+            Box::new(sources_blob_store_port(vec![digest('a')])),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let missing = app_service
+            .missing_sources(
+                &"other-space".parse()?,
+                &"my-app".parse()?,
+                &[digest('a')],
+            )
+            .await?;
+        assert_eq!(missing, [digest('a')]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_source_already_stored() -> crate::Result<()> {
+        let mut blob_store_port = sources_blob_store_port(vec![digest('a')]);
+        blob_store_port.expect_put().never();
+
+        let app_service = AppService::new(
+            Box::new(kube_port_with_app()),
+            Box::new(blob_store_port),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let blob = app_service
+            .upload_source(
+                &"paastel-space".parse()?,
+                &"my-app".parse()?,
+                &digest('a'),
+                body(),
+            )
+            .await?;
+        assert_eq!(blob.id(), &source_id(&digest('a')));
+
+        Ok(())
+    }
+
+    fn new_sources() -> crate::Result<NewSources> {
+        Ok(NewSources::new(
+            "my-app".parse()?,
+            "paastel-space".parse()?,
+            vec![
+                SourceEntry::new(
+                    "bin/start".parse()?,
+                    SourceKind::File {
+                        digest: digest('a'),
+                        executable: true,
+                    },
+                ),
+                SourceEntry::new(
+                    "start".parse()?,
+                    SourceKind::Symlink {
+                        target: "bin/start".to_string(),
+                    },
+                ),
+                SourceEntry::new(
+                    "main.rs".parse()?,
+                    SourceKind::File {
+                        digest: digest('b'),
+                        executable: false,
+                    },
+                ),
+            ],
+        ))
+    }
+
+    #[tokio::test]
+    async fn assemble_sources_missing() -> crate::Result<()> {
+        let mut archive_port = MockOutgoingArchivePort::new();
+        archive_port.expect_create_writer().never();
+
+        let app_service = AppService::new(
+            Box::new(kube_port_with_app()),
+            Box::new(sources_blob_store_port(vec![digest('a')])),
+            Box::new(archive_port),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let result = app_service.assemble_sources(&new_sources()?).await;
+        assert!(matches!(result, Err(Error::SourcesMissing(1))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn assemble_sources_ok() -> crate::Result<()> {
+        let mut blob_store_port =
+            sources_blob_store_port(vec![digest('a'), digest('b')]);
+        blob_store_port
+            .expect_get()
+            .times(3)
+            .returning(|_| Ok(body()));
+        blob_store_port.expect_put().times(1).returning(|_, body| {
+            let chunks: Vec<Bytes> =
+                futures::executor::block_on(body.try_collect())
+                    .map_err(|e| Error::Storage(e.to_string()))?;
+            Ok(chunks.iter().map(|c| c.len() as u64).sum())
+        });
+        let mut archive_port = MockOutgoingArchivePort::new();
+        archive_port.expect_create_writer().times(1).returning(|| {
+            let mut writer = MockArchiveWriter::new();
+            writer.expect_format().return_const(ArchiveFormat::TarGz);
+            writer
+                .expect_append_file()
+                .withf(|path, executable, size, _| {
+                    path.as_ref() == "bin/start" && *executable && *size == 11
+                })
+                .times(1)
+                .returning(|_, _, _, _| Ok(()));
+            writer
+                .expect_append_file()
+                .withf(|path, executable, _, _| {
+                    path.as_ref() == "main.rs" && !*executable
+                })
+                .times(1)
+                .returning(|_, _, _, _| Ok(()));
+            writer
+                .expect_append_symlink()
+                .withf(|path, target| {
+                    path.as_ref() == "start" && target == "bin/start"
+                })
+                .times(1)
+                .returning(|_, _| Ok(()));
+            writer.expect_finish().times(1).returning(|| Ok(body()));
+            Ok(Box::new(writer))
+        });
+        archive_port
+            .expect_inspect()
+            .withf(|format, _| *format == ArchiveFormat::TarGz)
+            .times(1)
+            .returning(|format, _| Ok(ArchiveInfo::new(format, 3, 22)));
+
+        let app_service = AppService::new(
+            Box::new(kube_port_with_app()),
+            Box::new(blob_store_port),
+            Box::new(archive_port),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let blob = app_service.assemble_sources(&new_sources()?).await?;
+        assert_eq!(blob.digest().as_ref(), HELLO_WORLD_DIGEST);
+        assert_eq!(blob.size(), 11);
+
+        Ok(())
+    }
+
+    /// Blob of sources of application staged by tests
+    fn app_blob_id() -> crate::Result<BlobId> {
+        Ok(BlobId::of_app(
+            &"paastel-space".parse::<Namespace>()?,
+            &"my-app".parse::<AppName>()?,
+        ))
+    }
+
+    fn new_stage(blob_id: &BlobId) -> crate::Result<NewStage> {
+        Ok(NewStage::new(
+            "my-app".parse::<AppName>()?,
+            "paastel-space".parse::<Namespace>()?,
+            blob_id.clone(),
+            Some(Builder::Dockerfile),
+        ))
+    }
+
+    fn stage(phase: StagePhase, logs: Option<BlobId>) -> crate::Result<Stage> {
+        let stage = Stage::new(
+            StageId::generate(),
+            "my-app".parse::<AppName>()?,
+            "paastel-space".parse::<Namespace>()?,
+            BlobId::generate(),
+            Builder::Buildpack,
+            "registry/paastel-space/my-app:1".to_string(),
+        )
+        .with_phase(phase, None);
+        Ok(match logs {
+            Some(logs) => stage.with_logs(logs),
+            None => stage,
+        })
+    }
+
+    /// Magic of zip split between chunks
+    fn zip_magic() -> Vec<std::io::Result<Bytes>> {
+        vec![
+            Ok(Bytes::from_static(b"PK")),
+            Ok(Bytes::from_static(b"\x03\x04rest")),
+        ]
+    }
+
+    #[tokio::test]
+    async fn stage_app_unknown_format() -> crate::Result<()> {
+        let mut kube_port = kube_port_with_app();
+        kube_port.expect_create_stage().never();
+        let mut blob_store_port = MockOutgoingBlobStorePort::new();
+        blob_store_port
+            .expect_head()
+            .times(1)
+            .returning(|id| Ok(BlobInfo::new(id.clone(), 11)));
+        blob_store_port
+            .expect_get()
+            .times(1)
+            .returning(|_| Ok(body()));
+
+        let app_service = AppService::new(
+            Box::new(kube_port),
+            Box::new(blob_store_port),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let result = app_service.stage_app(&new_stage(&app_blob_id()?)?).await;
+        assert!(matches!(result, Err(Error::InvalidArchive(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stage_app_ok() -> crate::Result<()> {
+        let blob_id = app_blob_id()?;
+        let mut kube_port = kube_port_with_app();
+        kube_port
+            .expect_create_stage()
+            .withf(|stage, source_url| {
+                stage.phase() == StagePhase::Pending
+                    && stage.builder() == Builder::Dockerfile
+                    && stage.format() == ArchiveFormat::Zip
+                    && stage
+                        .image()
+                        .starts_with("registry/paastel-space/my-app:")
+                    && source_url == "file:///blobs/source"
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let mut blob_store_port = MockOutgoingBlobStorePort::new();
+        blob_store_port
+            .expect_head()
+            .with(eq(blob_id.clone()))
+            .times(1)
+            .returning(|id| Ok(BlobInfo::new(id.clone(), 11)));
+        blob_store_port
+            .expect_get()
+            .times(1)
+            .returning(|_| Ok(Box::pin(stream::iter(zip_magic()))));
+        blob_store_port
+            .expect_source_url()
+            .times(1)
+            .returning(|_| Ok("file:///blobs/source".to_string()));
+
+        let app_service = AppService::new(
+            Box::new(kube_port),
+            Box::new(blob_store_port),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        )
+        .with_registry("registry");
+        let stage = app_service.stage_app(&new_stage(&blob_id)?).await?;
+        assert_eq!(stage.blob_id(), &blob_id);
+        assert_eq!(stage.built_image(), None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stage_app_builder_of_app() -> crate::Result<()> {
+        let mut kube_port = MockOutgoingKubernetesPort::new();
+        kube_port
+            .expect_find_app()
+            .times(1)
+            .returning(|namespace, name| {
+                let config = AppConfig::default()
+                    .with_builder(Some(Builder::Dockerfile));
+                Ok(Some(
+                    Application::new(name.clone(), namespace.clone(), None)
+                        .with_config(config),
+                ))
+            });
+        kube_port
+            .expect_create_stage()
+            .withf(|stage, _| stage.builder() == Builder::Dockerfile)
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let mut blob_store_port = MockOutgoingBlobStorePort::new();
+        blob_store_port
+            .expect_head()
+            .times(1)
+            .returning(|id| Ok(BlobInfo::new(id.clone(), 11)));
+        blob_store_port
+            .expect_get()
+            .times(1)
+            .returning(|_| Ok(Box::pin(stream::iter(zip_magic()))));
+        blob_store_port
+            .expect_source_url()
+            .times(1)
+            .returning(|_| Ok("file:///blobs/source".to_string()));
+
+        let app_service = AppService::new(
+            Box::new(kube_port),
+            Box::new(blob_store_port),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let new_stage = NewStage::new(
+            "my-app".parse::<AppName>()?,
+            "paastel-space".parse::<Namespace>()?,
+            app_blob_id()?,
+            None,
+        );
+        let stage = app_service.stage_app(&new_stage).await?;
+        assert_eq!(stage.builder(), Builder::Dockerfile);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stage_app_blob_not_found() -> crate::Result<()> {
+        let mut kube_port = kube_port_with_app();
+        kube_port.expect_create_stage().never();
+        let mut blob_store_port = MockOutgoingBlobStorePort::new();
+        blob_store_port
+            .expect_head()
+            .times(1)
+            .returning(|_| Err(Error::BlobNotFound));
+
+        let app_service = AppService::new(
+            Box::new(kube_port),
+            Box::new(blob_store_port),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let result = app_service.stage_app(&new_stage(&app_blob_id()?)?).await;
+        assert!(matches!(result, Err(Error::BlobNotFound)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stage_app_blob_of_other_namespace() -> crate::Result<()> {
+        let mut kube_port = MockOutgoingKubernetesPort::new();
+        kube_port
+            .expect_find_app()
+            .times(2)
+            .returning(|namespace, name| {
+                Ok(Some(Application::new(
+                    name.clone(),
+                    namespace.clone(),
+                    None,
+                )))
+            });
+        kube_port.expect_create_stage().never();
+        let mut blob_store_port = MockOutgoingBlobStorePort::new();
+        blob_store_port.expect_head().never();
+        blob_store_port.expect_get().never();
+
+        let app_service = AppService::new(
+            Box::new(kube_port),
+            Box::new(blob_store_port),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let other = BlobId::of_app(
+            &"other-space".parse::<Namespace>()?,
+            &"my-app".parse::<AppName>()?,
+        );
+        for blob_id in [other, BlobId::generate()] {
+            let result = app_service.stage_app(&new_stage(&blob_id)?).await;
+            assert!(matches!(result, Err(Error::BlobNotFound)));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn show_stage_running() -> crate::Result<()> {
+        let pending = stage(StagePhase::Pending, None)?;
+        let cluster = MemoryCluster::default()
+            .with_stage_phase(StagePhase::Running, None);
+        cluster
+            .create_stage(&pending, "file:///blobs/source")
+            .await?;
+
+        let app_service = AppService::new(
+            Box::new(cluster.clone()),
+            Box::new(MockOutgoingBlobStorePort::new()),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let stage = app_service
+            .show_stage(pending.namespace(), pending.id())
+            .await?;
+        assert_eq!(stage.phase(), StagePhase::Running);
+        assert_eq!(stage.logs(), None);
+        assert_eq!(cluster.stage(pending.id()), Some(pending));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn show_stage_record_logs() -> crate::Result<()> {
+        let pending = stage(StagePhase::Pending, None)?;
+        let logs_id = pending.id().logs_blob_id();
+        let cluster = MemoryCluster::default().with_stage_phase(
+            StagePhase::Failed,
+            Some("BackoffLimitExceeded".into()),
+        );
+        cluster
+            .create_stage(&pending, "file:///blobs/source")
+            .await?;
+        let mut blob_store_port = MockOutgoingBlobStorePort::new();
+        blob_store_port
+            .expect_put()
+            .with(eq(logs_id.clone()), mockall::predicate::always())
+            .times(1)
+            .returning(|_, _| Ok(27));
+
+        let app_service = AppService::new(
+            Box::new(cluster.clone()),
+            Box::new(blob_store_port),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let stage = app_service
+            .show_stage(pending.namespace(), pending.id())
+            .await?;
+        assert_eq!(stage.reason(), Some("BackoffLimitExceeded"));
+        assert_eq!(stage.logs(), Some(&logs_id));
+        // NOTE: ended phase is kept once build is gone
+        assert_eq!(cluster.stage(pending.id()), Some(stage));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stage_logs_recorded() -> crate::Result<()> {
+        let logs_id = BlobId::generate();
+        let succeeded = stage(StagePhase::Succeeded, Some(logs_id.clone()))?;
+        let cluster = MemoryCluster::default();
+        cluster
+            .create_stage(&succeeded, "file:///blobs/source")
+            .await?;
+        let mut blob_store_port = MockOutgoingBlobStorePort::new();
+        blob_store_port
+            .expect_get()
+            .with(eq(logs_id))
+            .times(1)
+            .returning(|_| Ok(body()));
+
+        let app_service = AppService::new(
+            Box::new(cluster),
+            Box::new(blob_store_port),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let logs: Vec<Bytes> = app_service
+            .stage_logs(succeeded.namespace(), succeeded.id())
+            .await?
+            .try_collect()
+            .await
+            .map_err(|e| Error::Storage(e.to_string()))?;
+        assert_eq!(logs.concat(), b"hello world");
+
+        Ok(())
+    }
+
+    fn deploy_port(stage: Stage) -> MockOutgoingKubernetesPort {
+        let mut kube_port = kube_port_with_app();
+        kube_port
+            .expect_find_stage()
+            .times(1)
+            .returning(move |_, _| Ok(Some(stage.clone())));
+        kube_port
+    }
+
+    #[tokio::test]
+    async fn deploy_app_ok() -> crate::Result<()> {
+        let logs_id = BlobId::generate();
+        let succeeded = stage(StagePhase::Succeeded, Some(logs_id))?;
+        let mut kube_port = deploy_port(succeeded.clone());
+        kube_port
+            .expect_deploy_app()
+            .with(
+                eq(succeeded.namespace().clone()),
+                eq(succeeded.name().clone()),
+                eq("registry/paastel-space/my-app:1"),
+            )
+            .times(1)
+            .returning(|namespace, name, image| {
+                Ok(Application::new(name.clone(), namespace.clone(), None)
+                    .with_image(image))
+            });
+
+        let app_service = AppService::new(
+            Box::new(kube_port),
+            Box::new(MockOutgoingBlobStorePort::new()),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let deploy = Deploy::new(
+            succeeded.name().clone(),
+            succeeded.namespace().clone(),
+            succeeded.id().clone(),
+        );
+        let app = app_service.deploy_app(&deploy).await?;
+        assert_eq!(app.image(), Some("registry/paastel-space/my-app:1"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn deploy_app_stage_not_succeeded() -> crate::Result<()> {
+        let running = stage(StagePhase::Running, None)?;
+        let mut kube_port = deploy_port(running.clone());
+        kube_port.expect_deploy_app().never();
+
+        let app_service = AppService::new(
+            Box::new(kube_port),
+            Box::new(MockOutgoingBlobStorePort::new()),
+            Box::new(MockOutgoingArchivePort::new()),
+            Box::new(MockOutgoingUploadStorePort::new()),
+        );
+        let deploy = Deploy::new(
+            running.name().clone(),
+            running.namespace().clone(),
+            running.id().clone(),
+        );
+        let result = app_service.deploy_app(&deploy).await;
+        assert!(matches!(result, Err(Error::StageNotSucceeded)));
+
+        Ok(())
+    }
+}
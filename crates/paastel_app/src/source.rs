@@ -0,0 +1,174 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{fmt::Display, str::FromStr};
+
+use derive_new::new;
+
+use crate::{AppName, BlobId, Digest, Error, Namespace};
+
+/// Infix of ids of blobs holding a single source file
+const SOURCE_BLOB_INFIX: &str = "sha256";
+
+impl BlobId {
+    /// Blob of a source file, same content is stored once by namespace so
+    /// tenants never see or reuse files of another
+    pub fn of_source(
+        namespace: &Namespace,
+        digest: &Digest,
+    ) -> crate::Result<Self> {
+        Self::from_str(&format!("{namespace}-{SOURCE_BLOB_INFIX}-{digest}"))
+    }
+}
+
+/// Relative `/` separated path of a source file, it never escapes root of
+/// sources
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SourcePath(String);
+
+impl FromStr for SourcePath {
+    type Err = Error;
+
+    fn from_str(value: &str) -> crate::Result<Self> {
+        let valid = !value.is_empty()
+            && !value.starts_with('/')
+            && !value.contains(['\\', '\0'])
+            && value
+                .split('/')
+                .all(|part| !matches!(part, "" | "." | ".."));
+        if !valid {
+            return Err(Error::DomainError(format!(
+                "`source path` {value} must be relative without `.` or `..`"
+            )));
+        }
+        Ok(Self(value.to_string()))
+    }
+}
+
+impl AsRef<str> for SourcePath {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Display for SourcePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Content of a source entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceKind {
+    File {
+        digest: Digest,
+        executable: bool,
+    },
+    /// Link target relative to directory of entry
+    Symlink {
+        target: String,
+    },
+}
+
+/// Entry of sources manifest sent by client
+#[derive(Debug, Clone, PartialEq, Eq, new)]
+pub struct SourceEntry {
+    path: SourcePath,
+    kind: SourceKind,
+}
+
+impl SourceEntry {
+    pub fn path(&self) -> &SourcePath {
+        &self.path
+    }
+
+    pub fn kind(&self) -> &SourceKind {
+        &self.kind
+    }
+
+    /// Digest of file content, none for symlinks
+    pub fn digest(&self) -> Option<&Digest> {
+        match &self.kind {
+            SourceKind::File { digest, .. } => Some(digest),
+            SourceKind::Symlink { .. } => None,
+        }
+    }
+}
+
+/// Sources of application as a list of entries whose files are already on
+/// blob store, assembled into an archive
+#[derive(Debug, Clone, new)]
+pub struct NewSources {
+    name: AppName,
+    namespace: Namespace,
+    entries: Vec<SourceEntry>,
+}
+
+impl NewSources {
+    pub fn name(&self) -> &AppName {
+        &self.name
+    }
+
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    pub fn entries(&self) -> &[SourceEntry] {
+        &self.entries
+    }
+
+    /// Digests of files, each once
+    pub fn digests(&self) -> Vec<Digest> {
+        let mut digests: Vec<Digest> = self
+            .entries
+            .iter()
+            .filter_map(SourceEntry::digest)
+            .cloned()
+            .collect();
+        digests.sort();
+        digests.dedup();
+        digests
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_path() {
+        for path in ["main.rs", "src/lib.rs", ".env", "a/.b/c..d"] {
+            assert!(path.parse::<SourcePath>().is_ok(), "{path}");
+        }
+        for path in ["", "/etc/passwd", "../a", "a/../b", "a//b", "./a", "a\\b"]
+        {
+            assert!(path.parse::<SourcePath>().is_err(), "{path}");
+        }
+    }
+
+    #[test]
+    fn source_blob_id() {
+        let digest = "A".repeat(64).parse::<Digest>().unwrap();
+        let team: Namespace = "team".parse().unwrap();
+        let other: Namespace = "other".parse().unwrap();
+        assert_eq!(
+            BlobId::of_source(&team, &digest).unwrap().as_ref(),
+            format!("team-sha256-{}", "a".repeat(64))
+        );
+        assert_ne!(
+            BlobId::of_source(&team, &digest).unwrap(),
+            BlobId::of_source(&other, &digest).unwrap()
+        );
+    }
+}
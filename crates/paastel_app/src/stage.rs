@@ -0,0 +1,343 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{fmt::Display, str::FromStr};
+
+use derive_new::new;
+
+use crate::{AppName, ArchiveFormat, BlobId, Error, Namespace};
+
+/// Identifier of a staging of application sources
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StageId(String);
+
+impl StageId {
+    /// Generate a random stage id, valid as kubernetes object name
+    pub fn generate() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+
+    /// Blob id where logs of stage are recorded
+    pub fn logs_blob_id(&self) -> BlobId {
+        BlobId::from_str(&format!("stage-logs-{}", self.0))
+            .unwrap_or_else(|_| BlobId::generate())
+    }
+}
+
+impl FromStr for StageId {
+    type Err = Error;
+
+    fn from_str(value: &str) -> crate::Result<Self> {
+        // NOTE: same rules of application names, stage id is an object name
+        let name = AppName::from_str(value).map_err(|_| {
+            Error::DomainError(format!("`stage id` {value} is invalid"))
+        })?;
+        Ok(Self(name.to_string()))
+    }
+}
+
+impl AsRef<str> for StageId {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Display for StageId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// How container image is built from sources
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Builder {
+    /// Cloud native buildpacks detect language and build image
+    #[default]
+    Buildpack,
+    /// Build `Dockerfile` on root of sources
+    Dockerfile,
+}
+
+impl FromStr for Builder {
+    type Err = Error;
+
+    fn from_str(value: &str) -> crate::Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "buildpack" => Ok(Self::Buildpack),
+            "dockerfile" => Ok(Self::Dockerfile),
+            _ => Err(Error::DomainError(format!(
+                "`builder` {value} is invalid, use buildpack or dockerfile"
+            ))),
+        }
+    }
+}
+
+impl AsRef<str> for Builder {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Buildpack => "buildpack",
+            Self::Dockerfile => "dockerfile",
+        }
+    }
+}
+
+impl Display for Builder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+/// Lifecycle of a stage
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StagePhase {
+    /// Job created, build not started yet
+    #[default]
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl StagePhase {
+    /// Stage will not change anymore
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Succeeded | Self::Failed)
+    }
+}
+
+impl FromStr for StagePhase {
+    type Err = Error;
+
+    fn from_str(value: &str) -> crate::Result<Self> {
+        match value {
+            "Pending" => Ok(Self::Pending),
+            "Running" => Ok(Self::Running),
+            "Succeeded" => Ok(Self::Succeeded),
+            "Failed" => Ok(Self::Failed),
+            _ => Err(Error::DomainError(format!(
+                "`stage phase` {value} is invalid"
+            ))),
+        }
+    }
+}
+
+impl AsRef<str> for StagePhase {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Pending => "Pending",
+            Self::Running => "Running",
+            Self::Succeeded => "Succeeded",
+            Self::Failed => "Failed",
+        }
+    }
+}
+
+impl Display for StagePhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+/// Request to build an uploaded source archive
+#[derive(Debug, Clone, new)]
+pub struct NewStage {
+    name: AppName,
+    namespace: Namespace,
+    blob_id: BlobId,
+    /// Builder configured on application when none
+    builder: Option<Builder>,
+}
+
+impl NewStage {
+    pub fn name(&self) -> &AppName {
+        &self.name
+    }
+
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    pub fn blob_id(&self) -> &BlobId {
+        &self.blob_id
+    }
+
+    pub fn builder(&self) -> Option<Builder> {
+        self.builder
+    }
+}
+
+/// Request to deploy image built by a stage
+#[derive(Debug, Clone, new)]
+pub struct Deploy {
+    name: AppName,
+    namespace: Namespace,
+    stage_id: StageId,
+}
+
+impl Deploy {
+    pub fn name(&self) -> &AppName {
+        &self.name
+    }
+
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    pub fn stage_id(&self) -> &StageId {
+        &self.stage_id
+    }
+}
+
+/// Build of an uploaded source archive into a container image
+#[derive(Debug, Clone, PartialEq, Eq, new)]
+pub struct Stage {
+    id: StageId,
+    name: AppName,
+    namespace: Namespace,
+    blob_id: BlobId,
+    builder: Builder,
+    /// Image reference pushed by build
+    image: String,
+    /// Format of source archive, unpacked by build
+    #[new(default)]
+    format: ArchiveFormat,
+    #[new(default)]
+    phase: StagePhase,
+    /// Why stage failed
+    #[new(default)]
+    reason: Option<String>,
+    /// Blob with logs, recorded once stage finish
+    #[new(default)]
+    logs: Option<BlobId>,
+}
+
+impl Stage {
+    pub fn id(&self) -> &StageId {
+        &self.id
+    }
+
+    pub fn name(&self) -> &AppName {
+        &self.name
+    }
+
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    pub fn blob_id(&self) -> &BlobId {
+        &self.blob_id
+    }
+
+    pub fn builder(&self) -> Builder {
+        self.builder
+    }
+
+    pub fn image(&self) -> &str {
+        self.image.as_str()
+    }
+
+    /// Image reference, only when build succeeded
+    pub fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+
+    pub fn with_format(mut self, format: ArchiveFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn built_image(&self) -> Option<&str> {
+        (self.phase == StagePhase::Succeeded).then_some(self.image.as_str())
+    }
+
+    pub fn phase(&self) -> StagePhase {
+        self.phase
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    pub fn logs(&self) -> Option<&BlobId> {
+        self.logs.as_ref()
+    }
+
+    pub fn with_phase(
+        mut self,
+        phase: StagePhase,
+        reason: Option<String>,
+    ) -> Self {
+        self.phase = phase;
+        self.reason = reason;
+        self
+    }
+
+    pub fn with_logs(mut self, logs: BlobId) -> Self {
+        self.logs = Some(logs);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stage_id() {
+        let id = StageId::generate();
+        assert_eq!(id.to_string().parse::<StageId>().unwrap(), id);
+        assert!("Stage_1".parse::<StageId>().is_err());
+        assert!(id.logs_blob_id().as_ref().ends_with(id.as_ref()));
+    }
+
+    #[test]
+    fn builder() {
+        assert_eq!(
+            "Dockerfile".parse::<Builder>().unwrap(),
+            Builder::Dockerfile
+        );
+        assert_eq!(Builder::default(), Builder::Buildpack);
+        assert!("make".parse::<Builder>().is_err());
+    }
+
+    #[test]
+    fn stage_phase() {
+        for phase in [
+            StagePhase::Pending,
+            StagePhase::Running,
+            StagePhase::Succeeded,
+            StagePhase::Failed,
+        ] {
+            assert_eq!(phase.to_string().parse::<StagePhase>().unwrap(), phase);
+        }
+        assert!(!StagePhase::Running.is_terminal());
+        assert!(StagePhase::Failed.is_terminal());
+    }
+
+    #[test]
+    fn stage_built_image() {
+        let stage = Stage::new(
+            StageId::generate(),
+            "my-app".parse().unwrap(),
+            "paastel-space".parse().unwrap(),
+            BlobId::generate(),
+            Builder::Buildpack,
+            "registry/my-app:1".to_string(),
+        );
+        assert_eq!(stage.built_image(), None);
+
+        let stage = stage.with_phase(StagePhase::Succeeded, None);
+        assert_eq!(stage.built_image(), Some("registry/my-app:1"));
+    }
+}
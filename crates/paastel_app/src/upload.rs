@@ -0,0 +1,188 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{fmt::Display, str::FromStr};
+
+use derive_new::new;
+
+use crate::{AppName, AppUpload, ArchiveFormat, Digest, Error, Namespace};
+
+/// Identifier of a resumable upload of application sources
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UploadId(String);
+
+impl UploadId {
+    /// Generate a random upload id
+    pub fn generate() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+impl FromStr for UploadId {
+    type Err = Error;
+
+    fn from_str(value: &str) -> crate::Result<Self> {
+        let valid = !value.is_empty()
+            && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid {
+            return Err(Error::DomainError(
+                "`upload id` must consist of alphanumeric characters or '-'"
+                    .to_string(),
+            ));
+        }
+        Ok(Self(value.to_string()))
+    }
+}
+
+impl AsRef<str> for UploadId {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Display for UploadId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Request to start a resumable upload of an archive of `size` bytes
+#[derive(Debug, Clone, new)]
+pub struct NewUpload {
+    name: AppName,
+    namespace: Namespace,
+    /// SHA-256 of whole archive, checked once upload completes
+    digest: Digest,
+    format: ArchiveFormat,
+    size: u64,
+}
+
+impl NewUpload {
+    pub fn name(&self) -> &AppName {
+        &self.name
+    }
+
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    pub fn digest(&self) -> &Digest {
+        &self.digest
+    }
+
+    pub fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// Resumable upload, parts are appended in order until `received` reaches
+/// `size`
+#[derive(Debug, Clone, PartialEq, Eq, new)]
+pub struct Upload {
+    id: UploadId,
+    name: AppName,
+    namespace: Namespace,
+    digest: Digest,
+    format: ArchiveFormat,
+    size: u64,
+    /// Bytes of contiguous parts stored so far
+    #[new(default)]
+    received: u64,
+}
+
+impl Upload {
+    pub fn id(&self) -> &UploadId {
+        &self.id
+    }
+
+    pub fn name(&self) -> &AppName {
+        &self.name
+    }
+
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    pub fn digest(&self) -> &Digest {
+        &self.digest
+    }
+
+    pub fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.received == self.size
+    }
+
+    pub fn with_received(mut self, received: u64) -> Self {
+        self.received = received;
+        self
+    }
+
+    /// Upload of whole archive once every part is received
+    pub fn to_app_upload(&self) -> AppUpload {
+        AppUpload::new(
+            self.name.clone(),
+            self.namespace.clone(),
+            self.digest.clone(),
+            self.format,
+        )
+    }
+}
+
+/// Part of a resumable upload starting at `offset`
+#[derive(Debug, Clone, new)]
+pub struct UploadPart {
+    name: AppName,
+    namespace: Namespace,
+    id: UploadId,
+    offset: u64,
+    /// SHA-256 of part only
+    digest: Digest,
+}
+
+impl UploadPart {
+    pub fn name(&self) -> &AppName {
+        &self.name
+    }
+
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    pub fn id(&self) -> &UploadId {
+        &self.id
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn digest(&self) -> &Digest {
+        &self.digest
+    }
+}
@@ -15,26 +15,47 @@
 use std::sync::Arc;
 
 use crate::{
-    ArcValidateCredentialUseCase, AuthService, Credential, OutArgon2Port,
-    OutKubernetesPort, UserSecret,
+    ArcChangePasswordUseCase, ArcCreateUserUseCase, ArcDeleteUserUseCase,
+    ArcListUsersUseCase, ArcLoginUseCase, ArcRefreshTokenUseCase,
+    ArcValidateCredentialUseCase, ArcValidateTokenUseCase, AuthService,
+    Credential, OutArgon2Port, OutKubernetesPort, OutSigningKeyPort,
+    TokenService, UserSecret,
 };
 
-#[allow(dead_code)]
 #[derive(Clone)]
 pub struct AuthApplication {
     pub validate_credential: ArcValidateCredentialUseCase,
+    pub login: ArcLoginUseCase,
+    pub refresh_token: ArcRefreshTokenUseCase,
+    pub validate_token: ArcValidateTokenUseCase,
+    pub create_user: ArcCreateUserUseCase,
+    pub list_users: ArcListUsersUseCase,
+    pub change_password: ArcChangePasswordUseCase,
+    pub delete_user: ArcDeleteUserUseCase,
 }
 
 impl AuthApplication {
     pub fn new(
         kubernetes_port: OutKubernetesPort,
         password_port: OutArgon2Port<Credential, UserSecret>,
+        signing_port: OutSigningKeyPort,
     ) -> Self {
+        let auth_service =
+            Arc::new(AuthService::new(kubernetes_port, password_port));
+        let token_service = Arc::new(TokenService::new(
+            auth_service.clone(),
+            auth_service.clone(),
+            signing_port,
+        ));
         Self {
-            validate_credential: Arc::new(AuthService::new(
-                kubernetes_port,
-                password_port,
-            )),
+            validate_credential: auth_service.clone(),
+            login: token_service.clone(),
+            refresh_token: token_service.clone(),
+            validate_token: token_service,
+            create_user: auth_service.clone(),
+            list_users: auth_service.clone(),
+            change_password: auth_service.clone(),
+            delete_user: auth_service,
         }
     }
 }
@@ -16,7 +16,7 @@ use std::{fmt::Display, str::FromStr};
 
 use derive_new::new;
 
-use crate::Error;
+use crate::{Error, Roles};
 
 /// Minimium username length
 const MIN_USERNAME_LENGTH: usize = 3;
@@ -57,7 +57,7 @@ impl TryFrom<&[u8]> for Username {
     type Error = Error;
 
     fn try_from(value: &[u8]) -> crate::Result<Self> {
-        match std::str::from_utf8(&value) {
+        match std::str::from_utf8(value) {
             Ok(v) => v.parse(),
             Err(_) => Err(Error::DomainError(
                 "failed try username from vec<u8>".to_string(),
@@ -138,7 +138,7 @@ impl TryFrom<&[u8]> for PasswordHash {
     type Error = Error;
 
     fn try_from(value: &[u8]) -> crate::Result<Self> {
-        match std::str::from_utf8(&value) {
+        match std::str::from_utf8(value) {
             Ok(v) => v.parse(),
             Err(_) => Err(Error::DomainError(
                 "failed try password hash from vec<u8>".to_string(),
@@ -271,6 +271,7 @@ impl RetrievePassword<Password> for Credential {
 pub struct UserSecret {
     username: Username,
     password: PasswordHash,
+    roles: Roles,
 }
 
 impl UserSecret {
@@ -287,6 +288,18 @@ impl UserSecret {
         &self.username
     }
 
+    pub fn roles(&self) -> &Roles {
+        &self.roles
+    }
+
+    /// Return copy of user secret with a new password hash
+    pub fn with_password(&self, password: PasswordHash) -> Self {
+        Self {
+            password,
+            ..self.clone()
+        }
+    }
+
     // pub fn password(&self) -> &Password {
     //     &self.password
     // }
@@ -310,12 +323,17 @@ impl UserSecrets {
         self.len() == 0
     }
 
-    pub fn iter(&self) -> UserSecretsIterator {
+    pub fn iter(&self) -> UserSecretsIterator<'_> {
         UserSecretsIterator {
             data: self,
             index: 0,
         }
     }
+
+    /// Find user secret by username
+    pub fn find(&self, username: &Username) -> Option<&UserSecret> {
+        self.iter().find(|us| us.username() == username)
+    }
 }
 
 impl IntoIterator for UserSecrets {
@@ -473,6 +491,181 @@ impl Display for LabelKey {
     }
 }
 
+/// Command to create a new user
+#[derive(new, Debug, Clone, PartialEq, Eq)]
+pub struct NewUser {
+    credential: Credential,
+    roles: Roles,
+}
+
+impl NewUser {
+    pub fn credential(&self) -> &Credential {
+        &self.credential
+    }
+
+    pub fn username(&self) -> &Username {
+        self.credential.username()
+    }
+
+    pub fn roles(&self) -> &Roles {
+        &self.roles
+    }
+}
+
+/// Kind of signed token issued by PaaStel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TokenType {
+    /// Short lived token sent as `Authorization: Bearer`
+    Access,
+    /// Long lived token only accepted to issue a new token pair
+    Refresh,
+}
+
+impl FromStr for TokenType {
+    type Err = Error;
+
+    fn from_str(value: &str) -> crate::Result<Self> {
+        match value {
+            "access" => Ok(Self::Access),
+            "refresh" => Ok(Self::Refresh),
+            _ => {
+                Err(Error::DomainError(format!("`token type` unknown {value}")))
+            }
+        }
+    }
+}
+
+impl AsRef<str> for TokenType {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Access => "access",
+            Self::Refresh => "refresh",
+        }
+    }
+}
+
+impl Display for TokenType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+/// Content carried by a signed token
+#[derive(new, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Claims {
+    /// Username owner of token
+    subject: Username,
+
+    /// Access or refresh
+    token_type: TokenType,
+
+    /// Roles of subject when token was issued
+    roles: Roles,
+
+    /// Seconds since unix epoch when token was issued
+    issued_at: u64,
+
+    /// Seconds since unix epoch when token stop being valid
+    expires_at: u64,
+}
+
+impl Claims {
+    pub fn subject(&self) -> &Username {
+        &self.subject
+    }
+
+    pub fn token_type(&self) -> TokenType {
+        self.token_type
+    }
+
+    pub fn roles(&self) -> &Roles {
+        &self.roles
+    }
+
+    pub fn issued_at(&self) -> u64 {
+        self.issued_at
+    }
+
+    pub fn expires_at(&self) -> u64 {
+        self.expires_at
+    }
+
+    /// Return true when `now` (seconds since unix epoch) is past expiration
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+}
+
+/// Token signed by outgoing signing key port
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SignedToken(String);
+
+impl SignedToken {
+    fn new<S: Into<String>>(s: S) -> Self {
+        Self(s.into())
+    }
+}
+
+impl TryFrom<String> for SignedToken {
+    type Error = Error;
+
+    fn try_from(value: String) -> crate::Result<Self> {
+        value.as_str().parse()
+    }
+}
+
+impl FromStr for SignedToken {
+    type Err = Error;
+
+    fn from_str(value: &str) -> crate::Result<Self> {
+        if value.trim().is_empty() {
+            return Err(Error::DomainError("`token` not be empty".to_string()));
+        }
+        Ok(Self::new(value.trim()))
+    }
+}
+
+impl AsRef<str> for SignedToken {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl std::fmt::Debug for SignedToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SignedToken(**********)")
+    }
+}
+
+impl Display for SignedToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "**********")
+    }
+}
+
+/// Access and refresh tokens returned on login
+#[derive(new, Debug, Clone, PartialEq, Eq)]
+pub struct TokenPair {
+    access_token: SignedToken,
+    refresh_token: SignedToken,
+    /// Seconds since unix epoch when access token expires
+    expires_at: u64,
+}
+
+impl TokenPair {
+    pub fn access_token(&self) -> &SignedToken {
+        &self.access_token
+    }
+
+    pub fn refresh_token(&self) -> &SignedToken {
+        &self.refresh_token
+    }
+
+    pub fn expires_at(&self) -> u64 {
+        self.expires_at
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -545,4 +738,39 @@ mod tests {
         let result = Credential::new("validUser", "12345");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_token_type_round_trip() {
+        for token_type in [TokenType::Access, TokenType::Refresh] {
+            let parsed: TokenType = token_type.to_string().parse().unwrap();
+            assert_eq!(parsed, token_type);
+        }
+        assert!("bearer".parse::<TokenType>().is_err());
+    }
+
+    #[test]
+    fn test_claims_expired() {
+        let claims = Claims::new(
+            "validUser".parse().unwrap(),
+            TokenType::Access,
+            Roles::default(),
+            100,
+            200,
+        );
+        assert!(!claims.is_expired(199));
+        assert!(claims.is_expired(200));
+    }
+
+    #[test]
+    fn test_signed_token_empty() {
+        let result = SignedToken::from_str("  ");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_signed_token_hidden() {
+        let token = SignedToken::from_str("header.payload.sig").unwrap();
+        assert_eq!(token.to_string(), "**********");
+        assert_eq!(token.as_ref(), "header.payload.sig");
+    }
 }
@@ -20,6 +20,18 @@ pub enum Error {
     InvalidPassword,
     #[error("not found secret")]
     SecretNotFound,
+    #[error("invalid token")]
+    InvalidToken,
+    #[error("expired token")]
+    ExpiredToken,
+    #[error("signing error {0}")]
+    Signing(String),
+    #[error("user already exists")]
+    UserAlreadyExists,
+    #[error("not found user")]
+    UserNotFound,
+    #[error("kubernetes error {0}")]
+    Kubernetes(String),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -18,6 +18,9 @@ pub use error::*;
 pub mod domain;
 pub use domain::*;
 
+pub mod policy;
+pub use policy::*;
+
 pub mod service;
 pub use service::*;
 
@@ -0,0 +1,244 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::Error;
+
+/// Separator between grants on `paastel.io/roles` annotation
+const GRANT_SEPARATOR: char = ',';
+
+/// Separator between role and namespace of a grant, ex: `developer:team-a`
+const SCOPE_SEPARATOR: char = ':';
+
+/// What a request wants to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    /// List and get resources
+    Read,
+    /// Create, update and delete applications
+    Write,
+    /// Cluster wide operations like users management
+    Manage,
+}
+
+/// Role of user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Admin,
+    Developer,
+    Viewer,
+}
+
+impl Role {
+    /// Return true when role allow action, scope is not considered
+    pub fn allows(&self, action: Action) -> bool {
+        match self {
+            Self::Admin => true,
+            Self::Developer => matches!(action, Action::Read | Action::Write),
+            Self::Viewer => matches!(action, Action::Read),
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = Error;
+
+    fn from_str(value: &str) -> crate::Result<Self> {
+        match value.trim() {
+            "admin" => Ok(Self::Admin),
+            "developer" => Ok(Self::Developer),
+            "viewer" => Ok(Self::Viewer),
+            _ => Err(Error::DomainError(format!("`role` unknown {value}"))),
+        }
+    }
+}
+
+impl AsRef<str> for Role {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Admin => "admin",
+            Self::Developer => "developer",
+            Self::Viewer => "viewer",
+        }
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+/// Role granted on one namespace or on all when namespace is none
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RoleGrant {
+    role: Role,
+    namespace: Option<String>,
+}
+
+impl RoleGrant {
+    pub fn new(role: Role, namespace: Option<String>) -> Self {
+        Self { role, namespace }
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    /// Return true when grant allow action on target namespace, cluster wide
+    /// targets (namespace none) are only allowed for grants without scope
+    pub fn allows(&self, action: Action, namespace: Option<&str>) -> bool {
+        let in_scope = match (self.namespace(), namespace) {
+            (None, _) => true,
+            (Some(scope), Some(target)) => scope == target,
+            (Some(_), None) => false,
+        };
+        in_scope && self.role.allows(action)
+    }
+}
+
+impl FromStr for RoleGrant {
+    type Err = Error;
+
+    fn from_str(value: &str) -> crate::Result<Self> {
+        match value.trim().split_once(SCOPE_SEPARATOR) {
+            Some((role, namespace)) if !namespace.trim().is_empty() => {
+                Ok(Self::new(role.parse()?, Some(namespace.trim().to_string())))
+            }
+            Some(_) => Err(Error::DomainError(format!(
+                "`role` namespace not be empty {value}"
+            ))),
+            None => Ok(Self::new(value.parse()?, None)),
+        }
+    }
+}
+
+impl Display for RoleGrant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.namespace() {
+            Some(namespace) => {
+                write!(f, "{}{SCOPE_SEPARATOR}{namespace}", self.role)
+            }
+            None => write!(f, "{}", self.role),
+        }
+    }
+}
+
+/// Roles of user as found on `paastel.io/roles` annotation,
+/// ex: `admin` or `developer:team-a,viewer:team-b`
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Roles(Vec<RoleGrant>);
+
+impl Roles {
+    pub fn new(grants: Vec<RoleGrant>) -> Self {
+        Self(grants)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, RoleGrant> {
+        self.0.iter()
+    }
+
+    /// Policy check, any grant allowing action is enough
+    pub fn is_allowed(&self, action: Action, namespace: Option<&str>) -> bool {
+        self.iter().any(|grant| grant.allows(action, namespace))
+    }
+}
+
+impl FromStr for Roles {
+    type Err = Error;
+
+    fn from_str(value: &str) -> crate::Result<Self> {
+        let grants = value
+            .split(GRANT_SEPARATOR)
+            .filter(|grant| !grant.trim().is_empty())
+            .map(str::parse)
+            .collect::<crate::Result<Vec<RoleGrant>>>()?;
+        Ok(Self(grants))
+    }
+}
+
+impl Display for Roles {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let grants: Vec<String> =
+            self.iter().map(|grant| grant.to_string()).collect();
+        write!(f, "{}", grants.join(&GRANT_SEPARATOR.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roles_parse() {
+        let roles: Roles = "admin, developer:team-a".parse().unwrap();
+        assert_eq!(
+            roles,
+            Roles::new(vec![
+                RoleGrant::new(Role::Admin, None),
+                RoleGrant::new(Role::Developer, Some("team-a".to_string())),
+            ])
+        );
+        assert_eq!(roles.to_string(), "admin,developer:team-a");
+    }
+
+    #[test]
+    fn test_roles_parse_empty() {
+        let roles: Roles = "".parse().unwrap();
+        assert!(roles.is_empty());
+    }
+
+    #[test]
+    fn test_roles_parse_unknown() {
+        assert!("root".parse::<Roles>().is_err());
+        assert!("developer:".parse::<Roles>().is_err());
+    }
+
+    #[test]
+    fn test_admin_allowed() {
+        let roles: Roles = "admin".parse().unwrap();
+        assert!(roles.is_allowed(Action::Manage, None));
+        assert!(roles.is_allowed(Action::Write, Some("team-a")));
+    }
+
+    #[test]
+    fn test_developer_scope() {
+        let roles: Roles = "developer:team-a".parse().unwrap();
+        assert!(roles.is_allowed(Action::Write, Some("team-a")));
+        assert!(!roles.is_allowed(Action::Write, Some("team-b")));
+        assert!(!roles.is_allowed(Action::Read, None));
+        assert!(!roles.is_allowed(Action::Manage, Some("team-a")));
+    }
+
+    #[test]
+    fn test_viewer_read_only() {
+        let roles: Roles = "viewer".parse().unwrap();
+        assert!(roles.is_allowed(Action::Read, Some("team-a")));
+        assert!(!roles.is_allowed(Action::Write, Some("team-a")));
+    }
+
+    #[test]
+    fn test_no_roles_denied() {
+        assert!(!Roles::default().is_allowed(Action::Read, Some("team-a")));
+    }
+}
@@ -17,8 +17,8 @@ use async_trait::async_trait;
 use mockall::automock;
 
 use crate::{
-    Credential, Password, PasswordHash, RetrievePassword, SecretLabel,
-    UserSecret,
+    Claims, Credential, NewUser, Password, PasswordHash, RetrievePassword,
+    SecretLabel, SignedToken, TokenPair, UserSecret, UserSecrets, Username,
 };
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -36,6 +36,74 @@ pub trait ValidateCredentialUseCase {
     ) -> crate::Result<UserSecret>;
 }
 
+/// # Issue token use case
+///
+/// Incoming port, exchange a credential for access and refresh tokens
+#[async_trait]
+pub trait LoginUseCase {
+    async fn login(&self, credential: &Credential) -> crate::Result<TokenPair>;
+}
+
+/// # Refresh token use case
+///
+/// Incoming port, exchange a refresh token for a new token pair
+#[async_trait]
+pub trait RefreshTokenUseCase {
+    async fn refresh(
+        &self,
+        refresh_token: &SignedToken,
+    ) -> crate::Result<TokenPair>;
+}
+
+/// # Validate token use case
+///
+/// Incoming port, check signature and expiration of an access token
+#[async_trait]
+pub trait ValidateTokenUseCase {
+    async fn validate_token(
+        &self,
+        access_token: &SignedToken,
+    ) -> crate::Result<Claims>;
+}
+
+/// # Create user use case
+///
+/// Incoming port
+#[async_trait]
+pub trait CreateUserUseCase {
+    async fn create_user(
+        &self,
+        new_user: &NewUser,
+    ) -> crate::Result<UserSecret>;
+}
+
+/// # List users use case
+///
+/// Incoming port
+#[async_trait]
+pub trait ListUsersUseCase {
+    async fn list_users(&self) -> crate::Result<UserSecrets>;
+}
+
+/// # Change password use case
+///
+/// Incoming port, credential carry username and the new password
+#[async_trait]
+pub trait ChangePasswordUseCase {
+    async fn change_password(
+        &self,
+        credential: &Credential,
+    ) -> crate::Result<()>;
+}
+
+/// # Delete user use case
+///
+/// Incoming port
+#[async_trait]
+pub trait DeleteUserUseCase {
+    async fn delete_user(&self, username: &Username) -> crate::Result<()>;
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 // Ports Outgoing
 ///////////////////////////////////////////////////////////////////////////////
@@ -48,6 +116,27 @@ pub trait OutgoingKubernetesPort {
         &self,
         label: &SecretLabel,
     ) -> crate::Result<crate::UserSecrets>;
+
+    /// Create a new secret with label holding user secret
+    async fn create_secret(
+        &self,
+        label: &SecretLabel,
+        user_secret: &UserSecret,
+    ) -> crate::Result<()>;
+
+    /// Replace password hash and roles of secret with same username
+    async fn update_secret(
+        &self,
+        label: &SecretLabel,
+        user_secret: &UserSecret,
+    ) -> crate::Result<()>;
+
+    /// Delete secret with label holding username
+    async fn delete_secret(
+        &self,
+        label: &SecretLabel,
+        username: &Username,
+    ) -> crate::Result<()>;
 }
 
 pub type OutKubernetesPort = Box<dyn OutgoingKubernetesPort + Send + Sync>;
@@ -65,7 +154,22 @@ where
         password_text: &T,
         password_hash: &H,
     ) -> crate::Result<()>;
+
+    /// Hash password text using a fresh salt
+    async fn hash(&self, password_text: &T) -> crate::Result<PasswordHash>;
 }
 
 pub type OutArgon2Port<T, H> =
     Box<dyn OutgoingArgon2HashPort<T, H> + Send + Sync>;
+
+/// Outgoing port to sign and verify tokens
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait OutgoingSigningKeyPort {
+    async fn sign(&self, claims: &Claims) -> crate::Result<SignedToken>;
+
+    /// Check signature and return claims, expiration is not checked here
+    async fn verify(&self, token: &SignedToken) -> crate::Result<Claims>;
+}
+
+pub type OutSigningKeyPort = Box<dyn OutgoingSigningKeyPort + Send + Sync>;
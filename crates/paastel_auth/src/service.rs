@@ -12,19 +12,31 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use async_trait::async_trait;
 use derive_new::new;
 
 use crate::{
-    Credential, Error, OutArgon2Port, OutKubernetesPort, SecretLabel,
-    UserSecret, ValidateCredentialUseCase,
+    ChangePasswordUseCase, Claims, CreateUserUseCase, Credential,
+    DeleteUserUseCase, Error, ListUsersUseCase, LoginUseCase, NewUser,
+    OutArgon2Port, OutKubernetesPort, OutSigningKeyPort, RefreshTokenUseCase,
+    Roles, SecretLabel, SignedToken, TokenPair, TokenType, UserSecret,
+    UserSecrets, Username, ValidateCredentialUseCase, ValidateTokenUseCase,
 };
 
+/// Default lifetime of access token
+pub const ACCESS_TOKEN_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Default lifetime of refresh token
+pub const REFRESH_TOKEN_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
 /// # AuthService
 ///
-/// This service implement use cases from authentication
+/// This service implement use cases from authentication and users management
 #[derive(new)]
 pub struct AuthService {
     kubernetes_port: OutKubernetesPort,
@@ -34,6 +46,15 @@ pub struct AuthService {
 pub type ArcValidateCredentialUseCase =
     Arc<dyn ValidateCredentialUseCase + Send + Sync>;
 
+pub type ArcCreateUserUseCase = Arc<dyn CreateUserUseCase + Send + Sync>;
+
+pub type ArcListUsersUseCase = Arc<dyn ListUsersUseCase + Send + Sync>;
+
+pub type ArcChangePasswordUseCase =
+    Arc<dyn ChangePasswordUseCase + Send + Sync>;
+
+pub type ArcDeleteUserUseCase = Arc<dyn DeleteUserUseCase + Send + Sync>;
+
 #[async_trait]
 impl ValidateCredentialUseCase for AuthService {
     async fn validate_credential(
@@ -70,15 +91,235 @@ impl ValidateCredentialUseCase for AuthService {
     }
 }
 
+#[async_trait]
+impl CreateUserUseCase for AuthService {
+    async fn create_user(
+        &self,
+        new_user: &NewUser,
+    ) -> crate::Result<UserSecret> {
+        let username = new_user.username();
+
+        tracing::info!(?username, "create user on PaaStel cluster");
+
+        let label = SecretLabel::default();
+        let secrets =
+            self.kubernetes_port.find_secrets_by_label(&label).await?;
+        if secrets.find(username).is_some() {
+            tracing::error!(?username, "username already exists");
+            return Err(Error::UserAlreadyExists);
+        }
+
+        let password = self.password_port.hash(new_user.credential()).await?;
+        let user_secret = UserSecret::new(
+            username.clone(),
+            password,
+            new_user.roles().clone(),
+        );
+        self.kubernetes_port
+            .create_secret(&label, &user_secret)
+            .await?;
+        Ok(user_secret)
+    }
+}
+
+#[async_trait]
+impl ListUsersUseCase for AuthService {
+    async fn list_users(&self) -> crate::Result<UserSecrets> {
+        let label = SecretLabel::default();
+        self.kubernetes_port.find_secrets_by_label(&label).await
+    }
+}
+
+#[async_trait]
+impl ChangePasswordUseCase for AuthService {
+    async fn change_password(
+        &self,
+        credential: &Credential,
+    ) -> crate::Result<()> {
+        let username = credential.username();
+
+        tracing::info!(?username, "change password on PaaStel cluster");
+
+        let label = SecretLabel::default();
+        let secrets =
+            self.kubernetes_port.find_secrets_by_label(&label).await?;
+        let user_secret = secrets.find(username).ok_or(Error::UserNotFound)?;
+
+        let password = self.password_port.hash(credential).await?;
+        self.kubernetes_port
+            .update_secret(&label, &user_secret.with_password(password))
+            .await
+    }
+}
+
+#[async_trait]
+impl DeleteUserUseCase for AuthService {
+    async fn delete_user(&self, username: &Username) -> crate::Result<()> {
+        tracing::info!(?username, "delete user on PaaStel cluster");
+
+        let label = SecretLabel::default();
+        let secrets =
+            self.kubernetes_port.find_secrets_by_label(&label).await?;
+        if secrets.find(username).is_none() {
+            return Err(Error::UserNotFound);
+        }
+
+        self.kubernetes_port.delete_secret(&label, username).await
+    }
+}
+
+/// # TokenService
+///
+/// This service implement use cases from token based sessions, credentials
+/// are only checked on login, later requests just verify token signature
+#[derive(new)]
+pub struct TokenService {
+    credential: ArcValidateCredentialUseCase,
+    users: ArcListUsersUseCase,
+    signing_port: OutSigningKeyPort,
+    #[new(value = "ACCESS_TOKEN_TTL")]
+    access_ttl: Duration,
+    #[new(value = "REFRESH_TOKEN_TTL")]
+    refresh_ttl: Duration,
+}
+
+pub type ArcLoginUseCase = Arc<dyn LoginUseCase + Send + Sync>;
+
+pub type ArcRefreshTokenUseCase = Arc<dyn RefreshTokenUseCase + Send + Sync>;
+
+pub type ArcValidateTokenUseCase = Arc<dyn ValidateTokenUseCase + Send + Sync>;
+
+impl TokenService {
+    /// Override default lifetime of access and refresh tokens
+    pub fn with_ttl(
+        mut self,
+        access_ttl: Duration,
+        refresh_ttl: Duration,
+    ) -> Self {
+        self.access_ttl = access_ttl;
+        self.refresh_ttl = refresh_ttl;
+        self
+    }
+
+    async fn issue(
+        &self,
+        username: &Username,
+        roles: &Roles,
+    ) -> crate::Result<TokenPair> {
+        let now = now();
+        let access_expires_at = now + self.access_ttl.as_secs();
+        let access = Claims::new(
+            username.clone(),
+            TokenType::Access,
+            roles.clone(),
+            now,
+            access_expires_at,
+        );
+        let refresh = Claims::new(
+            username.clone(),
+            TokenType::Refresh,
+            roles.clone(),
+            now,
+            now + self.refresh_ttl.as_secs(),
+        );
+
+        Ok(TokenPair::new(
+            self.signing_port.sign(&access).await?,
+            self.signing_port.sign(&refresh).await?,
+            access_expires_at,
+        ))
+    }
+
+    async fn verify(
+        &self,
+        token: &SignedToken,
+        token_type: TokenType,
+    ) -> crate::Result<Claims> {
+        let claims = self.signing_port.verify(token).await?;
+
+        if claims.token_type() != token_type {
+            tracing::debug!(?token_type, "unexpected token type");
+            return Err(Error::InvalidToken);
+        }
+
+        if claims.is_expired(now()) {
+            tracing::debug!(subject = ?claims.subject(), "token expired");
+            return Err(Error::ExpiredToken);
+        }
+
+        Ok(claims)
+    }
+}
+
+#[async_trait]
+impl LoginUseCase for TokenService {
+    async fn login(&self, credential: &Credential) -> crate::Result<TokenPair> {
+        let user_secret =
+            self.credential.validate_credential(credential).await?;
+
+        tracing::info!(username = ?user_secret.username(), "issue token pair");
+
+        self.issue(user_secret.username(), user_secret.roles())
+            .await
+    }
+}
+
+#[async_trait]
+impl RefreshTokenUseCase for TokenService {
+    async fn refresh(
+        &self,
+        refresh_token: &SignedToken,
+    ) -> crate::Result<TokenPair> {
+        let claims = self.verify(refresh_token, TokenType::Refresh).await?;
+
+        let username = claims.subject();
+
+        tracing::info!(?username, "refresh token pair");
+
+        // NOTE: user may be deleted or its roles changed since login
+        let users = self.users.list_users().await?;
+        let Some(user_secret) = users.find(username) else {
+            tracing::debug!(?username, "user of refresh token not found");
+            return Err(Error::InvalidToken);
+        };
+        self.issue(user_secret.username(), user_secret.roles())
+            .await
+    }
+}
+
+#[async_trait]
+impl ValidateTokenUseCase for TokenService {
+    async fn validate_token(
+        &self,
+        access_token: &SignedToken,
+    ) -> crate::Result<Claims> {
+        self.verify(access_token, TokenType::Access).await
+    }
+}
+
+/// Seconds since unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use mockall::predicate::eq;
 
     use crate::{
-        AuthService, Credential, MockOutgoingArgon2HashPort,
-        MockOutgoingKubernetesPort, OutKubernetesPort, OutgoingArgon2HashPort,
-        PasswordHash, SecretLabel, UserSecret, UserSecrets, Username,
-        ValidateCredentialUseCase,
+        AuthService, ChangePasswordUseCase, Claims, CreateUserUseCase,
+        Credential, DeleteUserUseCase, Error, LoginUseCase,
+        MockOutgoingArgon2HashPort, MockOutgoingKubernetesPort,
+        MockOutgoingSigningKeyPort, NewUser, OutKubernetesPort,
+        OutgoingArgon2HashPort, PasswordHash, RefreshTokenUseCase,
+        RetrievePassword, Roles, SecretLabel, SignedToken, TokenService,
+        TokenType, UserSecret, UserSecrets, Username,
+        ValidateCredentialUseCase, ValidateTokenUseCase,
     };
 
     #[tokio::test]
@@ -90,6 +331,7 @@ mod tests {
         let user_secret = UserSecret::new(
             "username".parse::<Username>()?,
             "password_hashed".parse::<PasswordHash>()?,
+            Roles::default(),
         );
         let password_port = new_password_port(credential.clone(), user_secret)?;
 
@@ -100,6 +342,235 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn token_service_login_ok() -> crate::Result<()> {
+        let kube_port =
+            new_kube_port(SecretLabel::default(), "password_hashed")?;
+        let credential = Credential::new("username", "password_text")?;
+        let user_secret = UserSecret::new(
+            "username".parse::<Username>()?,
+            "password_hashed".parse::<PasswordHash>()?,
+            Roles::default(),
+        );
+        let password_port = new_password_port(credential.clone(), user_secret)?;
+        let auth_service = AuthService::new(kube_port, Box::new(password_port));
+
+        let mut signing_port = MockOutgoingSigningKeyPort::new();
+        signing_port.expect_sign().times(2).returning(|claims| {
+            format!("{}.{}", claims.subject(), claims.token_type()).parse()
+        });
+
+        let auth_service = Arc::new(auth_service);
+        let token_service = TokenService::new(
+            auth_service.clone(),
+            auth_service,
+            Box::new(signing_port),
+        );
+        let pair = token_service.login(&credential).await?;
+
+        assert_eq!(pair.access_token().as_ref(), "username.access");
+        assert_eq!(pair.refresh_token().as_ref(), "username.refresh");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn token_service_validate_expired() -> crate::Result<()> {
+        let token_service = new_token_service(
+            TokenType::Access,
+            0,
+            Box::new(MockOutgoingKubernetesPort::new()),
+        )?;
+        let result = token_service
+            .validate_token(&"token".parse::<SignedToken>()?)
+            .await;
+        assert!(matches!(result, Err(Error::ExpiredToken)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn token_service_validate_refresh_as_access() -> crate::Result<()> {
+        let token_service = new_token_service(
+            TokenType::Refresh,
+            u64::MAX,
+            Box::new(MockOutgoingKubernetesPort::new()),
+        )?;
+        let result = token_service
+            .validate_token(&"token".parse::<SignedToken>()?)
+            .await;
+        assert!(matches!(result, Err(Error::InvalidToken)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn token_service_refresh_ok() -> crate::Result<()> {
+        let mut kube_port = Box::new(MockOutgoingKubernetesPort::new());
+        kube_port
+            .expect_find_secrets_by_label()
+            .times(1)
+            .returning(|_| {
+                Ok(UserSecrets::new(vec![UserSecret::new(
+                    "username".parse::<Username>()?,
+                    "password_hashed".parse::<PasswordHash>()?,
+                    "admin".parse::<Roles>()?,
+                )]))
+            });
+        let token_service =
+            new_token_service(TokenType::Refresh, u64::MAX, kube_port)?;
+        let pair = token_service
+            .refresh(&"token".parse::<SignedToken>()?)
+            .await?;
+        // NOTE: roles of user secret replace the ones of refresh token
+        assert_eq!(pair.access_token().as_ref(), "username.access.admin");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn token_service_refresh_deleted_user() -> crate::Result<()> {
+        let mut kube_port = Box::new(MockOutgoingKubernetesPort::new());
+        kube_port
+            .expect_find_secrets_by_label()
+            .times(1)
+            .returning(|_| Ok(UserSecrets::new(vec![])));
+        let token_service =
+            new_token_service(TokenType::Refresh, u64::MAX, kube_port)?;
+        let result = token_service
+            .refresh(&"token".parse::<SignedToken>()?)
+            .await;
+        assert!(matches!(result, Err(Error::InvalidToken)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_user_ok() -> crate::Result<()> {
+        let mut kube_port = Box::new(MockOutgoingKubernetesPort::new());
+        kube_port
+            .expect_find_secrets_by_label()
+            .times(1)
+            .returning(|_| Ok(UserSecrets::new(vec![])));
+        kube_port
+            .expect_create_secret()
+            .withf(|label, us| {
+                label == &SecretLabel::default()
+                    && us.username().as_ref() == "username"
+                    && us.password().as_ref() == "password_hashed"
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let mut password_port = MockOutgoingArgon2HashPort::new();
+        password_port
+            .expect_hash()
+            .times(1)
+            .returning(|_| "password_hashed".parse());
+
+        let auth_service = AuthService::new(kube_port, Box::new(password_port));
+        let new_user = NewUser::new(
+            Credential::new("username", "password_text")?,
+            "developer".parse::<Roles>()?,
+        );
+        let user_secret = auth_service.create_user(&new_user).await?;
+        assert_eq!(user_secret.roles(), &"developer".parse::<Roles>()?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_user_already_exists() -> crate::Result<()> {
+        let kube_port =
+            new_kube_port(SecretLabel::default(), "password_hashed")?;
+        let auth_service = AuthService::new(
+            kube_port,
+            Box::new(MockOutgoingArgon2HashPort::new()),
+        );
+        let new_user = NewUser::new(
+            Credential::new("username", "password_text")?,
+            Roles::default(),
+        );
+        let result = auth_service.create_user(&new_user).await;
+        assert!(matches!(result, Err(Error::UserAlreadyExists)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn change_password_ok() -> crate::Result<()> {
+        let mut kube_port_mock = MockOutgoingKubernetesPort::new();
+        kube_port_mock
+            .expect_find_secrets_by_label()
+            .times(1)
+            .returning(|_| {
+                Ok(UserSecrets::new(vec![UserSecret::new(
+                    "username".parse::<Username>()?,
+                    "password_hashed".parse::<PasswordHash>()?,
+                    Roles::default(),
+                )]))
+            });
+        kube_port_mock
+            .expect_update_secret()
+            .withf(|_, us| us.password().as_ref() == "new_password_hashed")
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let mut password_port = MockOutgoingArgon2HashPort::new();
+        password_port
+            .expect_hash()
+            .times(1)
+            .returning(|_| "new_password_hashed".parse());
+
+        let auth_service =
+            AuthService::new(Box::new(kube_port_mock), Box::new(password_port));
+        let credential = Credential::new("username", "new_password")?;
+        auth_service.change_password(&credential).await
+    }
+
+    #[tokio::test]
+    async fn delete_user_not_found() -> crate::Result<()> {
+        let kube_port =
+            new_kube_port(SecretLabel::default(), "password_hashed")?;
+        let auth_service = AuthService::new(
+            kube_port,
+            Box::new(MockOutgoingArgon2HashPort::new()),
+        );
+        let result = auth_service
+            .delete_user(&"unknown".parse::<Username>()?)
+            .await;
+        assert!(matches!(result, Err(Error::UserNotFound)));
+        Ok(())
+    }
+
+    /// Token service verifying any token as claims of `username` with
+    /// `developer` role, tokens are signed as `{subject}.{type}.{roles}`
+    fn new_token_service(
+        token_type: TokenType,
+        expires_at: u64,
+        kube_port: OutKubernetesPort,
+    ) -> crate::Result<TokenService> {
+        let auth_service = Arc::new(AuthService::new(
+            kube_port,
+            Box::new(MockOutgoingArgon2HashPort::new()),
+        ));
+        let mut signing_port = MockOutgoingSigningKeyPort::new();
+        signing_port.expect_verify().times(1).returning(move |_| {
+            Ok(Claims::new(
+                "username".parse::<Username>()?,
+                token_type,
+                "developer".parse::<Roles>()?,
+                0,
+                expires_at,
+            ))
+        });
+        signing_port.expect_sign().returning(|claims| {
+            format!(
+                "{}.{}.{}",
+                claims.subject(),
+                claims.token_type(),
+                claims.roles()
+            )
+            .parse()
+        });
+        Ok(TokenService::new(
+            auth_service.clone(),
+            auth_service,
+            Box::new(signing_port),
+        ))
+    }
+
     fn new_kube_port(
         label: SecretLabel,
         password_hashed: &'static str,
@@ -113,6 +584,7 @@ mod tests {
                 Ok(UserSecrets::new(vec![UserSecret::new(
                     "username".parse::<Username>()?,
                     password_hashed.parse::<PasswordHash>()?,
+                    Roles::default(),
                 )]))
             });
         Ok(kube_port)
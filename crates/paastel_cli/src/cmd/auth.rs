@@ -12,105 +12,41 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-// use std::time::Duration;
-
-use std::fmt::Display;
-
-use clap::{Arg, ArgAction, ArgMatches, Command};
-use derive_new::new;
-use requestty::Question;
-// use reqwest::ClientBuilder;
-
-use crate::{error::Error, util::flag};
-
-// Name your user agent after your app?
-// static APP_USER_AGENT: &str =
-// concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
-
-#[derive(new)]
-pub struct Username(String);
-
-impl Display for Username {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "{}", self.0)
-    }
-}
-
-impl AsRef<str> for Username {
-    fn as_ref(&self) -> &str {
-        self.0.as_str()
-    }
-}
-
-/// Password encoded
-#[derive(new)]
-pub struct PasswordEncoded(String);
-
-impl Display for PasswordEncoded {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "{}", self.0)
-    }
-}
-
-impl AsRef<str> for PasswordEncoded {
-    fn as_ref(&self) -> &str {
-        self.0.as_str()
-    }
-}
-
-impl AsRef<[u8]> for PasswordEncoded {
-    fn as_ref(&self) -> &[u8] {
-        self.0.as_bytes()
-    }
-}
-
-#[derive(new)]
-pub struct PasswordDecoded(String);
-
-impl PasswordDecoded {
-    pub fn show_sensitive(&self) -> &str {
-        self.0.as_str()
-    }
-}
-
-impl Display for PasswordDecoded {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "*******")
-    }
-}
-
-impl AsRef<str> for PasswordDecoded {
-    fn as_ref(&self) -> &str {
-        self.0.as_str()
-    }
-}
-
-impl AsRef<[u8]> for PasswordDecoded {
-    fn as_ref(&self) -> &[u8] {
-        self.0.as_bytes()
-    }
-}
-
-// impl From<PasswordDecoded> for PasswordEncoded {
-//     fn from(_value: PasswordDecoded) -> Self {
-//         todo!()
-//     }
-// }
-
-#[derive(new)]
-pub struct Credential {
-    username: Username,
-    password: PasswordEncoded,
-}
-
-impl Credential {
-    pub fn username(&self) -> &Username {
-        &self.username
-    }
-
-    pub fn password(&self) -> &PasswordEncoded {
-        &self.password
-    }
+use std::{
+    path::PathBuf,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use dialoguer::{Input, Password};
+use paastel_client::{api_url, Auth, Client, Tokens};
+use paastel_settings::{
+    Context, CredentialRef, Namespace, SecretStore, Settings, Token,
+};
+use tokio::task::block_in_place;
+use url::Url;
+
+use crate::{
+    error::Error,
+    util::{
+        self,
+        credential::{self, CredentialStore, KeyringStore},
+        opt,
+    },
+};
+
+/// Access token is refreshed this earlier than its expiry
+const EXPIRY_MARGIN: u64 = 30;
+
+/// Tokens issued by server as kept on credential store
+fn into_token(tokens: Tokens) -> Token {
+    Token::new(
+        tokens.access_token().to_string(),
+        tokens.expiry(),
+        tokens.refresh_token().to_string(),
+        tokens.token_type().to_string(),
+    )
 }
 
 pub fn command() -> Command {
@@ -119,6 +55,14 @@ pub fn command() -> Command {
     );
     Command::new("login")
         .about("PaaStel login to the server at <url>")
+        .long_about(
+            "The login command verifies the credentials against the PaaStel \
+            server at <url> and saves the url and username as a context \
+            of settings, used by next commands, \
+            the tokens issued by the server are kept on the keyring or on \
+            a file encrypted with a passphrase, settings only reference \
+            them. Missing username or password are prompted",
+        )
         .override_usage(usage)
         .arg(
             Arg::new("url")
@@ -127,105 +71,245 @@ pub fn command() -> Command {
                 .required(true),
         )
         .arg(
-            flag("username", "Username that will be used to login")
+            opt("username", "Username that will be used to login")
                 .env("PAASTEL_USERNAME"),
         )
         .arg(
-            flag("password", "Password that will be used to login")
-                .env("PAASTEL_PASSWORD"),
+            opt("password", "Password that will be used to login")
+                .env("PAASTEL_PASSWORD")
+                .hide_env_values(true),
+        )
+        .arg(opt(
+            "context",
+            "Name of context saved on settings, default host of url",
+        ))
+        .arg(opt(
+            "namespace",
+            "Namespace of applications on context, default `paastel-space`",
+        ))
+        .arg(
+            opt(
+                "ca-bundle",
+                "PEM bundle of certificate authorities trusted for url",
+            )
+            .value_parser(value_parser!(PathBuf)),
         )
+        .arg(
+            opt(
+                "credential-store",
+                "Where tokens are saved, `keyring` or `file` encrypted with \
+                a passphrase, default keyring when available",
+            )
+            .value_parser(SecretStore::from_str),
+        )
+}
+
+pub async fn login(
+    matches: &ArgMatches,
+    settings: &Settings,
+) -> Result<(), Error> {
+    let api =
+        api_url(matches.get_one::<String>("url").expect("url is required"))?;
+    let username = match matches.get_one::<String>("username") {
+        Some(username) => username.clone(),
+        None => Input::new().with_prompt("Username").interact_text()?,
+    };
+    if username.trim().is_empty() {
+        return Err(Error::Unauthorized("username is empty".to_string()));
+    }
+    let password = match matches.get_one::<String>("password") {
+        Some(password) => password.clone(),
+        None => Password::new().with_prompt("Password").interact()?,
+    };
+
+    let name = match matches.get_one::<String>("context") {
+        Some(name) => name.clone(),
+        None => api.host_str().unwrap_or("local").to_string(),
+    };
+    let mut context = match settings.contexts().get(&name) {
+        Some(context) => context.clone().with_api(api.as_str()),
+        None => Context::new(api.to_string()),
+    }
+    .with_wss(wss_url(&api)?.as_str());
+    if let Some(namespace) = matches.get_one::<String>("namespace") {
+        context = context.with_namespace(Namespace::new(namespace.clone()));
+    }
+    if let Some(ca_bundle) = matches.get_one::<PathBuf>("ca-bundle") {
+        context = context.with_ca_bundle(ca_bundle.canonicalize()?);
+    }
+
+    let client = util::client(api.as_str(), context.ca_bundle())?;
+    let token = match client.login(&username, &password).await {
+        Err(e) if e.status() == Some(401) => {
+            return Err(Error::Unauthorized(
+                "invalid username or password".to_string(),
+            ))
+        }
+        result => into_token(result?),
+    };
+
+    let me = match client
+        .with_auth(Auth::Bearer(token.access_token().to_string()))
+        .me()
+        .await
+    {
+        Err(e) if e.status() == Some(401) => {
+            return Err(Error::Unauthorized(
+                "token issued on login was rejected".to_string(),
+            ))
+        }
+        result => result?,
+    };
+
+    let key = format!("{}@{name}", me.username());
+    let store = matches.get_one::<SecretStore>("credential-store").copied();
+    let credential = store_token(settings, store, &key, &token)?;
+    if let Some(previous) = context
+        .credential()
+        .filter(|previous| *previous != &credential)
+    {
+        forget(settings, previous);
+    }
+
+    let mut settings = settings.clone();
+    settings.set_context(
+        name.as_str(),
+        context.with_login(me.username(), credential),
+    );
+    settings.use_context(&name);
+    settings
+        .save()
+        .map_err(|e| Error::Settings(e.to_string()))?;
+
+    println!("logged in to {api} as {}, context {name}", me.username());
+    Ok(())
 }
 
-fn is_valid(password: &str, _: &requestty::Answers) -> bool {
-    password.contains(|c: char| c.is_ascii_digit())
-        && password.contains(char::is_alphabetic)
+/// Client of api on settings, authenticated with tokens of login kept on
+/// credential store, refreshed when expired
+pub(crate) async fn client(settings: &Settings) -> Result<Client, Error> {
+    let api = settings.api().ok_or_else(|| {
+        Error::Settings("api url missing, run `paastel login`".to_string())
+    })?;
+    let client = util::client(api, settings.ca_bundle())?;
+    if let Some(token) = fresh_token(&client, settings).await? {
+        return Ok(
+            client.with_auth(Auth::Bearer(token.access_token().to_string()))
+        );
+    }
+    // NOTE: settings never hold passwords, without tokens of login the
+    // client is anonymous
+    if let Some(username) = settings.username() {
+        tracing::warn!("no credential of {username}, run `paastel login`");
+    }
+    Ok(client)
 }
 
-fn letter_and_numbers(
-    password: &str,
-    ans: &requestty::Answers,
-) -> Result<(), String> {
-    if is_valid(password, ans) {
-        Ok(())
-    } else {
-        Err("Password needs to have at least 1 letter and 1 number.".to_owned())
+/// Tokens of settings, refreshed and saved when access token expired,
+/// `None` when settings have no tokens
+async fn fresh_token(
+    client: &Client,
+    settings: &Settings,
+) -> Result<Option<Token>, Error> {
+    let Some(credential) = settings.credential() else {
+        return Ok(None);
+    };
+    let store = credential::open(credential.store(), settings)?;
+    let secret =
+        block_in_place(|| store.get(credential.key()))?.ok_or_else(|| {
+            Error::Credential(format!(
+                "{credential} missing, run `paastel login`"
+            ))
+        })?;
+    let token: Token = serde_json::from_str(&secret)
+        .map_err(|e| Error::Credential(format!("{credential} {e}")))?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default();
+    if !token.is_expired(now + EXPIRY_MARGIN) {
+        return Ok(Some(token));
     }
+
+    tracing::debug!("refreshing access token");
+    let token = match client.refresh(token.refresh_token()).await {
+        Err(e) if e.status() == Some(401) => {
+            return Err(Error::Unauthorized(
+                "session expired, run `paastel login`".to_string(),
+            ))
+        }
+        result => into_token(result?),
+    };
+
+    let secret = serde_json::to_string(&token)
+        .map_err(|e| Error::Credential(e.to_string()))?;
+    block_in_place(|| store.set(credential.key(), &secret))?;
+    Ok(Some(token))
 }
 
-pub async fn login(_matches: &ArgMatches) -> Result<(), Error> {
-    let questions = vec![
-        Question::password("password1")
-            .message("Enter a password")
-            .validate(letter_and_numbers)
-            .build(),
-        Question::password("password2")
-            .message("Enter a masked password")
-            .mask('*')
-            .validate_on_key(is_valid)
-            .validate(letter_and_numbers)
-            .build(),
-    ];
-
-    println!("{:#?}", requestty::prompt(questions));
-    // let username = match matches.get_one::<String>("username") {
-    //     Some(u) => {
-    //         let u = u.as_str();
-    //         if u.trim().is_empty() {
-    //             panic!("invalid username");
-    //         } else {
-    //             u
-    //         }
-    //     }
-    //     None => "ask username",
-    // };
-
-    // let password = match matches.get_one::<String>("password") {
-    //     Some(u) => {
-    //         let u = u.as_str();
-    //         if u.trim().is_empty() {
-    //             panic!("invalid username");
-    //         } else {
-    //             u
-    //         }
-    //     }
-    //     None => "ask username",
-    // };
-
-    // // NOTE: validate url and required value
-    // let url = match matches.get_one::<String>("url") {
-    //     Some(u) => {
-    //         let u = u.as_str();
-    //         if u.trim().is_empty() {
-    //             panic!("invalid username");
-    //         } else {
-    //             u
-    //         }
-    //     }
-    //     None => "ask username",
-    // };
-
-    // TODO: update settings with username, password, url
-
-    // // verify credentials
-    // let client = ClientBuilder::new()
-    //     .user_agent(APP_USER_AGENT)
-    //     .timeout(Duration::from_secs(5))
-    //     .build()
-    //     .unwrap();
-    // let res = client
-    //     .get(&format!("{url}{}", "/me"))
-    //     .basic_auth(username, Some(password))
-    //     .send()
-    //     .await
-    //     .unwrap();
-
-    // if res.status().is_success() {
-    //     println!("sucesso");
-    // } else {
-    //     println!("falha");
-    // }
-
-    // TODO: save settings (create file)
+/// Save tokens on given store, when none is given on keyring falling back
+/// to encrypted file when there is no keyring
+fn store_token(
+    settings: &Settings,
+    store: Option<SecretStore>,
+    key: &str,
+    token: &Token,
+) -> Result<CredentialRef, Error> {
+    let secret = serde_json::to_string(token)
+        .map_err(|e| Error::Credential(e.to_string()))?;
+    let store = match store {
+        Some(store) => store,
+        None => match block_in_place(|| KeyringStore.set(key, &secret)) {
+            Ok(()) => {
+                return Ok(CredentialRef::new(SecretStore::Keyring, key.into()))
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "keyring unavailable ({e}), using encrypted file"
+                );
+                SecretStore::File
+            }
+        },
+    };
+    let opened = credential::open(store, settings)?;
+    block_in_place(|| opened.set(key, &secret))?;
+    Ok(CredentialRef::new(opened.kind(), key.to_string()))
+}
 
-    Ok(())
+/// Remove secret of a previous login, failures only logged
+pub(crate) fn forget(settings: &Settings, credential: &CredentialRef) {
+    let result = credential::open(credential.store(), settings)
+        .and_then(|store| block_in_place(|| store.delete(credential.key())));
+    if let Err(e) = result {
+        tracing::debug!(%credential, "failed remove previous credential {e}");
+    }
+}
+
+/// Websockets url served along api
+fn wss_url(api: &Url) -> Result<Url, Error> {
+    let mut wss = api.clone();
+    let scheme = match api.scheme() {
+        "https" => "wss",
+        _ => "ws",
+    };
+    wss.set_scheme(scheme).map_err(|_| {
+        Error::UrlParse(format!("no websockets url for `{api}`"))
+    })?;
+    Ok(wss)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wss_url_of_api() {
+        let api = api_url("https://paastel.example.com").unwrap();
+        assert_eq!(
+            wss_url(&api).unwrap().as_str(),
+            "wss://paastel.example.com/"
+        );
+        let api = api_url("http://localhost:8080").unwrap();
+        assert_eq!(wss_url(&api).unwrap().as_str(), "ws://localhost:8080/");
+    }
 }
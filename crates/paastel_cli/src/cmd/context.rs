@@ -0,0 +1,180 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::path::PathBuf;
+
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use paastel_settings::{Context, Namespace, Settings};
+
+use crate::{cmd::auth, error::Error, util::opt};
+
+pub fn command() -> Command {
+    let name = Arg::new("name")
+        .value_name("NAME")
+        .action(ArgAction::Set)
+        .required(true);
+
+    Command::new("context")
+        .about("PaaStel server contexts management")
+        .long_about(
+            "Manage the PaaStel servers known by settings, ex: staging and \
+            production, commands use the current context. `paastel login` \
+            adds a context and makes it current",
+        )
+        .subcommand_required(true)
+        .subcommand(Command::new("list").about("List contexts"))
+        .subcommand(
+            Command::new("use")
+                .about("Use context on next commands")
+                .arg(name.clone()),
+        )
+        .subcommand(
+            Command::new("add")
+                .about("Add a context, replacing one with same name")
+                .arg(name.clone())
+                .arg(
+                    Arg::new("url")
+                        .value_name("URL")
+                        .action(ArgAction::Set)
+                        .required(true)
+                        .help("Url of PaaStel api"),
+                )
+                .arg(opt(
+                    "namespace",
+                    "Namespace of applications, default `paastel-space`",
+                ))
+                .arg(
+                    opt(
+                        "ca-bundle",
+                        "PEM bundle of certificate authorities trusted for \
+                        url",
+                    )
+                    .value_parser(value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("remove")
+                .about("Remove a context and its credentials")
+                .arg(name),
+        )
+}
+
+pub fn matches(m: &ArgMatches, settings: &Settings) -> Result<(), Error> {
+    match m.subcommand() {
+        Some(("list", _)) => list(settings),
+        Some(("use", m)) => use_context(settings, name(m)),
+        Some(("add", m)) => add(settings, m),
+        Some(("remove", m)) => remove(settings, name(m)),
+        _ => Ok(()),
+    }
+}
+
+fn list(settings: &Settings) -> Result<(), Error> {
+    use prettytable::{format, row, Cell, Row, Table};
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.add_row(Row::new(vec![
+        Cell::new("Current").style_spec("bFg"),
+        Cell::new("Name").style_spec("bFg"),
+        Cell::new("Api").style_spec("bFg"),
+        Cell::new("Namespace").style_spec("bFg"),
+        Cell::new("Username").style_spec("bFg"),
+    ]));
+    for (name, context) in settings.contexts() {
+        let current = match settings.current_context() == Some(name) {
+            true => "*",
+            false => "",
+        };
+        table.add_row(row![
+            current,
+            name,
+            context.api(),
+            context.namespace().as_ref(),
+            context.username().unwrap_or("")
+        ]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn use_context(settings: &Settings, name: &str) -> Result<(), Error> {
+    let mut settings = settings.clone();
+    if !settings.use_context(name) {
+        return Err(not_found(name));
+    }
+    save(&settings)?;
+    println!("using context {name}");
+    Ok(())
+}
+
+fn add(settings: &Settings, m: &ArgMatches) -> Result<(), Error> {
+    let name = name(m);
+    let url = m.get_one::<String>("url").expect("url is required");
+    let api = paastel_client::api_url(url)?;
+
+    let mut context = Context::new(api.to_string());
+    if let Some(namespace) = m.get_one::<String>("namespace") {
+        context = context.with_namespace(Namespace::new(namespace.clone()));
+    }
+    if let Some(ca_bundle) = m.get_one::<PathBuf>("ca-bundle") {
+        context = context.with_ca_bundle(ca_bundle.canonicalize()?);
+    }
+
+    let mut settings = settings.clone();
+    if let Some(previous) = settings.contexts().get(name) {
+        if let Some(credential) = previous.credential() {
+            auth::forget(&settings, credential);
+        }
+    }
+    settings.set_context(name, context);
+    // NOTE: first context is used right away
+    if settings.current_context().is_none() {
+        settings.use_context(name);
+    }
+    save(&settings)?;
+    println!(
+        "context {name} added, run `paastel login {api} --context {name}`"
+    );
+    Ok(())
+}
+
+fn remove(settings: &Settings, name: &str) -> Result<(), Error> {
+    let mut settings = settings.clone();
+    let context = settings
+        .remove_context(name)
+        .ok_or_else(|| not_found(name))?;
+    if let Some(credential) = context.credential() {
+        auth::forget(&settings, credential);
+    }
+    save(&settings)?;
+    println!("context {name} removed");
+    Ok(())
+}
+
+fn save(settings: &Settings) -> Result<(), Error> {
+    settings.save().map_err(|e| Error::Settings(e.to_string()))
+}
+
+fn name(m: &ArgMatches) -> &str {
+    m.get_one::<String>("name")
+        .map(String::as_str)
+        .expect("name is required")
+}
+
+fn not_found(name: &str) -> Error {
+    Error::Settings(format!(
+        "context {name} not found, see `paastel context list`"
+    ))
+}
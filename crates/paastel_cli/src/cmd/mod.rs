@@ -13,6 +13,8 @@
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
 pub mod auth;
+pub mod context;
 pub mod push;
 pub mod settings;
+pub mod user;
 pub mod version;
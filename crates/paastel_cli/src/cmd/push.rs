@@ -12,84 +12,694 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-use std::{path::Path, time::Duration};
+use std::{
+    collections::BTreeSet,
+    io::{SeekFrom, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
-use clap::{Arg, ArgMatches, Command};
-use reqwest::ClientBuilder;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use futures::TryStreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use paastel_client::{App, Client, SourceEntry, Stage, Upload};
+use paastel_manifest::{Manifest, MANIFEST_FILE};
+use paastel_settings::Settings;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 
-use crate::error::Error;
+use crate::{
+    cmd::auth,
+    error::Error,
+    util::{
+        compress::{self, Archive, Format, Source, SourceKind},
+        opt,
+    },
+};
 
-// Name your user agent after your app?
-static APP_USER_AGENT: &str =
-    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
+/// Interval between checks of staging and deployment
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Size of upload parts, each part is sent on its own request
+const PART_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Upload in progress, kept on cache directory to resume an interrupted push
+/// of same archive
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct ResumeState {
+    /// Url of api
+    api: String,
+    namespace: String,
+    name: String,
+    upload_id: String,
+}
+
+impl ResumeState {
+    /// Archives are deterministic, same sources resume same upload
+    fn path(digest: &str) -> Option<PathBuf> {
+        dirs::cache_dir()
+            .map(|dir| dir.join("paastel").join("uploads").join(digest))
+    }
+
+    /// State of upload of archive to same application, if any
+    fn load(digest: &str, remote: &Remote, name: &str) -> Option<Self> {
+        let content = std::fs::read_to_string(Self::path(digest)?).ok()?;
+        let state: Self = toml::from_str(&content)
+            .map_err(|e| tracing::debug!("invalid upload state {e}"))
+            .ok()?;
+        (state.api == remote.client.api().as_str()
+            && state.namespace == remote.namespace
+            && state.name == name)
+            .then_some(state)
+    }
+
+    fn save(&self, digest: &str) -> Result<(), Error> {
+        let Some(path) = Self::path(digest) else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    fn remove(digest: &str) {
+        if let Some(path) = Self::path(digest) {
+            if let Err(e) = std::fs::remove_file(&path) {
+                tracing::debug!(?path, "failed remove upload state {e}");
+            }
+        }
+    }
+}
+
+/// File with digest of content or symbolic link with target
+fn source_entry(source: &Source) -> SourceEntry {
+    match source.kind() {
+        SourceKind::File {
+            digest, executable, ..
+        } => SourceEntry::file(source.name(), digest.as_str(), *executable),
+        SourceKind::Symlink { target } => {
+            SourceEntry::symlink(source.name(), target.as_str())
+        }
+    }
+}
 
 pub fn command() -> Command {
     Command::new("push")
         .about("Push an application declared in the specified manifest")
         .long_about(
-            "The login command allows you to authenticate against \
-            an Paastel instance and updates the settings file with the \
-            generated authentication token",
+            "The push command uploads the directory to the PaaStel \
+            instance on settings, builds an image from it and deploys the \
+            image, the application is created when missing. Only files \
+            the instance does not already store are sent, unless an \
+            archive format is given then the whole directory is archived. \
+            A `paastel.yml` manifest on directory declares how application \
+            is built and run, options given on command line take precedence",
+        )
+        .arg(
+            Arg::new("path")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .default_value(".")
+                .action(ArgAction::Set)
+                .help("Directory with application sources"),
+        )
+        .arg(
+            opt(
+                "manifest",
+                "Path of manifest, default `paastel.yml` on PATH",
+            )
+            .value_parser(value_parser!(PathBuf)),
         )
         .arg(
-            Arg::new("name")
-                .long("name")
+            opt("name", "Name of application, default directory name")
                 .env("PAASTEL_NAME")
-                .short('n')
-                .help("that will be used to login"),
+                .short('n'),
+        )
+        .arg(opt(
+            "builder",
+            "Builder of image, `buildpack` or `dockerfile`",
+        ))
+        .arg(
+            opt(
+                "archive-format",
+                "Archive of sources, `zip`, `tar.gz` or `tar.zst`, default \
+                send changed files only or best format accepted by server",
+            )
+            .value_parser(Format::from_str),
         )
+        .arg(
+            opt("timeout", "Seconds waiting staging and deployment")
+                .value_parser(value_parser!(u64))
+                .default_value("600"),
+        )
+}
+
+/// Push pushes an app
+pub async fn push(
+    matches: &ArgMatches,
+    settings: &Settings,
+) -> Result<(), Error> {
+    let dir = matches
+        .get_one::<PathBuf>("path")
+        .expect("path has default value")
+        .canonicalize()?;
+    let manifest = match matches.get_one::<PathBuf>("manifest") {
+        Some(path) => Some(load_manifest(path)?),
+        None => {
+            let path = dir.join(MANIFEST_FILE);
+            match path.exists() {
+                true => Some(load_manifest(&path)?),
+                false => None,
+            }
+        }
+    };
+    let name = match (matches.get_one::<String>("name"), &manifest) {
+        (Some(name), _) => name.clone(),
+        (None, Some(manifest)) => manifest.name().to_string(),
+        (None, None) => dir_name(&dir)?,
+    };
+    let manifest = manifest
+        .map(|manifest| manifest.with_name(name.as_str()))
+        .transpose()
+        .map_err(|e| Error::Push(format!("manifest {e}")))?;
+    let builder = match (matches.get_one::<String>("builder"), &manifest) {
+        (Some(builder), _) => Some(builder.clone()),
+        (None, Some(manifest)) => manifest.builder().map(|b| b.to_string()),
+        (None, None) => None,
+    };
+    let timeout = Duration::from_secs(
+        *matches
+            .get_one::<u64>("timeout")
+            .expect("timeout has default value"),
+    );
+
+    let namespace = manifest
+        .as_ref()
+        .and_then(Manifest::namespace)
+        .unwrap_or(settings.namespace().as_ref());
+    let remote = Remote {
+        client: auth::client(settings).await?,
+        namespace: namespace.to_string(),
+    };
+    remote.prepare(&name, manifest.as_ref()).await?;
+    // NOTE: an explicit archive format skips deduplication of sources
+    let blob_id = match matches.get_one::<Format>("archive-format") {
+        Some(format) => push_archive(&remote, &dir, &name, *format).await?,
+        None => match remote.upload_sources(&name, &dir).await? {
+            Some(blob_id) => blob_id,
+            None => {
+                let format = remote.archive_format().await?;
+                push_archive(&remote, &dir, &name, format).await?
+            }
+        },
+    };
+    let url = remote
+        .release(&name, &blob_id, builder.as_deref(), timeout)
+        .await?;
+    println!("application {name} ready at {url}");
+    Ok(())
 }
 
-// Push pushes an app
-// TODO: (tail logs)
-// TODO: wait for staging to be done (complete or fail)
-// TODO: deploy
-// TODO: wait for app
-pub async fn push(_matches: &ArgMatches) -> Result<(), Error> {
-    // NOTE: in memory
-    let out_dir = Path::new("/tmp/paastel_compress.zip");
-
-    // NOTE: name is optional or generating one or get from folder name
-    // TODO: compress folder (ok)
-    // compress::dir(
-    //     current_dir().unwrap().to_str().unwrap(),
-    //     "/tmp/paastel_compress.zip",
-    // )
-    // .unwrap();
-
-    // TODO: upload  minio->s3
-    let client = ClientBuilder::new()
-        .user_agent(APP_USER_AGENT)
-        .timeout(Duration::from_secs(5))
-        .build()
-        .unwrap();
-
-    let content: Vec<u8> = tokio::fs::read(out_dir).await?;
-
-    let part = reqwest::multipart::Part::bytes(content)
-        .mime_str("application/zip")
-        .unwrap()
-        .file_name(out_dir.file_name().unwrap().to_str().unwrap());
-
-    let file = reqwest::multipart::Form::new().part("file", part);
-
-    let res = client
-        .post(
-            "http://127.0.0.1:3000/namespaces/default/applications/mysuperapp/store",
+/// Archive directory into a temporary file and upload it, returns blob id
+async fn push_archive(
+    remote: &Remote,
+    dir: &Path,
+    name: &str,
+    format: Format,
+) -> Result<String, Error> {
+    println!("archiving {} as {format}", dir.display());
+    let path = std::env::temp_dir()
+        .join(format!("paastel-{name}-{}.{format}", std::process::id()));
+    let result = async {
+        let archive = compress::dir(dir, &path, format)?;
+        println!("archived {} files", archive.files());
+        remote.upload(name, (&path, &archive)).await
+    }
+    .await;
+    if let Err(e) = std::fs::remove_file(&path) {
+        tracing::debug!(?path, "failed remove archive {e}");
+    }
+    result
+}
+
+/// Progress of bytes sent
+fn progress_bar(size: u64) -> Result<ProgressBar, Error> {
+    Ok(ProgressBar::new(size).with_style(
+        ProgressStyle::with_template(
+            "{bytes}/{total_bytes} [{bar:40}] {bytes_per_sec} eta {eta}",
         )
-        .basic_auth("admin@paastel.io", Some("password"))
-        .multipart(file)
-        .send()
-        .await
-        .unwrap();
+        .map_err(|e| Error::Push(e.to_string()))?
+        .progress_chars("=> "),
+    ))
+}
+
+/// Api of PaaStel instance on settings, scoped to current namespace
+struct Remote {
+    client: Client,
+    namespace: String,
+}
+
+impl Remote {
+    /// Preferred archive format accepted by server, servers without info
+    /// only accept zip
+    async fn archive_format(&self) -> Result<Format, Error> {
+        let info = match self.client.info().await {
+            Err(e) if e.status() == Some(404) => return Ok(Format::Zip),
+            result => result?,
+        };
+        negotiate(info.archive_formats()).ok_or_else(|| {
+            Error::Push(format!(
+                "no supported archive format in {:?}",
+                info.archive_formats()
+            ))
+        })
+    }
+
+    /// Create application when missing, configured from manifest if any
+    async fn prepare(
+        &self,
+        name: &str,
+        manifest: Option<&Manifest>,
+    ) -> Result<(), Error> {
+        match manifest {
+            Some(manifest) => self.apply_manifest(manifest).await,
+            None => self.create_app(name).await,
+        }
+    }
+
+    /// Build and deploy uploaded sources, returns route of application
+    async fn release(
+        &self,
+        name: &str,
+        blob_id: &str,
+        builder: Option<&str>,
+        timeout: Duration,
+    ) -> Result<String, Error> {
+        let stage = self
+            .client
+            .stage_app(&self.namespace, name, blob_id, builder)
+            .await?;
+        println!("staging {name}, stage {}", stage.id());
+        let deadline = Instant::now() + timeout;
+        let stage = self.wait_stage(stage.id(), deadline).await?;
+        let image = stage.image().ok_or_else(|| {
+            Error::Push(format!("stage {} built no image", stage.id()))
+        })?;
+
+        self.client
+            .deploy_app(&self.namespace, name, stage.id())
+            .await?;
+        println!("deploying {name}");
+        let app = self.wait_app(name, image, deadline).await?;
+
+        Ok(app.url().unwrap_or("<no route>").to_string())
+    }
 
-    if res.status().is_success() {
-        println!("upload: sucesso");
-    } else {
-        println!("falha");
+    async fn create_app(&self, name: &str) -> Result<(), Error> {
+        match self.client.create_app(&self.namespace, name).await {
+            Err(e) if e.status() == Some(409) => {
+                tracing::debug!(name, "application already exists");
+                return Ok(());
+            }
+            result => result?,
+        };
+        println!("application {name} created");
+        Ok(())
     }
 
-    // TODO: stage `build`
+    /// Create application when missing and configure it from manifest
+    async fn apply_manifest(&self, manifest: &Manifest) -> Result<(), Error> {
+        let name = manifest.name();
+        let body = manifest
+            .to_yaml()
+            .map_err(|e| Error::Push(format!("manifest {e}")))?;
+        self.client
+            .apply_manifest(&self.namespace, name, body)
+            .await?;
+        println!("application {name} configured from manifest");
+        Ok(())
+    }
+
+    /// Upload only source files missing on server, which assembles archive,
+    /// `None` when server does not support it
+    async fn upload_sources(
+        &self,
+        name: &str,
+        dir: &Path,
+    ) -> Result<Option<String>, Error> {
+        let sources = compress::sources(dir)?;
+        let digests: BTreeSet<&str> = sources
+            .iter()
+            .filter_map(|source| match source.kind() {
+                SourceKind::File { digest, .. } => Some(digest.as_str()),
+                SourceKind::Symlink { .. } => None,
+            })
+            .collect();
+        let digests: Vec<&str> = digests.into_iter().collect();
+
+        let missing = match self
+            .client
+            .missing_sources(&self.namespace, name, &digests)
+            .await
+        {
+            // NOTE: route is missing on older servers
+            Err(e) if matches!(e.status(), Some(404 | 405)) => return Ok(None),
+            result => result?,
+        };
+        let mut missing: BTreeSet<String> = missing.into_iter().collect();
+
+        let files: Vec<(&Source, u64)> = sources
+            .iter()
+            .filter_map(|source| match source.kind() {
+                SourceKind::File { digest, size, .. }
+                    if missing.remove(digest) =>
+                {
+                    Some((source, *size))
+                }
+                _ => None,
+            })
+            .collect();
+        println!("uploading {} of {} files", files.len(), sources.len());
+        let bar = progress_bar(files.iter().map(|(_, size)| size).sum())?;
+        for (source, size) in files {
+            self.upload_source(name, source).await?;
+            bar.inc(size);
+        }
+        bar.finish_and_clear();
+
+        let entries: Vec<SourceEntry> =
+            sources.iter().map(source_entry).collect();
+        let blob = self
+            .client
+            .assemble_sources(&self.namespace, name, &entries)
+            .await?;
+        tracing::debug!(
+            blob_id = blob.blob_id(),
+            size = blob.size(),
+            "sources assembled"
+        );
+        Ok(Some(blob.blob_id().to_string()))
+    }
+
+    async fn upload_source(
+        &self,
+        name: &str,
+        source: &Source,
+    ) -> Result<(), Error> {
+        let SourceKind::File { digest, .. } = source.kind() else {
+            return Ok(());
+        };
+        self.client
+            .upload_source(&self.namespace, name, digest, source.path())
+            .await?;
+        Ok(())
+    }
+
+    /// Upload archive part by part, an upload interrupted before is resumed
+    /// and servers without resumable uploads get whole archive
+    async fn upload(
+        &self,
+        name: &str,
+        (path, archive): (&Path, &Archive),
+    ) -> Result<String, Error> {
+        let digest = archive.digest();
+        let resumed = match ResumeState::load(digest, self, name) {
+            Some(state) => self.upload_progress(name, &state.upload_id).await?,
+            None => None,
+        };
+        let progress = match resumed {
+            Some(progress) => {
+                println!("resuming upload at {} bytes", progress.received());
+                progress
+            }
+            None => match self.initiate_upload(name, archive).await? {
+                Some(progress) => progress,
+                None => {
+                    tracing::debug!("resumable uploads not supported");
+                    return self.upload_whole(name, (path, archive)).await;
+                }
+            },
+        };
+        ResumeState {
+            api: self.client.api().to_string(),
+            namespace: self.namespace.clone(),
+            name: name.to_string(),
+            upload_id: progress.id().to_string(),
+        }
+        .save(digest)?;
+
+        let bar = progress_bar(archive.size())?;
+        bar.set_position(progress.received());
+
+        let mut file = tokio::fs::File::open(path).await?;
+        let mut received = progress.received();
+        while received < archive.size() {
+            received = self
+                .upload_part(name, progress.id(), &mut file, received)
+                .await?;
+            bar.set_position(received);
+        }
+        bar.finish_and_clear();
+
+        let result = self
+            .client
+            .complete_upload(&self.namespace, name, progress.id())
+            .await
+            .map_err(Error::from);
+        // NOTE: server drops upload once completed or found invalid
+        if !matches!(result, Err(Error::Server(status, _)) if status >= 500) {
+            ResumeState::remove(digest);
+        }
+        let blob = result?;
+        tracing::debug!(
+            blob_id = blob.blob_id(),
+            size = blob.size(),
+            "uploaded"
+        );
+        Ok(blob.blob_id().to_string())
+    }
+
+    /// Progress of upload, `None` when server no longer has it
+    async fn upload_progress(
+        &self,
+        name: &str,
+        upload_id: &str,
+    ) -> Result<Option<Upload>, Error> {
+        match self
+            .client
+            .show_upload(&self.namespace, name, upload_id)
+            .await
+        {
+            Err(e) if e.status() == Some(404) => Ok(None),
+            result => Ok(Some(result?)),
+        }
+    }
+
+    /// Start resumable upload, `None` when server does not support it
+    async fn initiate_upload(
+        &self,
+        name: &str,
+        archive: &Archive,
+    ) -> Result<Option<Upload>, Error> {
+        match self
+            .client
+            .initiate_upload(
+                &self.namespace,
+                name,
+                archive.digest(),
+                archive.size(),
+                archive.format().extension(),
+            )
+            .await
+        {
+            // NOTE: route is missing on older servers
+            Err(e) if matches!(e.status(), Some(404 | 405)) => Ok(None),
+            result => Ok(Some(result?)),
+        }
+    }
+
+    /// Send part at offset, client retries it on network and server
+    /// errors, returns bytes received by server
+    async fn upload_part(
+        &self,
+        name: &str,
+        upload_id: &str,
+        file: &mut tokio::fs::File,
+        offset: u64,
+    ) -> Result<u64, Error> {
+        let mut part = Vec::with_capacity(PART_SIZE as usize);
+        file.seek(SeekFrom::Start(offset)).await?;
+        file.take(PART_SIZE).read_to_end(&mut part).await?;
+        let digest = format!("{:x}", Sha256::digest(&part));
+
+        match self
+            .client
+            .upload_part(
+                &self.namespace,
+                name,
+                upload_id,
+                offset,
+                part,
+                &digest,
+            )
+            .await
+        {
+            Ok(progress) => Ok(progress.received()),
+            // NOTE: part received before its response got lost
+            Err(e) if e.status() == Some(409) => {
+                let progress = self
+                    .upload_progress(name, upload_id)
+                    .await?
+                    .ok_or_else(|| Error::Push("upload lost".to_string()))?;
+                Ok(progress.received())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Single request upload of whole archive
+    async fn upload_whole(
+        &self,
+        name: &str,
+        (path, archive): (&Path, &Archive),
+    ) -> Result<String, Error> {
+        println!("uploading {} bytes", archive.size());
+        let blob = self
+            .client
+            .upload_app(
+                &self.namespace,
+                name,
+                path,
+                archive.format().media_type(),
+                archive.digest(),
+            )
+            .await?;
+        tracing::debug!(
+            blob_id = blob.blob_id(),
+            size = blob.size(),
+            "uploaded"
+        );
+        Ok(blob.blob_id().to_string())
+    }
+
+    /// Wait build finish, logs are printed when it fails
+    async fn wait_stage(
+        &self,
+        id: &str,
+        deadline: Instant,
+    ) -> Result<Stage, Error> {
+        let mut phase = String::new();
+        loop {
+            let stage = self.client.show_stage(&self.namespace, id).await?;
+            if stage.phase() != phase {
+                println!("stage {}", stage.phase());
+                phase = stage.phase().to_string();
+            }
+            match phase.as_str() {
+                "Succeeded" => return Ok(stage),
+                "Failed" => {
+                    let mut logs =
+                        self.client.stage_logs(&self.namespace, id).await?;
+                    let mut stderr = std::io::stderr();
+                    while let Some(chunk) = logs.try_next().await? {
+                        stderr.write_all(&chunk)?;
+                    }
+                    return Err(Error::Push(format!(
+                        "staging failed {}",
+                        stage.reason().unwrap_or_default()
+                    )));
+                }
+                _ => wait(deadline, "staging").await?,
+            }
+        }
+    }
+
+    /// Wait until `image` rolled out, readiness of a previous image does
+    /// not count
+    async fn wait_app(
+        &self,
+        name: &str,
+        image: &str,
+        deadline: Instant,
+    ) -> Result<App, Error> {
+        loop {
+            let app = self.client.show_app(&self.namespace, name).await?;
+            if app.image() == Some(image) && app.is_ready() {
+                return Ok(app);
+            }
+            tracing::debug!(
+                image = app.image(),
+                ready_replicas = app.ready_replicas(),
+                message = app.message(),
+                "application not ready"
+            );
+            wait(deadline, "deployment").await?;
+        }
+    }
+}
 
+/// Sleep until next check, fails once deadline is reached
+async fn wait(deadline: Instant, what: &str) -> Result<(), Error> {
+    if Instant::now() + POLL_INTERVAL > deadline {
+        return Err(Error::Push(format!("timeout waiting {what}")));
+    }
+    tokio::time::sleep(POLL_INTERVAL).await;
     Ok(())
 }
+
+/// First format, by preference, accepted by server
+fn negotiate(accepted: &[String]) -> Option<Format> {
+    Format::ALL
+        .into_iter()
+        .find(|format| accepted.iter().any(|a| a == format.extension()))
+}
+
+/// Name of application from directory, lowercase as required by server
+fn dir_name(dir: &Path) -> Result<String, Error> {
+    dir.file_name()
+        .map(|name| name.to_string_lossy().to_lowercase().replace('_', "-"))
+        .ok_or_else(|| Error::Push("application name required".to_string()))
+}
+
+fn load_manifest(path: &Path) -> Result<Manifest, Error> {
+    Manifest::from_path(path)
+        .map_err(|e| Error::Push(format!("manifest {} {e}", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dir_name_lowercase() {
+        let name = dir_name(Path::new("/home/user/My_App")).unwrap();
+        assert_eq!(name, "my-app");
+    }
+
+    #[test]
+    fn negotiate_preferred_format() {
+        let accepted = ["zip".to_string(), "tar.gz".to_string()];
+        assert_eq!(negotiate(&accepted), Some(Format::TarGz));
+        assert_eq!(negotiate(&["tar.zst".to_string()]), Some(Format::TarZst));
+        assert_eq!(negotiate(&["rar".to_string()]), None);
+    }
+
+    #[test]
+    fn resume_state_of_same_app() {
+        let state = ResumeState {
+            api: "https://paastel.local/".to_string(),
+            namespace: "ns".to_string(),
+            name: "my-app".to_string(),
+            upload_id: "upload".to_string(),
+        };
+        let content = toml::to_string(&state).unwrap();
+        assert_eq!(toml::from_str::<ResumeState>(&content).unwrap(), state);
+        assert!(ResumeState::path(&"a".repeat(64)).is_some_and(
+            |path| path.ends_with(format!("uploads/{}", "a".repeat(64)))
+        ));
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use dialoguer::Password;
+use paastel_client::Client;
+use paastel_settings::Settings;
+
+use crate::{cmd::auth, error::Error, util::opt};
+
+pub fn command() -> Command {
+    let username = Arg::new("username")
+        .value_name("USERNAME")
+        .action(ArgAction::Set)
+        .required(true);
+    let password = opt("password", "Password of user, prompted when missing")
+        .env("PAASTEL_USER_PASSWORD");
+
+    Command::new("user")
+        .about("PaaStel users management")
+        .long_about(
+            "Manage users allowed to access PaaStel api, \
+            requires an admin user on settings",
+        )
+        .subcommand_required(true)
+        .subcommand(Command::new("list").about("List users"))
+        .subcommand(
+            Command::new("create")
+                .about("Create a new user")
+                .arg(username.clone())
+                .arg(password.clone())
+                .arg(
+                    opt(
+                        "role",
+                        "Role granted, ex: admin, developer:team-a, viewer",
+                    )
+                    .action(ArgAction::Append),
+                ),
+        )
+        .subcommand(
+            Command::new("passwd")
+                .about("Change password of user")
+                .arg(username.clone())
+                .arg(password),
+        )
+        .subcommand(Command::new("delete").about("Delete a user").arg(username))
+}
+
+pub async fn matches(m: &ArgMatches, settings: &Settings) -> Result<(), Error> {
+    let client = auth::client(settings).await?;
+
+    match m.subcommand() {
+        Some(("list", _)) => list(&client).await,
+        Some(("create", m)) => create(&client, m).await,
+        Some(("passwd", m)) => passwd(&client, m).await,
+        Some(("delete", m)) => delete(&client, m).await,
+        _ => Ok(()),
+    }
+}
+
+async fn list(client: &Client) -> Result<(), Error> {
+    let users = client.list_users().await?;
+
+    use prettytable::{format, row, Cell, Row, Table};
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.add_row(Row::new(vec![
+        Cell::new("Username").style_spec("bFg"),
+        Cell::new("Roles").style_spec("bFg"),
+    ]));
+    for user in users {
+        table.add_row(row![user.username(), user.roles().join(",")]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+async fn create(client: &Client, m: &ArgMatches) -> Result<(), Error> {
+    let username = username(m);
+    let password = password(m)?;
+    let roles: Vec<String> = m
+        .get_many::<String>("role")
+        .map(|roles| roles.cloned().collect())
+        .unwrap_or_default();
+
+    client.create_user(username, &password, &roles).await?;
+
+    println!("user {username} created");
+    Ok(())
+}
+
+async fn passwd(client: &Client, m: &ArgMatches) -> Result<(), Error> {
+    let username = username(m);
+    let password = password(m)?;
+
+    client.change_password(username, &password).await?;
+
+    println!("password of {username} changed");
+    Ok(())
+}
+
+async fn delete(client: &Client, m: &ArgMatches) -> Result<(), Error> {
+    let username = username(m);
+
+    client.delete_user(username).await?;
+
+    println!("user {username} deleted");
+    Ok(())
+}
+
+fn username(m: &ArgMatches) -> &str {
+    m.get_one::<String>("username")
+        .map(String::as_str)
+        .expect("username is required")
+}
+
+fn password(m: &ArgMatches) -> Result<String, Error> {
+    match m.get_one::<String>("password") {
+        Some(password) => Ok(password.clone()),
+        None => Ok(Password::new()
+            .with_prompt("Password")
+            .with_confirmation("Repeat password", "Passwords mismatching")
+            .interact()?),
+    }
+}
@@ -16,24 +16,40 @@ use std::fmt::Display;
 
 #[derive(Debug)]
 pub enum Error {
-    // UrlParse(String),
+    UrlParse(String),
     Settings(String),
     Io(String),
-    // Toml(String),
-    // Base64(String),
-    // Server,
+    Toml(String),
+    Base64(String),
+    Http(String),
+    Tls(String),
+    Network(String),
+    Unauthorized(String),
+    Credential(String),
+    Server(u16, String),
+    Prompt(String),
+    Archive(String),
+    Push(String),
     Unknown,
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            // Error::UrlParse(e) => write!(f, "parser url {e}"),
+            Error::UrlParse(e) => write!(f, "parser url {e}"),
             Error::Settings(e) => write!(f, "settings {e}"),
             Error::Io(e) => write!(f, "io {e}"),
-            // Error::Toml(e) => write!(f, "toml parser {e}"),
-            // Error::Base64(e) => write!(f, "base64 {e}"),
-            // Error::Server => write!(f, "server error"),
+            Error::Toml(e) => write!(f, "toml parser {e}"),
+            Error::Base64(e) => write!(f, "base64 {e}"),
+            Error::Http(e) => write!(f, "http {e}"),
+            Error::Tls(e) => write!(f, "tls {e}"),
+            Error::Network(e) => write!(f, "network {e}"),
+            Error::Unauthorized(e) => write!(f, "unauthorized {e}"),
+            Error::Credential(e) => write!(f, "credential {e}"),
+            Error::Server(status, e) => write!(f, "server {status} {e}"),
+            Error::Prompt(e) => write!(f, "prompt {e}"),
+            Error::Archive(e) => write!(f, "archive {e}"),
+            Error::Push(e) => write!(f, "push {e}"),
             Error::Unknown => write!(f, "unknown"),
         }
     }
@@ -41,11 +57,11 @@ impl Display for Error {
 
 impl std::error::Error for Error {}
 
-// impl From<url::ParseError> for Error {
-//     fn from(value: url::ParseError) -> Self {
-//         Self::UrlParse(value.to_string())
-//     }
-// }
+impl From<url::ParseError> for Error {
+    fn from(value: url::ParseError) -> Self {
+        Self::UrlParse(value.to_string())
+    }
+}
 
 impl From<std::io::Error> for Error {
     fn from(value: std::io::Error) -> Self {
@@ -53,23 +69,59 @@ impl From<std::io::Error> for Error {
     }
 }
 
-// impl From<toml::de::Error> for Error {
-//     fn from(value: toml::de::Error) -> Self {
-//         Self::Toml(value.to_string())
-//     }
-// }
+impl From<toml::de::Error> for Error {
+    fn from(value: toml::de::Error) -> Self {
+        Self::Toml(value.to_string())
+    }
+}
 
-// impl From<toml::ser::Error> for Error {
-//     fn from(value: toml::ser::Error) -> Self {
-//         Self::Toml(value.to_string())
-//     }
-// }
+impl From<toml::ser::Error> for Error {
+    fn from(value: toml::ser::Error) -> Self {
+        Self::Toml(value.to_string())
+    }
+}
 
-// impl From<base64::DecodeError> for Error {
-//     fn from(value: base64::DecodeError) -> Self {
-//         Self::Base64(value.to_string())
-//     }
-// }
+impl From<base64::DecodeError> for Error {
+    fn from(value: base64::DecodeError) -> Self {
+        Self::Base64(value.to_string())
+    }
+}
+
+impl From<paastel_client::Error> for Error {
+    fn from(value: paastel_client::Error) -> Self {
+        use paastel_client::Error as E;
+
+        match value {
+            E::Api { status, problem } => Self::Server(status, problem.detail),
+            E::Tls { url, reason } => Self::Tls(format!("{url} {reason}")),
+            e @ (E::Network { .. } | E::Timeout { .. }) => {
+                Self::Network(e.to_string())
+            }
+            e @ E::Url { .. } => Self::UrlParse(e.to_string()),
+            e @ E::CaBundle { .. } => Self::Settings(e.to_string()),
+            e @ E::Io { .. } => Self::Io(e.to_string()),
+            e => Self::Http(e.to_string()),
+        }
+    }
+}
+
+impl From<keyring::Error> for Error {
+    fn from(value: keyring::Error) -> Self {
+        Self::Credential(value.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(value: zip::result::ZipError) -> Self {
+        Self::Archive(value.to_string())
+    }
+}
+
+impl From<dialoguer::Error> for Error {
+    fn from(value: dialoguer::Error) -> Self {
+        Self::Prompt(value.to_string())
+    }
+}
 
 // impl From<paastel_rest::error::Error> for Error {
 //     fn from(_: paastel_rest::error::Error) -> Self {
@@ -12,7 +12,7 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-// pub mod cmd;
+pub mod cmd;
 pub mod error;
 pub mod util;
 
@@ -23,16 +23,35 @@ use paastel_settings::{Location, Settings};
 pub async fn execute() -> Result<(), error::Error> {
     util::init_tracing();
 
-    let command = Command::new("paastel").arg(
-        Arg::new("settings-file")
-            .long("settings-file")
-            .value_parser(ValueParser::new(util::parse_settings_var))
-            .default_value(Location::default_path().into_os_string())
-            .env("PAASTEL_SETTINGS")
-            .help("Set path of settings file"),
-    );
+    let command = Command::new("paastel")
+        .version(cmd::version::get_version_string())
+        .arg(
+            Arg::new("settings-file")
+                .long("settings-file")
+                .value_parser(ValueParser::new(util::parse_settings_var))
+                .default_value(Location::default_path().into_os_string())
+                .env("PAASTEL_SETTINGS")
+                .help(
+                    "Set path of settings file, `env:` reads settings only \
+                    from PAASTEL_* variables, `memory:` never saves them",
+                ),
+        )
+        .subcommand(cmd::auth::command())
+        .subcommand(cmd::context::command())
+        .subcommand(cmd::push::command())
+        .subcommand(cmd::settings::command())
+        .subcommand(cmd::user::command());
     let matches = command.clone().get_matches();
-    let _settings = matches.get_one::<Settings>("settings-file").unwrap();
+    let settings = matches.get_one::<Settings>("settings-file").unwrap();
+
+    match matches.subcommand() {
+        Some(("login", m)) => cmd::auth::login(m, settings).await?,
+        Some(("context", m)) => cmd::context::matches(m, settings)?,
+        Some(("push", m)) => cmd::push::push(m, settings).await?,
+        Some(("settings", m)) => cmd::settings::matches(m, settings)?,
+        Some(("user", m)) => cmd::user::matches(m, settings).await?,
+        _ => {}
+    }
 
     Ok(())
 }
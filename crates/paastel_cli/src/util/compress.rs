@@ -12,72 +12,651 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-use std::io::{Read, Seek, Write};
-use std::iter::Iterator;
-use zip::result::ZipError;
-use zip::write::FileOptions;
-
 use std::fs::File;
-use std::path::Path;
+use std::io::{BufReader, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use sha2::{Digest, Sha256};
 use walkdir::{DirEntry, WalkDir};
+use zip::write::FileOptions;
+
+use crate::error::Error;
+
+/// Ignore file read from root of source directory
+pub const IGNORE_FILE: &str = ".paastelignore";
+
+/// Ignore file used when `.paastelignore` is missing
+pub const GIT_IGNORE_FILE: &str = ".gitignore";
+
+/// Directories never archived
+const ALWAYS_IGNORED: [&str; 1] = [".git"];
+
+/// Compression level of tar.zst archives
+const ZSTD_LEVEL: i32 = 3;
+
+/// Format of archive, must be accepted by server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Zip,
+    TarGz,
+    TarZst,
+}
+
+impl Format {
+    /// All formats, preferred first
+    pub const ALL: [Format; 3] = [Format::TarZst, Format::TarGz, Format::Zip];
+
+    /// Extension of archive file, also name of format on server
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Zip => "zip",
+            Format::TarGz => "tar.gz",
+            Format::TarZst => "tar.zst",
+        }
+    }
+
+    /// Content type of upload
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Format::Zip => "application/zip",
+            Format::TarGz => "application/gzip",
+            Format::TarZst => "application/zstd",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zip" => Ok(Format::Zip),
+            "tar.gz" | "tgz" => Ok(Format::TarGz),
+            "tar.zst" => Ok(Format::TarZst),
+            _ => Err(Error::Archive(format!("unknown format {s}"))),
+        }
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+/// Archive written by [`dir`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Archive {
+    format: Format,
+    /// Hex encoded SHA-256 of archive
+    digest: String,
+    size: u64,
+    /// Number of files archived
+    files: usize,
+}
+
+impl Archive {
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn digest(&self) -> &str {
+        self.digest.as_str()
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn files(&self) -> usize {
+        self.files
+    }
+}
+
+/// Content of a source entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceKind {
+    File {
+        /// Hex encoded SHA-256 of file
+        digest: String,
+        size: u64,
+        executable: bool,
+    },
+    Symlink {
+        target: String,
+    },
+}
+
+/// Entry of sources listed by [`sources`], archive entry without content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    /// Name inside archive
+    name: String,
+    path: PathBuf,
+    kind: SourceKind,
+}
+
+impl Source {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    pub fn kind(&self) -> &SourceKind {
+        &self.kind
+    }
+}
+
+/// Gitignore style patterns of `.paastelignore`, falling back to
+/// `.gitignore`, only file on root of directory is read
+fn ignore_rules(src_dir: &Path) -> Result<Gitignore, Error> {
+    let mut builder = GitignoreBuilder::new(src_dir);
+    for name in ALWAYS_IGNORED {
+        builder
+            .add_line(None, &format!("/{name}/"))
+            .map_err(|e| Error::Archive(e.to_string()))?;
+    }
+
+    let file = [IGNORE_FILE, GIT_IGNORE_FILE]
+        .into_iter()
+        .map(|name| src_dir.join(name))
+        .find(|path| path.is_file());
+    if let Some(file) = file {
+        tracing::debug!(?file, "ignore rules");
+        if let Some(e) = builder.add(&file) {
+            return Err(Error::Archive(e.to_string()));
+        }
+    }
+
+    builder.build().map_err(|e| Error::Archive(e.to_string()))
+}
+
+fn is_ignored(rules: &Gitignore, src_dir: &Path, entry: &DirEntry) -> bool {
+    match entry.path().strip_prefix(src_dir) {
+        Ok(name) if !name.as_os_str().is_empty() => rules
+            .matched_path_or_any_parents(name, entry.file_type().is_dir())
+            .is_ignore(),
+        _ => false,
+    }
+}
+
+/// Name of entry inside archive, `/` separated whatever the platform
+fn entry_name(src_dir: &Path, path: &Path) -> Result<String, Error> {
+    let name = path
+        .strip_prefix(src_dir)
+        .map_err(|e| Error::Archive(e.to_string()))?;
+    let parts = name
+        .components()
+        .map(|c| {
+            c.as_os_str().to_str().ok_or_else(|| {
+                Error::Archive(format!("invalid file name {path:?}"))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(parts.join("/"))
+}
+
+/// Walk source directory sorted by name, ignored entries are skipped
+fn walk<'a>(
+    src_dir: &'a Path,
+    rules: &'a Gitignore,
+) -> impl Iterator<Item = Result<DirEntry, Error>> + 'a {
+    WalkDir::new(src_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(move |entry| !is_ignored(rules, src_dir, entry))
+        .map(|entry| entry.map_err(|e| Error::Archive(e.to_string())))
+}
+
+/// Files with any executable bit are archived as executables
+#[cfg(unix)]
+fn is_executable(entry: &DirEntry) -> Result<bool, Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let metadata = entry
+        .metadata()
+        .map_err(|e| Error::Archive(e.to_string()))?;
+    Ok(metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_entry: &DirEntry) -> Result<bool, Error> {
+    Ok(false)
+}
+
+/// Target of symbolic link, must stay inside source directory once unpacked
+fn link_target(src_dir: &Path, entry: &DirEntry) -> Result<String, Error> {
+    let target = std::fs::read_link(entry.path())?;
+    let mut depth = entry.depth().saturating_sub(1);
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => {
+                return Err(Error::Archive(format!(
+                    "link {} to {} escapes {}",
+                    entry.path().display(),
+                    target.display(),
+                    src_dir.display()
+                )))
+            }
+        }
+    }
+    target.to_str().map(ToString::to_string).ok_or_else(|| {
+        Error::Archive(format!("invalid link target {target:?}"))
+    })
+}
 
-fn zip_dir<T>(
-    it: &mut dyn Iterator<Item = DirEntry>,
-    prefix: &str,
-    writer: T,
-    method: zip::CompressionMethod,
-) -> zip::result::ZipResult<()>
+/// Entries are sorted by name and written with fixed timestamps and
+/// permissions, same sources yield same archive
+fn zip_dir<T>(src_dir: &Path, writer: T) -> Result<usize, Error>
 where
     T: Write + Seek,
 {
+    let rules = ignore_rules(src_dir)?;
     let mut zip = zip::ZipWriter::new(writer);
     let options = FileOptions::default()
-        .compression_method(method)
-        .unix_permissions(0o755);
+        .compression_method(zip::CompressionMethod::Deflated)
+        .last_modified_time(zip::DateTime::default());
+    let file_options = options.unix_permissions(0o644);
+    let exec_options = options.unix_permissions(0o755);
+    let dir_options = options.unix_permissions(0o755);
 
-    let mut buffer = Vec::new();
-    for entry in it {
+    let mut files = 0;
+    for entry in walk(src_dir, &rules) {
+        let entry = entry?;
         let path = entry.path();
-        let name = path.strip_prefix(Path::new(prefix)).unwrap();
-
-        // Write file or directory explicitly
-        // Some unzip tools unzip files with directory paths correctly, some do not!
-        if path.is_file() {
-            println!("adding file {:?} as {:?} ...", path, name);
-            zip.start_file(name.to_str().unwrap(), options)?;
-            let mut f = File::open(path)?;
-
-            f.read_to_end(&mut buffer)?;
-            zip.write_all(&buffer)?;
-            buffer.clear();
-        } else if !name.as_os_str().is_empty() {
+        let name = entry_name(src_dir, path)?;
+        let file_type = entry.file_type();
+
+        if file_type.is_symlink() {
+            tracing::debug!(?path, name, "adding symlink");
+            let target = link_target(src_dir, &entry)?;
+            zip.add_symlink(name, target, options)?;
+            files += 1;
+        } else if file_type.is_file() {
+            tracing::debug!(?path, name, "adding file");
+            let options = match is_executable(&entry)? {
+                true => exec_options,
+                false => file_options,
+            };
+            zip.start_file(name, options)?;
+            // NOTE: copied chunk by chunk, files are never fully buffered
+            std::io::copy(&mut File::open(path)?, &mut zip)?;
+            files += 1;
+        } else if file_type.is_dir() && !name.is_empty() {
             // Only if not root! Avoids path spec / warning
             // and mapname conversion failed error on unzip
-            println!("adding dir {:?} as {:?} ...", path, name);
-            zip.add_directory(name.to_str().unwrap(), options)?;
+            tracing::debug!(?path, name, "adding dir");
+            zip.add_directory(name, dir_options)?;
+        } else if !name.is_empty() {
+            tracing::warn!(?path, "skipping special file");
         }
     }
     zip.finish()?;
-    Result::Ok(())
+    Ok(files)
+}
+
+/// Same as [`zip_dir`], deterministic header mode drops owners and
+/// timestamps and keeps only executable bit of permissions
+fn tar_dir<T>(src_dir: &Path, writer: T) -> Result<(usize, T), Error>
+where
+    T: Write,
+{
+    let rules = ignore_rules(src_dir)?;
+    let mut tar = tar::Builder::new(writer);
+    tar.mode(tar::HeaderMode::Deterministic);
+    tar.follow_symlinks(false);
+
+    let mut files = 0;
+    for entry in walk(src_dir, &rules) {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry_name(src_dir, path)?;
+        let file_type = entry.file_type();
+
+        if file_type.is_symlink() {
+            tracing::debug!(?path, name, "adding symlink");
+            let target = link_target(src_dir, &entry)?;
+            let mut header = tar::Header::new_gnu();
+            header.set_metadata_in_mode(
+                &std::fs::symlink_metadata(path)?,
+                tar::HeaderMode::Deterministic,
+            );
+            header.set_size(0);
+            tar.append_link(&mut header, name, target)?;
+            files += 1;
+        } else if file_type.is_file() {
+            tracing::debug!(?path, name, "adding file");
+            tar.append_path_with_name(path, name)?;
+            files += 1;
+        } else if file_type.is_dir() && !name.is_empty() {
+            tracing::debug!(?path, name, "adding dir");
+            tar.append_path_with_name(path, name)?;
+        } else if !name.is_empty() {
+            tracing::warn!(?path, "skipping special file");
+        }
+    }
+    Ok((files, tar.into_inner()?))
+}
+
+/// SHA-256 and size of file, read in chunks
+fn digest(path: &Path) -> Result<(String, u64), Error> {
+    let mut hasher = Sha256::new();
+    let size =
+        std::io::copy(&mut BufReader::new(File::open(path)?), &mut hasher)?;
+    Ok((format!("{:x}", hasher.finalize()), size))
 }
 
-pub fn dir(src_dir: &str, dst_file: &str) -> zip::result::ZipResult<()> {
-    if !Path::new(src_dir).is_dir() {
-        return Err(ZipError::FileNotFound);
+/// Archive directory into a file of given format
+pub fn dir(
+    src_dir: &Path,
+    dst_file: &Path,
+    format: Format,
+) -> Result<Archive, Error> {
+    if !src_dir.is_dir() {
+        return Err(Error::Archive(format!(
+            "{} is not a directory",
+            src_dir.display()
+        )));
     }
 
-    let path = Path::new(dst_file);
-    let file = File::create(path).unwrap();
+    let file = File::create(dst_file)?;
+    let files = match format {
+        Format::Zip => zip_dir(src_dir, file)?,
+        Format::TarGz => {
+            // NOTE: no mtime nor file name on gzip header
+            let encoder = flate2::GzBuilder::new()
+                .mtime(0)
+                .write(file, flate2::Compression::default());
+            let (files, encoder) = tar_dir(src_dir, encoder)?;
+            encoder.finish()?;
+            files
+        }
+        Format::TarZst => {
+            let encoder = zstd::stream::write::Encoder::new(file, ZSTD_LEVEL)?;
+            let (files, encoder) = tar_dir(src_dir, encoder)?;
+            encoder.finish()?;
+            files
+        }
+    };
+    let (digest, size) = digest(dst_file)?;
 
-    let walkdir = WalkDir::new(src_dir);
-    let it = walkdir.into_iter();
+    Ok(Archive {
+        format,
+        digest,
+        size,
+        files,
+    })
+}
+
+/// Files and symbolic links archived by [`dir`], with digest of each file
+pub fn sources(src_dir: &Path) -> Result<Vec<Source>, Error> {
+    if !src_dir.is_dir() {
+        return Err(Error::Archive(format!(
+            "{} is not a directory",
+            src_dir.display()
+        )));
+    }
+
+    let rules = ignore_rules(src_dir)?;
+    let mut sources = Vec::new();
+    for entry in walk(src_dir, &rules) {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type();
+        let kind = if file_type.is_symlink() {
+            SourceKind::Symlink {
+                target: link_target(src_dir, &entry)?,
+            }
+        } else if file_type.is_file() {
+            let (digest, size) = digest(path)?;
+            SourceKind::File {
+                digest,
+                size,
+                executable: is_executable(&entry)?,
+            }
+        } else {
+            continue;
+        };
+        sources.push(Source {
+            name: entry_name(src_dir, path)?,
+            path: path.to_path_buf(),
+            kind,
+        });
+    }
+    Ok(sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    fn write(root: &Path, name: &str, content: &str) {
+        let path = root.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn names(archive: &Path) -> Vec<String> {
+        let mut zip =
+            zip::ZipArchive::new(File::open(archive).unwrap()).unwrap();
+        (0..zip.len())
+            .map(|i| zip.by_index(i).unwrap().name().to_string())
+            .collect()
+    }
+
+    fn source() -> tempfile::TempDir {
+        let src = tempfile::tempdir().unwrap();
+        write(src.path(), "main.rs", "fn main() {}");
+        write(src.path(), "target/debug/app", "binary");
+        write(src.path(), ".git/HEAD", "ref: refs/heads/main");
+        write(src.path(), "src/lib.rs", "pub fn lib() {}");
+        src
+    }
 
-    zip_dir(
-        &mut it.filter_map(|e| e.ok()),
-        src_dir,
-        file,
-        zip::CompressionMethod::Stored,
-    )?;
+    #[test]
+    fn gitignore_fallback() {
+        let src = source();
+        write(src.path(), ".gitignore", "/target\n");
+        let out = tempfile::tempdir().unwrap();
+        let dst = out.path().join("app.zip");
 
-    Ok(())
+        let archive = dir(src.path(), &dst, Format::Zip).unwrap();
+
+        assert_eq!(archive.files(), 3);
+        assert_eq!(
+            names(&dst),
+            [".gitignore", "main.rs", "src/", "src/lib.rs"]
+        );
+    }
+
+    #[test]
+    fn paastelignore_before_gitignore() {
+        let src = source();
+        write(src.path(), ".gitignore", "/target\n");
+        write(src.path(), ".paastelignore", "*.rs\n!main.rs\n");
+        let out = tempfile::tempdir().unwrap();
+        let dst = out.path().join("app.zip");
+
+        dir(src.path(), &dst, Format::Zip).unwrap();
+
+        assert_eq!(
+            names(&dst),
+            [
+                ".gitignore",
+                ".paastelignore",
+                "main.rs",
+                "src/",
+                "target/",
+                "target/debug/",
+                "target/debug/app"
+            ]
+        );
+    }
+
+    #[test]
+    fn same_sources_same_digest() {
+        let src = source();
+        let out = tempfile::tempdir().unwrap();
+        let first = dir(src.path(), &out.path().join("first.zip"), Format::Zip)
+            .unwrap();
+
+        // rewrite content, only timestamps change
+        write(src.path(), "main.rs", "fn main() {}");
+        let second =
+            dir(src.path(), &out.path().join("second.zip"), Format::Zip)
+                .unwrap();
+        assert_eq!(first, second);
+
+        write(src.path(), "main.rs", "fn main() { todo!() }");
+        let third = dir(src.path(), &out.path().join("third.zip"), Format::Zip)
+            .unwrap();
+        assert_ne!(first.digest(), third.digest());
+    }
+
+    #[test]
+    fn digest_of_archive() {
+        let src = source();
+        let out = tempfile::tempdir().unwrap();
+        let dst = out.path().join("app.zip");
+        let archive = dir(src.path(), &dst, Format::Zip).unwrap();
+
+        let mut content = Vec::new();
+        File::open(&dst).unwrap().read_to_end(&mut content).unwrap();
+        assert_eq!(archive.size(), content.len() as u64);
+        assert_eq!(archive.digest(), format!("{:x}", Sha256::digest(&content)));
+    }
+
+    #[test]
+    fn not_a_directory() {
+        let out = tempfile::tempdir().unwrap();
+        let result = dir(
+            &out.path().join("missing"),
+            &out.path().join("a.zip"),
+            Format::Zip,
+        );
+        assert!(matches!(result, Err(Error::Archive(_))));
+    }
+
+    #[cfg(unix)]
+    fn tar_entries(archive: &Path) -> Vec<(String, tar::EntryType, u32)> {
+        let decoder =
+            zstd::stream::read::Decoder::new(File::open(archive).unwrap())
+                .unwrap();
+        tar::Archive::new(decoder)
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let header = entry.header();
+                (
+                    entry.path().unwrap().display().to_string(),
+                    header.entry_type(),
+                    header.mode().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tar_keeps_symlinks_and_exec_bit() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let src = source();
+        write(src.path(), ".gitignore", "/target\n");
+        write(src.path(), "bin/start", "#!/bin/sh");
+        let start = src.path().join("bin/start");
+        std::fs::set_permissions(
+            &start,
+            std::fs::Permissions::from_mode(0o700),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(
+            "../main.rs",
+            src.path().join("bin/main.rs"),
+        )
+        .unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let dst = out.path().join("app.tar.zst");
+
+        let archive = dir(src.path(), &dst, Format::TarZst).unwrap();
+
+        assert_eq!(archive.format(), Format::TarZst);
+        assert_eq!(archive.files(), 5);
+        assert_eq!(
+            tar_entries(&dst),
+            [
+                (".gitignore".to_string(), tar::EntryType::Regular, 0o644),
+                ("bin".to_string(), tar::EntryType::Directory, 0o755),
+                ("bin/main.rs".to_string(), tar::EntryType::Symlink, 0o755),
+                ("bin/start".to_string(), tar::EntryType::Regular, 0o755),
+                ("main.rs".to_string(), tar::EntryType::Regular, 0o644),
+                ("src".to_string(), tar::EntryType::Directory, 0o755),
+                ("src/lib.rs".to_string(), tar::EntryType::Regular, 0o644),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_outside_sources() {
+        let src = source();
+        std::os::unix::fs::symlink(
+            "../../etc/passwd",
+            src.path().join("src/passwd"),
+        )
+        .unwrap();
+        let out = tempfile::tempdir().unwrap();
+
+        for format in Format::ALL {
+            let dst = out.path().join(format!("app.{format}"));
+            let result = dir(src.path(), &dst, format);
+            assert!(matches!(result, Err(Error::Archive(_))), "{format}");
+        }
+    }
+
+    #[test]
+    fn tar_gz_same_sources_same_digest() {
+        let src = source();
+        let out = tempfile::tempdir().unwrap();
+        let first =
+            dir(src.path(), &out.path().join("first.tar.gz"), Format::TarGz)
+                .unwrap();
+
+        write(src.path(), "main.rs", "fn main() {}");
+        let second =
+            dir(src.path(), &out.path().join("second.tar.gz"), Format::TarGz)
+                .unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn sources_with_digests() {
+        let src = source();
+        write(src.path(), ".gitignore", "/target\n");
+
+        let sources = sources(src.path()).unwrap();
+
+        let names: Vec<_> = sources.iter().map(Source::name).collect();
+        assert_eq!(names, [".gitignore", "main.rs", "src/lib.rs"]);
+        assert_eq!(
+            sources[1].kind(),
+            &SourceKind::File {
+                digest: format!("{:x}", Sha256::digest(b"fn main() {}")),
+                size: 12,
+                executable: false,
+            }
+        );
+    }
 }
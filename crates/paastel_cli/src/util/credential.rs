@@ -0,0 +1,342 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use dialoguer::Password;
+use paastel_settings::{Location, SecretStore, Settings};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// Service name of secrets on keyring
+const KEYRING_SERVICE: &str = "paastel";
+
+/// Encrypted file next to settings file
+const CREDENTIALS_FILE: &str = "credentials.json";
+
+/// Version of encrypted file format
+const CREDENTIALS_VERSION: u32 = 1;
+
+/// Passphrase of encrypted file, prompted when missing
+const PASSPHRASE_ENV: &str = "PAASTEL_PASSPHRASE";
+
+/// Length of salt used deriving key from passphrase
+const SALT_LENGTH: usize = 16;
+
+/// Keeps secrets out of settings, settings only hold a
+/// [`paastel_settings::CredentialRef`]
+pub trait CredentialStore {
+    /// Store backing implementation
+    fn kind(&self) -> SecretStore;
+
+    /// Secret under key, `None` when missing
+    fn get(&self, key: &str) -> Result<Option<String>, Error>;
+
+    /// Save secret under key, replacing previous one
+    fn set(&self, key: &str, secret: &str) -> Result<(), Error>;
+
+    /// Remove secret under key, missing key is not an error
+    fn delete(&self, key: &str) -> Result<(), Error>;
+}
+
+/// Open store of reference, passphrase of encrypted file is read from
+/// `PAASTEL_PASSPHRASE` or prompted
+pub fn open(
+    store: SecretStore,
+    settings: &Settings,
+) -> Result<Box<dyn CredentialStore>, Error> {
+    match store {
+        SecretStore::Keyring => Ok(Box::new(KeyringStore)),
+        SecretStore::File => {
+            let path = credentials_path(settings);
+            let passphrase = passphrase(!path.exists())?;
+            Ok(Box::new(FileStore::new(path, passphrase)))
+        }
+    }
+}
+
+/// Encrypted file next to settings file, on default settings directory
+/// when settings are not on a file
+fn credentials_path(settings: &Settings) -> PathBuf {
+    let settings_path = match settings.location().path() {
+        Some(path) => path.to_path_buf(),
+        None => Location::default_path(),
+    };
+    settings_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(CREDENTIALS_FILE)
+}
+
+fn passphrase(confirm: bool) -> Result<String, Error> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let mut prompt = Password::new().with_prompt("Passphrase of credentials");
+    if confirm {
+        prompt = prompt
+            .with_confirmation("Repeat passphrase", "Passphrases mismatch");
+    }
+    Ok(prompt.interact()?)
+}
+
+/// Keyring of operating system, Secret Service on linux
+pub struct KeyringStore;
+
+impl KeyringStore {
+    fn entry(key: &str) -> Result<keyring::Entry, Error> {
+        Ok(keyring::Entry::new(KEYRING_SERVICE, key)?)
+    }
+}
+
+impl CredentialStore for KeyringStore {
+    fn kind(&self) -> SecretStore {
+        SecretStore::Keyring
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, Error> {
+        match Self::entry(key)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<(), Error> {
+        Ok(Self::entry(key)?.set_password(secret)?)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), Error> {
+        match Self::entry(key)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Secret encrypted with XChaCha20-Poly1305, key is bound as associated
+/// data so entries cannot be swapped
+#[derive(Serialize, Deserialize)]
+struct Sealed {
+    nonce: String,
+    secret: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CredentialsFile {
+    version: u32,
+    /// Salt of Argon2id deriving key from passphrase
+    salt: String,
+    entries: BTreeMap<String, Sealed>,
+}
+
+/// File encrypted with a key derived from a passphrase, fallback when
+/// there is no keyring, ex: headless servers
+pub struct FileStore {
+    path: PathBuf,
+    passphrase: String,
+}
+
+impl FileStore {
+    pub fn new(path: PathBuf, passphrase: String) -> Self {
+        Self { path, passphrase }
+    }
+
+    fn load(&self) -> Result<CredentialsFile, Error> {
+        if !self.path.exists() {
+            let mut salt = [0u8; SALT_LENGTH];
+            OsRng.fill_bytes(&mut salt);
+            return Ok(CredentialsFile {
+                version: CREDENTIALS_VERSION,
+                salt: BASE64_STANDARD.encode(salt),
+                entries: BTreeMap::new(),
+            });
+        }
+        let text = std::fs::read_to_string(&self.path)?;
+        let file: CredentialsFile =
+            serde_json::from_str(&text).map_err(|e| {
+                Error::Credential(format!("{} {e}", self.path.display()))
+            })?;
+        if file.version != CREDENTIALS_VERSION {
+            return Err(Error::Credential(format!(
+                "{} has unsupported version {}",
+                self.path.display(),
+                file.version
+            )));
+        }
+        Ok(file)
+    }
+
+    fn save(&self, file: &CredentialsFile) -> Result<(), Error> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = serde_json::to_string_pretty(file)
+            .map_err(|e| Error::Credential(e.to_string()))?;
+
+        let partial = self.path.with_extension("partial");
+        let mut options = std::fs::OpenOptions::new();
+        options.create(true).truncate(true).write(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut out = options.open(&partial)?;
+        out.write_all(text.as_bytes())?;
+        out.sync_all()?;
+        std::fs::rename(&partial, &self.path)?;
+        Ok(())
+    }
+
+    fn cipher(
+        &self,
+        file: &CredentialsFile,
+    ) -> Result<XChaCha20Poly1305, Error> {
+        let salt = BASE64_STANDARD.decode(&file.salt)?;
+        let mut key = [0u8; 32];
+        argon2::Argon2::default()
+            .hash_password_into(self.passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| Error::Credential(format!("derive key {e}")))?;
+        Ok(XChaCha20Poly1305::new(&key.into()))
+    }
+}
+
+impl CredentialStore for FileStore {
+    fn kind(&self) -> SecretStore {
+        SecretStore::File
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, Error> {
+        let file = self.load()?;
+        let Some(sealed) = file.entries.get(key) else {
+            return Ok(None);
+        };
+        let nonce = BASE64_STANDARD.decode(&sealed.nonce)?;
+        if nonce.len() != 24 {
+            return Err(Error::Credential(format!("{key} has invalid nonce")));
+        }
+        let secret = BASE64_STANDARD.decode(&sealed.secret)?;
+        let plain = self
+            .cipher(&file)?
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &secret,
+                    aad: key.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                Error::Credential(format!(
+                    "wrong passphrase or {} corrupted",
+                    self.path.display()
+                ))
+            })?;
+        String::from_utf8(plain)
+            .map(Some)
+            .map_err(|e| Error::Credential(e.to_string()))
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<(), Error> {
+        let mut file = self.load()?;
+        // NOTE: a wrong passphrase must not encrypt entries with another key
+        if let Some(other) = file.entries.keys().next().cloned() {
+            self.get(&other)?;
+        }
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let sealed = self
+            .cipher(&file)?
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: secret.as_bytes(),
+                    aad: key.as_bytes(),
+                },
+            )
+            .map_err(|e| Error::Credential(format!("encrypt {e}")))?;
+        file.entries.insert(
+            key.to_string(),
+            Sealed {
+                nonce: BASE64_STANDARD.encode(nonce),
+                secret: BASE64_STANDARD.encode(sealed),
+            },
+        );
+        self.save(&file)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), Error> {
+        let mut file = self.load()?;
+        if file.entries.remove(key).is_some() {
+            self.save(&file)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_store_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CREDENTIALS_FILE);
+        let store = FileStore::new(path.clone(), "passphrase".to_string());
+
+        assert_eq!(store.get("admin@local").unwrap(), None);
+        store.set("admin@local", "secret-token").unwrap();
+        store.set("dev@local", "other-token").unwrap();
+
+        assert_eq!(
+            store.get("admin@local").unwrap().as_deref(),
+            Some("secret-token")
+        );
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("secret-token"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        store.delete("admin@local").unwrap();
+        assert_eq!(store.get("admin@local").unwrap(), None);
+        assert_eq!(
+            store.get("dev@local").unwrap().as_deref(),
+            Some("other-token")
+        );
+    }
+
+    #[test]
+    fn file_store_wrong_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CREDENTIALS_FILE);
+        FileStore::new(path.clone(), "passphrase".to_string())
+            .set("admin@local", "secret-token")
+            .unwrap();
+
+        let store = FileStore::new(path, "wrong".to_string());
+        assert!(matches!(
+            store.get("admin@local"),
+            Err(Error::Credential(_))
+        ));
+        assert!(store.set("dev@local", "token").is_err());
+    }
+}
@@ -1,9 +1,17 @@
 use std::path::Path;
 
 use clap::{Arg, ArgAction};
+use paastel_client::Client;
 use paastel_settings::{Location, Settings};
 
-// pub mod compress;
+use crate::error::Error;
+
+// Name your user agent after your app?
+static APP_USER_AGENT: &str =
+    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
+
+pub mod compress;
+pub mod credential;
 // pub mod style;
 
 pub fn flag(name: &'static str, help: &'static str) -> Arg {
@@ -37,9 +45,21 @@ pub(crate) fn init_tracing() {
 }
 
 pub(crate) fn parse_settings_var(env: &str) -> Result<Settings, String> {
-    let path = Path::new(env).to_path_buf();
-    let settings_location: Location = path.into();
+    let settings_location = Location::from(env.to_string());
     let settings =
         Settings::try_from(&settings_location).map_err(|e| e.to_string())?;
     Ok(settings)
 }
+
+/// Client of PaaStel api at url, trusting certificates of CA bundle
+/// besides the system ones
+pub(crate) fn client(
+    api: &str,
+    ca_bundle: Option<&Path>,
+) -> Result<Client, Error> {
+    let client = Client::new(api)?.with_user_agent(APP_USER_AGENT);
+    Ok(match ca_bundle {
+        Some(path) => client.with_ca_bundle(path)?,
+        None => client,
+    })
+}
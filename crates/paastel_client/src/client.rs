@@ -0,0 +1,459 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{path::Path, time::Duration};
+
+use bytes::Bytes;
+use reqwest::{
+    header::{self, HeaderName},
+    Certificate, Method, Response,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use url::Url;
+
+use crate::error::{Error, Problem};
+
+/// Timeout of establishing a connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Timeout of requests, ex: show an application
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Timeout of requests sending or receiving files, ex: upload an archive
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Retries of a failed request before giving up
+const RETRIES: u32 = 2;
+
+/// Wait before first retry, doubled on each retry
+const BACKOFF: Duration = Duration::from_millis(500);
+
+/// Sent unless another user agent is given
+const USER_AGENT: &str =
+    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Authentication of requests to api
+#[derive(Clone, Default)]
+pub enum Auth {
+    /// Access token issued on login
+    Bearer(String),
+    /// Username and password
+    Basic(String, Option<String>),
+    #[default]
+    None,
+}
+
+/// Client of PaaStel api, requests failing on network or server errors
+/// are retried when safe to send again
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    api: Url,
+    auth: Auth,
+    user_agent: String,
+    timeout: Duration,
+    transfer_timeout: Duration,
+    retries: u32,
+}
+
+impl Client {
+    /// Client of api at url, ex: `https://paastel.example.com`
+    pub fn new(api: &str) -> Result<Self, Error> {
+        Ok(Self {
+            http: http_client(Vec::new())?,
+            api: api_url(api)?,
+            auth: Auth::None,
+            user_agent: USER_AGENT.to_string(),
+            timeout: TIMEOUT,
+            transfer_timeout: TRANSFER_TIMEOUT,
+            retries: RETRIES,
+        })
+    }
+
+    /// Trust certificate authorities of PEM bundle besides the system ones
+    pub fn with_ca_bundle(mut self, path: &Path) -> Result<Self, Error> {
+        let bundle_error = |reason: String| Error::CaBundle {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let pem =
+            std::fs::read(path).map_err(|e| bundle_error(e.to_string()))?;
+        let roots = Certificate::from_pem_bundle(&pem)
+            .map_err(|e| bundle_error(e.to_string()))?;
+        self.http = http_client(roots)?;
+        Ok(self)
+    }
+
+    pub fn with_auth(mut self, auth: Auth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_transfer_timeout(mut self, timeout: Duration) -> Self {
+        self.transfer_timeout = timeout;
+        self
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Url of api ending in `/`
+    pub fn api(&self) -> &Url {
+        &self.api
+    }
+
+    pub fn auth(&self) -> &Auth {
+        &self.auth
+    }
+
+    /// Url of path below `api/v1`, segments are escaped
+    pub(crate) fn url<I>(&self, segments: I) -> Url
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut url = self.api.clone();
+        url.path_segments_mut()
+            .expect("api url is http or https")
+            .pop_if_empty()
+            .extend(["api", "v1"])
+            .extend(segments);
+        url
+    }
+
+    /// Send request and decode json body of response
+    pub(crate) async fn json<T: DeserializeOwned>(
+        &self,
+        request: Request<'_>,
+    ) -> Result<T, Error> {
+        let url = request.url.clone();
+        self.send(request)
+            .await?
+            .json()
+            .await
+            .map_err(|e| Error::from_reqwest(&url, e))
+    }
+
+    /// Send request, retried while it fails with a retryable error, errors
+    /// answered by server are mapped to [`Error::Api`]
+    pub(crate) async fn send(
+        &self,
+        request: Request<'_>,
+    ) -> Result<Response, Error> {
+        let mut attempt = 0;
+        loop {
+            match self.execute(&request).await {
+                Err(e) if attempt < self.retries && request.retryable(&e) => {
+                    attempt += 1;
+                    tracing::debug!(
+                        url = %request.url,
+                        attempt,
+                        "retrying request {e}"
+                    );
+                    tokio::time::sleep(BACKOFF * 2u32.pow(attempt - 1)).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn execute(&self, request: &Request<'_>) -> Result<Response, Error> {
+        let timeout = match request.transfer {
+            true => self.transfer_timeout,
+            false => self.timeout,
+        };
+        let mut builder = self
+            .http
+            .request(request.method.clone(), request.url.clone())
+            .timeout(timeout)
+            .header(header::USER_AGENT, &self.user_agent);
+        if !request.anonymous {
+            builder = match &self.auth {
+                Auth::Bearer(token) => builder.bearer_auth(token),
+                Auth::Basic(username, password) => {
+                    builder.basic_auth(username, password.as_deref())
+                }
+                Auth::None => builder,
+            };
+        }
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        builder = match &request.body {
+            Body::Empty => builder,
+            Body::Bytes(content_type, bytes) => builder
+                .header(header::CONTENT_TYPE, content_type)
+                .body(bytes.clone()),
+            // NOTE: file is opened on each attempt, so it can be sent again
+            Body::File(content_type, path) => {
+                let io_error = |source| Error::Io {
+                    path: path.to_path_buf(),
+                    source,
+                };
+                let file =
+                    tokio::fs::File::open(path).await.map_err(io_error)?;
+                let size = file.metadata().await.map_err(io_error)?.len();
+                builder
+                    .header(header::CONTENT_TYPE, content_type)
+                    .header(header::CONTENT_LENGTH, size)
+                    .body(file)
+            }
+        };
+
+        let res = builder
+            .send()
+            .await
+            .map_err(|e| Error::from_reqwest(&request.url, e))?;
+        check(res).await
+    }
+}
+
+/// Request to api, built by typed methods of [`Client`]
+pub(crate) struct Request<'a> {
+    method: Method,
+    url: Url,
+    headers: Vec<(HeaderName, String)>,
+    body: Body<'a>,
+    /// Sends or receives files, has a longer timeout
+    transfer: bool,
+    /// Sent without credentials, ex: login
+    anonymous: bool,
+}
+
+enum Body<'a> {
+    Empty,
+    Bytes(String, Bytes),
+    File(String, &'a Path),
+}
+
+impl<'a> Request<'a> {
+    pub(crate) fn new(method: Method, url: Url) -> Self {
+        Self {
+            method,
+            url,
+            headers: Vec::new(),
+            body: Body::Empty,
+            transfer: false,
+            anonymous: false,
+        }
+    }
+
+    pub(crate) fn json<T: Serialize>(self, body: &T) -> Result<Self, Error> {
+        let json = serde_json::to_vec(body)
+            .map_err(|e| Error::Encode(e.to_string()))?;
+        Ok(self.bytes("application/json", json))
+    }
+
+    pub(crate) fn bytes(
+        mut self,
+        content_type: &str,
+        body: impl Into<Bytes>,
+    ) -> Self {
+        self.body = Body::Bytes(content_type.to_string(), body.into());
+        self
+    }
+
+    pub(crate) fn file(mut self, content_type: &str, path: &'a Path) -> Self {
+        self.body = Body::File(content_type.to_string(), path);
+        self.transfer = true;
+        self
+    }
+
+    pub(crate) fn header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers
+            .push((HeaderName::from_static(name), value.to_string()));
+        self
+    }
+
+    pub(crate) fn transfer(mut self) -> Self {
+        self.transfer = true;
+        self
+    }
+
+    pub(crate) fn anonymous(mut self) -> Self {
+        self.anonymous = true;
+        self
+    }
+
+    /// Failed connections are safe to send again, other failures only when
+    /// sending request twice has same effect of sending it once
+    fn retryable(&self, e: &Error) -> bool {
+        let idempotent =
+            matches!(self.method, Method::GET | Method::PUT | Method::DELETE);
+        match e {
+            Error::Network { .. } => true,
+            Error::Timeout { .. } | Error::Http(_) => idempotent,
+            Error::Api { status, .. } => {
+                idempotent && matches!(status, 500 | 502 | 503 | 504)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Map non success status to [`Error::Api`] with problem details sent by
+/// server
+async fn check(res: Response) -> Result<Response, Error> {
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res);
+    }
+    let request_id = res
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .map(ToString::to_string);
+    let body = res.text().await.unwrap_or_default();
+    let problem = serde_json::from_str::<Problem>(&body)
+        .unwrap_or_else(|_| Problem::from_body(status, body, request_id));
+    Err(Error::Api {
+        status: status.as_u16(),
+        problem: Box::new(problem),
+    })
+}
+
+fn http_client(roots: Vec<Certificate>) -> Result<reqwest::Client, Error> {
+    let mut builder =
+        reqwest::ClientBuilder::new().connect_timeout(CONNECT_TIMEOUT);
+    for root in roots {
+        builder = builder.add_root_certificate(root);
+    }
+    builder.build().map_err(Error::Http)
+}
+
+/// Url of api, http or https ending in `/` so paths join below it
+pub fn api_url(url: &str) -> Result<Url, Error> {
+    let url_error = |reason: String| Error::Url {
+        url: url.to_string(),
+        reason,
+    };
+    let mut api = Url::parse(url).map_err(|e| url_error(e.to_string()))?;
+    if !matches!(api.scheme(), "http" | "https") {
+        return Err(url_error("must be an http or https url".to_string()));
+    }
+    if !api.path().ends_with('/') {
+        let path = format!("{}/", api.path());
+        api.set_path(&path);
+    }
+    api.set_query(None);
+    api.set_fragment(None);
+    Ok(api)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use super::*;
+
+    /// Server answering each connection with next response, returns its
+    /// url and count of requests received
+    fn serve(responses: Vec<&'static str>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let count = Arc::new(AtomicUsize::new(0));
+        let received = count.clone();
+        std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let _ = stream.read(&mut [0; 4096]);
+                received.fetch_add(1, Ordering::SeqCst);
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        (format!("http://{addr}"), count)
+    }
+
+    #[test]
+    fn url_below_api() {
+        let client =
+            Client::new("https://paastel.example.com/base?q=1").unwrap();
+        assert_eq!(client.api().as_str(), "https://paastel.example.com/base/");
+        assert_eq!(
+            client.url(["users", "a/b"]).as_str(),
+            "https://paastel.example.com/base/api/v1/users/a%2Fb"
+        );
+        assert_eq!(
+            client.url(["users", "a/b c", "password"]).path(),
+            "/base/api/v1/users/a%2Fb%20c/password"
+        );
+        assert!(Client::new("ftp://paastel.example.com").is_err());
+    }
+
+    #[tokio::test]
+    async fn tls_and_network_errors() {
+        // NOTE: plain http server answering a tls handshake
+        let (url, _) = serve(vec!["HTTP/1.1 400 Bad Request\r\n\r\n"]);
+        let client = Client::new(&url.replace("http", "https"))
+            .unwrap()
+            .with_retries(0);
+        assert!(matches!(client.info().await, Err(Error::Tls { .. })));
+
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = Client::new(&format!("http://{addr}"))
+            .unwrap()
+            .with_retries(0);
+        assert!(matches!(client.info().await, Err(Error::Network { .. })));
+    }
+
+    #[tokio::test]
+    async fn retry_idempotent_requests() {
+        const UNAVAILABLE: &str = "HTTP/1.1 503 Service Unavailable\r\n\
+            content-length: 4\r\nconnection: close\r\n\r\nbusy";
+        const INFO: &str = "HTTP/1.1 200 OK\r\n\
+            content-type: application/json\r\ncontent-length: 45\r\n\
+            connection: close\r\n\r\n\
+            {\"version\":\"0.1.0\",\"archive_formats\":[\"zip\"]}";
+
+        let (url, count) = serve(vec![UNAVAILABLE, INFO]);
+        let info = Client::new(&url).unwrap().info().await.unwrap();
+        assert_eq!(info.archive_formats(), ["zip"]);
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        let (url, count) = serve(vec![UNAVAILABLE, INFO]);
+        let e = Client::new(&url)
+            .unwrap()
+            .login("admin", "password")
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(e.status(), Some(503));
+        // NOTE: response without problem details, ex: from a proxy
+        assert_eq!(e.problem().unwrap().detail, "busy");
+        assert_eq!(e.problem().unwrap().code, "http_503");
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::path::PathBuf;
+
+use reqwest::StatusCode;
+use serde::Deserialize;
+use url::Url;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("`{url}` {reason}")]
+    Url { url: String, reason: String },
+
+    #[error("ca bundle {} {reason}", path.display())]
+    CaBundle { path: PathBuf, reason: String },
+
+    #[error("read {} {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("encode request {0}")]
+    Encode(String),
+
+    #[error("tls {url} {reason}")]
+    Tls { url: Url, reason: String },
+
+    #[error("{url} unreachable, {reason}")]
+    Network { url: Url, reason: String },
+
+    #[error("{url} timed out")]
+    Timeout { url: Url },
+
+    #[error("decode response of {url} {reason}")]
+    Decode { url: Url, reason: String },
+
+    #[error("http {0}")]
+    Http(reqwest::Error),
+
+    #[error("server {status} {}", problem.detail)]
+    Api { status: u16, problem: Box<Problem> },
+}
+
+impl Error {
+    /// Status of response when server answered with an error
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::Api { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Problem details of response when server answered with an error
+    pub fn problem(&self) -> Option<&Problem> {
+        match self {
+            Self::Api { problem, .. } => Some(problem),
+            _ => None,
+        }
+    }
+
+    /// Tell apart failures of tls, ex: untrusted certificate, from failures
+    /// reaching server
+    pub(crate) fn from_reqwest(url: &Url, e: reqwest::Error) -> Self {
+        let mut source = std::error::Error::source(&e);
+        while let Some(cause) = source {
+            // NOTE: io errors hide wrapped error from chain of sources
+            let mut inner = cause;
+            while let Some(io) = inner.downcast_ref::<std::io::Error>() {
+                match io.get_ref() {
+                    Some(wrapped) => inner = wrapped,
+                    None => break,
+                }
+            }
+            if let Some(tls) = inner.downcast_ref::<rustls::Error>() {
+                return Self::Tls {
+                    url: url.clone(),
+                    reason: tls.to_string(),
+                };
+            }
+            source = cause.source();
+        }
+        if e.is_timeout() {
+            return Self::Timeout { url: url.clone() };
+        }
+        if e.is_connect() {
+            return Self::Network {
+                url: url.clone(),
+                reason: e.to_string(),
+            };
+        }
+        if e.is_decode() {
+            return Self::Decode {
+                url: url.clone(),
+                reason: e.to_string(),
+            };
+        }
+        Self::Http(e)
+    }
+}
+
+/// Problem details of RFC 7807 with PaaStel extension members, sent by
+/// server on errors
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    #[serde(default)]
+    pub instance: Option<String>,
+    /// Stable identifier of error
+    pub code: String,
+    /// Value of `x-request-id`, for matching server logs
+    #[serde(default)]
+    pub request_id: Option<String>,
+}
+
+impl Problem {
+    /// Problem of a response without problem details, ex: answered by a
+    /// proxy in front of server
+    pub(crate) fn from_body(
+        status: StatusCode,
+        body: String,
+        request_id: Option<String>,
+    ) -> Self {
+        let title = status.canonical_reason().unwrap_or_default().to_string();
+        Self {
+            kind: "about:blank".to_string(),
+            detail: match body.trim().is_empty() {
+                true => title.clone(),
+                false => body,
+            },
+            title,
+            status: status.as_u16(),
+            instance: None,
+            code: format!("http_{}", status.as_u16()),
+            request_id,
+        }
+    }
+}
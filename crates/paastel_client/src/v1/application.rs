@@ -0,0 +1,150 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::{client::Request, Client, Error};
+
+#[derive(Serialize)]
+struct CreateAppRequest<'a> {
+    name: &'a str,
+}
+
+#[derive(Serialize)]
+struct DeployRequest<'a> {
+    stage_id: &'a str,
+}
+
+/// Application of a namespace with status of its workload
+#[derive(Debug, Clone, Deserialize)]
+pub struct App {
+    name: String,
+    namespace: String,
+    created_by: Option<String>,
+    image: Option<String>,
+    instances: Option<i32>,
+    routes: Vec<String>,
+    configurations: Vec<String>,
+    ready: bool,
+    ready_replicas: i32,
+    url: Option<String>,
+    message: Option<String>,
+}
+
+impl App {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn namespace(&self) -> &str {
+        self.namespace.as_str()
+    }
+
+    pub fn created_by(&self) -> Option<&str> {
+        self.created_by.as_deref()
+    }
+
+    /// Image deployed, none until a stage is deployed
+    pub fn image(&self) -> Option<&str> {
+        self.image.as_deref()
+    }
+
+    pub fn instances(&self) -> Option<i32> {
+        self.instances
+    }
+
+    pub fn routes(&self) -> &[String] {
+        &self.routes
+    }
+
+    pub fn configurations(&self) -> &[String] {
+        &self.configurations
+    }
+
+    /// All replicas of deployed image are ready
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    pub fn ready_replicas(&self) -> i32 {
+        self.ready_replicas
+    }
+
+    /// Route application is reachable from
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// Why application is not ready
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+impl Client {
+    pub async fn create_app(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> Result<App, Error> {
+        let url = self.url(["namespaces", namespace, "applications"]);
+        let request =
+            Request::new(Method::POST, url).json(&CreateAppRequest { name })?;
+        self.json(request).await
+    }
+
+    pub async fn show_app(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> Result<App, Error> {
+        let url = self.url(["namespaces", namespace, "applications", name]);
+        self.json(Request::new(Method::GET, url)).await
+    }
+
+    /// Create application when missing and configure it from manifest in
+    /// yaml
+    pub async fn apply_manifest(
+        &self,
+        namespace: &str,
+        name: &str,
+        manifest: impl Into<String>,
+    ) -> Result<App, Error> {
+        let url = self.url([
+            "namespaces",
+            namespace,
+            "applications",
+            name,
+            "manifest",
+        ]);
+        let request = Request::new(Method::PUT, url)
+            .bytes("application/yaml", manifest.into());
+        self.json(request).await
+    }
+
+    /// Run image built by a succeeded stage
+    pub async fn deploy_app(
+        &self,
+        namespace: &str,
+        name: &str,
+        stage_id: &str,
+    ) -> Result<App, Error> {
+        let url =
+            self.url(["namespaces", namespace, "applications", name, "deploy"]);
+        let request =
+            Request::new(Method::PUT, url).json(&DeployRequest { stage_id })?;
+        self.json(request).await
+    }
+}
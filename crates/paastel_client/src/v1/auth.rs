@@ -0,0 +1,80 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::{client::Request, Client, Error};
+
+#[derive(Serialize)]
+struct LoginRequest<'a> {
+    username: &'a str,
+    password: &'a str,
+}
+
+#[derive(Serialize)]
+struct RefreshRequest<'a> {
+    refresh_token: &'a str,
+}
+
+/// Tokens issued on login, access token authenticates requests until
+/// expiry and refresh token exchanges it for new tokens
+#[derive(Clone, Deserialize)]
+pub struct Tokens {
+    access_token: String,
+    expiry: u64,
+    refresh_token: String,
+    token_type: String,
+}
+
+impl Tokens {
+    pub fn access_token(&self) -> &str {
+        self.access_token.as_str()
+    }
+
+    /// Seconds since epoch access token expires
+    pub fn expiry(&self) -> u64 {
+        self.expiry
+    }
+
+    pub fn refresh_token(&self) -> &str {
+        self.refresh_token.as_str()
+    }
+
+    pub fn token_type(&self) -> &str {
+        self.token_type.as_str()
+    }
+}
+
+impl Client {
+    /// Exchange username and password for tokens
+    pub async fn login(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<Tokens, Error> {
+        let request = Request::new(Method::POST, self.url(["auth", "login"]))
+            .anonymous()
+            .json(&LoginRequest { username, password })?;
+        self.json(request).await
+    }
+
+    /// Exchange refresh token for new tokens
+    pub async fn refresh(&self, refresh_token: &str) -> Result<Tokens, Error> {
+        let request = Request::new(Method::POST, self.url(["auth", "refresh"]))
+            .anonymous()
+            .json(&RefreshRequest { refresh_token })?;
+        self.json(request).await
+    }
+}
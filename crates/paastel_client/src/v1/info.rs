@@ -0,0 +1,43 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use reqwest::Method;
+use serde::Deserialize;
+
+use crate::{client::Request, Client, Error};
+
+/// Server capabilities, read before pushing
+#[derive(Debug, Clone, Deserialize)]
+pub struct Info {
+    version: String,
+    archive_formats: Vec<String>,
+}
+
+impl Info {
+    pub fn version(&self) -> &str {
+        self.version.as_str()
+    }
+
+    /// Accepted source archives, preferred first
+    pub fn archive_formats(&self) -> &[String] {
+        &self.archive_formats
+    }
+}
+
+impl Client {
+    pub async fn info(&self) -> Result<Info, Error> {
+        self.json(Request::new(Method::GET, self.url(["info"])).anonymous())
+            .await
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::path::Path;
+
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::{client::Request, Blob, Client, Error};
+
+#[derive(Serialize)]
+struct MissingSourcesRequest<'a> {
+    digests: &'a [&'a str],
+}
+
+#[derive(Deserialize)]
+struct MissingSourcesResponse {
+    missing: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct AssembleSourcesRequest<'a> {
+    entries: &'a [SourceEntry],
+}
+
+/// Entry of sources assembled into an archive, regular file with digest of
+/// content or symbolic link with target
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceEntry {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    digest: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    executable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+}
+
+impl SourceEntry {
+    pub fn file(
+        path: impl Into<String>,
+        digest: impl Into<String>,
+        executable: bool,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            digest: Some(digest.into()),
+            executable,
+            target: None,
+        }
+    }
+
+    pub fn symlink(path: impl Into<String>, target: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            digest: None,
+            executable: false,
+            target: Some(target.into()),
+        }
+    }
+}
+
+impl Client {
+    /// Digests of source files server does not store yet
+    pub async fn missing_sources(
+        &self,
+        namespace: &str,
+        name: &str,
+        digests: &[&str],
+    ) -> Result<Vec<String>, Error> {
+        let url = self.url([
+            "namespaces",
+            namespace,
+            "applications",
+            name,
+            "sources",
+            "missing",
+        ]);
+        let request = Request::new(Method::POST, url)
+            .json(&MissingSourcesRequest { digests })?;
+        let res: MissingSourcesResponse = self.json(request).await?;
+        Ok(res.missing)
+    }
+
+    /// Upload content of source file with digest
+    pub async fn upload_source(
+        &self,
+        namespace: &str,
+        name: &str,
+        digest: &str,
+        path: &Path,
+    ) -> Result<Blob, Error> {
+        let url = self.url([
+            "namespaces",
+            namespace,
+            "applications",
+            name,
+            "sources",
+            digest,
+        ]);
+        let request = Request::new(Method::PUT, url)
+            .file("application/octet-stream", path);
+        self.json(request).await
+    }
+
+    /// Archive entries on server, contents of files must be uploaded before
+    pub async fn assemble_sources(
+        &self,
+        namespace: &str,
+        name: &str,
+        entries: &[SourceEntry],
+    ) -> Result<Blob, Error> {
+        let url = self.url([
+            "namespaces",
+            namespace,
+            "applications",
+            name,
+            "sources",
+        ]);
+        let request = Request::new(Method::POST, url)
+            .json(&AssembleSourcesRequest { entries })?
+            .transfer();
+        self.json(request).await
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::pin::Pin;
+
+use bytes::Bytes;
+use futures::{Stream, TryStreamExt};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::{client::Request, Client, Error};
+
+/// Build logs written so far, sent whole by server, logs written after
+/// request are not followed
+pub type LogStream = Pin<Box<dyn Stream<Item = Result<Bytes, Error>> + Send>>;
+
+#[derive(Serialize)]
+struct StageRequest<'a> {
+    blob_id: &'a str,
+    builder: Option<&'a str>,
+}
+
+/// Build of an uploaded archive into an image
+#[derive(Debug, Clone, Deserialize)]
+pub struct Stage {
+    id: String,
+    app: String,
+    namespace: String,
+    blob_id: String,
+    builder: String,
+    image: Option<String>,
+    phase: String,
+    reason: Option<String>,
+}
+
+impl Stage {
+    pub fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    pub fn app(&self) -> &str {
+        self.app.as_str()
+    }
+
+    pub fn namespace(&self) -> &str {
+        self.namespace.as_str()
+    }
+
+    pub fn blob_id(&self) -> &str {
+        self.blob_id.as_str()
+    }
+
+    pub fn builder(&self) -> &str {
+        self.builder.as_str()
+    }
+
+    /// Image reference, only when build succeeded
+    pub fn image(&self) -> Option<&str> {
+        self.image.as_deref()
+    }
+
+    /// Phase of build, ex: `Pending`, `Succeeded` or `Failed`
+    pub fn phase(&self) -> &str {
+        self.phase.as_str()
+    }
+
+    /// Why build failed
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+}
+
+impl Client {
+    /// Build image of application from uploaded archive, builder is
+    /// `buildpack` (default) or `dockerfile`
+    pub async fn stage_app(
+        &self,
+        namespace: &str,
+        name: &str,
+        blob_id: &str,
+        builder: Option<&str>,
+    ) -> Result<Stage, Error> {
+        let url =
+            self.url(["namespaces", namespace, "applications", name, "stage"]);
+        let request = Request::new(Method::POST, url)
+            .json(&StageRequest { blob_id, builder })?;
+        self.json(request).await
+    }
+
+    pub async fn show_stage(
+        &self,
+        namespace: &str,
+        id: &str,
+    ) -> Result<Stage, Error> {
+        let url = self.url(["namespaces", namespace, "stages", id]);
+        self.json(Request::new(Method::GET, url)).await
+    }
+
+    pub async fn stage_logs(
+        &self,
+        namespace: &str,
+        id: &str,
+    ) -> Result<LogStream, Error> {
+        let url = self.url(["namespaces", namespace, "stages", id, "logs"]);
+        let res = self
+            .send(Request::new(Method::GET, url.clone()).transfer())
+            .await?;
+        Ok(Box::pin(
+            res.bytes_stream()
+                .map_err(move |e| Error::from_reqwest(&url, e)),
+        ))
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::path::Path;
+
+use bytes::Bytes;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::{client::Request, Client, Error};
+
+/// Header with SHA-256 of uploaded content, checked by server
+const CONTENT_SHA256_HEADER: &str = "x-paastel-content-sha256";
+
+#[derive(Serialize)]
+struct InitiateUploadRequest<'a> {
+    digest: &'a str,
+    size: u64,
+    format: &'a str,
+}
+
+/// Archive stored by server, staged by its id
+#[derive(Debug, Clone, Deserialize)]
+pub struct Blob {
+    blob_id: String,
+    digest: String,
+    size: u64,
+}
+
+impl Blob {
+    pub fn blob_id(&self) -> &str {
+        self.blob_id.as_str()
+    }
+
+    pub fn digest(&self) -> &str {
+        self.digest.as_str()
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// Resumable upload of an archive sent part by part
+#[derive(Debug, Clone, Deserialize)]
+pub struct Upload {
+    id: String,
+    size: u64,
+    received: u64,
+}
+
+impl Upload {
+    pub fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Offset of next part
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+}
+
+impl Client {
+    /// Upload whole archive on a single request, media type tells its
+    /// format, ex: `application/zip`
+    pub async fn upload_app(
+        &self,
+        namespace: &str,
+        name: &str,
+        archive: &Path,
+        media_type: &str,
+        digest: &str,
+    ) -> Result<Blob, Error> {
+        let url =
+            self.url(["namespaces", namespace, "applications", name, "store"]);
+        let request = Request::new(Method::POST, url)
+            .header(CONTENT_SHA256_HEADER, digest)
+            .file(media_type, archive);
+        self.json(request).await
+    }
+
+    /// Start resumable upload of archive with digest, format is its
+    /// extension, ex: `tar.gz`
+    pub async fn initiate_upload(
+        &self,
+        namespace: &str,
+        name: &str,
+        digest: &str,
+        size: u64,
+        format: &str,
+    ) -> Result<Upload, Error> {
+        let url = self.url([
+            "namespaces",
+            namespace,
+            "applications",
+            name,
+            "uploads",
+        ]);
+        let request =
+            Request::new(Method::POST, url).json(&InitiateUploadRequest {
+                digest,
+                size,
+                format,
+            })?;
+        self.json(request).await
+    }
+
+    pub async fn show_upload(
+        &self,
+        namespace: &str,
+        name: &str,
+        id: &str,
+    ) -> Result<Upload, Error> {
+        let url = self.url([
+            "namespaces",
+            namespace,
+            "applications",
+            name,
+            "uploads",
+            id,
+        ]);
+        self.json(Request::new(Method::GET, url)).await
+    }
+
+    /// Send part starting at offset, digest is SHA-256 of part
+    pub async fn upload_part(
+        &self,
+        namespace: &str,
+        name: &str,
+        id: &str,
+        offset: u64,
+        part: impl Into<Bytes>,
+        digest: &str,
+    ) -> Result<Upload, Error> {
+        let offset = offset.to_string();
+        let url = self.url([
+            "namespaces",
+            namespace,
+            "applications",
+            name,
+            "uploads",
+            id,
+            "parts",
+            offset.as_str(),
+        ]);
+        let request = Request::new(Method::PUT, url)
+            .header(CONTENT_SHA256_HEADER, digest)
+            .bytes("application/octet-stream", part)
+            .transfer();
+        self.json(request).await
+    }
+
+    /// Store archive once all parts were received
+    pub async fn complete_upload(
+        &self,
+        namespace: &str,
+        name: &str,
+        id: &str,
+    ) -> Result<Blob, Error> {
+        let url = self.url([
+            "namespaces",
+            namespace,
+            "applications",
+            name,
+            "uploads",
+            id,
+            "complete",
+        ]);
+        self.json(Request::new(Method::POST, url).transfer()).await
+    }
+}
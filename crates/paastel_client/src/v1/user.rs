@@ -0,0 +1,88 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::{client::Request, Client, Error};
+
+#[derive(Serialize)]
+struct CreateUserRequest<'a> {
+    username: &'a str,
+    password: &'a str,
+    roles: &'a [String],
+}
+
+#[derive(Serialize)]
+struct ChangePasswordRequest<'a> {
+    password: &'a str,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct User {
+    username: String,
+    roles: Vec<String>,
+}
+
+impl User {
+    pub fn username(&self) -> &str {
+        self.username.as_str()
+    }
+
+    /// Roles granted, ex: `admin`, `developer:team-a`
+    pub fn roles(&self) -> &[String] {
+        &self.roles
+    }
+}
+
+impl Client {
+    pub async fn list_users(&self) -> Result<Vec<User>, Error> {
+        self.json(Request::new(Method::GET, self.url(["users"])))
+            .await
+    }
+
+    pub async fn create_user(
+        &self,
+        username: &str,
+        password: &str,
+        roles: &[String],
+    ) -> Result<User, Error> {
+        let request = Request::new(Method::POST, self.url(["users"])).json(
+            &CreateUserRequest {
+                username,
+                password,
+                roles,
+            },
+        )?;
+        self.json(request).await
+    }
+
+    pub async fn change_password(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<(), Error> {
+        let url = self.url(["users", username, "password"]);
+        let request = Request::new(Method::PUT, url)
+            .json(&ChangePasswordRequest { password })?;
+        self.send(request).await?;
+        Ok(())
+    }
+
+    pub async fn delete_user(&self, username: &str) -> Result<(), Error> {
+        let url = self.url(["users", username]);
+        self.send(Request::new(Method::DELETE, url)).await?;
+        Ok(())
+    }
+}
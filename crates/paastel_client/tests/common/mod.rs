@@ -0,0 +1,136 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use paastel_app::MemoryCluster;
+use paastel_auth::{
+    Credential, NewUser, SecretLabel, UserSecret, UserSecrets, Username,
+};
+use paastel_client::{Auth, Client};
+use paastel_hash::{Argon2Adapter, Hs256Adapter};
+use paastel_storage::{archive::ArchiveAdapter, local::LocalAdapter};
+use tempfile::TempDir;
+
+pub const ADMIN: &str = "admin";
+pub const ADMIN_PASSWORD: &str = "admin-password";
+pub const NAMESPACE: &str = "paastel-space";
+
+/// User secrets kept in memory instead of cluster secrets
+#[derive(Default)]
+struct MemorySecrets(Mutex<Vec<UserSecret>>);
+
+#[async_trait]
+impl paastel_auth::OutgoingKubernetesPort for MemorySecrets {
+    async fn find_secrets_by_label(
+        &self,
+        _label: &SecretLabel,
+    ) -> paastel_auth::Result<UserSecrets> {
+        Ok(UserSecrets::new(self.0.lock().unwrap().clone()))
+    }
+
+    async fn create_secret(
+        &self,
+        _label: &SecretLabel,
+        user_secret: &UserSecret,
+    ) -> paastel_auth::Result<()> {
+        self.0.lock().unwrap().push(user_secret.clone());
+        Ok(())
+    }
+
+    async fn update_secret(
+        &self,
+        _label: &SecretLabel,
+        user_secret: &UserSecret,
+    ) -> paastel_auth::Result<()> {
+        let mut secrets = self.0.lock().unwrap();
+        let secret = secrets
+            .iter_mut()
+            .find(|secret| secret.username() == user_secret.username())
+            .ok_or(paastel_auth::Error::UserNotFound)?;
+        *secret = user_secret.clone();
+        Ok(())
+    }
+
+    async fn delete_secret(
+        &self,
+        _label: &SecretLabel,
+        username: &Username,
+    ) -> paastel_auth::Result<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .retain(|secret| secret.username() != username);
+        Ok(())
+    }
+}
+
+/// Rest server listening on a random port of localhost, over applications
+/// kept in memory and blobs stored on a temporary directory
+pub struct Server {
+    url: String,
+    _storage: TempDir,
+}
+
+impl Server {
+    /// Server with an admin user
+    pub async fn start() -> Self {
+        let storage = tempfile::tempdir().unwrap();
+        let local = LocalAdapter::new(storage.path()).await.unwrap();
+        let application = paastel_app::AppApplication::new(
+            Box::<MemoryCluster>::default(),
+            Box::new(local.clone()),
+            Box::new(ArchiveAdapter::default()),
+            Box::new(local),
+            "registry.paastel.local",
+        );
+        let credential = paastel_auth::AuthApplication::new(
+            Box::<MemorySecrets>::default(),
+            Box::new(Argon2Adapter::default()),
+            Box::new(Hs256Adapter::default()),
+        );
+        let admin = NewUser::new(
+            Credential::new(ADMIN, ADMIN_PASSWORD).unwrap(),
+            ADMIN.parse().unwrap(),
+        );
+        credential.create_user.create_user(&admin).await.unwrap();
+
+        let app = paastel_rest::make_app(
+            credential,
+            application,
+            &paastel_rest::ServerConfig::default(),
+        );
+        let listener =
+            tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        Self {
+            url,
+            _storage: storage,
+        }
+    }
+
+    pub fn client(&self) -> Client {
+        Client::new(&self.url).unwrap()
+    }
+
+    /// Client authenticated with tokens issued to admin
+    pub async fn admin(&self) -> Client {
+        let tokens = self.client().login(ADMIN, ADMIN_PASSWORD).await.unwrap();
+        self.client()
+            .with_auth(Auth::Bearer(tokens.access_token().to_string()))
+    }
+}
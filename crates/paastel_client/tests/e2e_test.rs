@@ -0,0 +1,285 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+mod common;
+
+use std::io::Write;
+
+use futures::TryStreamExt;
+use paastel_client::{Auth, SourceEntry};
+use sha2::{Digest, Sha256};
+
+use common::{Server, ADMIN, ADMIN_PASSWORD, NAMESPACE};
+
+fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Zip archive with a single file
+fn zip_archive(content: &[u8]) -> Vec<u8> {
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    writer
+        .start_file("index.html", zip::write::FileOptions::default())
+        .unwrap();
+    writer.write_all(content).unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+#[tokio::test]
+async fn login_refresh_and_me() {
+    let server = Server::start().await;
+
+    let info = server.client().info().await.unwrap();
+    assert!(info.archive_formats().iter().any(|format| format == "zip"));
+
+    let tokens = server.client().login(ADMIN, ADMIN_PASSWORD).await.unwrap();
+    assert_eq!(tokens.token_type(), "Bearer");
+    let tokens = server
+        .client()
+        .refresh(tokens.refresh_token())
+        .await
+        .unwrap();
+    let me = server
+        .client()
+        .with_auth(Auth::Bearer(tokens.access_token().to_string()))
+        .me()
+        .await
+        .unwrap();
+    assert_eq!(me.username(), ADMIN);
+    assert_eq!(me.roles(), [ADMIN]);
+
+    let e = server.client().login(ADMIN, "wrong-password").await;
+    let e = e.err().unwrap();
+    assert_eq!(e.status(), Some(401));
+    assert_eq!(e.problem().unwrap().code, "invalid_credentials");
+
+    let e = server.client().me().await.err().unwrap();
+    assert_eq!(e.status(), Some(401));
+    assert!(e.problem().unwrap().request_id.is_some());
+}
+
+#[tokio::test]
+async fn manage_users() {
+    let server = Server::start().await;
+    let admin = server.admin().await;
+
+    let roles = ["developer:team-a".to_string()];
+    let user = admin
+        .create_user("alice", "alice-password", &roles)
+        .await
+        .unwrap();
+    assert_eq!(user.roles(), roles);
+    let e = admin
+        .create_user("alice", "alice-password", &roles)
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(e.status(), Some(409));
+
+    let users = admin.list_users().await.unwrap();
+    let mut usernames: Vec<&str> = users.iter().map(|u| u.username()).collect();
+    usernames.sort();
+    assert_eq!(usernames, ["admin", "alice"]);
+
+    admin
+        .change_password("alice", "alice-new-password")
+        .await
+        .unwrap();
+    let alice = server.client().with_auth(Auth::Basic(
+        "alice".to_string(),
+        Some("alice-new-password".to_string()),
+    ));
+    assert_eq!(alice.me().await.unwrap().username(), "alice");
+    let e = alice.list_users().await.err().unwrap();
+    assert_eq!(e.status(), Some(403));
+
+    admin.delete_user("alice").await.unwrap();
+    let e = admin.delete_user("alice").await.err().unwrap();
+    assert_eq!(e.problem().unwrap().code, "user_not_found");
+}
+
+#[tokio::test]
+async fn create_configure_and_show_app() {
+    let server = Server::start().await;
+    let admin = server.admin().await;
+
+    let app = admin.create_app(NAMESPACE, "my-app").await.unwrap();
+    assert_eq!(app.name(), "my-app");
+    assert_eq!(app.created_by(), Some(ADMIN));
+    assert!(!app.is_ready());
+    let e = admin.create_app(NAMESPACE, "my-app").await.err().unwrap();
+    assert_eq!(e.problem().unwrap().code, "app_already_exists");
+
+    let manifest = "version: 1\nname: other-app\ninstances: 2\n";
+    let app = admin
+        .apply_manifest(NAMESPACE, "other-app", manifest)
+        .await
+        .unwrap();
+    assert_eq!(app.instances(), Some(2));
+    let app = admin.show_app(NAMESPACE, "other-app").await.unwrap();
+    assert_eq!(app.instances(), Some(2));
+
+    let e = admin.show_app(NAMESPACE, "missing").await.err().unwrap();
+    assert_eq!(e.status(), Some(404));
+    assert_eq!(e.problem().unwrap().code, "app_not_found");
+}
+
+#[tokio::test]
+async fn push_sources_stage_and_deploy() {
+    let server = Server::start().await;
+    let admin = server.admin().await;
+    let manifest = "version: 1\nname: my-app\nbuilder: dockerfile\n";
+    admin
+        .apply_manifest(NAMESPACE, "my-app", manifest)
+        .await
+        .unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.html");
+    let content = b"<h1>hello</h1>";
+    std::fs::write(&path, content).unwrap();
+    let digest = sha256(content);
+
+    let missing = admin
+        .missing_sources(NAMESPACE, "my-app", &[digest.as_str()])
+        .await
+        .unwrap();
+    assert_eq!(missing, [digest.as_str()]);
+    admin
+        .upload_source(NAMESPACE, "my-app", &digest, &path)
+        .await
+        .unwrap();
+    let missing = admin
+        .missing_sources(NAMESPACE, "my-app", &[digest.as_str()])
+        .await
+        .unwrap();
+    assert!(missing.is_empty());
+
+    let entries = [
+        SourceEntry::file("index.html", digest.as_str(), false),
+        SourceEntry::symlink("home.html", "index.html"),
+    ];
+    let blob = admin
+        .assemble_sources(NAMESPACE, "my-app", &entries)
+        .await
+        .unwrap();
+
+    let stage = admin
+        .stage_app(NAMESPACE, "my-app", blob.blob_id(), None)
+        .await
+        .unwrap();
+    assert_eq!(stage.app(), "my-app");
+    assert_eq!(stage.builder(), "dockerfile");
+    let stage = admin.show_stage(NAMESPACE, stage.id()).await.unwrap();
+    assert_eq!(stage.phase(), "Succeeded");
+    let logs: Vec<u8> = admin
+        .stage_logs(NAMESPACE, stage.id())
+        .await
+        .unwrap()
+        .map_ok(|chunk| chunk.to_vec())
+        .try_concat()
+        .await
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(logs).unwrap(),
+        format!("stage {} Succeeded", stage.id())
+    );
+
+    let app = admin
+        .deploy_app(NAMESPACE, "my-app", stage.id())
+        .await
+        .unwrap();
+    assert!(app.is_ready());
+    assert_eq!(app.image(), stage.image());
+    assert_eq!(app.url(), Some("http://my-app.paastel.local"));
+}
+
+#[tokio::test]
+async fn upload_archive_whole_and_by_parts() {
+    let server = Server::start().await;
+    let admin = server.admin().await;
+    admin.create_app(NAMESPACE, "my-app").await.unwrap();
+
+    let archive = zip_archive(b"<h1>hello</h1>");
+    let digest = sha256(&archive);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("my-app.zip");
+    std::fs::write(&path, &archive).unwrap();
+
+    let blob = admin
+        .upload_app(NAMESPACE, "my-app", &path, "application/zip", &digest)
+        .await
+        .unwrap();
+    assert_eq!(blob.digest(), digest);
+    assert_eq!(blob.size(), archive.len() as u64);
+    let e = admin
+        .upload_app(
+            NAMESPACE,
+            "my-app",
+            &path,
+            "application/zip",
+            &"0".repeat(64),
+        )
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(e.problem().unwrap().code, "digest_mismatch");
+
+    let size = archive.len() as u64;
+    let upload = admin
+        .initiate_upload(NAMESPACE, "my-app", &digest, size, "zip")
+        .await
+        .unwrap();
+    let (first, second) = archive.split_at(archive.len() / 2);
+    let progress = admin
+        .upload_part(
+            NAMESPACE,
+            "my-app",
+            upload.id(),
+            0,
+            first.to_vec(),
+            &sha256(first),
+        )
+        .await
+        .unwrap();
+    assert_eq!(progress.received(), first.len() as u64);
+    let e = admin
+        .complete_upload(NAMESPACE, "my-app", upload.id())
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(e.problem().unwrap().code, "upload_incomplete");
+
+    admin
+        .upload_part(
+            NAMESPACE,
+            "my-app",
+            upload.id(),
+            progress.received(),
+            second.to_vec(),
+            &sha256(second),
+        )
+        .await
+        .unwrap();
+    let progress = admin
+        .show_upload(NAMESPACE, "my-app", upload.id())
+        .await
+        .unwrap();
+    assert_eq!(progress.received(), size);
+    let blob = admin
+        .complete_upload(NAMESPACE, "my-app", upload.id())
+        .await
+        .unwrap();
+    assert_eq!(blob.digest(), digest);
+}
@@ -12,6 +12,9 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
+pub mod token;
+pub use token::*;
+
 use argon2::{
     password_hash::{
         rand_core::OsRng, PasswordHash, PasswordVerifier, SaltString,
@@ -23,21 +26,21 @@ use paastel_auth::{
     Credential, OutgoingArgon2HashPort, RetrievePassword, UserSecret,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Argon2Adapter<'a> {
-    #[allow(dead_code)]
-    salt: SaltString,
     inner: Argon2<'a>,
 }
 
 impl<'a> Argon2Adapter<'a> {
+    /// Hash password in PHC string format, each call use a fresh salt
     pub fn hash_password(
         &self,
         password: &str,
     ) -> Result<String, argon2::password_hash::Error> {
+        let salt = SaltString::generate(&mut OsRng);
         let password_hash = self
             .inner
-            .hash_password(password.as_bytes(), &self.salt)?
+            .hash_password(password.as_bytes(), &salt)?
             .to_string();
         Ok(password_hash)
     }
@@ -50,8 +53,8 @@ impl<'a> OutgoingArgon2HashPort<Credential, UserSecret> for Argon2Adapter<'a> {
         password_text: &Credential,
         password_hash: &UserSecret,
     ) -> paastel_auth::Result<()> {
-        let parsed_hash =
-            PasswordHash::new(password_hash.password().as_ref()).unwrap();
+        let parsed_hash = PasswordHash::new(password_hash.password().as_ref())
+            .map_err(|_| paastel_auth::Error::InvalidPassword)?;
         self.inner
             .verify_password(
                 password_text.password().as_ref().as_bytes(),
@@ -60,20 +63,20 @@ impl<'a> OutgoingArgon2HashPort<Credential, UserSecret> for Argon2Adapter<'a> {
             .map_err(|_| paastel_auth::Error::InvalidPassword)?;
         Ok(())
     }
-}
 
-impl<'a> Default for Argon2Adapter<'a> {
-    fn default() -> Self {
-        Self {
-            salt: SaltString::generate(&mut OsRng),
-            inner: Argon2::default(),
-        }
+    async fn hash(
+        &self,
+        password_text: &Credential,
+    ) -> paastel_auth::Result<paastel_auth::PasswordHash> {
+        self.hash_password(password_text.password().as_ref())
+            .map_err(|e| paastel_auth::Error::DomainError(e.to_string()))?
+            .parse()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use paastel_auth::{PasswordHash, Username};
+    use paastel_auth::{PasswordHash, Roles, Username};
 
     use super::*;
 
@@ -82,11 +85,31 @@ mod tests {
         let credential = Credential::new("username", "password")?;
         let user_secret = UserSecret::new(
             "username".parse::<Username>()?, 
-            "$argon2id$v=19$m=19456,t=2,p=1$1SoziBLmGitKRfXC2+e7Ng$hfPRJDDkKyLH3FyHuqxm397sxPkmVkzydPI+LDQp+OU".parse::<PasswordHash>()?);
+            "$argon2id$v=19$m=19456,t=2,p=1$1SoziBLmGitKRfXC2+e7Ng$hfPRJDDkKyLH3FyHuqxm397sxPkmVkzydPI+LDQp+OU".parse::<PasswordHash>()?,
+            Roles::default());
         let argon2_adapter = Argon2Adapter::default();
         // let hash_password = argon2_adapter.hash_password(password).unwrap();
         let result = argon2_adapter.check(&credential, &user_secret).await;
         assert!(result.is_ok());
         Ok(())
     }
+
+    #[tokio::test]
+    async fn hash_then_check_ok() -> Result<(), paastel_auth::Error> {
+        let credential = Credential::new("username", "password")?;
+        let argon2_adapter = Argon2Adapter::default();
+        let password_hash = argon2_adapter.hash(&credential).await?;
+        let user_secret = UserSecret::new(
+            "username".parse::<Username>()?,
+            password_hash.clone(),
+            Roles::default(),
+        );
+        let result = argon2_adapter.check(&credential, &user_secret).await;
+        assert!(result.is_ok());
+
+        // NOTE: fresh salt per hash
+        let other_hash = argon2_adapter.hash(&credential).await?;
+        assert_ne!(password_hash, other_hash);
+        Ok(())
+    }
 }
@@ -0,0 +1,197 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use paastel_auth::{Claims, OutgoingSigningKeyPort, SignedToken};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Minimum size in bytes of signing key
+const MIN_KEY_LENGTH: usize = 32;
+
+/// Fixed JWS header, only HS256 is accepted
+const JWT_HEADER: &str = r#"{"alg":"HS256","typ":"JWT"}"#;
+
+/// Payload of token, claim names follow RFC 7519
+#[derive(Serialize, Deserialize)]
+struct JwtClaims {
+    sub: String,
+    typ: String,
+    #[serde(default)]
+    roles: String,
+    iat: u64,
+    exp: u64,
+}
+
+/// Signs tokens as compact JWT using HMAC-SHA256
+pub struct Hs256Adapter {
+    key: Vec<u8>,
+}
+
+impl Hs256Adapter {
+    /// Create adapter from shared secret, must have at least 32 bytes
+    pub fn new<K: AsRef<[u8]>>(key: K) -> paastel_auth::Result<Self> {
+        let key = key.as_ref();
+        if key.len() < MIN_KEY_LENGTH {
+            return Err(paastel_auth::Error::Signing(format!(
+                "signing key must have at least {MIN_KEY_LENGTH} bytes"
+            )));
+        }
+        Ok(Self { key: key.to_vec() })
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        // NOTE: hmac accept keys of any size
+        HmacSha256::new_from_slice(&self.key).expect("hmac accept any key size")
+    }
+}
+
+impl std::fmt::Debug for Hs256Adapter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Hs256Adapter").finish_non_exhaustive()
+    }
+}
+
+/// Generate random key, tokens are invalidated when process restart
+impl Default for Hs256Adapter {
+    fn default() -> Self {
+        let mut key = vec![0u8; MIN_KEY_LENGTH];
+        OsRng.fill_bytes(&mut key);
+        Self { key }
+    }
+}
+
+#[async_trait]
+impl OutgoingSigningKeyPort for Hs256Adapter {
+    async fn sign(&self, claims: &Claims) -> paastel_auth::Result<SignedToken> {
+        let payload = serde_json::to_vec(&JwtClaims {
+            sub: claims.subject().to_string(),
+            typ: claims.token_type().to_string(),
+            roles: claims.roles().to_string(),
+            iat: claims.issued_at(),
+            exp: claims.expires_at(),
+        })
+        .map_err(|e| paastel_auth::Error::Signing(e.to_string()))?;
+
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(JWT_HEADER),
+            URL_SAFE_NO_PAD.encode(payload)
+        );
+        let mut mac = self.mac();
+        mac.update(signing_input.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+        format!("{signing_input}.{signature}").parse()
+    }
+
+    async fn verify(
+        &self,
+        token: &SignedToken,
+    ) -> paastel_auth::Result<Claims> {
+        let (signing_input, signature) = token
+            .as_ref()
+            .rsplit_once('.')
+            .ok_or(paastel_auth::Error::InvalidToken)?;
+        let (header, payload) = signing_input
+            .split_once('.')
+            .ok_or(paastel_auth::Error::InvalidToken)?;
+
+        if header != URL_SAFE_NO_PAD.encode(JWT_HEADER) {
+            tracing::debug!("unsupported token header");
+            return Err(paastel_auth::Error::InvalidToken);
+        }
+
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| paastel_auth::Error::InvalidToken)?;
+        let mut mac = self.mac();
+        mac.update(signing_input.as_bytes());
+        mac.verify_slice(&signature)
+            .map_err(|_| paastel_auth::Error::InvalidToken)?;
+
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| paastel_auth::Error::InvalidToken)?;
+        let claims: JwtClaims = serde_json::from_slice(&payload)
+            .map_err(|_| paastel_auth::Error::InvalidToken)?;
+
+        Ok(Claims::new(
+            claims.sub.parse()?,
+            claims.typ.parse()?,
+            claims.roles.parse()?,
+            claims.iat,
+            claims.exp,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use paastel_auth::{Roles, TokenType, Username};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn claims() -> Result<Claims, paastel_auth::Error> {
+        Ok(Claims::new(
+            "username".parse::<Username>()?,
+            TokenType::Access,
+            "developer:team-a".parse::<Roles>()?,
+            1_700_000_000,
+            1_700_000_900,
+        ))
+    }
+
+    #[tokio::test]
+    async fn sign_verify_ok() -> Result<(), paastel_auth::Error> {
+        let adapter = Hs256Adapter::default();
+        let token = adapter.sign(&claims()?).await?;
+        let result = adapter.verify(&token).await?;
+        assert_eq!(result, claims()?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn verify_other_key() -> Result<(), paastel_auth::Error> {
+        let token = Hs256Adapter::default().sign(&claims()?).await?;
+        let result = Hs256Adapter::default().verify(&token).await;
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn verify_tampered_payload() -> Result<(), paastel_auth::Error> {
+        let adapter = Hs256Adapter::default();
+        let token = adapter.sign(&claims()?).await?;
+        let mut parts: Vec<&str> = token.as_ref().split('.').collect();
+        let forged = URL_SAFE_NO_PAD.encode(
+            r#"{"sub":"admin@paastel.io","typ":"access","iat":0,"exp":0}"#,
+        );
+        parts[1] = forged.as_str();
+        let result = adapter.verify(&parts.join(".").parse()?).await;
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn new_short_key() {
+        assert!(Hs256Adapter::new("short").is_err());
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{collections::BTreeMap, result::Result as StdResult};
+
+use kube::{
+    api::{Patch, PatchParams, PostParams},
+    Api, CustomResource, Error as KError, ResourceExt,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::client::KubernetesClient;
+
+/// Name of App custom resource definition
+pub const APP_CRD_NAME: &str = "apps.application.paastel.io";
+
+/// Application managed by PaaStel
+#[derive(
+    CustomResource, Clone, Debug, Default, Deserialize, Serialize, JsonSchema,
+)]
+#[kube(
+    group = "application.paastel.io",
+    version = "v1",
+    kind = "App",
+    namespaced,
+    status = "AppStatus"
+)]
+pub struct AppSpec {
+    /// Where sources come from, ex: path of pushed directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// Image reference deployed, nothing runs until an image is staged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// Number of replicas, default 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instances: Option<i32>,
+    /// Port application listen on, default 8080
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<i32>,
+    /// Hosts routed to application, default `<app>.<namespace>.<domain>`,
+    /// either `<label>.<domain>` or below `<namespace>.<domain>`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<String>,
+    /// Environment of application, `PORT` is always set from port
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Secrets mounted on `/configurations/<name>`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub configurations: Vec<String>,
+    /// Builder of stages requested without one, `buildpack` or `dockerfile`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub builder: Option<String>,
+}
+
+/// Observed state of application, written by controller
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AppStatus {
+    /// Image reconciled into deployment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// Generation of spec reconciled, status is stale while it differs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub observed_generation: Option<i64>,
+    #[serde(default)]
+    pub ready_replicas: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<AppCondition>,
+}
+
+/// Condition of application, ex: `Ready`
+#[derive(
+    Clone, Debug, Default, PartialEq, Deserialize, Serialize, JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct AppCondition {
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// `True`, `False` or `Unknown`
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    pub last_transition_time: String,
+}
+
+/// Reads and writes applications.
+#[derive(Clone)]
+pub(crate) struct KubernetesAppsAdapter {
+    client: KubernetesClient,
+}
+
+impl KubernetesAppsAdapter {
+    pub fn new(client: &KubernetesClient) -> Self {
+        Self {
+            client: client.clone(),
+        }
+    }
+
+    fn api(&self, namespace: &str) -> Api<App> {
+        Api::namespaced(self.client.as_ref().clone(), namespace)
+    }
+}
+
+impl KubernetesAppsAdapter {
+    pub(crate) async fn get_opt(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> StdResult<Option<App>, KError> {
+        self.api(namespace).get_opt(name).await
+    }
+
+    pub(crate) async fn create(
+        &self,
+        namespace: &str,
+        app: &App,
+    ) -> StdResult<App, KError> {
+        self.api(namespace)
+            .create(&PostParams::default(), app)
+            .await
+    }
+
+    pub(crate) async fn replace(
+        &self,
+        namespace: &str,
+        app: &App,
+    ) -> StdResult<App, KError> {
+        self.api(namespace)
+            .replace(&app.name_any(), &PostParams::default(), app)
+            .await
+    }
+
+    pub(crate) async fn patch_image(
+        &self,
+        namespace: &str,
+        name: &str,
+        image: &str,
+    ) -> StdResult<App, KError> {
+        let patch = serde_json::json!({ "spec": { "image": image } });
+        self.api(namespace)
+            .patch(name, &PatchParams::default(), &Patch::Merge(patch))
+            .await
+    }
+}
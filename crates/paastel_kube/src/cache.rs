@@ -0,0 +1,407 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures::{stream::BoxStream, Stream, StreamExt};
+use k8s_openapi::api::core::v1::Secret;
+use kube::{
+    api::{ListParams, WatchEvent, WatchParams},
+    runtime::{
+        reflector::{self, Store},
+        watcher::{self, Event},
+        WatchStreamExt,
+    },
+    Api, ResourceExt,
+};
+use paastel_auth::SecretLabel;
+
+use crate::client::KubernetesClient;
+
+/// Gauge, 1 when initial list is done and last watch event was ok
+const METRIC_READY: &str = "paastel_credentials_cache_ready";
+
+/// Gauge, seconds since unix epoch cache was last known in sync, set on
+/// watch events and bookmarks
+const METRIC_LAST_SYNC: &str = "paastel_credentials_cache_last_sync_seconds";
+
+/// Gauge, number of labelled secrets on cache
+const METRIC_SIZE: &str = "paastel_credentials_cache_secrets";
+
+/// Counter, watch errors, each one trigger a reconnect with backoff
+const METRIC_ERRORS: &str = "paastel_credentials_cache_watch_errors_total";
+
+/// Seconds api server keeps a watch open, below its 295 seconds limit
+const WATCH_TIMEOUT: u32 = 290;
+
+/// Item of secrets watch
+enum Watched {
+    /// Change on labelled secrets
+    Event(Box<Event<Secret>>),
+    /// Progress sent by api server on an idle watch, cache is in sync
+    Bookmark,
+}
+
+impl From<Event<Secret>> for Watched {
+    fn from(event: Event<Secret>) -> Self {
+        Self::Event(Box::new(event))
+    }
+}
+
+/// State of secrets watch, see [`watch`]
+enum WatchState {
+    /// List secrets, replacing content of cache
+    List,
+    /// Start a watch from resource version
+    Watch(String),
+    /// Watch in progress, at resource version
+    Watching(String, BoxStream<'static, kube::Result<WatchEvent<Secret>>>),
+}
+
+#[derive(Default)]
+struct CacheStatus {
+    /// Initial list was received
+    listed: AtomicBool,
+    /// Last watch event was not an error
+    healthy: AtomicBool,
+    /// Seconds since unix epoch cache was last known in sync
+    last_sync: AtomicU64,
+}
+
+/// In memory store of labelled user secrets kept up to date by a watcher,
+/// avoids listing secrets on api server for each authenticated request
+#[derive(Clone)]
+pub struct SecretsCache {
+    label: String,
+    store: Store<Secret>,
+    status: Arc<CacheStatus>,
+}
+
+impl SecretsCache {
+    /// Start watching secrets with label on default namespace, watch
+    /// errors are retried with exponential backoff
+    pub fn spawn(client: &KubernetesClient, label: &SecretLabel) -> Self {
+        let api: Api<Secret> = Api::default_namespaced(client.as_ref().clone());
+        let label = label.to_string();
+        let stream = watch(api, label.clone()).default_backoff();
+        Self::from_stream(label, stream)
+    }
+
+    fn from_stream<S>(label: String, stream: S) -> Self
+    where
+        S: Stream<Item = Result<Watched, watcher::Error>> + Send + 'static,
+    {
+        let (store, mut writer) = reflector::store();
+        let status = Arc::new(CacheStatus::default());
+        let cache = Self {
+            label,
+            store,
+            status,
+        };
+
+        let driver = cache.clone();
+        tokio::spawn(async move {
+            let mut stream = Box::pin(stream);
+            while let Some(item) = stream.next().await {
+                if let Ok(Watched::Event(event)) = &item {
+                    writer.apply_watcher_event(event);
+                }
+                driver.observe(item);
+            }
+            tracing::warn!(label = driver.label, "secrets watch stream ended");
+            driver.status.healthy.store(false, Ordering::Relaxed);
+            driver.publish();
+        });
+
+        cache
+    }
+
+    fn observe(&self, item: Result<Watched, watcher::Error>) {
+        match item {
+            Ok(watched) => {
+                if let Watched::Event(event) = watched {
+                    if let Event::Restarted(_) = *event {
+                        tracing::info!(
+                            label = self.label,
+                            "secrets cache listed"
+                        );
+                        self.status.listed.store(true, Ordering::Relaxed);
+                    }
+                }
+                self.status.healthy.store(true, Ordering::Relaxed);
+                self.status.last_sync.store(now(), Ordering::Relaxed);
+            }
+            Err(e) => {
+                tracing::warn!(label = self.label, "secrets watch failed {e}");
+                self.status.healthy.store(false, Ordering::Relaxed);
+                metrics::counter!(METRIC_ERRORS).increment(1);
+            }
+        }
+        self.publish();
+    }
+
+    fn publish(&self) {
+        metrics::gauge!(METRIC_READY).set(if self.is_ready() {
+            1.0
+        } else {
+            0.0
+        });
+        metrics::gauge!(METRIC_LAST_SYNC)
+            .set(self.status.last_sync.load(Ordering::Relaxed) as f64);
+        metrics::gauge!(METRIC_SIZE).set(self.store.state().len() as f64);
+    }
+
+    /// Label selector watched
+    pub fn label(&self) -> &str {
+        self.label.as_str()
+    }
+
+    /// Initial list is done and watch is not failing
+    pub fn is_ready(&self) -> bool {
+        self.status.listed.load(Ordering::Relaxed)
+            && self.status.healthy.load(Ordering::Relaxed)
+    }
+
+    /// Time since cache was last known in sync, none before first event
+    pub fn freshness(&self) -> Option<Duration> {
+        match self.status.last_sync.load(Ordering::Relaxed) {
+            0 => None,
+            last_sync => {
+                Some(Duration::from_secs(now().saturating_sub(last_sync)))
+            }
+        }
+    }
+
+    /// Snapshot of secrets on cache
+    pub fn secrets(&self) -> Vec<Arc<Secret>> {
+        self.store.state()
+    }
+}
+
+/// Watch labelled secrets, as [`kube::runtime::watcher`] but bookmarks are
+/// surfaced to tell an idle watch from a stalled one
+fn watch(
+    api: Api<Secret>,
+    label: String,
+) -> impl Stream<Item = Result<Watched, watcher::Error>> + Send {
+    futures::stream::unfold(
+        (api, label, WatchState::List),
+        |(api, label, state)| async move {
+            let (item, state) = step(&api, &label, state).await;
+            Some((item, (api, label, state)))
+        },
+    )
+}
+
+/// Progress watch until next item
+async fn step(
+    api: &Api<Secret>,
+    label: &str,
+    mut state: WatchState,
+) -> (Result<Watched, watcher::Error>, WatchState) {
+    loop {
+        state = match state {
+            WatchState::List => {
+                let params = ListParams::default().labels(label);
+                return match api.list(&params).await {
+                    Ok(list) => match list.metadata.resource_version {
+                        Some(version) if !version.is_empty() => (
+                            Ok(Watched::from(Event::Restarted(list.items))),
+                            WatchState::Watch(version),
+                        ),
+                        _ => (
+                            Err(watcher::Error::NoResourceVersion),
+                            WatchState::List,
+                        ),
+                    },
+                    Err(e) => (
+                        Err(watcher::Error::InitialListFailed(e)),
+                        WatchState::List,
+                    ),
+                };
+            }
+            WatchState::Watch(version) => {
+                // NOTE: bookmarks are requested by default
+                let params =
+                    WatchParams::default().labels(label).timeout(WATCH_TIMEOUT);
+                match api.watch(&params, &version).await {
+                    Ok(events) => WatchState::Watching(version, events.boxed()),
+                    Err(e) => {
+                        return (
+                            Err(watcher::Error::WatchStartFailed(e)),
+                            WatchState::Watch(version),
+                        )
+                    }
+                }
+            }
+            WatchState::Watching(version, mut events) => {
+                match events.next().await {
+                    Some(Ok(
+                        WatchEvent::Added(secret)
+                        | WatchEvent::Modified(secret),
+                    )) => {
+                        let version =
+                            secret.resource_version().unwrap_or(version);
+                        return (
+                            Ok(Watched::from(Event::Applied(secret))),
+                            WatchState::Watching(version, events),
+                        );
+                    }
+                    Some(Ok(WatchEvent::Deleted(secret))) => {
+                        let version =
+                            secret.resource_version().unwrap_or(version);
+                        return (
+                            Ok(Watched::from(Event::Deleted(secret))),
+                            WatchState::Watching(version, events),
+                        );
+                    }
+                    Some(Ok(WatchEvent::Bookmark(bookmark))) => {
+                        let version = bookmark.metadata.resource_version;
+                        return (
+                            Ok(Watched::Bookmark),
+                            WatchState::Watching(version, events),
+                        );
+                    }
+                    Some(Ok(WatchEvent::Error(e))) => {
+                        // NOTE: gone, version is too old to watch, list again
+                        let state = if e.code == 410 {
+                            WatchState::List
+                        } else {
+                            WatchState::Watching(version, events)
+                        };
+                        return (Err(watcher::Error::WatchError(e)), state);
+                    }
+                    Some(Err(e)) => {
+                        return (
+                            Err(watcher::Error::WatchFailed(e)),
+                            WatchState::Watching(version, events),
+                        );
+                    }
+                    // NOTE: watch timed out on server, start again
+                    None => WatchState::Watch(version),
+                }
+            }
+        };
+    }
+}
+
+/// Seconds since unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn secret(name: &str) -> Secret {
+        let mut secret = Secret::default();
+        secret.metadata.name = Some(name.to_string());
+        secret.metadata.namespace = Some("default".to_string());
+        secret
+    }
+
+    /// Wait until `check` holds, fail after a while
+    async fn eventually(check: impl Fn() -> bool) {
+        let poll = async {
+            while !check() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), poll)
+            .await
+            .expect("condition not met");
+    }
+
+    #[tokio::test]
+    async fn cache_ready_after_list() {
+        let events = vec![
+            Ok(Watched::from(Event::Restarted(vec![secret("admin")]))),
+            Ok(Watched::from(Event::Applied(secret("developer")))),
+        ];
+        let cache = SecretsCache::from_stream(
+            SecretLabel::default().to_string(),
+            stream::iter(events).chain(stream::pending()),
+        );
+        eventually(|| cache.secrets().len() == 2).await;
+
+        assert!(cache.is_ready());
+        assert!(cache.freshness().is_some());
+        assert_eq!(cache.secrets().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn cache_not_ready_on_watch_error() {
+        let events = vec![
+            Ok(Watched::from(Event::Restarted(vec![secret("admin")]))),
+            Err(watcher::Error::NoResourceVersion),
+        ];
+        let cache = SecretsCache::from_stream(
+            SecretLabel::default().to_string(),
+            stream::iter(events).chain(stream::pending()),
+        );
+        // NOTE: listed and not ready once error after list is observed
+        eventually(|| {
+            cache.status.listed.load(Ordering::Relaxed) && !cache.is_ready()
+        })
+        .await;
+
+        assert!(!cache.is_ready());
+        // NOTE: last known secrets are still served
+        assert_eq!(cache.secrets().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn cache_not_ready_before_list() {
+        let cache = SecretsCache::from_stream(
+            SecretLabel::default().to_string(),
+            stream::pending(),
+        );
+
+        assert!(!cache.is_ready());
+        assert!(cache.freshness().is_none());
+    }
+
+    #[tokio::test]
+    async fn cache_in_sync_on_bookmark() {
+        let (sender, bookmarks) = futures::channel::mpsc::unbounded();
+        let events =
+            vec![Ok(Watched::from(Event::Restarted(vec![secret("admin")])))];
+        let cache = SecretsCache::from_stream(
+            SecretLabel::default().to_string(),
+            stream::iter(events).chain(bookmarks),
+        );
+        eventually(|| cache.is_ready()).await;
+
+        // NOTE: no events since, staleness shows on freshness
+        cache.status.last_sync.store(1, Ordering::Relaxed);
+        assert!(cache.freshness().unwrap() > Duration::from_secs(60));
+
+        sender.unbounded_send(Ok(Watched::Bookmark)).unwrap();
+        eventually(|| cache.status.last_sync.load(Ordering::Relaxed) > 1).await;
+        assert!(cache.freshness().unwrap() < Duration::from_secs(60));
+    }
+}
@@ -25,6 +25,18 @@ impl KubernetesClient {
             client: Client::try_default().await?,
         })
     }
+
+    /// Client inferred from environment, with `namespace` as default
+    /// namespace where user secrets are kept
+    pub async fn with_namespace(
+        namespace: impl Into<String>,
+    ) -> Result<Self, crate::error::Error> {
+        let mut config = kube::Config::infer().await?;
+        config.default_namespace = namespace.into();
+        Ok(Self {
+            client: Client::try_from(config)?,
+        })
+    }
 }
 
 impl AsRef<Client> for KubernetesClient {
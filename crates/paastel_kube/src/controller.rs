@@ -0,0 +1,891 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    result::Result as StdResult,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use derive_new::new;
+use futures::StreamExt;
+use k8s_openapi::{
+    api::{
+        apps::v1::{Deployment, DeploymentSpec},
+        batch::v1::Job,
+        core::v1::{
+            Container, ContainerPort, EnvVar, PodSpec, PodTemplateSpec,
+            SecretVolumeSource, Service, ServicePort, ServiceSpec, Volume,
+            VolumeMount,
+        },
+        networking::v1::{
+            HTTPIngressPath, HTTPIngressRuleValue, Ingress, IngressBackend,
+            IngressRule, IngressServiceBackend, IngressSpec,
+            ServiceBackendPort,
+        },
+    },
+    apimachinery::pkg::{
+        apis::meta::v1::{LabelSelector, ObjectMeta, OwnerReference},
+        util::intstr::IntOrString,
+    },
+    chrono::Utc,
+};
+use kube::{
+    api::{Patch, PatchParams},
+    runtime::{
+        controller::Action,
+        reflector::{ObjectRef, Store},
+        watcher, Controller,
+    },
+    Api, Client, Resource, ResourceExt,
+};
+use paastel_app::{ArcShowStageUseCase, Namespace, StageId};
+
+use crate::{
+    app::{App, AppCondition, AppStatus},
+    client::KubernetesClient,
+    error::Error,
+    leader::LeaderElection,
+    mapper::{LABEL_APP, LABEL_STAGE},
+    stage::Stage,
+};
+
+/// Lease held by the replica running the controller
+const CONTROLLER_LEASE: &str = "paastel-controller";
+
+/// Field manager used on server side apply of children
+const FIELD_MANAGER: &str = "paastel-controller";
+
+/// Replicas when instances are not set
+const DEFAULT_INSTANCES: i32 = 1;
+
+/// Port application listen on when not set
+const DEFAULT_PORT: i32 = 8080;
+
+/// Directory where bound configurations are mounted
+const CONFIGURATIONS_PATH: &str = "/configurations";
+
+/// Port exposed by service
+const SERVICE_PORT: i32 = 80;
+
+/// Reconcile again even without changes
+const RESYNC_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// First delay after a failed reconcile, doubled on each failure
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Maximum delay between failed reconciles
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// Condition type of application serving traffic
+pub(crate) const CONDITION_READY: &str = "Ready";
+
+#[derive(Debug, Clone, new)]
+pub struct ControllerConfig {
+    /// Domain of default routes, `<app>.<namespace>.<domain>`
+    domain: String,
+    /// Ingress class of routes, cluster default when none
+    #[new(default)]
+    ingress_class: Option<String>,
+}
+
+impl ControllerConfig {
+    pub fn with_ingress_class(
+        mut self,
+        ingress_class: impl Into<String>,
+    ) -> Self {
+        self.ingress_class = Some(ingress_class.into());
+        self
+    }
+}
+
+struct Context {
+    client: Client,
+    config: ControllerConfig,
+    /// Apps watched by controller, deleted ones are gone from it
+    apps: Store<App>,
+    /// Consecutive failures by app, drive backoff of requeue
+    failures: Mutex<HashMap<ObjectRef<App>, u32>>,
+}
+
+impl Context {
+    fn failed(&self, app: &App) -> u32 {
+        let Ok(mut failures) = self.failures.lock() else {
+            return 1;
+        };
+        // NOTE: deleted apps are never reconciled again, drop their count
+        failures.retain(|key, _| self.apps.get(key).is_some());
+        let count = failures.entry(ObjectRef::from_obj(app)).or_default();
+        *count = count.saturating_add(1);
+        *count
+    }
+
+    fn succeeded(&self, app: &App) {
+        if let Ok(mut failures) = self.failures.lock() {
+            failures.remove(&ObjectRef::from_obj(app));
+        }
+    }
+}
+
+/// Reconcile every App into a Deployment, a ClusterIP Service and an
+/// Ingress and record every Stage once its build job ends, only on the
+/// replica holding the controller lease, never returns
+pub async fn run(
+    client: KubernetesClient,
+    config: ControllerConfig,
+    show_stage: ArcShowStageUseCase,
+) {
+    let client = client.as_ref().clone();
+    LeaderElection::new(client.clone(), CONTROLLER_LEASE)
+        .run(move || {
+            let apps = reconcile_apps(client.clone(), config.clone());
+            let stages = reconcile_stages(client.clone(), show_stage.clone());
+            async move {
+                tokio::join!(apps, stages);
+            }
+        })
+        .await;
+}
+
+/// Reconcile stages until watch stream ends, phase, reason and logs of
+/// build are recorded on stage as build job ends, before job is removed
+async fn reconcile_stages(client: Client, show_stage: ArcShowStageUseCase) {
+    let jobs = watcher::Config::default().labels(LABEL_STAGE);
+
+    tracing::info!("starting stage controller");
+    Controller::new(
+        Api::<Stage>::all(client.clone()),
+        watcher::Config::default(),
+    )
+    .owns(Api::<Job>::all(client), jobs)
+    .run(reconcile_stage, stage_error_policy, Arc::new(show_stage))
+    .for_each(|result| async move {
+        match result {
+            Ok((stage, _)) => tracing::debug!(%stage, "reconciled stage"),
+            Err(e) => tracing::warn!("stage reconcile failed {e}"),
+        }
+    })
+    .await;
+}
+
+async fn reconcile_stage(
+    stage: Arc<Stage>,
+    show_stage: Arc<ArcShowStageUseCase>,
+) -> StdResult<Action, Error> {
+    // NOTE: logs are recorded last, stage is done once they are
+    if stage
+        .status
+        .as_ref()
+        .is_some_and(|status| status.logs.is_some())
+    {
+        return Ok(Action::await_change());
+    }
+    let invalid = |e: paastel_app::Error| {
+        Error::Reconcile(format!("stage {} {e}", stage.name_any()))
+    };
+    let namespace: Namespace = stage
+        .namespace()
+        .unwrap_or_default()
+        .parse()
+        .map_err(invalid)?;
+    let id: StageId = stage.name_any().parse().map_err(invalid)?;
+
+    let found = show_stage
+        .show_stage(&namespace, &id)
+        .await
+        .map_err(invalid)?;
+    if found.phase().is_terminal() {
+        Ok(Action::await_change())
+    } else {
+        Ok(Action::requeue(RESYNC_INTERVAL))
+    }
+}
+
+fn stage_error_policy(
+    stage: Arc<Stage>,
+    error: &Error,
+    _show_stage: Arc<ArcShowStageUseCase>,
+) -> Action {
+    tracing::warn!(
+        stage = stage.name_any(),
+        "reconcile failed {error}, retry in {MIN_BACKOFF:?}"
+    );
+    Action::requeue(MIN_BACKOFF)
+}
+
+/// Reconcile apps until watch stream ends
+async fn reconcile_apps(client: Client, config: ControllerConfig) {
+    let children = watcher::Config::default().labels(LABEL_APP);
+
+    tracing::info!("starting app controller");
+    let controller = Controller::new(
+        Api::<App>::all(client.clone()),
+        watcher::Config::default(),
+    )
+    .owns(Api::<Deployment>::all(client.clone()), children.clone())
+    .owns(Api::<Service>::all(client.clone()), children.clone())
+    .owns(Api::<Ingress>::all(client.clone()), children);
+    let context = Arc::new(Context {
+        client,
+        config,
+        apps: controller.store(),
+        failures: Mutex::default(),
+    });
+    controller
+        .run(reconcile, error_policy, context)
+        .for_each(|result| async move {
+            match result {
+                Ok((app, _)) => tracing::debug!(%app, "reconciled app"),
+                Err(e) => tracing::warn!("app reconcile failed {e}"),
+            }
+        })
+        .await;
+}
+
+async fn reconcile(
+    app: Arc<App>,
+    ctx: Arc<Context>,
+) -> StdResult<Action, Error> {
+    let name = app.name_any();
+    let namespace = app.namespace().ok_or_else(|| {
+        Error::Reconcile(format!("app {name} without namespace"))
+    })?;
+    let owner = app
+        .controller_owner_ref(&())
+        .ok_or_else(|| Error::Reconcile(format!("app {name} without uid")))?;
+    let pp = PatchParams::apply(FIELD_MANAGER).force();
+
+    let status = match app.spec.image.as_deref() {
+        None => app_status(
+            &app,
+            Rollout::default(),
+            None,
+            "NoImage",
+            "no image staged yet",
+        ),
+        Some(image) => {
+            let deployment = app_deployment(&app, image, &owner);
+            let deployment =
+                Api::<Deployment>::namespaced(ctx.client.clone(), &namespace)
+                    .patch(&name, &pp, &Patch::Apply(&deployment))
+                    .await?;
+            Api::<Service>::namespaced(ctx.client.clone(), &namespace)
+                .patch(&name, &pp, &Patch::Apply(&app_service(&app, &owner)))
+                .await?;
+            let hosts = app_hosts(&app, &ctx.config);
+            let rejected = check_hosts(&app, &hosts, &ctx.config, &ctx.apps);
+            if rejected.is_none() {
+                let ingress = app_ingress(&app, &hosts, &ctx.config, &owner);
+                Api::<Ingress>::namespaced(ctx.client.clone(), &namespace)
+                    .patch(&name, &pp, &Patch::Apply(&ingress))
+                    .await?;
+            }
+
+            let desired = app.spec.instances.unwrap_or(DEFAULT_INSTANCES);
+            let rollout = Rollout::of(&deployment, desired);
+            match rejected {
+                Some(message) => {
+                    tracing::warn!(app = name, "{message}");
+                    let rollout = Rollout {
+                        complete: false,
+                        ..rollout
+                    };
+                    app_status(&app, rollout, None, "RouteRejected", &message)
+                }
+                None => {
+                    let url =
+                        hosts.first().map(|host| format!("http://{host}"));
+                    let reason = if rollout.complete {
+                        "NewReplicasAvailable"
+                    } else {
+                        "RolloutInProgress"
+                    };
+                    let message = format!(
+                        "{}/{desired} replicas ready",
+                        rollout.available
+                    );
+                    app_status(&app, rollout, url, reason, &message)
+                }
+            }
+        }
+    };
+
+    let patch = serde_json::json!({ "status": status });
+    Api::<App>::namespaced(ctx.client.clone(), &namespace)
+        .patch_status(&name, &PatchParams::default(), &Patch::Merge(patch))
+        .await?;
+
+    ctx.succeeded(&app);
+    Ok(Action::requeue(RESYNC_INTERVAL))
+}
+
+fn error_policy(app: Arc<App>, error: &Error, ctx: Arc<Context>) -> Action {
+    let failures = ctx.failed(&app);
+    let delay = backoff(failures);
+    tracing::warn!(
+        app = app.name_any(),
+        failures,
+        "reconcile failed {error}, retry in {delay:?}"
+    );
+    Action::requeue(delay)
+}
+
+/// Exponential backoff from consecutive failures
+fn backoff(failures: u32) -> Duration {
+    let exponent = failures.saturating_sub(1).min(16);
+    MIN_BACKOFF.saturating_mul(1 << exponent).min(MAX_BACKOFF)
+}
+
+fn labels(app: &App) -> BTreeMap<String, String> {
+    BTreeMap::from([(LABEL_APP.to_string(), app.name_any())])
+}
+
+fn child_metadata(app: &App, owner: &OwnerReference) -> ObjectMeta {
+    ObjectMeta {
+        name: Some(app.name_any()),
+        namespace: app.namespace(),
+        labels: Some(labels(app)),
+        owner_references: Some(vec![owner.clone()]),
+        ..Default::default()
+    }
+}
+
+fn app_deployment(
+    app: &App,
+    image: &str,
+    owner: &OwnerReference,
+) -> Deployment {
+    let port = app.spec.port.unwrap_or(DEFAULT_PORT);
+    Deployment {
+        metadata: child_metadata(app, owner),
+        spec: Some(DeploymentSpec {
+            replicas: Some(app.spec.instances.unwrap_or(DEFAULT_INSTANCES)),
+            selector: LabelSelector {
+                match_labels: Some(labels(app)),
+                ..Default::default()
+            },
+            template: PodTemplateSpec {
+                metadata: Some(ObjectMeta {
+                    labels: Some(labels(app)),
+                    ..Default::default()
+                }),
+                spec: Some(PodSpec {
+                    containers: vec![Container {
+                        name: "app".to_string(),
+                        image: Some(image.to_string()),
+                        ports: Some(vec![ContainerPort {
+                            container_port: port,
+                            name: Some("http".to_string()),
+                            ..Default::default()
+                        }]),
+                        env: Some(app_env(app, port)),
+                        volume_mounts: Some(
+                            app.spec
+                                .configurations
+                                .iter()
+                                .map(|name| VolumeMount {
+                                    name: configuration_volume(name),
+                                    mount_path: format!(
+                                        "{CONFIGURATIONS_PATH}/{name}"
+                                    ),
+                                    read_only: Some(true),
+                                    ..Default::default()
+                                })
+                                .collect(),
+                        ),
+                        ..Default::default()
+                    }],
+                    volumes: Some(
+                        app.spec
+                            .configurations
+                            .iter()
+                            .map(|name| Volume {
+                                name: configuration_volume(name),
+                                secret: Some(SecretVolumeSource {
+                                    secret_name: Some(name.clone()),
+                                    ..Default::default()
+                                }),
+                                ..Default::default()
+                            })
+                            .collect(),
+                    ),
+                    ..Default::default()
+                }),
+            },
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// `PORT` followed by environment of application
+fn app_env(app: &App, port: i32) -> Vec<EnvVar> {
+    let port = EnvVar {
+        name: "PORT".to_string(),
+        value: Some(port.to_string()),
+        ..Default::default()
+    };
+    std::iter::once(port)
+        .chain(
+            app.spec
+                .env
+                .iter()
+                .filter(|(name, _)| name.as_str() != "PORT")
+                .map(|(name, value)| EnvVar {
+                    name: name.clone(),
+                    value: Some(value.clone()),
+                    ..Default::default()
+                }),
+        )
+        .collect()
+}
+
+fn configuration_volume(name: &str) -> String {
+    format!("configuration-{name}")
+}
+
+fn app_service(app: &App, owner: &OwnerReference) -> Service {
+    Service {
+        metadata: child_metadata(app, owner),
+        spec: Some(ServiceSpec {
+            type_: Some("ClusterIP".to_string()),
+            selector: Some(labels(app)),
+            ports: Some(vec![ServicePort {
+                name: Some("http".to_string()),
+                port: SERVICE_PORT,
+                target_port: Some(IntOrString::String("http".to_string())),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn app_hosts(app: &App, config: &ControllerConfig) -> Vec<String> {
+    if !app.spec.routes.is_empty() {
+        return app.spec.routes.clone();
+    }
+    vec![format!(
+        "{}.{}.{}",
+        app.name_any(),
+        app.namespace().unwrap_or_default(),
+        config.domain
+    )]
+}
+
+/// Why hosts can not route to app, none when allowed. Hosts are below
+/// domain, either `<label>.<domain>` or below `<namespace>.<domain>` of
+/// app, and not held by an app created before
+fn check_hosts(
+    app: &App,
+    hosts: &[String],
+    config: &ControllerConfig,
+    apps: &Store<App>,
+) -> Option<String> {
+    let namespace = app.namespace().unwrap_or_default();
+    let suffix = format!(".{}", config.domain);
+    for host in hosts {
+        let Some(prefix) = host.strip_suffix(&suffix) else {
+            return Some(format!(
+                "route {host} is not below {}",
+                config.domain
+            ));
+        };
+        let labels: Vec<&str> = prefix.split('.').collect();
+        if labels.len() > 1 && labels.last() != Some(&namespace.as_str()) {
+            return Some(format!(
+                "route {host} is not below {namespace}.{}",
+                config.domain
+            ));
+        }
+        let holder = apps.state().into_iter().find(|other| {
+            holds_before(other, app) && app_hosts(other, config).contains(host)
+        });
+        if let Some(holder) = holder {
+            return Some(format!(
+                "route {host} is held by app {}/{}",
+                holder.namespace().unwrap_or_default(),
+                holder.name_any()
+            ));
+        }
+    }
+    None
+}
+
+/// Other app was created before app, ties broken by namespace and name
+fn holds_before(other: &App, app: &App) -> bool {
+    let key = |app: &App| {
+        (
+            app.metadata.creation_timestamp.clone().map(|time| time.0),
+            app.namespace(),
+            app.name_any(),
+        )
+    };
+    key(other) < key(app)
+}
+
+fn app_ingress(
+    app: &App,
+    hosts: &[String],
+    config: &ControllerConfig,
+    owner: &OwnerReference,
+) -> Ingress {
+    let rules = hosts
+        .iter()
+        .map(|host| IngressRule {
+            host: Some(host.clone()),
+            http: Some(HTTPIngressRuleValue {
+                paths: vec![HTTPIngressPath {
+                    path: Some("/".to_string()),
+                    path_type: "Prefix".to_string(),
+                    backend: IngressBackend {
+                        service: Some(IngressServiceBackend {
+                            name: app.name_any(),
+                            port: Some(ServiceBackendPort {
+                                number: Some(SERVICE_PORT),
+                                ..Default::default()
+                            }),
+                        }),
+                        ..Default::default()
+                    },
+                }],
+            }),
+        })
+        .collect();
+
+    Ingress {
+        metadata: child_metadata(app, owner),
+        spec: Some(IngressSpec {
+            ingress_class_name: config.ingress_class.clone(),
+            rules: Some(rules),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Progress of deployment towards its latest spec
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Rollout {
+    /// Available replicas of latest spec, pods of older replica sets are
+    /// not counted
+    available: i32,
+    /// Latest spec observed, `desired` replicas of it available and no
+    /// older replica left
+    complete: bool,
+}
+
+impl Rollout {
+    fn of(deployment: &Deployment, desired: i32) -> Self {
+        let Some(status) = deployment.status.as_ref() else {
+            return Self::default();
+        };
+        let observed = matches!(
+            (deployment.metadata.generation, status.observed_generation),
+            (Some(generation), Some(observed)) if observed >= generation
+        );
+        let replicas = status.replicas.unwrap_or_default();
+        let updated = status.updated_replicas.unwrap_or_default();
+        let available = status.available_replicas.unwrap_or_default();
+        Self {
+            available: if observed { available.min(updated) } else { 0 },
+            complete: observed
+                && updated >= desired
+                && available >= desired
+                && replicas <= updated,
+        }
+    }
+}
+
+/// Status with Ready condition, transition time only changes with status
+fn app_status(
+    app: &App,
+    rollout: Rollout,
+    url: Option<String>,
+    reason: &str,
+    message: &str,
+) -> AppStatus {
+    let ready = if app.spec.image.is_some() && rollout.complete {
+        "True"
+    } else {
+        "False"
+    };
+    let last_transition_time = app
+        .status
+        .iter()
+        .flat_map(|status| status.conditions.iter())
+        .find(|c| c.type_ == CONDITION_READY && c.status == ready)
+        .map(|c| c.last_transition_time.clone())
+        .unwrap_or_else(|| Utc::now().to_rfc3339());
+
+    AppStatus {
+        image: app.spec.image.clone(),
+        observed_generation: app.metadata.generation,
+        ready_replicas: rollout.available,
+        url,
+        conditions: vec![AppCondition {
+            type_: CONDITION_READY.to_string(),
+            status: ready.to_string(),
+            reason: Some(reason.to_string()),
+            message: Some(message.to_string()),
+            last_transition_time,
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use k8s_openapi::api::apps::v1::DeploymentStatus;
+
+    use super::*;
+    use crate::app::AppSpec;
+
+    fn app(image: Option<&str>) -> App {
+        let mut app = App::new(
+            "my-app",
+            AppSpec {
+                image: image.map(ToString::to_string),
+                instances: Some(2),
+                ..Default::default()
+            },
+        );
+        app.metadata.namespace = Some("paastel-space".to_string());
+        app.metadata.uid = Some("6f0b9c4e".to_string());
+        app
+    }
+
+    #[test]
+    fn deployment_from_app() {
+        let app = app(Some("registry/my-app:1"));
+        let owner = app.controller_owner_ref(&()).unwrap();
+
+        let deployment = app_deployment(&app, "registry/my-app:1", &owner);
+        let spec = deployment.spec.unwrap();
+        let container = &spec.template.spec.unwrap().containers[0];
+
+        assert_eq!(spec.replicas, Some(2));
+        assert_eq!(container.image.as_deref(), Some("registry/my-app:1"));
+        assert_eq!(deployment.metadata.owner_references, Some(vec![owner]));
+    }
+
+    #[test]
+    fn deployment_env_and_configurations() {
+        let mut app = app(Some("registry/my-app:1"));
+        app.spec.env = BTreeMap::from([
+            ("PORT".to_string(), "9000".to_string()),
+            ("RUST_LOG".to_string(), "info".to_string()),
+        ]);
+        app.spec.configurations = vec!["my-database".to_string()];
+        let owner = app.controller_owner_ref(&()).unwrap();
+
+        let deployment = app_deployment(&app, "registry/my-app:1", &owner);
+        let pod = deployment.spec.unwrap().template.spec.unwrap();
+        let container = &pod.containers[0];
+        let env: Vec<_> = container
+            .env
+            .iter()
+            .flatten()
+            .map(|e| (e.name.as_str(), e.value.as_deref()))
+            .collect();
+
+        assert_eq!(env, [("PORT", Some("8080")), ("RUST_LOG", Some("info"))]);
+        let mounts = container.volume_mounts.as_ref().unwrap();
+        assert_eq!(mounts[0].mount_path, "/configurations/my-database");
+        let volumes = pod.volumes.unwrap();
+        assert_eq!(volumes[0].name, mounts[0].name);
+        assert_eq!(
+            volumes[0].secret.as_ref().unwrap().secret_name.as_deref(),
+            Some("my-database")
+        );
+    }
+
+    #[test]
+    fn ingress_default_host() {
+        let app = app(Some("registry/my-app:1"));
+        let owner = app.controller_owner_ref(&()).unwrap();
+        let config = ControllerConfig::new("paastel.local".to_string())
+            .with_ingress_class("nginx");
+
+        let hosts = app_hosts(&app, &config);
+        assert_eq!(hosts, vec!["my-app.paastel-space.paastel.local"]);
+
+        let ingress = app_ingress(&app, &hosts, &config, &owner);
+        let spec = ingress.spec.unwrap();
+        assert_eq!(spec.ingress_class_name.as_deref(), Some("nginx"));
+        assert_eq!(spec.rules.unwrap()[0].host, Some(hosts[0].clone()));
+    }
+
+    fn routed_app(
+        namespace: &str,
+        name: &str,
+        routes: &[&str],
+        created: i64,
+    ) -> App {
+        let mut app = App::new(
+            name,
+            AppSpec {
+                routes: routes.iter().map(ToString::to_string).collect(),
+                ..Default::default()
+            },
+        );
+        app.metadata.namespace = Some(namespace.to_string());
+        app.metadata.creation_timestamp =
+            Some(k8s_openapi::apimachinery::pkg::apis::meta::v1::Time(
+                k8s_openapi::chrono::DateTime::from_timestamp(created, 0)
+                    .unwrap(),
+            ));
+        app
+    }
+
+    fn store(apps: Vec<App>) -> Store<App> {
+        let (store, mut writer) = kube::runtime::reflector::store();
+        writer.apply_watcher_event(&watcher::Event::Restarted(apps));
+        store
+    }
+
+    #[test]
+    fn hosts_below_domain_of_namespace() {
+        let config = ControllerConfig::new("paastel.local".to_string());
+        let apps = store(vec![]);
+        let check = |routes: &[&str]| {
+            let app = routed_app("team-a", "web", routes, 1);
+            check_hosts(&app, &app_hosts(&app, &config), &config, &apps)
+        };
+
+        assert_eq!(check(&[]), None);
+        assert_eq!(check(&["www.paastel.local"]), None);
+        assert_eq!(check(&["api.web.team-a.paastel.local"]), None);
+        assert!(check(&["example.com"]).is_some());
+        assert!(check(&["paastel.local"]).is_some());
+        assert!(check(&["web.team-b.paastel.local"]).is_some());
+    }
+
+    #[test]
+    fn hosts_held_by_older_app() {
+        let config = ControllerConfig::new("paastel.local".to_string());
+        let older = routed_app("team-a", "web", &["www.paastel.local"], 1);
+        let newer = routed_app("team-b", "shop", &["www.paastel.local"], 2);
+        let apps = store(vec![older.clone(), newer.clone()]);
+
+        let rejected =
+            check_hosts(&newer, &app_hosts(&newer, &config), &config, &apps);
+        assert_eq!(
+            rejected.as_deref(),
+            Some("route www.paastel.local is held by app team-a/web")
+        );
+        assert_eq!(
+            check_hosts(&older, &app_hosts(&older, &config), &config, &apps),
+            None
+        );
+    }
+
+    fn complete() -> Rollout {
+        Rollout {
+            available: 2,
+            complete: true,
+        }
+    }
+
+    #[test]
+    fn status_keep_transition_time() {
+        let mut app = app(Some("registry/my-app:1"));
+        app.metadata.generation = Some(3);
+        let status = app_status(&app, complete(), None, "", "");
+        assert_eq!(status.conditions[0].status, "True");
+        assert_eq!(status.observed_generation, Some(3));
+
+        let mut previous = status.clone();
+        previous.conditions[0].last_transition_time =
+            "2024-01-01T00:00:00Z".into();
+        app.status = Some(previous);
+
+        let status = app_status(&app, complete(), None, "", "");
+        assert_eq!(
+            status.conditions[0].last_transition_time,
+            "2024-01-01T00:00:00Z"
+        );
+
+        let status = app_status(&app, Rollout::default(), None, "", "");
+        assert_eq!(status.conditions[0].status, "False");
+        assert_ne!(
+            status.conditions[0].last_transition_time,
+            "2024-01-01T00:00:00Z"
+        );
+    }
+
+    #[test]
+    fn status_without_image() {
+        let status = app_status(&app(None), complete(), None, "NoImage", "");
+        assert_eq!(status.conditions[0].status, "False");
+    }
+
+    fn deployment(
+        generation: i64,
+        observed: i64,
+        replicas: i32,
+        updated: i32,
+        available: i32,
+    ) -> Deployment {
+        Deployment {
+            metadata: ObjectMeta {
+                generation: Some(generation),
+                ..Default::default()
+            },
+            status: Some(DeploymentStatus {
+                observed_generation: Some(observed),
+                replicas: Some(replicas),
+                updated_replicas: Some(updated),
+                available_replicas: Some(available),
+                ready_replicas: Some(available),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rollout_of_latest_spec() {
+        // NOTE: pods of old replica set are ready while new spec is unseen
+        let rollout = Rollout::of(&deployment(2, 1, 2, 2, 2), 2);
+        assert_eq!(rollout, Rollout::default());
+
+        // NOTE: one new pod up, old ones still running
+        let rollout = Rollout::of(&deployment(2, 2, 3, 1, 3), 2);
+        assert_eq!(
+            rollout,
+            Rollout {
+                available: 1,
+                complete: false
+            }
+        );
+
+        let rollout = Rollout::of(&deployment(2, 2, 2, 2, 2), 2);
+        assert_eq!(rollout, complete());
+
+        let rollout = Rollout::of(&Deployment::default(), 1);
+        assert_eq!(rollout, Rollout::default());
+    }
+
+    #[test]
+    fn backoff_grows_until_max() {
+        assert_eq!(backoff(1), MIN_BACKOFF);
+        assert_eq!(backoff(2), MIN_BACKOFF * 2);
+        assert_eq!(backoff(3), MIN_BACKOFF * 4);
+        assert_eq!(backoff(100), MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{result::Result as StdResult, time::Duration};
+
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use kube::{
+    api::{Patch, PatchParams},
+    runtime::{conditions, wait::await_condition},
+    Api, CustomResourceExt,
+};
+
+use crate::{
+    app::{App, APP_CRD_NAME},
+    client::KubernetesClient,
+    error::Error,
+    stage::{Stage, STAGE_CRD_NAME},
+};
+
+/// Field manager used on server side apply
+const FIELD_MANAGER: &str = "paastel";
+
+/// Time to wait custom resource definition be established
+const CRD_ESTABLISHED_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Apply PaaStel custom resource definitions and wait they be established
+pub async fn install_crds(client: &KubernetesClient) -> StdResult<(), Error> {
+    let crds: Api<CustomResourceDefinition> = Api::all(client.as_ref().clone());
+    apply_crd(&crds, APP_CRD_NAME, App::crd()).await?;
+    apply_crd(&crds, STAGE_CRD_NAME, Stage::crd()).await?;
+    Ok(())
+}
+
+async fn apply_crd(
+    crds: &Api<CustomResourceDefinition>,
+    name: &str,
+    crd: CustomResourceDefinition,
+) -> StdResult<(), Error> {
+    tracing::info!("init crd {name}");
+    crds.patch(
+        name,
+        &PatchParams::apply(FIELD_MANAGER).force(),
+        &Patch::Apply(crd),
+    )
+    .await?;
+
+    let established =
+        await_condition(crds.clone(), name, conditions::is_crd_established());
+    tokio::time::timeout(CRD_ESTABLISHED_TIMEOUT, established)
+        .await
+        .map_err(|_| Error::CrdNotEstablished(name.to_string()))?
+        .map_err(|e| Error::Wait(e.to_string()))?;
+
+    tracing::info!("complete init crd {name}");
+    Ok(())
+}
@@ -14,6 +14,14 @@
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
-    #[error("kube error")]
+    #[error("kube error {0}")]
     Kube(#[from] kube::Error),
+    #[error("kube config error {0}")]
+    Config(#[from] kube::config::InferConfigError),
+    #[error("custom resource definition {0} not established")]
+    CrdNotEstablished(String),
+    #[error("wait error {0}")]
+    Wait(String),
+    #[error("reconcile error {0}")]
+    Reconcile(String),
 }
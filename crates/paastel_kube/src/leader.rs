@@ -0,0 +1,275 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{future::Future, result::Result as StdResult, time::Duration};
+
+use k8s_openapi::{
+    api::coordination::v1::{Lease, LeaseSpec},
+    apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta},
+    chrono::{DateTime, Duration as ChronoDuration, Utc},
+};
+use kube::{api::PostParams, Api, Client, Error as KError};
+
+/// Seconds a lease is held without renew before another replica takes it
+const LEASE_DURATION: i64 = 15;
+
+/// Delay between renews of a held lease
+const RENEW_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Deadline of a request taking a lease not held yet
+const ACQUIRE_TIMEOUT: Duration =
+    Duration::from_secs(LEASE_DURATION as u64 - RENEW_INTERVAL.as_secs());
+
+/// Delay between tries to acquire a lease held by another replica
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Lease on default namespace of client, only its holder runs `task`
+#[derive(Clone)]
+pub struct LeaderElection {
+    api: Api<Lease>,
+    name: String,
+    identity: String,
+}
+
+impl LeaderElection {
+    /// Election of lease `name`, replica is identified by its pod name
+    pub fn new(client: Client, name: impl Into<String>) -> Self {
+        let identity = std::env::var("HOSTNAME")
+            .unwrap_or_else(|_| format!("paastel-{}", std::process::id()));
+        Self {
+            api: Api::default_namespaced(client),
+            name: name.into(),
+            identity,
+        }
+    }
+
+    /// Run `task` while lease is held, it is aborted when lease is lost
+    /// and started again once acquired, never returns
+    pub async fn run<F, Fut>(self, task: F)
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        loop {
+            let mut renewed = self.acquire().await;
+            tracing::info!(lease = self.name, "leading as {}", self.identity);
+
+            let running = tokio::spawn(task());
+            loop {
+                tokio::time::sleep(RENEW_INTERVAL).await;
+                if running.is_finished() {
+                    tracing::warn!(lease = self.name, "leader task ended");
+                    break;
+                }
+                let timeout = renew_timeout(renewed, Utc::now());
+                let (started, result) = self.try_acquire_within(timeout).await;
+                match result {
+                    Ok(true) => renewed = started,
+                    Ok(false) => break,
+                    Err(e) => {
+                        tracing::warn!(lease = self.name, "renew failed {e}");
+                        // NOTE: others take the lease once it expires, stop
+                        // before that so two never run at once
+                        let held = (Utc::now() - renewed).num_seconds();
+                        let margin = RENEW_INTERVAL.as_secs() as i64;
+                        if held + margin >= LEASE_DURATION {
+                            break;
+                        }
+                    }
+                }
+            }
+
+            running.abort();
+            tracing::warn!(lease = self.name, "lost leadership");
+        }
+    }
+
+    /// Wait until lease is held by this replica, returns time it was
+    /// renewed at
+    async fn acquire(&self) -> DateTime<Utc> {
+        loop {
+            match self.try_acquire_within(ACQUIRE_TIMEOUT).await {
+                (started, Ok(true)) => return started,
+                (_, Ok(false)) => {
+                    tracing::debug!(lease = self.name, "not leader")
+                }
+                (_, Err(e)) => {
+                    tracing::warn!(lease = self.name, "acquire failed {e}")
+                }
+            }
+            tokio::time::sleep(RETRY_INTERVAL).await;
+        }
+    }
+
+    /// Take or renew lease, elapsed `timeout` is an error, returns time
+    /// before request since lease counts from it
+    async fn try_acquire_within(
+        &self,
+        timeout: Duration,
+    ) -> (DateTime<Utc>, StdResult<bool, String>) {
+        let started = Utc::now();
+        let result = tokio::time::timeout(timeout, self.try_acquire())
+            .await
+            .map_err(|_| "timed out".to_string())
+            .and_then(|result| result.map_err(|e| e.to_string()));
+        (started, result)
+    }
+
+    /// Take or renew lease, false when another replica holds it or wins
+    /// the race for it
+    async fn try_acquire(&self) -> StdResult<bool, KError> {
+        let now = Utc::now();
+        let result = match self.api.get_opt(&self.name).await? {
+            None => {
+                let lease = Lease {
+                    metadata: ObjectMeta {
+                        name: Some(self.name.clone()),
+                        ..Default::default()
+                    },
+                    spec: Some(next_spec(None, &self.identity, now)),
+                };
+                self.api.create(&PostParams::default(), &lease).await
+            }
+            Some(lease) => {
+                let spec = lease.spec.as_ref();
+                if held_by_other(spec, &self.identity, now) {
+                    return Ok(false);
+                }
+                // NOTE: resource version of read lease makes replace fail
+                // when another replica took it in between
+                let lease = Lease {
+                    spec: Some(next_spec(spec, &self.identity, now)),
+                    ..lease
+                };
+                self.api
+                    .replace(&self.name, &PostParams::default(), &lease)
+                    .await
+            }
+        };
+        match result {
+            Ok(_) => Ok(true),
+            Err(KError::Api(e)) if e.code == 409 => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Lease is held by another identity and renewed within its duration
+fn held_by_other(
+    spec: Option<&LeaseSpec>,
+    identity: &str,
+    now: DateTime<Utc>,
+) -> bool {
+    let Some(spec) = spec else {
+        return false;
+    };
+    let Some(holder) = spec.holder_identity.as_deref() else {
+        return false;
+    };
+    if holder == identity || holder.is_empty() {
+        return false;
+    }
+    let duration = spec
+        .lease_duration_seconds
+        .map_or(LEASE_DURATION, i64::from);
+    spec.renew_time
+        .as_ref()
+        .or(spec.acquire_time.as_ref())
+        .is_some_and(|MicroTime(renewed)| {
+            (now - *renewed).num_seconds() < duration
+        })
+}
+
+/// Time left to renew lease renewed at `renewed`, client read timeout is
+/// longer than the lease so a hung renew must end before lease expires
+fn renew_timeout(renewed: DateTime<Utc>, now: DateTime<Utc>) -> Duration {
+    let margin = ChronoDuration::from_std(RENEW_INTERVAL).unwrap_or_default();
+    let expires = renewed + ChronoDuration::seconds(LEASE_DURATION);
+    (expires - margin - now).to_std().unwrap_or_default()
+}
+
+/// Spec renewed by `identity`, transitions count changes of holder
+fn next_spec(
+    previous: Option<&LeaseSpec>,
+    identity: &str,
+    now: DateTime<Utc>,
+) -> LeaseSpec {
+    let previous = previous.cloned().unwrap_or_default();
+    let same_holder = previous.holder_identity.as_deref() == Some(identity);
+    let transitions = previous.lease_transitions.unwrap_or_default();
+    LeaseSpec {
+        holder_identity: Some(identity.to_string()),
+        lease_duration_seconds: Some(LEASE_DURATION as i32),
+        acquire_time: if same_holder {
+            previous.acquire_time
+        } else {
+            Some(MicroTime(now))
+        },
+        renew_time: Some(MicroTime(now)),
+        lease_transitions: Some(if same_holder {
+            transitions
+        } else {
+            transitions + 1
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lease_held_until_expired() {
+        let now = Utc::now();
+        let spec = next_spec(None, "paastel-0", now);
+
+        assert!(held_by_other(Some(&spec), "paastel-1", now));
+        assert!(!held_by_other(Some(&spec), "paastel-0", now));
+        assert!(!held_by_other(None, "paastel-1", now));
+
+        let later = now + ChronoDuration::seconds(LEASE_DURATION);
+        assert!(!held_by_other(Some(&spec), "paastel-1", later));
+    }
+
+    #[test]
+    fn renew_ends_before_lease_expires() {
+        let renewed = Utc::now();
+        let now = renewed + ChronoDuration::from_std(RENEW_INTERVAL).unwrap();
+        let timeout = renew_timeout(renewed, now);
+        assert_eq!(
+            timeout,
+            Duration::from_secs(LEASE_DURATION as u64) - RENEW_INTERVAL * 2
+        );
+
+        let later = renewed + ChronoDuration::seconds(LEASE_DURATION);
+        assert_eq!(renew_timeout(renewed, later), Duration::ZERO);
+    }
+
+    #[test]
+    fn lease_transitions_on_new_holder() {
+        let now = Utc::now();
+        let spec = next_spec(None, "paastel-0", now);
+        assert_eq!(spec.lease_transitions, Some(1));
+
+        let later = now + ChronoDuration::seconds(1);
+        let renewed = next_spec(Some(&spec), "paastel-0", later);
+        assert_eq!(renewed.lease_transitions, Some(1));
+        assert_eq!(renewed.acquire_time, Some(MicroTime(now)));
+        assert_eq!(renewed.renew_time, Some(MicroTime(later)));
+
+        let taken = next_spec(Some(&renewed), "paastel-1", later);
+        assert_eq!(taken.lease_transitions, Some(2));
+        assert_eq!(taken.acquire_time, Some(MicroTime(later)));
+    }
+}
@@ -13,48 +13,42 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
+pub mod app;
+pub mod cache;
 pub mod client;
+pub mod controller;
+pub mod crd;
 pub mod error;
+pub mod leader;
 pub mod mapper;
 pub mod secrets;
+pub mod stage;
 
+use app::KubernetesAppsAdapter;
 use async_trait::async_trait;
+use cache::SecretsCache;
 use client::KubernetesClient;
-use kube::api::ListParams;
+use k8s_openapi::chrono::Utc;
+use kube::Resource;
 use mapper::KubernetesMapper;
 use secrets::KubernetsSecretsAdapter;
+use stage::KubernetesStagesAdapter;
 
-use paastel_auth::{OutgoingKubernetesPort, SecretLabel};
-
-// use std::{
-//     collections::{BTreeMap, HashMap},
-//     result::Result as StdResult,
-// };
-
-// use async_trait::async_trait;
-// use k8s_openapi::{
-//     api::core::v1::Secret,
-//     apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition,
-// };
-// use kube::{
-//     api::{ListParams, Patch, PatchParams, PostParams},
-//     core::ObjectList,
-//     runtime::{conditions, wait::await_condition},
-//     Api, Client, CustomResource, CustomResourceExt, Error as KError, Resource,
-// };
-
-// use paastel::{
-//     AuthKubeSecretPort, AuthUser, AuthUsers, CreateAppCommand,
-//     CreateKubeCRDPort, Error as PaastelError, Result, Username,
-// };
-// use schemars::JsonSchema;
-// use serde::{Deserialize, Serialize};
-// use tracing::info;
+use paastel_app::{
+    AppConfig, AppName, Application, Namespace, NewApp, StageId,
+};
+use paastel_auth::{OutgoingKubernetesPort, SecretLabel, UserSecret, Username};
+
+/// Reads and replaces of app before giving up on concurrent changes
+const CONFIGURE_ATTEMPTS: usize = 3;
 
 #[derive(Clone)]
 pub struct KubernetesAdapter {
     mapper: KubernetesMapper,
     secrets: KubernetsSecretsAdapter,
+    secrets_cache: Option<SecretsCache>,
+    apps: KubernetesAppsAdapter,
+    stages: KubernetesStagesAdapter,
 }
 
 impl KubernetesAdapter {
@@ -62,8 +56,35 @@ impl KubernetesAdapter {
         Self {
             mapper: KubernetesMapper::default(),
             secrets: KubernetsSecretsAdapter::new(client),
+            secrets_cache: None,
+            apps: KubernetesAppsAdapter::new(client),
+            stages: KubernetesStagesAdapter::new(client),
         }
     }
+
+    /// Serve labelled secrets from cache, api server is only listed while
+    /// cache is not ready or for other labels
+    pub fn with_secrets_cache(mut self, secrets_cache: SecretsCache) -> Self {
+        self.secrets_cache = Some(secrets_cache);
+        self
+    }
+
+    /// Image of build jobs unpacking source archives, it must provide
+    /// `unzip`, `tar` and `zstd`
+    pub fn with_unpack_image(
+        mut self,
+        unpack_image: impl Into<String>,
+    ) -> Self {
+        self.mapper = self.mapper.with_unpack_image(unpack_image);
+        self
+    }
+
+    /// Node holding blobs of local storage, build jobs fetching sources
+    /// from a `file://` url are scheduled on it
+    pub fn with_blob_node(mut self, blob_node: impl Into<String>) -> Self {
+        self.mapper = self.mapper.with_blob_node(blob_node);
+        self
+    }
 }
 
 #[async_trait]
@@ -72,8 +93,17 @@ impl OutgoingKubernetesPort for KubernetesAdapter {
         &self,
         label: &SecretLabel,
     ) -> paastel_auth::Result<paastel_auth::UserSecrets> {
-        let label_str = label.to_string();
-        let lp = ListParams::default().match_any().labels(&label_str);
+        if let Some(cache) = self.secrets_cache.as_ref().filter(|cache| {
+            cache.is_ready() && cache.label() == label.to_string()
+        }) {
+            let secrets = cache.secrets();
+            let user_secrets = self
+                .mapper
+                .secrets_to_domain(secrets.iter().map(AsRef::as_ref));
+            return Ok(user_secrets);
+        }
+
+        let lp = self.mapper.from_label_to_lp(label);
         let secrets_list = self
             .secrets
             .get_all(&lp)
@@ -82,141 +112,263 @@ impl OutgoingKubernetesPort for KubernetesAdapter {
         let user_secrets = self.mapper.list_secrets_to_domain(&secrets_list);
         Ok(user_secrets)
     }
+
+    async fn create_secret(
+        &self,
+        label: &SecretLabel,
+        user_secret: &UserSecret,
+    ) -> paastel_auth::Result<()> {
+        let secret = self.mapper.user_secret_to_secret(label, user_secret);
+        self.secrets
+            .create(&secret)
+            .await
+            .map_err(|e| paastel_auth::Error::Kubernetes(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn update_secret(
+        &self,
+        label: &SecretLabel,
+        user_secret: &UserSecret,
+    ) -> paastel_auth::Result<()> {
+        let name = self.secret_name(label, user_secret.username()).await?;
+        let mut secret = self.mapper.user_secret_to_secret(label, user_secret);
+        secret.metadata.generate_name = None;
+        self.secrets
+            .patch(&name, &secret)
+            .await
+            .map_err(|e| paastel_auth::Error::Kubernetes(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn delete_secret(
+        &self,
+        label: &SecretLabel,
+        username: &Username,
+    ) -> paastel_auth::Result<()> {
+        let name = self.secret_name(label, username).await?;
+        self.secrets
+            .delete(&name)
+            .await
+            .map_err(|e| paastel_auth::Error::Kubernetes(e.to_string()))
+    }
 }
 
-// #[derive(Debug, Clone)]
-// pub struct KubeSecrets {
-//     api: Api<Secret>,
-// }
-
-// impl KubeSecrets {
-//     pub fn new(client: Client) -> Self {
-//         Self {
-//             api: Api::default_namespaced(client),
-//         }
-//     }
-
-//     async fn get_all(&self) -> StdResult<ObjectList<Secret>, KError> {
-//         let lp = ListParams::default()
-//             .match_any()
-//             .timeout(60)
-//             .labels("paastel.io/api-user-credentials=true");
-//         self.api.list(&lp).await
-//     }
-// }
-
-// #[async_trait]
-// impl AuthKubeSecretPort for KubernetesAdapter {
-//     async fn list(&self) -> Result<AuthUsers> {
-//         let secrets = self
-//             .secrets
-//             .get_all()
-//             .await
-//             .map_err(|e| PaastelError::KubePort(e.to_string()))?;
-
-//         let content: HashMap<Username, AuthUser> = secrets
-//             .into_iter()
-//             .map(|c| {
-//                 let data = c.data.unwrap();
-//                 let metadata = c.metadata;
-//                 let username = {
-//                     let u = &data.get("username").unwrap().0;
-//                     String::from_utf8(u.to_owned()).unwrap()
-//                 };
-//                 let password = {
-//                     let p = &data.get("password").unwrap().0;
-//                     String::from_utf8(p.to_owned()).unwrap()
-//                 };
-//                 let secrt_name = metadata.name.unwrap();
-//                 let auth_user = AuthUser::new(
-//                     username.clone().into(),
-//                     password,
-//                     secrt_name,
-//                 );
-//                 (username.into(), auth_user)
-//             })
-//             .collect();
-
-//         let auth_users = AuthUsers::new(content);
-//         Ok(auth_users)
-//     }
-// }
-
-// #[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema)]
-// #[kube(
-//     group = "application.paastel.io",
-//     version = "v1",
-//     kind = "App",
-//     namespaced
-// )]
-// struct AppSpec {
-//     origin: String,
-// }
-
-// #[derive(Debug, Clone)]
-// pub struct KubeCustomResource {
-//     api: Api<App>,
-// }
-
-// impl KubeCustomResource {
-//     pub fn new(client: Client) -> Self {
-//         Self {
-//             api: Api::default_namespaced(client),
-//         }
-//     }
-
-//     #[allow(dead_code)]
-//     async fn init(&self) -> StdResult<(), KError> {
-//         info!("init crd App");
-//         let client = Client::try_default().await?;
-//         let crds: Api<CustomResourceDefinition> = Api::all(client);
-
-//         // Apply the CRD so users can create Foo instances in Kubernetes
-//         crds.patch(
-//             "apps.application.paastel.io",
-//             &PatchParams::apply("apps_manager"),
-//             &Patch::Apply(App::crd()),
-//         )
-//         .await?;
-
-//         // Wait for the CRD to be ready
-//         let _ = tokio::time::timeout(
-//             std::time::Duration::from_secs(10),
-//             await_condition(
-//                 crds,
-//                 "apps.application.paastel.io",
-//                 conditions::is_crd_established(),
-//             ),
-//         )
-//         .await
-//         .unwrap();
-//         info!("complete init crd App");
-//         Ok(())
-//     }
-
-//     pub async fn create(&self, name: &str) -> StdResult<(), KError> {
-//         let spec = AppSpec {
-//             origin: "path".to_string(),
-//         };
-//         let mut app = App::new(name, spec);
-//         let mut annnotations = BTreeMap::new();
-//         annnotations.insert(
-//             "paastal.io/created-by".to_string(),
-//             "admin@paastel.io".to_string(),
-//         );
-//         app.meta_mut().annotations = Some(annnotations);
-//         // println!("{}", serde_yaml::to_string(&app).unwrap()); // crd yaml
-//         self.api.create(&PostParams::default(), &app).await?;
-//         Ok(())
-//     }
-// }
-
-// #[async_trait]
-// impl CreateKubeCRDPort for KubernetesAdapter {
-//     async fn crd(&self, command: &CreateAppCommand) -> Result<()> {
-//         // NOTE: run just one
-//         // self.crd.init().await.unwrap();
-//         self.crd.create(command.name.0.as_str()).await.unwrap();
-//         Ok(())
-//     }
-// }
+impl KubernetesAdapter {
+    /// Find name of labelled secret holding username
+    async fn secret_name(
+        &self,
+        label: &SecretLabel,
+        username: &Username,
+    ) -> paastel_auth::Result<String> {
+        let lp = self.mapper.from_label_to_lp(label);
+        let secrets_list = self
+            .secrets
+            .get_all(&lp)
+            .await
+            .map_err(|_| paastel_auth::Error::SecretNotFound)?;
+        self.mapper
+            .find_secret_name(&secrets_list, username)
+            .ok_or(paastel_auth::Error::UserNotFound)
+    }
+}
+
+#[async_trait]
+impl paastel_app::OutgoingKubernetesPort for KubernetesAdapter {
+    async fn find_app(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+    ) -> paastel_app::Result<Option<Application>> {
+        let app = self
+            .apps
+            .get_opt(namespace.as_ref(), name.as_ref())
+            .await
+            .map_err(|e| paastel_app::Error::Kubernetes(e.to_string()))?;
+        Ok(app.and_then(|app| self.mapper.app_to_domain(&app)))
+    }
+
+    async fn create_app(
+        &self,
+        new_app: &NewApp,
+    ) -> paastel_app::Result<Application> {
+        let app = self.mapper.new_app_to_app(new_app);
+        let app = self
+            .apps
+            .create(new_app.namespace().as_ref(), &app)
+            .await
+            .map_err(|e| match e {
+                kube::Error::Api(ref response) if response.code == 409 => {
+                    paastel_app::Error::AppAlreadyExists
+                }
+                e => app_error(e),
+            })?;
+        self.mapper.app_to_domain(&app).ok_or_else(|| {
+            paastel_app::Error::Kubernetes("invalid created app".to_string())
+        })
+    }
+
+    async fn configure_app(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+        config: &AppConfig,
+    ) -> paastel_app::Result<Application> {
+        let mut attempt = 1;
+        let app = loop {
+            let mut app = self
+                .apps
+                .get_opt(namespace.as_ref(), name.as_ref())
+                .await
+                .map_err(app_error)?
+                .ok_or(paastel_app::Error::AppNotFound)?;
+            self.mapper.configure_app(&mut app, config);
+            // NOTE: replace fails on conflict when app changed since read,
+            // ex: controller wrote its status, read it again and retry
+            match self.apps.replace(namespace.as_ref(), &app).await {
+                Ok(app) => break app,
+                Err(kube::Error::Api(ref response)) if response.code == 409 => {
+                    if attempt == CONFIGURE_ATTEMPTS {
+                        return Err(paastel_app::Error::AppConflict);
+                    }
+                    tracing::debug!(%name, attempt, "app changed, retry");
+                    attempt += 1;
+                }
+                Err(e) => return Err(app_error(e)),
+            }
+        };
+        self.mapper.app_to_domain(&app).ok_or_else(|| {
+            paastel_app::Error::Kubernetes("invalid configured app".to_string())
+        })
+    }
+
+    async fn deploy_app(
+        &self,
+        namespace: &Namespace,
+        name: &AppName,
+        image: &str,
+    ) -> paastel_app::Result<Application> {
+        let app = self
+            .apps
+            .patch_image(namespace.as_ref(), name.as_ref(), image)
+            .await
+            .map_err(|e| match e {
+                kube::Error::Api(ref response) if response.code == 404 => {
+                    paastel_app::Error::AppNotFound
+                }
+                e => app_error(e),
+            })?;
+        self.mapper.app_to_domain(&app).ok_or_else(|| {
+            paastel_app::Error::Kubernetes("invalid deployed app".to_string())
+        })
+    }
+
+    async fn create_stage(
+        &self,
+        stage: &paastel_app::Stage,
+        source_url: &str,
+    ) -> paastel_app::Result<()> {
+        let namespace = stage.namespace().as_ref();
+        let resource = self
+            .stages
+            .create(namespace, &self.mapper.stage_to_resource(stage))
+            .await
+            .map_err(app_error)?;
+
+        // NOTE: job is removed with its stage
+        let owner = resource.controller_owner_ref(&());
+        let job = self.mapper.stage_to_job(stage, source_url, owner);
+        if let Err(e) = self.stages.create_job(namespace, &job).await {
+            // NOTE: a stage without build job would never end
+            let id = stage.id();
+            if let Err(e) = self.stages.delete(namespace, id.as_ref()).await {
+                tracing::warn!(%id, "failed delete stage without job {e}");
+            }
+            return Err(app_error(e));
+        }
+        Ok(())
+    }
+
+    async fn find_stage(
+        &self,
+        namespace: &Namespace,
+        id: &StageId,
+    ) -> paastel_app::Result<Option<paastel_app::Stage>> {
+        let Some(resource) = self
+            .stages
+            .get_opt(namespace.as_ref(), id.as_ref())
+            .await
+            .map_err(kube_error)?
+        else {
+            return Ok(None);
+        };
+        let Some(stage) = self.mapper.resource_to_stage(&resource) else {
+            return Ok(None);
+        };
+
+        if stage.phase().is_terminal() {
+            return Ok(Some(stage));
+        }
+
+        let job = self
+            .stages
+            .get_job_opt(namespace.as_ref(), &self.mapper.stage_job_name(id))
+            .await
+            .map_err(kube_error)?;
+        let (phase, reason) = match job {
+            Some(job) => self.mapper.job_phase(&job),
+            None => self.mapper.missing_job_phase(&resource, Utc::now()),
+        };
+        Ok(Some(stage.with_phase(phase, reason)))
+    }
+
+    async fn update_stage(
+        &self,
+        stage: &paastel_app::Stage,
+    ) -> paastel_app::Result<()> {
+        self.stages
+            .patch_status(
+                stage.namespace().as_ref(),
+                stage.id().as_ref(),
+                &self.mapper.stage_to_status(stage),
+            )
+            .await
+            .map_err(stage_error)?;
+        Ok(())
+    }
+
+    async fn stage_logs(
+        &self,
+        namespace: &Namespace,
+        id: &StageId,
+    ) -> paastel_app::Result<String> {
+        self.stages
+            .job_logs(namespace.as_ref(), &self.mapper.stage_job_name(id))
+            .await
+            .map_err(stage_error)
+    }
+}
+
+/// Not found on create means namespace is missing
+fn app_error(e: kube::Error) -> paastel_app::Error {
+    match e {
+        kube::Error::Api(ref response) if response.code == 404 => {
+            paastel_app::Error::NamespaceNotFound
+        }
+        e => kube_error(e),
+    }
+}
+
+/// Not found on stage, its job or pods means stage is gone
+fn stage_error(e: kube::Error) -> paastel_app::Error {
+    match e {
+        kube::Error::Api(ref response) if response.code == 404 => {
+            paastel_app::Error::StageNotFound
+        }
+        e => kube_error(e),
+    }
+}
+
+fn kube_error(e: kube::Error) -> paastel_app::Error {
+    paastel_app::Error::Kubernetes(e.to_string())
+}
@@ -15,11 +15,33 @@
 use std::collections::BTreeMap;
 
 use derive_new::new;
-use k8s_openapi::{api::core::v1::Secret, ByteString};
-use kube::{api::ListParams, core::ObjectList};
+use k8s_openapi::{
+    api::{
+        batch::v1::{Job, JobSpec},
+        core::v1::{
+            Container, HostPathVolumeSource, PodSpec, PodTemplateSpec, Secret,
+            Volume, VolumeMount,
+        },
+    },
+    apimachinery::pkg::apis::meta::v1::{ObjectMeta, OwnerReference, Time},
+    chrono::{DateTime, Utc},
+    ByteString,
+};
+use kube::{api::ListParams, core::ObjectList, ResourceExt};
+use paastel_app::{
+    AppConfig, AppName, AppStatus, Application, ArchiveFormat, Builder,
+    Namespace, NewApp, StageId, StagePhase,
+};
 
 use paastel_auth::{
-    PasswordHash, SecretLabel, UserSecret, UserSecrets, Username,
+    PasswordHash, RetrievePassword, Roles, SecretLabel, UserSecret,
+    UserSecrets, Username,
+};
+
+use crate::{
+    app::{App, AppSpec},
+    controller::CONDITION_READY,
+    stage::{Stage, StageSpec, StageStatus},
 };
 
 /// Secret field username
@@ -28,60 +50,848 @@ const SECRET_FIELD_USERNAME: &str = "username";
 /// Secret field password
 const SECRET_FIELD_PASSWORD: &str = "password";
 
-#[derive(Default, Clone, new)]
-pub struct KubernetesMapper {}
+/// Secret annotation with roles of user, ex: `admin` or `developer:team-a`
+pub const SECRET_ANNOTATION_ROLES: &str = "paastel.io/roles";
+
+/// Prefix of generated name of user secrets
+const SECRET_GENERATE_NAME: &str = "paastel-user-";
+
+/// App annotation with username of creator
+pub const APP_ANNOTATION_CREATED_BY: &str = "paastel.io/created-by";
+
+/// Label with name of application
+pub const LABEL_APP: &str = "paastel.io/app";
+
+/// Label with stage id of build jobs
+pub const LABEL_STAGE: &str = "paastel.io/stage";
+
+/// Prefix of build jobs name
+const STAGE_JOB_PREFIX: &str = "paastel-stage-";
+
+/// Image downloading source archive
+const STAGE_FETCH_IMAGE: &str = "curlimages/curl:8.7.1";
+
+/// Image unpacking source archives, built from `images/unpack`, it
+/// provides `unzip`, `tar` and `zstd` so build pods install nothing
+pub const DEFAULT_UNPACK_IMAGE: &str =
+    "ghcr.io/microbio-rs/paastel-unpack:0.1.0";
+
+/// Cloud native buildpacks builder
+const STAGE_BUILDPACK_IMAGE: &str = "paketobuildpacks/builder-jammy-base";
+
+/// Dockerfile builder
+const STAGE_DOCKERFILE_IMAGE: &str = "gcr.io/kaniko-project/executor:v1.22.0";
+
+/// Volume shared by build containers
+const STAGE_WORKSPACE: &str = "/workspace";
+
+/// Build jobs are removed one day after finish, logs are recorded before
+const STAGE_JOB_TTL: i32 = 24 * 60 * 60;
+
+/// Seconds a stage may be without build job, it is created right after
+const STAGE_JOB_GRACE: i64 = 60;
+
+#[derive(Clone, new)]
+pub struct KubernetesMapper {
+    /// Image of build jobs unpacking source archives
+    #[new(value = "DEFAULT_UNPACK_IMAGE.to_string()")]
+    unpack_image: String,
+    /// Node holding blobs of local storage, build jobs reading them run on
+    /// it
+    #[new(default)]
+    blob_node: Option<String>,
+}
+
+impl Default for KubernetesMapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl KubernetesMapper {
+    pub fn with_unpack_image(
+        mut self,
+        unpack_image: impl Into<String>,
+    ) -> Self {
+        self.unpack_image = unpack_image.into();
+        self
+    }
+
+    pub fn with_blob_node(mut self, blob_node: impl Into<String>) -> Self {
+        self.blob_node = Some(blob_node.into());
+        self
+    }
+
     pub fn list_secrets_to_domain(
         &self,
         secrets_list: &ObjectList<Secret>,
     ) -> UserSecrets {
-        let content: Vec<UserSecret> = secrets_list
-            .iter()
+        self.secrets_to_domain(secrets_list.iter())
+    }
+
+    pub fn secrets_to_domain<'a>(
+        &self,
+        secrets: impl Iterator<Item = &'a Secret>,
+    ) -> UserSecrets {
+        let content: Vec<UserSecret> = secrets
             .filter_map(check_secret_data)
             .filter_map(check_secret_content)
             .collect();
         UserSecrets::new(content)
     }
 
-    pub fn from_label_to_lp(&self, _label: &SecretLabel) -> ListParams {
-        todo!()
+    pub fn from_label_to_lp(&self, label: &SecretLabel) -> ListParams {
+        ListParams::default().match_any().labels(&label.to_string())
+    }
+
+    /// Build a labelled secret holding user secret, name is generated by
+    /// api server because usernames are not valid object names
+    pub fn user_secret_to_secret(
+        &self,
+        label: &SecretLabel,
+        user_secret: &UserSecret,
+    ) -> Secret {
+        let mut secret = Secret {
+            type_: Some("Opaque".to_string()),
+            string_data: Some(BTreeMap::from([
+                (
+                    SECRET_FIELD_USERNAME.to_string(),
+                    user_secret.username().to_string(),
+                ),
+                (
+                    SECRET_FIELD_PASSWORD.to_string(),
+                    user_secret.password().to_string(),
+                ),
+            ])),
+            ..Default::default()
+        };
+        secret.metadata.generate_name = Some(SECRET_GENERATE_NAME.to_string());
+        secret.metadata.labels = Some(BTreeMap::from([(
+            label.key().to_string(),
+            label.value().to_string(),
+        )]));
+        secret.metadata.annotations = Some(BTreeMap::from([(
+            SECRET_ANNOTATION_ROLES.to_string(),
+            user_secret.roles().to_string(),
+        )]));
+        secret
+    }
+
+    /// Return name of secret holding username
+    pub fn find_secret_name(
+        &self,
+        secrets_list: &ObjectList<Secret>,
+        username: &Username,
+    ) -> Option<String> {
+        secrets_list
+            .iter()
+            .filter_map(check_secret_data)
+            .filter_map(|content| {
+                let name = content.0.name.clone();
+                check_secret_content(content).map(|us| (name, us))
+            })
+            .find(|(_, us)| us.username() == username)
+            .and_then(|(name, _)| name)
+    }
+
+    pub fn new_app_to_app(&self, new_app: &NewApp) -> App {
+        let mut app = App::new(new_app.name().as_ref(), AppSpec::default());
+        app.metadata.namespace = Some(new_app.namespace().to_string());
+        app.metadata.annotations = Some(BTreeMap::from([(
+            APP_ANNOTATION_CREATED_BY.to_string(),
+            new_app.created_by().to_string(),
+        )]));
+        app
+    }
+
+    /// Map App resource to domain, objects with invalid name or without
+    /// namespace are ignored
+    pub fn app_to_domain(&self, app: &App) -> Option<Application> {
+        let name = app
+            .name_any()
+            .parse::<AppName>()
+            .map_err(|e| tracing::warn!("invalid app name {e}"))
+            .ok()?;
+        let namespace = app
+            .namespace()?
+            .parse::<Namespace>()
+            .map_err(|e| tracing::warn!("invalid app namespace {e}"))
+            .ok()?;
+        let created_by =
+            app.annotations().get(APP_ANNOTATION_CREATED_BY).cloned();
+        let builder = app.spec.builder.as_deref().and_then(|builder| {
+            builder
+                .parse::<Builder>()
+                .map_err(|e| tracing::warn!("invalid app builder {e}"))
+                .ok()
+        });
+        let config = AppConfig::new(
+            app.spec.instances,
+            app.spec.routes.clone(),
+            app.spec.env.clone(),
+            app.spec.configurations.clone(),
+        )
+        .with_builder(builder);
+        let mut application = Application::new(name, namespace, created_by)
+            .with_config(config)
+            .with_status(self.app_status_to_domain(app));
+        if let Some(image) = app.spec.image.as_deref() {
+            application = application.with_image(image);
+        }
+        Some(application)
+    }
+
+    /// Replace spec fields declared by config, image and port are kept
+    pub fn configure_app(&self, app: &mut App, config: &AppConfig) {
+        app.spec.instances = config.instances();
+        app.spec.routes = config.routes().to_vec();
+        app.spec.env = config.env().clone();
+        app.spec.configurations = config.configurations().to_vec();
+        app.spec.builder = config.builder().map(|b| b.to_string());
+    }
+
+    /// Application is ready once controller reconciled its current spec
+    /// and image and Ready condition is true
+    fn app_status_to_domain(&self, app: &App) -> AppStatus {
+        let Some(status) = app.status.as_ref() else {
+            return AppStatus::default();
+        };
+        let condition = status
+            .conditions
+            .iter()
+            .find(|c| c.type_ == CONDITION_READY);
+        let ready = app.spec.image.is_some()
+            && status.image == app.spec.image
+            && status.observed_generation == app.metadata.generation
+            && condition.is_some_and(|c| c.status == "True");
+        AppStatus::new(
+            ready,
+            status.ready_replicas,
+            status.url.clone(),
+            condition.and_then(|c| c.message.clone()),
+        )
+    }
+
+    pub fn stage_job_name(&self, id: &StageId) -> String {
+        format!("{STAGE_JOB_PREFIX}{id}")
+    }
+
+    pub fn stage_to_resource(&self, stage: &paastel_app::Stage) -> Stage {
+        let mut resource = Stage::new(
+            stage.id().as_ref(),
+            StageSpec {
+                app: stage.name().to_string(),
+                blob_id: stage.blob_id().to_string(),
+                builder: stage.builder().to_string(),
+                image: stage.image().to_string(),
+                format: stage.format().to_string(),
+            },
+        );
+        resource.metadata.namespace = Some(stage.namespace().to_string());
+        resource.metadata.labels = Some(BTreeMap::from([(
+            LABEL_APP.to_string(),
+            stage.name().to_string(),
+        )]));
+        resource.status = Some(self.stage_to_status(stage));
+        resource
+    }
+
+    pub fn stage_to_status(&self, stage: &paastel_app::Stage) -> StageStatus {
+        StageStatus {
+            phase: Some(stage.phase().to_string()),
+            reason: stage.reason().map(ToString::to_string),
+            logs: stage.logs().map(ToString::to_string),
+        }
+    }
+
+    /// Map Stage resource to domain, objects with invalid fields are ignored
+    pub fn resource_to_stage(
+        &self,
+        resource: &Stage,
+    ) -> Option<paastel_app::Stage> {
+        let parsed = (|| {
+            let stage = paastel_app::Stage::new(
+                resource.name_any().parse()?,
+                resource.spec.app.parse()?,
+                resource.namespace().unwrap_or_default().parse()?,
+                resource.spec.blob_id.parse()?,
+                resource.spec.builder.parse()?,
+                resource.spec.image.clone(),
+            )
+            .with_format(resource.spec.format.parse()?);
+            let status = resource.status.clone().unwrap_or_default();
+            let phase = match status.phase {
+                Some(phase) => phase.parse()?,
+                None => StagePhase::default(),
+            };
+            let stage = stage.with_phase(phase, status.reason);
+            Ok::<_, paastel_app::Error>(match status.logs {
+                Some(logs) => stage.with_logs(logs.parse()?),
+                None => stage,
+            })
+        })();
+        parsed
+            .map_err(|e| {
+                tracing::warn!(name = resource.name_any(), "invalid stage {e}")
+            })
+            .ok()
+    }
+
+    /// Build job: fetch and unpack sources then build and push image
+    pub fn stage_to_job(
+        &self,
+        stage: &paastel_app::Stage,
+        source_url: &str,
+        owner: Option<OwnerReference>,
+    ) -> Job {
+        let labels = BTreeMap::from([
+            (LABEL_APP.to_string(), stage.name().to_string()),
+            (LABEL_STAGE.to_string(), stage.id().to_string()),
+        ]);
+        let archive = format!("{STAGE_WORKSPACE}/source.{}", stage.format());
+        let source = format!("{STAGE_WORKSPACE}/source");
+
+        let mut volumes = vec![Volume {
+            name: "workspace".to_string(),
+            empty_dir: Some(Default::default()),
+            ..Default::default()
+        }];
+        let mut fetch_mounts = vec![workspace_mount()];
+        let mut node_name = None;
+        // NOTE: blobs on local directory are read from node holding them,
+        // any node when it is unknown, so only single node clusters
+        if let Some(dir) = source_url
+            .strip_prefix("file://")
+            .and_then(|path| path.rsplit_once('/'))
+            .map(|(dir, _)| dir.to_string())
+        {
+            volumes.push(Volume {
+                name: "blobs".to_string(),
+                host_path: Some(HostPathVolumeSource {
+                    path: dir.clone(),
+                    type_: Some("Directory".to_string()),
+                }),
+                ..Default::default()
+            });
+            fetch_mounts.push(VolumeMount {
+                name: "blobs".to_string(),
+                mount_path: dir,
+                read_only: Some(true),
+                ..Default::default()
+            });
+            node_name.clone_from(&self.blob_node);
+        }
+
+        let fetch = Container {
+            name: "fetch".to_string(),
+            image: Some(STAGE_FETCH_IMAGE.to_string()),
+            args: Some(vec![
+                "-fsSL".to_string(),
+                "-o".to_string(),
+                archive.clone(),
+                source_url.to_string(),
+            ]),
+            volume_mounts: Some(fetch_mounts),
+            ..Default::default()
+        };
+        let unpack = Container {
+            name: "unpack".to_string(),
+            image: Some(self.unpack_image.clone()),
+            command: Some(unpack_command(stage.format(), &archive, &source)),
+            volume_mounts: Some(vec![workspace_mount()]),
+            ..Default::default()
+        };
+        let build = match stage.builder() {
+            Builder::Buildpack => Container {
+                name: "build".to_string(),
+                image: Some(STAGE_BUILDPACK_IMAGE.to_string()),
+                command: Some(vec![
+                    "/cnb/lifecycle/creator".to_string(),
+                    format!("-app={source}"),
+                    stage.image().to_string(),
+                ]),
+                volume_mounts: Some(vec![workspace_mount()]),
+                ..Default::default()
+            },
+            Builder::Dockerfile => Container {
+                name: "build".to_string(),
+                image: Some(STAGE_DOCKERFILE_IMAGE.to_string()),
+                args: Some(vec![
+                    format!("--context=dir://{source}"),
+                    format!("--destination={}", stage.image()),
+                ]),
+                volume_mounts: Some(vec![workspace_mount()]),
+                ..Default::default()
+            },
+        };
+
+        Job {
+            metadata: ObjectMeta {
+                name: Some(self.stage_job_name(stage.id())),
+                namespace: Some(stage.namespace().to_string()),
+                labels: Some(labels.clone()),
+                owner_references: owner.map(|owner| vec![owner]),
+                ..Default::default()
+            },
+            spec: Some(JobSpec {
+                backoff_limit: Some(0),
+                ttl_seconds_after_finished: Some(STAGE_JOB_TTL),
+                template: PodTemplateSpec {
+                    metadata: Some(ObjectMeta {
+                        labels: Some(labels),
+                        ..Default::default()
+                    }),
+                    spec: Some(PodSpec {
+                        restart_policy: Some("Never".to_string()),
+                        node_name,
+                        init_containers: Some(vec![fetch, unpack]),
+                        containers: vec![build],
+                        volumes: Some(volumes),
+                        ..Default::default()
+                    }),
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Phase of stage whose build job is missing, it is pending while job
+    /// may still be created and failed after
+    pub fn missing_job_phase(
+        &self,
+        resource: &Stage,
+        now: DateTime<Utc>,
+    ) -> (StagePhase, Option<String>) {
+        let created = resource.metadata.creation_timestamp.as_ref();
+        match created {
+            Some(Time(created))
+                if (now - *created).num_seconds() < STAGE_JOB_GRACE =>
+            {
+                (StagePhase::Pending, None)
+            }
+            _ => (StagePhase::Failed, Some("build job not found".to_string())),
+        }
+    }
+
+    /// Phase of stage from build job conditions, failure reason carries
+    /// condition reason and message
+    pub fn job_phase(&self, job: &Job) -> (StagePhase, Option<String>) {
+        let Some(status) = job.status.as_ref() else {
+            return (StagePhase::Pending, None);
+        };
+
+        let condition = status.conditions.iter().flatten().find(|c| {
+            c.status == "True" && (c.type_ == "Complete" || c.type_ == "Failed")
+        });
+        match condition {
+            Some(c) if c.type_ == "Complete" => (StagePhase::Succeeded, None),
+            Some(c) => {
+                let reason = [c.reason.as_deref(), c.message.as_deref()]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join(": ");
+                (StagePhase::Failed, Some(reason).filter(|r| !r.is_empty()))
+            }
+            None if status.active.unwrap_or_default() > 0 => {
+                (StagePhase::Running, None)
+            }
+            None => (StagePhase::Pending, None),
+        }
+    }
+}
+
+/// Command extracting archive into source directory
+fn unpack_command(
+    format: ArchiveFormat,
+    archive: &str,
+    source: &str,
+) -> Vec<String> {
+    match format {
+        ArchiveFormat::Zip => vec![
+            "unzip".to_string(),
+            "-o".to_string(),
+            "-q".to_string(),
+            archive.to_string(),
+            "-d".to_string(),
+            source.to_string(),
+        ],
+        ArchiveFormat::TarGz => vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            format!("mkdir -p {source} && tar -xzf {archive} -C {source}"),
+        ],
+        ArchiveFormat::TarZst => vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            format!(
+                "mkdir -p {source} && zstd -dc {archive} | tar -x -C {source}"
+            ),
+        ],
+    }
+}
+
+fn workspace_mount() -> VolumeMount {
+    VolumeMount {
+        name: "workspace".to_string(),
+        mount_path: STAGE_WORKSPACE.to_string(),
+        ..Default::default()
     }
 }
 
 fn check_secret_content(
-    (_metadata, data): (&String, &BTreeMap<String, ByteString>),
+    (metadata, data): (&ObjectMeta, &BTreeMap<String, ByteString>),
 ) -> Option<UserSecret> {
-    if data.get(SECRET_FIELD_USERNAME).is_none()
-        || data.get(SECRET_FIELD_PASSWORD).is_none()
-    {
+    let (Some(username), Some(password)) = (
+        data.get(SECRET_FIELD_USERNAME),
+        data.get(SECRET_FIELD_PASSWORD),
+    ) else {
         tracing::info!("not found username or password on secret ...");
-        None
-    } else {
-        tracing::debug!("found username and password on secret ...");
-        Some(UserSecret::new(
-            Username::try_from(
-                data.get(SECRET_FIELD_USERNAME).unwrap().0.as_slice(),
-            )
-            .unwrap(),
-            PasswordHash::try_from(
-                data.get(SECRET_FIELD_PASSWORD).unwrap().0.as_slice(),
-            )
-            .unwrap(),
-        ))
-    }
+        return None;
+    };
+
+    tracing::debug!("found username and password on secret ...");
+    let username = Username::try_from(username.0.as_slice())
+        .map_err(|e| tracing::warn!(?metadata.name, "invalid username {e}"))
+        .ok()?;
+    let password = PasswordHash::try_from(password.0.as_slice())
+        .map_err(|e| tracing::warn!(?metadata.name, "invalid password {e}"))
+        .ok()?;
+
+    Some(UserSecret::new(
+        username,
+        password,
+        roles_from_metadata(metadata),
+    ))
+}
+
+/// Parse roles annotation, invalid or missing annotation means no roles
+fn roles_from_metadata(metadata: &ObjectMeta) -> Roles {
+    metadata
+        .annotations
+        .as_ref()
+        .and_then(|annotations| annotations.get(SECRET_ANNOTATION_ROLES))
+        .map(|roles| {
+            roles.parse::<Roles>().unwrap_or_else(|e| {
+                tracing::warn!(?metadata.name, "invalid roles annotation {e}");
+                Roles::default()
+            })
+        })
+        .unwrap_or_default()
 }
 
 fn check_secret_data(
     secret: &Secret,
-) -> Option<(&String, &BTreeMap<String, ByteString>)> {
-    if secret.data.is_some() && secret.metadata.name.is_some() {
-        Some((
-            secret.metadata.name.as_ref().unwrap(),
-            secret.data.as_ref().unwrap(),
-        ))
-    } else {
-        tracing::info!("not found data on secret ...");
-        None
+) -> Option<(&ObjectMeta, &BTreeMap<String, ByteString>)> {
+    match (secret.metadata.name.as_ref(), secret.data.as_ref()) {
+        (Some(_), Some(data)) => Some((&secret.metadata, data)),
+        _ => {
+            tracing::info!("not found data on secret ...");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use paastel_auth::{Action, Role, RoleGrant};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn secret(roles: Option<&str>) -> Secret {
+        let mut secret = Secret::default();
+        secret.metadata.name = Some("admin".to_string());
+        secret.metadata.annotations = roles.map(|roles| {
+            BTreeMap::from([(
+                SECRET_ANNOTATION_ROLES.to_string(),
+                roles.to_string(),
+            )])
+        });
+        secret.data = Some(BTreeMap::from([
+            (
+                SECRET_FIELD_USERNAME.to_string(),
+                ByteString(b"admin@paastel.io".to_vec()),
+            ),
+            (
+                SECRET_FIELD_PASSWORD.to_string(),
+                ByteString(b"$argon2id$v=19$m=19456".to_vec()),
+            ),
+        ]));
+        secret
+    }
+
+    #[test]
+    fn secret_roles_annotation() {
+        let secret = secret(Some("admin"));
+        let user_secret =
+            check_secret_data(&secret).and_then(check_secret_content);
+        let roles = user_secret.unwrap().roles().clone();
+        assert_eq!(roles, Roles::new(vec![RoleGrant::new(Role::Admin, None)]));
+        assert!(roles.is_allowed(Action::Manage, None));
+    }
+
+    #[test]
+    fn secret_invalid_roles_annotation() {
+        let secret = secret(Some("root"));
+        let user_secret =
+            check_secret_data(&secret).and_then(check_secret_content);
+        assert!(user_secret.unwrap().roles().is_empty());
+    }
+
+    #[test]
+    fn user_secret_to_secret_round_trip() {
+        let mapper = KubernetesMapper::default();
+        let user_secret = UserSecret::new(
+            "admin@paastel.io".parse().unwrap(),
+            "$argon2id$v=19$m=19456".parse().unwrap(),
+            "developer:team-a".parse().unwrap(),
+        );
+        let mut secret =
+            mapper.user_secret_to_secret(&SecretLabel::default(), &user_secret);
+
+        // NOTE: api server move string data to data
+        secret.metadata.name = Some("paastel-user-x1y2z".to_string());
+        secret.data = secret.string_data.take().map(|data| {
+            data.into_iter()
+                .map(|(k, v)| (k, ByteString(v.into_bytes())))
+                .collect()
+        });
+
+        let result = check_secret_data(&secret).and_then(check_secret_content);
+        assert_eq!(result, Some(user_secret.clone()));
+
+        let list = ObjectList {
+            metadata: Default::default(),
+            items: vec![secret],
+            types: Default::default(),
+        };
+        assert_eq!(
+            mapper.find_secret_name(&list, user_secret.username()),
+            Some("paastel-user-x1y2z".to_string())
+        );
+    }
+
+    #[test]
+    fn secret_without_roles_annotation() {
+        let secret = secret(None);
+        let user_secret =
+            check_secret_data(&secret).and_then(check_secret_content);
+        assert!(user_secret.unwrap().roles().is_empty());
+    }
+
+    #[test]
+    fn app_round_trip() {
+        let mapper = KubernetesMapper::default();
+        let new_app = NewApp::new(
+            "my-app".parse().unwrap(),
+            "paastel-space".parse().unwrap(),
+            "admin".to_string(),
+        );
+
+        let mut app = mapper.new_app_to_app(&new_app);
+        let config =
+            AppConfig::default().with_builder(Some(Builder::Dockerfile));
+        mapper.configure_app(&mut app, &config);
+        assert_eq!(app.spec.builder.as_deref(), Some("dockerfile"));
+        let application = mapper.app_to_domain(&app).unwrap();
+
+        assert_eq!(application.name(), new_app.name());
+        assert_eq!(application.namespace(), new_app.namespace());
+        assert_eq!(application.created_by(), Some("admin"));
+        assert_eq!(application.config(), &config);
+        assert!(!application.status().is_ready());
+    }
+
+    #[test]
+    fn app_ready_only_with_reconciled_image() {
+        let mapper = KubernetesMapper::default();
+        let mut app = App::new(
+            "my-app",
+            AppSpec {
+                image: Some("registry/my-app:2".to_string()),
+                ..Default::default()
+            },
+        );
+        app.metadata.namespace = Some("paastel-space".to_string());
+        app.metadata.generation = Some(2);
+        app.status = Some(crate::app::AppStatus {
+            image: Some("registry/my-app:1".to_string()),
+            observed_generation: Some(1),
+            ready_replicas: 1,
+            url: Some("http://my-app.paastel-space.paastel.local".to_string()),
+            conditions: vec![crate::app::AppCondition {
+                type_: CONDITION_READY.to_string(),
+                status: "True".to_string(),
+                ..Default::default()
+            }],
+        });
+
+        let application = mapper.app_to_domain(&app).unwrap();
+        assert_eq!(application.image(), Some("registry/my-app:2"));
+        assert!(!application.status().is_ready());
+
+        if let Some(status) = app.status.as_mut() {
+            status.image = app.spec.image.clone();
+        }
+        let application = mapper.app_to_domain(&app).unwrap();
+        assert!(!application.status().is_ready());
+
+        if let Some(status) = app.status.as_mut() {
+            status.observed_generation = app.metadata.generation;
+        }
+        let application = mapper.app_to_domain(&app).unwrap();
+        assert!(application.status().is_ready());
+        assert_eq!(
+            application.status().url(),
+            Some("http://my-app.paastel-space.paastel.local")
+        );
+    }
+
+    fn domain_stage(builder: Builder) -> paastel_app::Stage {
+        paastel_app::Stage::new(
+            StageId::generate(),
+            "my-app".parse().unwrap(),
+            "paastel-space".parse().unwrap(),
+            paastel_app::BlobId::generate(),
+            builder,
+            "registry/paastel-space/my-app:1".to_string(),
+        )
+    }
+
+    #[test]
+    fn stage_round_trip() {
+        let mapper = KubernetesMapper::default();
+        let stage = domain_stage(Builder::Dockerfile)
+            .with_phase(StagePhase::Failed, Some("BackoffLimitExceeded".into()))
+            .with_logs(paastel_app::BlobId::generate());
+
+        let resource = mapper.stage_to_resource(&stage);
+        assert_eq!(mapper.resource_to_stage(&resource), Some(stage));
+    }
+
+    #[test]
+    fn stage_job_local_blob() {
+        let mapper = KubernetesMapper::default();
+        let stage = domain_stage(Builder::Buildpack);
+
+        let job =
+            mapper.stage_to_job(&stage, "file:///var/lib/paastel/blob", None);
+        let pod = job.spec.unwrap().template.spec.unwrap();
+
+        assert_eq!(pod.containers[0].name, "build");
+        assert_eq!(
+            pod.containers[0].command.as_ref().unwrap().last().unwrap(),
+            stage.image()
+        );
+        let host_path = pod.volumes.unwrap()[1].host_path.clone().unwrap();
+        assert_eq!(host_path.path, "/var/lib/paastel");
+        assert_eq!(pod.node_name, None);
+
+        let mapper = mapper.with_blob_node("node-0");
+        let job =
+            mapper.stage_to_job(&stage, "file:///var/lib/paastel/blob", None);
+        let pod = job.spec.unwrap().template.spec.unwrap();
+        assert_eq!(pod.node_name.as_deref(), Some("node-0"));
+
+        let job = mapper.stage_to_job(&stage, "http://blobs/blob", None);
+        let pod = job.spec.unwrap().template.spec.unwrap();
+        assert_eq!(pod.node_name, None);
+    }
+
+    #[test]
+    fn stage_missing_job() {
+        let mapper = KubernetesMapper::default();
+        let now = Utc::now();
+        let mut resource =
+            mapper.stage_to_resource(&domain_stage(Builder::Buildpack));
+        resource.metadata.creation_timestamp = Some(Time(now));
+
+        assert_eq!(
+            mapper.missing_job_phase(&resource, now),
+            (StagePhase::Pending, None)
+        );
+        let later = now + k8s_openapi::chrono::Duration::seconds(60);
+        assert_eq!(
+            mapper.missing_job_phase(&resource, later).0,
+            StagePhase::Failed
+        );
+    }
+
+    #[test]
+    fn stage_job_unpack_format() {
+        let mapper = KubernetesMapper::default();
+        let stage =
+            domain_stage(Builder::Buildpack).with_format(ArchiveFormat::TarZst);
+
+        let resource = mapper.stage_to_resource(&stage);
+        assert_eq!(resource.spec.format, "tar.zst");
+        assert_eq!(mapper.resource_to_stage(&resource), Some(stage.clone()));
+
+        let job = mapper.stage_to_job(&stage, "http://blobs/blob", None);
+        let pod = job.spec.unwrap().template.spec.unwrap();
+        let init = pod.init_containers.unwrap();
+        assert_eq!(
+            init[0].args.as_ref().unwrap()[2],
+            "/workspace/source.tar.zst"
+        );
+        assert_eq!(init[1].image.as_deref(), Some(DEFAULT_UNPACK_IMAGE));
+        let command = &init[1].command.as_ref().unwrap()[2];
+        assert!(command.contains("zstd -dc /workspace/source.tar.zst"));
+        assert!(!command.contains("apk"));
+
+        let mapper = mapper.with_unpack_image("registry/unpack:1");
+        let job = mapper.stage_to_job(&stage, "http://blobs/blob", None);
+        let pod = job.spec.unwrap().template.spec.unwrap();
+        let init = pod.init_containers.unwrap();
+        assert_eq!(init[1].image.as_deref(), Some("registry/unpack:1"));
+    }
+
+    #[test]
+    fn stage_format_defaults_to_zip() {
+        let spec: StageSpec = serde_json::from_value(serde_json::json!({
+            "app": "my-app",
+            "blob_id": "blob",
+            "builder": "buildpack",
+            "image": "registry/my-app:1",
+        }))
+        .unwrap();
+        assert_eq!(spec.format, "zip");
+    }
+
+    #[test]
+    fn stage_job_phase() {
+        use k8s_openapi::api::batch::v1::{JobCondition, JobStatus};
+
+        let mapper = KubernetesMapper::default();
+        let mut job = Job::default();
+        assert_eq!(mapper.job_phase(&job), (StagePhase::Pending, None));
+
+        job.status = Some(JobStatus {
+            active: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(mapper.job_phase(&job), (StagePhase::Running, None));
+
+        job.status = Some(JobStatus {
+            conditions: Some(vec![JobCondition {
+                type_: "Failed".to_string(),
+                status: "True".to_string(),
+                reason: Some("BackoffLimitExceeded".to_string()),
+                message: Some(
+                    "Job has reached the specified backoff limit".to_string(),
+                ),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        });
+        assert_eq!(
+            mapper.job_phase(&job),
+            (
+                StagePhase::Failed,
+                Some("BackoffLimitExceeded: Job has reached the specified backoff limit".to_string())
+            )
+        );
     }
 }
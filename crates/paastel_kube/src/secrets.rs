@@ -15,7 +15,11 @@
 use std::result::Result as StdResult;
 
 use k8s_openapi::api::core::v1::Secret;
-use kube::{api::ListParams, core::ObjectList, Api, Error as KError};
+use kube::{
+    api::{DeleteParams, ListParams, Patch, PatchParams, PostParams},
+    core::ObjectList,
+    Api, Error as KError,
+};
 
 use crate::client::KubernetesClient;
 
@@ -38,6 +42,28 @@ impl KubernetsSecretsAdapter {
         &self,
         list_params: &ListParams,
     ) -> StdResult<ObjectList<Secret>, KError> {
-        self.api.list(&list_params).await
+        self.api.list(list_params).await
+    }
+
+    pub(crate) async fn create(
+        &self,
+        secret: &Secret,
+    ) -> StdResult<Secret, KError> {
+        self.api.create(&PostParams::default(), secret).await
+    }
+
+    pub(crate) async fn patch(
+        &self,
+        name: &str,
+        secret: &Secret,
+    ) -> StdResult<Secret, KError> {
+        self.api
+            .patch(name, &PatchParams::default(), &Patch::Merge(secret))
+            .await
+    }
+
+    pub(crate) async fn delete(&self, name: &str) -> StdResult<(), KError> {
+        self.api.delete(name, &DeleteParams::default()).await?;
+        Ok(())
     }
 }
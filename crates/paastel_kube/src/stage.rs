@@ -0,0 +1,201 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{collections::BTreeMap, result::Result as StdResult};
+
+use k8s_openapi::api::{batch::v1::Job, core::v1::Pod};
+use kube::{
+    api::{
+        DeleteParams, ListParams, LogParams, Patch, PatchParams, PostParams,
+    },
+    Api, CustomResource, Error as KError,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::client::KubernetesClient;
+
+/// Name of Stage custom resource definition
+pub const STAGE_CRD_NAME: &str = "stages.application.paastel.io";
+
+/// Build of uploaded sources, status is filled from build job
+#[derive(
+    CustomResource, Clone, Debug, Default, Deserialize, Serialize, JsonSchema,
+)]
+#[kube(
+    group = "application.paastel.io",
+    version = "v1",
+    kind = "Stage",
+    namespaced,
+    status = "StageStatus"
+)]
+pub struct StageSpec {
+    /// Name of application
+    pub app: String,
+    /// Blob with source archive
+    pub blob_id: String,
+    /// `buildpack` or `dockerfile`
+    pub builder: String,
+    /// Image reference pushed by build
+    pub image: String,
+    /// `zip`, `tar.gz` or `tar.zst`, stages created before formats are zip
+    #[serde(default = "default_format")]
+    pub format: String,
+}
+
+fn default_format() -> String {
+    paastel_app::ArchiveFormat::Zip.to_string()
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+pub struct StageStatus {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phase: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Blob with logs of build
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logs: Option<String>,
+}
+
+/// Reads and writes stages and their build jobs.
+#[derive(Clone)]
+pub(crate) struct KubernetesStagesAdapter {
+    client: KubernetesClient,
+}
+
+impl KubernetesStagesAdapter {
+    pub fn new(client: &KubernetesClient) -> Self {
+        Self {
+            client: client.clone(),
+        }
+    }
+
+    fn stages(&self, namespace: &str) -> Api<Stage> {
+        Api::namespaced(self.client.as_ref().clone(), namespace)
+    }
+
+    fn jobs(&self, namespace: &str) -> Api<Job> {
+        Api::namespaced(self.client.as_ref().clone(), namespace)
+    }
+
+    fn pods(&self, namespace: &str) -> Api<Pod> {
+        Api::namespaced(self.client.as_ref().clone(), namespace)
+    }
+}
+
+impl KubernetesStagesAdapter {
+    pub(crate) async fn create(
+        &self,
+        namespace: &str,
+        stage: &Stage,
+    ) -> StdResult<Stage, KError> {
+        self.stages(namespace)
+            .create(&PostParams::default(), stage)
+            .await
+    }
+
+    pub(crate) async fn get_opt(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> StdResult<Option<Stage>, KError> {
+        self.stages(namespace).get_opt(name).await
+    }
+
+    pub(crate) async fn delete(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> StdResult<(), KError> {
+        self.stages(namespace)
+            .delete(name, &DeleteParams::default())
+            .await?;
+        Ok(())
+    }
+
+    pub(crate) async fn patch_status(
+        &self,
+        namespace: &str,
+        name: &str,
+        status: &StageStatus,
+    ) -> StdResult<Stage, KError> {
+        let patch = serde_json::json!({ "status": status });
+        self.stages(namespace)
+            .patch_status(name, &PatchParams::default(), &Patch::Merge(patch))
+            .await
+    }
+
+    pub(crate) async fn create_job(
+        &self,
+        namespace: &str,
+        job: &Job,
+    ) -> StdResult<Job, KError> {
+        self.jobs(namespace)
+            .create(&PostParams::default(), job)
+            .await
+    }
+
+    pub(crate) async fn get_job_opt(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> StdResult<Option<Job>, KError> {
+        self.jobs(namespace).get_opt(name).await
+    }
+
+    /// Logs of every container of job pods, init containers first
+    pub(crate) async fn job_logs(
+        &self,
+        namespace: &str,
+        job_name: &str,
+    ) -> StdResult<String, KError> {
+        let pods = self.pods(namespace);
+        let lp = ListParams::default().labels(&format!("job-name={job_name}"));
+
+        let mut logs = BTreeMap::new();
+        for pod in pods.list(&lp).await? {
+            let Some(pod_name) = pod.metadata.name.as_deref() else {
+                continue;
+            };
+            let containers = container_names(&pod);
+            let mut pod_logs = String::new();
+            for container in containers {
+                let lp = LogParams {
+                    container: Some(container.clone()),
+                    ..Default::default()
+                };
+                // NOTE: containers not started yet have no logs
+                let content =
+                    pods.logs(pod_name, &lp).await.unwrap_or_default();
+                pod_logs.push_str(&format!("==> {container} <==\n{content}\n"));
+            }
+            logs.insert(pod_name.to_string(), pod_logs);
+        }
+        Ok(logs.into_values().collect())
+    }
+}
+
+/// Names of init containers followed by containers
+fn container_names(pod: &Pod) -> Vec<String> {
+    let Some(spec) = pod.spec.as_ref() else {
+        return Vec::new();
+    };
+    spec.init_containers
+        .iter()
+        .flatten()
+        .chain(spec.containers.iter())
+        .map(|container| container.name.clone())
+        .collect()
+}
@@ -0,0 +1,42 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("io error {0}")]
+    Io(#[from] std::io::Error),
+    #[error("parse error {0}")]
+    Parse(String),
+    #[error("unsupported manifest version {0}, supported {1}")]
+    UnsupportedVersion(u32, u32),
+    #[error("invalid `{field}` {reason}")]
+    Invalid { field: String, reason: String },
+}
+
+impl Error {
+    pub(crate) fn invalid(field: impl Into<String>, reason: &str) -> Self {
+        Self::Invalid {
+            field: field.into(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(value: serde_yaml::Error) -> Self {
+        Self::Parse(value.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,379 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{collections::BTreeMap, fmt::Display, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// File name of manifest on application directory
+pub const MANIFEST_FILE: &str = "paastel.yml";
+
+/// Version of manifest schema written by this release
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Maximum length of a kubernetes object name (DNS-1123 label)
+const MAX_NAME_LENGTH: usize = 63;
+
+/// Maximum length of a host name (DNS-1123 subdomain)
+const MAX_HOST_LENGTH: usize = 253;
+
+/// Check value is a DNS-1123 label: lowercase alphanumeric or `-`, starting
+/// and ending with alphanumeric, ex: name of application or namespace
+pub fn check_dns_label(field: &str, value: &str) -> crate::Result<()> {
+    if value.is_empty() {
+        return Err(Error::invalid(field, "must not be empty"));
+    }
+
+    if value.len() > MAX_NAME_LENGTH {
+        return Err(Error::invalid(field, "must be at most 63 characters"));
+    }
+
+    let valid_chars = value
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    let valid_edges = !value.starts_with('-') && !value.ends_with('-');
+    if !valid_chars || !valid_edges {
+        return Err(Error::invalid(
+            field,
+            "must consist of lower case alphanumeric characters or '-', and must start and end with an alphanumeric character",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check value is a host name, dot separated DNS-1123 labels
+fn check_host(field: &str, value: &str) -> crate::Result<()> {
+    if value.len() > MAX_HOST_LENGTH {
+        return Err(Error::invalid(field, "must be at most 253 characters"));
+    }
+    value
+        .split('.')
+        .try_for_each(|label| check_dns_label(field, label))
+}
+
+/// Check value is an environment variable name, ex: `DATABASE_URL`
+fn check_env_name(field: &str, value: &str) -> crate::Result<()> {
+    let valid = value
+        .starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(Error::invalid(
+            field,
+            "must consist of alphanumeric characters or '_', and must not start with a digit",
+        ));
+    }
+    Ok(())
+}
+
+/// Builder of application image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Builder {
+    Buildpack,
+    Dockerfile,
+}
+
+impl AsRef<str> for Builder {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Buildpack => "buildpack",
+            Self::Dockerfile => "dockerfile",
+        }
+    }
+}
+
+impl Display for Builder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+/// Only version is read before choosing schema of manifest
+#[derive(Deserialize)]
+struct Versioned {
+    version: Option<u32>,
+}
+
+/// # Manifest
+///
+/// Declare how an application is built and run, usually `paastel.yml` on
+/// application directory
+///
+/// ```yaml
+/// version: 1
+/// name: my-app
+/// builder: dockerfile
+/// instances: 2
+/// env:
+///   RUST_LOG: info
+/// routes:
+///   - my-app.example.com
+/// configurations:
+///   - my-database
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    version: u32,
+    name: String,
+    /// Namespace of application, default namespace of settings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+    /// Builder of image, default `buildpack`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    builder: Option<Builder>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+    /// Number of replicas, default 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    instances: Option<i32>,
+    /// Hosts routed to application
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    routes: Vec<String>,
+    /// Configurations bound to application, mounted on
+    /// `/configurations/<name>`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    configurations: Vec<String>,
+}
+
+impl Manifest {
+    /// Manifest with only name, on current version
+    pub fn new(name: impl Into<String>) -> crate::Result<Self> {
+        let manifest = Self {
+            version: MANIFEST_VERSION,
+            name: name.into(),
+            namespace: None,
+            builder: None,
+            env: BTreeMap::new(),
+            instances: None,
+            routes: Vec::new(),
+            configurations: Vec::new(),
+        };
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// Loads and validate manifest from file
+    pub fn from_path<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// Serialize manifest as YAML
+    pub fn to_yaml(&self) -> crate::Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    pub fn builder(&self) -> Option<Builder> {
+        self.builder
+    }
+
+    pub fn env(&self) -> &BTreeMap<String, String> {
+        &self.env
+    }
+
+    pub fn instances(&self) -> Option<i32> {
+        self.instances
+    }
+
+    pub fn routes(&self) -> &[String] {
+        &self.routes
+    }
+
+    pub fn configurations(&self) -> &[String] {
+        &self.configurations
+    }
+
+    /// Replace name, ex: name given on command line
+    pub fn with_name(mut self, name: impl Into<String>) -> crate::Result<Self> {
+        self.name = name.into();
+        check_dns_label("name", &self.name)?;
+        Ok(self)
+    }
+
+    /// Replace builder, ex: builder given on command line
+    pub fn with_builder(mut self, builder: Builder) -> Self {
+        self.builder = Some(builder);
+        self
+    }
+
+    /// Check every field, error points to the offending field
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.version != MANIFEST_VERSION {
+            return Err(Error::UnsupportedVersion(
+                self.version,
+                MANIFEST_VERSION,
+            ));
+        }
+        check_dns_label("name", &self.name)?;
+        if let Some(namespace) = self.namespace.as_deref() {
+            check_dns_label("namespace", namespace)?;
+        }
+        for name in self.env.keys() {
+            check_env_name(&format!("env.{name}"), name)?;
+        }
+        if self.instances.is_some_and(|instances| instances < 0) {
+            return Err(Error::invalid("instances", "must not be negative"));
+        }
+        for (i, route) in self.routes.iter().enumerate() {
+            check_host(&format!("routes[{i}]"), route)?;
+        }
+        for (i, configuration) in self.configurations.iter().enumerate() {
+            let field = format!("configurations[{i}]");
+            check_dns_label(&field, configuration)?;
+            if self.configurations[..i].contains(configuration) {
+                return Err(Error::invalid(field, "is duplicated"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Manifest {
+    type Err = Error;
+
+    /// Parse manifest in YAML (or JSON) and validate it
+    fn from_str(value: &str) -> crate::Result<Self> {
+        let Versioned { version } = serde_yaml::from_str(value)?;
+        match version {
+            Some(MANIFEST_VERSION) => {
+                let manifest: Manifest = serde_yaml::from_str(value)?;
+                manifest.validate()?;
+                Ok(manifest)
+            }
+            Some(version) => {
+                Err(Error::UnsupportedVersion(version, MANIFEST_VERSION))
+            }
+            None => Err(Error::invalid("version", "is required")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const FULL: &str = r#"
+version: 1
+name: my-app
+namespace: team-a
+builder: dockerfile
+instances: 2
+env:
+  RUST_LOG: info
+  DATABASE_URL: postgres://db
+routes:
+  - my-app.example.com
+configurations:
+  - my-database
+"#;
+
+    #[test]
+    fn parse_full() {
+        let manifest: Manifest = FULL.parse().unwrap();
+        assert_eq!(manifest.name(), "my-app");
+        assert_eq!(manifest.namespace(), Some("team-a"));
+        assert_eq!(manifest.builder(), Some(Builder::Dockerfile));
+        assert_eq!(manifest.instances(), Some(2));
+        assert_eq!(manifest.env()["RUST_LOG"], "info");
+        assert_eq!(manifest.routes(), ["my-app.example.com"]);
+        assert_eq!(manifest.configurations(), ["my-database"]);
+    }
+
+    #[test]
+    fn parse_minimal() {
+        let manifest: Manifest = "version: 1\nname: my-app".parse().unwrap();
+        assert_eq!(manifest, Manifest::new("my-app").unwrap());
+    }
+
+    #[test]
+    fn yaml_round_trip() {
+        let manifest: Manifest = FULL.parse().unwrap();
+        let parsed: Manifest = manifest.to_yaml().unwrap().parse().unwrap();
+        assert_eq!(parsed, manifest);
+    }
+
+    #[test]
+    fn version_required_and_supported() {
+        let result = "name: my-app".parse::<Manifest>();
+        assert!(
+            matches!(result, Err(Error::Invalid { field, .. }) if field == "version")
+        );
+
+        let result = "version: 2\nname: my-app".parse::<Manifest>();
+        assert!(matches!(result, Err(Error::UnsupportedVersion(2, 1))));
+    }
+
+    #[test]
+    fn unknown_field() {
+        let result =
+            "version: 1\nname: my-app\nimage: nginx".parse::<Manifest>();
+        assert!(matches!(result, Err(Error::Parse(_))));
+    }
+
+    fn invalid_field(manifest: &str) -> String {
+        match manifest.parse::<Manifest>() {
+            Err(Error::Invalid { field, .. }) => field,
+            result => panic!("expected invalid field, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn invalid_fields() {
+        assert_eq!(invalid_field("version: 1\nname: My_App"), "name");
+        assert_eq!(
+            invalid_field("version: 1\nname: app\nnamespace: -team"),
+            "namespace"
+        );
+        assert_eq!(
+            invalid_field("version: 1\nname: app\nenv:\n  1VAR: x"),
+            "env.1VAR"
+        );
+        assert_eq!(
+            invalid_field("version: 1\nname: app\ninstances: -1"),
+            "instances"
+        );
+        assert_eq!(
+            invalid_field("version: 1\nname: app\nroutes: [ok.com, bad..com]"),
+            "routes[1]"
+        );
+        assert_eq!(
+            invalid_field("version: 1\nname: app\nconfigurations: [db, db]"),
+            "configurations[1]"
+        );
+    }
+
+    #[test]
+    fn with_name_validated() {
+        let manifest = Manifest::new("my-app").unwrap();
+        assert!(manifest.clone().with_name("other").is_ok());
+        assert!(manifest.with_name("Other").is_err());
+    }
+}
@@ -12,38 +12,149 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use paastel_auth::AuthApplication;
-use paastel_hash::Argon2Adapter;
-use paastel_kube::client::KubernetesClient;
+use paastel_app::{AppApplication, ArcExpireUploadsUseCase};
+use paastel_app::{OutBlobStorePort, OutUploadStorePort};
+use paastel_auth::{AuthApplication, SecretLabel};
+use paastel_hash::{Argon2Adapter, Hs256Adapter};
 use paastel_kube::KubernetesAdapter;
-use tokio::net::TcpListener;
+use paastel_kube::{
+    cache::SecretsCache,
+    client::KubernetesClient,
+    controller::{self, ControllerConfig},
+};
+use paastel_storage::{
+    archive::ArchiveAdapter, local::LocalAdapter, s3::S3Adapter,
+};
 
+use crate::config::ServerConfig;
+use crate::error::Error;
 use crate::router;
 use crate::state::AppState;
+use crate::tls::{self, TlsReloader};
 use crate::utils;
 
-pub(crate) async fn start_main_server() {
+/// Longest delay between lookups of abandoned uploads
+const EXPIRE_UPLOADS_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub(crate) async fn start_main_server(
+    config: &ServerConfig,
+) -> Result<(), Error> {
+    // NOTE: fail on bad certificates before touching the cluster
+    let tls_reloader = config.tls().map(TlsReloader::new).transpose()?;
     let hash_port = Argon2Adapter::default();
-    let kube_client = KubernetesClient::new().await.unwrap();
-    let kube_port = KubernetesAdapter::new(&kube_client);
-    let credential =
-        AuthApplication::new(Box::new(kube_port), Box::new(hash_port));
-    // AuthService::new(Box::new(kube_port.clone()), Box::new(hash_port));
-    // let create_app_usecase = AppService::new(Box::new(kube_port));
-    let app_state = AppState::new(Arc::new(credential));
-    let app = router::make_app(app_state.clone());
-
-    let listener = TcpListener::bind("0.0.0.0:3000").await.unwrap();
-
-    tracing::info!(
-        "listening rest server on {}",
-        listener.local_addr().unwrap()
+    let signing_port = match config.token_secret() {
+        Some(secret) => {
+            Hs256Adapter::new(secret).map_err(Error::TokenSecret)?
+        }
+        None => {
+            tracing::warn!(
+                "token secret not set, tokens will not survive restart"
+            );
+            Hs256Adapter::default()
+        }
+    };
+    let kube_client = match config.namespace() {
+        Some(namespace) => KubernetesClient::with_namespace(namespace).await?,
+        None => KubernetesClient::new().await?,
+    };
+    paastel_kube::crd::install_crds(&kube_client).await?;
+    let controller_config = match config.ingress_class() {
+        Some(ingress_class) => {
+            ControllerConfig::new(config.domain().to_string())
+                .with_ingress_class(ingress_class)
+        }
+        None => ControllerConfig::new(config.domain().to_string()),
+    };
+    let secrets_cache =
+        SecretsCache::spawn(&kube_client, &SecretLabel::default());
+    let mut kube_port = KubernetesAdapter::new(&kube_client)
+        .with_secrets_cache(secrets_cache.clone())
+        .with_unpack_image(config.unpack_image());
+    // NOTE: uploads are kept on same storage of blobs
+    let (blob_store_port, upload_store_port): (
+        OutBlobStorePort,
+        OutUploadStorePort,
+    ) = match config.storage_dir() {
+        Some(dir) => {
+            let local = LocalAdapter::new(dir).await.map_err(|e| {
+                Error::Storage(format!("{} {e}", dir.display()))
+            })?;
+            match config.node_name() {
+                Some(node) => kube_port = kube_port.with_blob_node(node),
+                None => tracing::warn!(
+                    "node name not set, build jobs reading local storage may \
+                    run on other nodes"
+                ),
+            }
+            (Box::new(local.clone()), Box::new(local))
+        }
+        None => {
+            let s3 = S3Adapter::from_env(config.storage_bucket()).await;
+            (Box::new(s3.clone()), Box::new(s3))
+        }
+    };
+    let application = AppApplication::new(
+        Box::new(kube_port.clone()),
+        blob_store_port,
+        Box::new(ArchiveAdapter::default()),
+        upload_store_port,
+        config.registry().to_string(),
+    );
+    tokio::spawn(controller::run(
+        kube_client.clone(),
+        controller_config,
+        application.show_stage.clone(),
+    ));
+    tokio::spawn(expire_uploads(
+        application.expire_uploads.clone(),
+        config.upload_ttl(),
+    ));
+    let credential = AuthApplication::new(
+        Box::new(kube_port),
+        Box::new(hash_port),
+        Box::new(signing_port),
+    );
+    let app_state = AppState::new(
+        Arc::new(credential),
+        Arc::new(application),
+        Some(secrets_cache),
     );
+    let app = router::make_app(app_state.clone(), config);
+
+    let listener = utils::bind(config.listen()).await?;
+
+    match tls_reloader {
+        Some(reloader) => {
+            tracing::info!(
+                "listening rest server on https://{}",
+                config.listen()
+            );
+            tls::serve(listener, app, reloader).await?;
+        }
+        None => {
+            tracing::info!(
+                "listening rest server on http://{}",
+                config.listen()
+            );
+            axum::serve(listener, app)
+                .with_graceful_shutdown(utils::shutdown_signal())
+                .await
+                .map_err(Error::Serve)?;
+        }
+    }
+    Ok(())
+}
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(utils::shutdown_signal())
-        .await
-        .unwrap();
+/// Delete uploads without parts received within ttl, every replica runs it
+/// as a delete of an expired upload is idempotent
+async fn expire_uploads(expire: ArcExpireUploadsUseCase, ttl: Duration) {
+    let mut interval = tokio::time::interval(ttl.min(EXPIRE_UPLOADS_INTERVAL));
+    loop {
+        interval.tick().await;
+        if let Err(e) = expire.expire_uploads(ttl).await {
+            tracing::warn!("failed expire uploads {e}");
+        }
+    }
 }
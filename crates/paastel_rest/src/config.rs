@@ -0,0 +1,381 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use config::{Config, Environment, File, FileFormat};
+use serde::Deserialize;
+
+use crate::error::Error;
+
+/// Path of server configuration file, optional
+pub const CONFIG_ENV: &str = "PAASTEL_CONFIG";
+
+/// Default bucket of uploaded sources
+const DEFAULT_STORAGE_BUCKET: &str = "paastel";
+
+/// Default domain of application routes
+const DEFAULT_DOMAIN: &str = "paastel.local";
+
+/// Default limit of request bodies, large enough for source archives
+const DEFAULT_BODY_LIMIT: usize = 128 * 1024 * 1024; // 128 MB
+
+/// Default seconds a request may take before `408 Request Timeout`
+const DEFAULT_REQUEST_TIMEOUT: u64 = 20;
+
+/// Default seconds an upload of sources may take
+const DEFAULT_TRANSFER_TIMEOUT: u64 = 30 * 60; // 30 minutes
+
+/// Default seconds an upload is kept without any part received
+const DEFAULT_UPLOAD_TTL: u64 = 24 * 60 * 60; // 24 hours
+
+/// Certificate chain and private key of HTTPS listener, both PEM, files
+/// are reloaded when changed
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsConfig {
+    cert: PathBuf,
+    key: PathBuf,
+    /// CA bundle of client certificates, enables mutual TLS where common
+    /// name of certificate subject is the username
+    client_ca: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    pub fn new(cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        Self {
+            cert: cert.into(),
+            key: key.into(),
+            client_ca: None,
+        }
+    }
+
+    pub fn with_client_ca(mut self, client_ca: impl Into<PathBuf>) -> Self {
+        self.client_ca = Some(client_ca.into());
+        self
+    }
+
+    pub fn client_ca(&self) -> Option<&Path> {
+        self.client_ca.as_deref()
+    }
+
+    pub fn cert(&self) -> &Path {
+        &self.cert
+    }
+
+    pub fn key(&self) -> &Path {
+        &self.key
+    }
+}
+
+/// Configuration of rest server, read from an optional toml file at
+/// `PAASTEL_CONFIG` and overridden by `PAASTEL_*` environment variables,
+/// ex: `PAASTEL_LISTEN=0.0.0.0:8080` or `PAASTEL_TLS__CERT=/tls/tls.crt`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Address of rest api
+    listen: SocketAddr,
+    /// Address of prometheus metrics, private by default
+    metrics_listen: SocketAddr,
+    /// Serve HTTPS instead of HTTP when set
+    tls: Option<TlsConfig>,
+    /// Namespace of user secrets, from kube config when missing
+    namespace: Option<String>,
+    /// Largest request body in bytes
+    body_limit: usize,
+    /// Seconds a request may take
+    request_timeout: u64,
+    /// Seconds an upload of sources or of an upload part may take,
+    /// replaces `request_timeout` on those routes
+    transfer_timeout: u64,
+    /// Seconds an abandoned upload is kept before its parts are deleted
+    upload_ttl: u64,
+    /// Shared secret used to sign access and refresh tokens
+    token_secret: Option<String>,
+    /// Local directory where uploaded sources are stored, replaces S3 when
+    /// set, build jobs read it from node of server
+    storage_dir: Option<PathBuf>,
+    /// Node running server, from downward api `spec.nodeName`, build jobs
+    /// reading `storage_dir` are scheduled on it
+    node_name: Option<String>,
+    /// Bucket where uploaded sources are stored
+    storage_bucket: String,
+    /// Registry where staged images are pushed
+    registry: String,
+    /// Image of build jobs unpacking sources, with `unzip`, `tar` and
+    /// `zstd`
+    unpack_image: String,
+    /// Domain of default application routes
+    domain: String,
+    /// Ingress class of application routes
+    ingress_class: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            listen: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            metrics_listen: SocketAddr::from(([127, 0, 0, 1], 3001)),
+            tls: None,
+            namespace: None,
+            body_limit: DEFAULT_BODY_LIMIT,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            transfer_timeout: DEFAULT_TRANSFER_TIMEOUT,
+            upload_ttl: DEFAULT_UPLOAD_TTL,
+            token_secret: None,
+            storage_dir: None,
+            node_name: None,
+            storage_bucket: DEFAULT_STORAGE_BUCKET.to_string(),
+            registry: paastel_app::DEFAULT_REGISTRY.to_string(),
+            unpack_image: paastel_kube::mapper::DEFAULT_UNPACK_IMAGE
+                .to_string(),
+            domain: DEFAULT_DOMAIN.to_string(),
+            ingress_class: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Loads configuration from file at `PAASTEL_CONFIG`, when set, and
+    /// environment
+    pub fn from_env() -> Result<Self, Error> {
+        let path = std::env::var_os(CONFIG_ENV).map(PathBuf::from);
+        Self::load(path.as_deref(), None)
+    }
+
+    /// Loads configuration from file at `path` and environment, `vars`
+    /// replaces process environment
+    pub fn load(
+        path: Option<&Path>,
+        vars: Option<config::Map<String, String>>,
+    ) -> Result<Self, Error> {
+        let mut builder = Config::builder();
+        if let Some(path) = path {
+            builder = builder.add_source(
+                File::from(path).format(FileFormat::Toml).required(true),
+            );
+        }
+        let config: Self = builder
+            .add_source(
+                Environment::with_prefix("paastel")
+                    .prefix_separator("_")
+                    .separator("__")
+                    .source(vars),
+            )
+            .build()?
+            .try_deserialize()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.listen == self.metrics_listen {
+            return Err(Error::invalid(
+                "metrics_listen",
+                "must differ from listen",
+            ));
+        }
+        if let Some(namespace) = &self.namespace {
+            if paastel_manifest::check_dns_label("namespace", namespace)
+                .is_err()
+            {
+                return Err(Error::invalid(
+                    "namespace",
+                    "must be lowercase alphanumeric or `-`, at most 63 \
+                    characters",
+                ));
+            }
+        }
+        if self.body_limit == 0 {
+            return Err(Error::invalid("body_limit", "must be positive"));
+        }
+        if self.request_timeout == 0 {
+            return Err(Error::invalid("request_timeout", "must be positive"));
+        }
+        if self.transfer_timeout == 0 {
+            return Err(Error::invalid("transfer_timeout", "must be positive"));
+        }
+        if self.upload_ttl == 0 {
+            return Err(Error::invalid("upload_ttl", "must be positive"));
+        }
+        Ok(())
+    }
+
+    pub fn listen(&self) -> SocketAddr {
+        self.listen
+    }
+
+    pub fn metrics_listen(&self) -> SocketAddr {
+        self.metrics_listen
+    }
+
+    pub fn tls(&self) -> Option<&TlsConfig> {
+        self.tls.as_ref()
+    }
+
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    pub fn body_limit(&self) -> usize {
+        self.body_limit
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout)
+    }
+
+    pub fn transfer_timeout(&self) -> Duration {
+        Duration::from_secs(self.transfer_timeout)
+    }
+
+    pub fn upload_ttl(&self) -> Duration {
+        Duration::from_secs(self.upload_ttl)
+    }
+
+    pub fn token_secret(&self) -> Option<&str> {
+        self.token_secret.as_deref()
+    }
+
+    pub fn storage_dir(&self) -> Option<&Path> {
+        self.storage_dir.as_deref()
+    }
+
+    pub fn node_name(&self) -> Option<&str> {
+        self.node_name.as_deref()
+    }
+
+    pub fn storage_bucket(&self) -> &str {
+        &self.storage_bucket
+    }
+
+    pub fn registry(&self) -> &str {
+        &self.registry
+    }
+
+    pub fn unpack_image(&self) -> &str {
+        &self.unpack_image
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn ingress_class(&self) -> Option<&str> {
+        self.ingress_class.as_deref()
+    }
+
+    pub fn with_listen(mut self, listen: SocketAddr) -> Self {
+        self.listen = listen;
+        self
+    }
+
+    pub fn with_metrics_listen(mut self, metrics_listen: SocketAddr) -> Self {
+        self.metrics_listen = metrics_listen;
+        self
+    }
+
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> config::Map<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn defaults_without_file() {
+        let config = ServerConfig::load(None, Some(vars(&[]))).unwrap();
+        assert_eq!(config.listen(), "0.0.0.0:3000".parse().unwrap());
+        assert_eq!(config.metrics_listen(), "127.0.0.1:3001".parse().unwrap());
+        assert!(config.tls().is_none());
+        assert_eq!(config.body_limit(), DEFAULT_BODY_LIMIT);
+        assert_eq!(config.request_timeout(), Duration::from_secs(20));
+        assert_eq!(config.transfer_timeout(), Duration::from_secs(1800));
+        assert_eq!(config.upload_ttl(), Duration::from_secs(86400));
+        assert_eq!(config.storage_bucket(), DEFAULT_STORAGE_BUCKET);
+    }
+
+    #[test]
+    fn env_overrides_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.toml");
+        std::fs::write(
+            &path,
+            "listen = \"127.0.0.1:8080\"\n\
+            namespace = \"paastel\"\n\
+            request_timeout = 60\n\
+            [tls]\n\
+            cert = \"/tls/tls.crt\"\n\
+            key = \"/tls/tls.key\"\n",
+        )
+        .unwrap();
+
+        let config = ServerConfig::load(
+            Some(&path),
+            Some(vars(&[
+                ("PAASTEL_LISTEN", "127.0.0.1:8443"),
+                ("PAASTEL_BODY_LIMIT", "1024"),
+                ("PAASTEL_TRANSFER_TIMEOUT", "600"),
+                ("PAASTEL_NODE_NAME", "node-0"),
+                ("PAASTEL_TOKEN_SECRET", "secret"),
+                ("PAASTEL_TLS__CLIENT_CA", "/tls/ca.crt"),
+            ])),
+        )
+        .unwrap();
+        assert_eq!(config.listen(), "127.0.0.1:8443".parse().unwrap());
+        assert_eq!(config.namespace(), Some("paastel"));
+        assert_eq!(config.body_limit(), 1024);
+        assert_eq!(config.request_timeout(), Duration::from_secs(60));
+        assert_eq!(config.transfer_timeout(), Duration::from_secs(600));
+        assert_eq!(config.node_name(), Some("node-0"));
+        assert_eq!(config.token_secret(), Some("secret"));
+        let tls = config.tls().unwrap();
+        assert_eq!(tls.cert(), Path::new("/tls/tls.crt"));
+        assert_eq!(tls.key(), Path::new("/tls/tls.key"));
+        assert_eq!(tls.client_ca(), Some(Path::new("/tls/ca.crt")));
+    }
+
+    #[test]
+    fn invalid_key_reported() {
+        let result = ServerConfig::load(
+            None,
+            Some(vars(&[("PAASTEL_NAMESPACE", "Not_A_Label")])),
+        );
+        assert!(matches!(
+            result,
+            Err(Error::InvalidConfig { ref key, .. }) if key == "namespace"
+        ));
+        assert!(matches!(
+            ServerConfig::load(
+                None,
+                Some(vars(&[("PAASTEL_LISTEN", "not an address")]))
+            ),
+            Err(Error::Config(_))
+        ));
+    }
+}
@@ -12,15 +12,244 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-use std::fmt::Display;
+use std::{borrow::Cow, fmt::Display, io, net::SocketAddr, path::PathBuf};
 
-#[derive(Debug)]
-pub enum Error {}
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-impl Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "rest error")
+/// Errors starting rest server, requests are not affected
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("config {0}")]
+    Config(#[from] config::ConfigError),
+    #[error("invalid config key `{key}`: {reason}")]
+    InvalidConfig { key: String, reason: String },
+    #[error("bind {addr} {source}")]
+    Bind { addr: SocketAddr, source: io::Error },
+    #[error("tls {path} {reason}")]
+    Tls { path: PathBuf, reason: String },
+    #[error("token secret {0}")]
+    TokenSecret(paastel_auth::Error),
+    #[error("kubernetes {0}")]
+    Kube(#[from] paastel_kube::error::Error),
+    #[error("storage {0}")]
+    Storage(String),
+    #[error("metrics {0}")]
+    Metrics(String),
+    #[error("serve {0}")]
+    Serve(io::Error),
+}
+
+impl Error {
+    pub(crate) fn invalid(
+        key: impl Into<String>,
+        reason: impl Into<String>,
+    ) -> Self {
+        Self::InvalidConfig {
+            key: key.into(),
+            reason: reason.into(),
+        }
     }
+
+    pub(crate) fn tls(path: impl Into<PathBuf>, reason: impl ToString) -> Self {
+        Self::Tls {
+            path: path.into(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Error of an API request, rendered as RFC 7807 `application/problem+json`
+/// by [`crate::middleware::problem`] which adds request id and path
+#[derive(Debug, Clone)]
+pub(crate) struct ApiError {
+    status: StatusCode,
+    /// Stable identifier of error, clients match on it instead of detail
+    code: Cow<'static, str>,
+    detail: String,
 }
 
-impl std::error::Error for Error {}
+impl ApiError {
+    pub(crate) fn new(
+        status: StatusCode,
+        code: &'static str,
+        detail: impl Into<String>,
+    ) -> Self {
+        Self {
+            status,
+            code: Cow::Borrowed(code),
+            detail: detail.into(),
+        }
+    }
+
+    pub(crate) fn unauthorized(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, "unauthorized", detail)
+    }
+
+    /// Same error for unknown user and wrong password, usernames are not
+    /// disclosed
+    pub(crate) fn invalid_credentials() -> Self {
+        Self::new(
+            StatusCode::UNAUTHORIZED,
+            "invalid_credentials",
+            "invalid username or password",
+        )
+    }
+
+    pub(crate) fn forbidden(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, "forbidden", detail)
+    }
+
+    pub(crate) fn bad_request(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "invalid_request", detail)
+    }
+
+    /// Error of a failure not caused by client, cause is only logged
+    pub(crate) fn internal(code: &'static str, cause: impl Display) -> Self {
+        tracing::error!(code, "{cause}");
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            code,
+            "internal server error",
+        )
+    }
+
+    /// Error of a response built without [`ApiError`], ex: rejections of
+    /// extractors, timeouts and body limit
+    pub(crate) fn from_status(status: StatusCode, detail: String) -> Self {
+        let code = match status {
+            StatusCode::BAD_REQUEST => "invalid_request",
+            StatusCode::UNAUTHORIZED => "unauthorized",
+            StatusCode::FORBIDDEN => "forbidden",
+            StatusCode::NOT_FOUND => "not_found",
+            StatusCode::METHOD_NOT_ALLOWED => "method_not_allowed",
+            StatusCode::REQUEST_TIMEOUT => "request_timeout",
+            StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+            StatusCode::UNPROCESSABLE_ENTITY => "unprocessable_entity",
+            status if status.is_server_error() => "internal",
+            _ => {
+                return Self {
+                    status,
+                    code: Cow::Owned(format!("http_{}", status.as_u16())),
+                    detail,
+                }
+            }
+        };
+        Self::new(status, code, detail)
+    }
+
+    pub(crate) fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Body of error, `request_id` and `instance` are known by middleware
+    pub(crate) fn problem(
+        &self,
+        instance: Option<String>,
+        request_id: Option<String>,
+    ) -> Problem {
+        Problem {
+            kind: "about:blank".to_string(),
+            title: self
+                .status
+                .canonical_reason()
+                .unwrap_or_default()
+                .to_string(),
+            status: self.status.as_u16(),
+            detail: self.detail.clone(),
+            instance,
+            code: self.code.to_string(),
+            request_id,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut response = self.status.into_response();
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+impl From<paastel_app::Error> for ApiError {
+    fn from(error: paastel_app::Error) -> Self {
+        use paastel_app::Error as E;
+
+        let (status, code) = match &error {
+            E::DomainError(_) => (StatusCode::BAD_REQUEST, "invalid_request"),
+            E::AppAlreadyExists => (StatusCode::CONFLICT, "app_already_exists"),
+            E::AppConflict => (StatusCode::CONFLICT, "app_conflict"),
+            E::StageNotSucceeded => {
+                (StatusCode::CONFLICT, "stage_not_succeeded")
+            }
+            E::UploadOffsetMismatch { .. } => {
+                (StatusCode::CONFLICT, "upload_offset_mismatch")
+            }
+            E::UploadIncomplete { .. } => {
+                (StatusCode::CONFLICT, "upload_incomplete")
+            }
+            E::SourcesMissing(_) => (StatusCode::CONFLICT, "sources_missing"),
+            E::DigestMismatch { .. } => {
+                (StatusCode::UNPROCESSABLE_ENTITY, "digest_mismatch")
+            }
+            E::InvalidArchive(_) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, "invalid_archive")
+            }
+            E::AppNotFound => (StatusCode::NOT_FOUND, "app_not_found"),
+            E::BlobNotFound => (StatusCode::NOT_FOUND, "blob_not_found"),
+            E::StageNotFound => (StatusCode::NOT_FOUND, "stage_not_found"),
+            E::UploadNotFound => (StatusCode::NOT_FOUND, "upload_not_found"),
+            E::NamespaceNotFound => {
+                (StatusCode::NOT_FOUND, "namespace_not_found")
+            }
+            E::Kubernetes(_) => return Self::internal("kubernetes", error),
+            E::Storage(_) => return Self::internal("storage", error),
+        };
+        Self::new(status, code, error.to_string())
+    }
+}
+
+impl From<paastel_auth::Error> for ApiError {
+    fn from(error: paastel_auth::Error) -> Self {
+        use paastel_auth::Error as E;
+
+        let (status, code) = match &error {
+            E::DomainError(_) => (StatusCode::BAD_REQUEST, "invalid_request"),
+            E::InvalidPassword | E::SecretNotFound => {
+                (StatusCode::UNAUTHORIZED, "invalid_credentials")
+            }
+            E::InvalidToken => (StatusCode::UNAUTHORIZED, "invalid_token"),
+            E::ExpiredToken => (StatusCode::UNAUTHORIZED, "expired_token"),
+            E::UserAlreadyExists => {
+                (StatusCode::CONFLICT, "user_already_exists")
+            }
+            E::UserNotFound => (StatusCode::NOT_FOUND, "user_not_found"),
+            E::Signing(_) => return Self::internal("signing", error),
+            E::Kubernetes(_) => return Self::internal("kubernetes", error),
+        };
+        Self::new(status, code, error.to_string())
+    }
+}
+
+/// Problem details of RFC 7807 with PaaStel extension members
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// Stable identifier of error
+    pub code: String,
+    /// Value of `x-request-id`, for matching server logs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
@@ -13,17 +13,55 @@
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
 pub(crate) mod app;
+pub mod config;
 pub mod error;
 pub(crate) mod middleware;
+pub(crate) mod openapi;
 pub(crate) mod prometheus;
 pub(crate) mod router;
 pub(crate) mod state;
+pub(crate) mod tls;
 pub(crate) mod utils;
 
-pub async fn serve() -> Result<(), error::Error> {
-    let (_main_server, _metrics_server) = tokio::join!(
-        app::start_main_server(),
-        prometheus::start_metrics_server()
-    );
+use std::sync::Arc;
+
+use paastel_app::AppApplication;
+use paastel_auth::AuthApplication;
+
+pub use config::{ServerConfig, TlsConfig};
+pub use error::Error;
+
+/// Filter of log lines, ex: `PAASTEL_LOG=paastel_rest=debug`
+const LOG_ENV: &str = "PAASTEL_LOG";
+
+pub fn init_tracing() {
+    use tracing_subscriber::prelude::*;
+
+    let env = tracing_subscriber::EnvFilter::try_from_env(LOG_ENV)
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(env))
+        .init();
+}
+
+/// Runs rest and metrics servers until shutdown signal, first startup
+/// error stops both
+pub async fn serve(config: ServerConfig) -> Result<(), Error> {
+    tokio::try_join!(
+        app::start_main_server(&config),
+        prometheus::start_metrics_server(config.metrics_listen())
+    )?;
     Ok(())
 }
+
+/// Routes of rest server over given applications, without cluster nor
+/// listener, ex: served in process by end to end tests of clients
+pub fn make_app(
+    credential: AuthApplication,
+    application: AppApplication,
+    config: &ServerConfig,
+) -> axum::Router {
+    let state =
+        state::AppState::new(Arc::new(credential), Arc::new(application), None);
+    router::make_app(state, config)
+}
@@ -1,4 +1,23 @@
+use std::process::ExitCode;
+
+use paastel_rest::ServerConfig;
+
 #[tokio::main]
-async fn main() {
-    paastel_rest::serve().await.unwrap();
+async fn main() -> ExitCode {
+    paastel_rest::init_tracing();
+
+    let config = match ServerConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match paastel_rest::serve(config).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            tracing::error!("{e}");
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -13,27 +13,42 @@
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
 use axum::{
-    extract::{Request, State},
-    http::{self, StatusCode},
+    extract::{RawPathParams, Request, State},
+    http::{self, header, HeaderValue},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
+    Extension, Json,
 };
 use base64::Engine;
-use paastel_auth::{Credential, Password, Username};
-// use paastel::BaseAuthCommand;
+use paastel_auth::{
+    Action, AuthApplication, Credential, Password, Roles, SignedToken, Username,
+};
+
+use crate::{error::ApiError, state::AppState, tls::ClientIdentity};
+
+/// Path param used to scope authorization
+const NAMESPACE_PARAM: &str = "namespace";
 
-use crate::state::AppState;
+/// Header set by `SetRequestIdLayer`, echoed on problem details
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Media type of RFC 7807 problem details
+const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Largest body of a plain error response kept as problem detail
+const MAX_DETAIL_LENGTH: usize = 4 * 1024;
 
 #[derive(Debug, Clone)]
 pub(crate) struct CurrentUser {
     pub(crate) username: String,
+    pub(crate) roles: Roles,
 }
 
 pub(crate) async fn auth(
     State(AppState { credential, .. }): State<AppState>,
     mut req: Request,
     next: Next,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, ApiError> {
     let auth_header = req
         .headers()
         .get(http::header::AUTHORIZATION)
@@ -41,58 +56,250 @@ pub(crate) async fn auth(
 
     let auth_header = if let Some(auth_header) = auth_header {
         auth_header
+    } else if let Some(identity) = req.extensions().get::<ClientIdentity>() {
+        let current_user = client_user(&credential, identity).await?;
+        req.extensions_mut().insert(current_user);
+        return Ok(next.run(req).await);
     } else {
-        return Err(StatusCode::UNAUTHORIZED);
+        return Err(ApiError::unauthorized("missing authorization"));
     };
 
-    let split = auth_header.split_once(' ');
-    match split {
+    let current_user = match auth_header.split_once(' ') {
         Some(("Basic", contents)) => {
-            let decoded =
-                decode(contents).map_err(|_| StatusCode::UNAUTHORIZED)?;
+            let (username, password) = decode(contents).ok_or_else(|| {
+                ApiError::unauthorized("malformed basic credentials")
+            })?;
             let cred = Credential::new(
-                &decoded
-                    .0
+                &username
                     .parse::<Username>()
-                    .map_err(|_| StatusCode::UNAUTHORIZED)?,
-                &decoded
-                    .1
-                    .as_ref()
-                    .unwrap()
+                    .map_err(|_| ApiError::invalid_credentials())?,
+                &password
                     .parse::<Password>()
-                    .map_err(|_| StatusCode::UNAUTHORIZED)?,
+                    .map_err(|_| ApiError::invalid_credentials())?,
             )
-            .map_err(|_| StatusCode::UNAUTHORIZED)?;
+            .map_err(|_| ApiError::invalid_credentials())?;
 
             let auth_user = credential
                 .validate_credential
                 .validate_credential(&cred)
                 .await
-                .map_err(|_| StatusCode::UNAUTHORIZED)?;
+                .map_err(|e| match ApiError::from(e) {
+                    e if e.status().is_server_error() => e,
+                    _ => ApiError::invalid_credentials(),
+                })?;
 
-            let current_user = CurrentUser {
+            CurrentUser {
                 username: auth_user.username().as_ref().to_string(),
-            };
-            req.extensions_mut().insert(current_user);
-            Ok(next.run(req).await)
+                roles: auth_user.roles().clone(),
+            }
+        }
+        Some(("Bearer", contents)) => {
+            let token = contents.parse::<SignedToken>().map_err(|_| {
+                ApiError::new(
+                    http::StatusCode::UNAUTHORIZED,
+                    "invalid_token",
+                    "malformed token",
+                )
+            })?;
+
+            // NOTE: only signature and expiration are checked, no kubernetes
+            // or argon2 round trip per request
+            let claims = credential
+                .validate_token
+                .validate_token(&token)
+                .await
+                .map_err(ApiError::from)?;
+
+            CurrentUser {
+                username: claims.subject().as_ref().to_string(),
+                roles: claims.roles().clone(),
+            }
         }
-        _ => Err(StatusCode::UNAUTHORIZED),
+        _ => {
+            return Err(ApiError::unauthorized(
+                "authorization scheme must be Basic or Bearer",
+            ))
+        }
+    };
+    req.extensions_mut().insert(current_user);
+    Ok(next.run(req).await)
+}
+
+/// User of a verified client certificate on mutual TLS, common name of
+/// subject must be an existing username
+async fn client_user(
+    credential: &AuthApplication,
+    identity: &ClientIdentity,
+) -> Result<CurrentUser, ApiError> {
+    let unknown = || {
+        tracing::info!(username = identity.0, "unknown client certificate");
+        ApiError::unauthorized("unknown client certificate")
+    };
+    let username = identity.0.parse::<Username>().map_err(|_| unknown())?;
+    let users = credential.list_users.list_users().await?;
+    let user = users.find(&username).ok_or_else(unknown)?;
+    Ok(CurrentUser {
+        username: user.username().as_ref().to_string(),
+        roles: user.roles().clone(),
+    })
+}
+
+/// Reject request with 403 when roles of current user not allow `action` on
+/// target namespace, must be layered after [`auth`]. Routes without
+/// `:namespace` param are cluster wide.
+pub(crate) async fn authorize(
+    State(action): State<Action>,
+    Extension(current_user): Extension<CurrentUser>,
+    params: RawPathParams,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let namespace = params
+        .iter()
+        .find(|(key, _)| *key == NAMESPACE_PARAM)
+        .map(|(_, value)| value);
+
+    if !current_user.roles.is_allowed(action, namespace) {
+        tracing::info!(
+            username = current_user.username,
+            ?action,
+            ?namespace,
+            "forbidden"
+        );
+        return Err(ApiError::forbidden(format!(
+            "{action:?} not allowed on {}",
+            namespace.unwrap_or("cluster")
+        )));
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Renders error responses as problem details carrying request id, plain
+/// bodies of extractor rejections, timeouts and body limit become detail
+pub(crate) async fn problem(req: Request, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(ToString::to_string);
+    let instance = req.uri().path().to_string();
+
+    let response = next.run(req).await;
+    let status = response.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return response;
     }
+
+    let (mut parts, body) = response.into_parts();
+    let error = match parts.extensions.remove::<ApiError>() {
+        Some(error) => error,
+        None => {
+            let detail = axum::body::to_bytes(body, MAX_DETAIL_LENGTH)
+                .await
+                .map(|bytes| String::from_utf8_lossy(&bytes).trim().to_string())
+                .unwrap_or_default();
+            ApiError::from_status(status, detail)
+        }
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+    let problem = error.problem(Some(instance), request_id);
+    let (_, body) = Json(problem).into_response().into_parts();
+    Response::from_parts(parts, body)
 }
 
-/// Decodes the two parts of basic auth using the colon
-fn decode(input: &str) -> Result<(String, Option<String>), ()> {
-    // Decode from base64 into a string
+/// Decodes the two parts of basic auth using the colon, `None` when
+/// malformed or without password
+fn decode(input: &str) -> Option<(String, String)> {
     let decoded = base64::engine::general_purpose::STANDARD
-        .decode(input)
-        .unwrap();
-    // .map_err(|_| err)?;
-    let decoded = String::from_utf8(decoded).unwrap();
-
-    // Return depending on if password is present
-    Ok(if let Some((id, password)) = decoded.split_once(':') {
-        (id.to_string(), Some(password.to_string()))
-    } else {
-        (decoded.to_string(), None)
-    })
+        .decode(input.trim())
+        .ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (id, password) = decoded.split_once(':')?;
+    Some((id.to_string(), password.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        routing::{get, post},
+        Router,
+    };
+    use tower::ServiceExt;
+    use tower_http::catch_panic::CatchPanicLayer;
+
+    use super::*;
+    use crate::error::Problem;
+
+    async fn panicking() -> &'static str {
+        panic!("handler bug")
+    }
+
+    async fn problem_of(app: &Router, request: Request) -> Problem {
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        Json::<Problem>::from_bytes(&bytes).unwrap().0
+    }
+
+    #[tokio::test]
+    async fn errors_as_problem_details() {
+        let app = Router::new()
+            .route(
+                "/conflict",
+                get(|| async {
+                    Err::<(), _>(ApiError::from(
+                        paastel_app::Error::AppAlreadyExists,
+                    ))
+                }),
+            )
+            .route("/json", post(|Json(_): Json<Vec<String>>| async {}))
+            .route("/panic", get(panicking))
+            .layer(CatchPanicLayer::new())
+            .layer(axum::middleware::from_fn(problem));
+
+        let request = Request::get("/conflict")
+            .header(REQUEST_ID_HEADER, "request-1")
+            .body(Body::empty())
+            .unwrap();
+        let conflict = problem_of(&app, request).await;
+        assert_eq!(conflict.status, 409);
+        assert_eq!(conflict.code, "app_already_exists");
+        assert_eq!(conflict.request_id.as_deref(), Some("request-1"));
+        assert_eq!(conflict.instance.as_deref(), Some("/conflict"));
+
+        let request = Request::post("/json")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("not json"))
+            .unwrap();
+        let rejected = problem_of(&app, request).await;
+        assert_eq!(rejected.status, 400);
+        assert_eq!(rejected.code, "invalid_request");
+        assert!(!rejected.detail.is_empty());
+
+        let request = Request::get("/panic").body(Body::empty()).unwrap();
+        let panicked = problem_of(&app, request).await;
+        assert_eq!(panicked.status, 500);
+        assert_eq!(panicked.code, "internal");
+        assert!(!panicked.detail.contains("handler bug"));
+    }
+
+    #[test]
+    fn decode_malformed_basic() {
+        assert_eq!(
+            decode("YWRtaW46c2VjcmV0"),
+            Some(("admin".to_string(), "secret".to_string()))
+        );
+        assert_eq!(decode("not base64!"), None);
+        // NOTE: `admin\xff:secret` is not UTF-8
+        assert_eq!(decode("YWRtaW7/OnNlY3JldA=="), None);
+        assert_eq!(decode("YWRtaW4="), None);
+    }
 }
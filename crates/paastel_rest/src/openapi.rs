@@ -0,0 +1,380 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use axum::{response::IntoResponse, Json};
+use utoipa::{
+    openapi::security::{Http, HttpAuthScheme, SecurityScheme},
+    Modify, OpenApi,
+};
+
+use crate::{
+    error::Problem,
+    router::v1::{
+        application::{
+            create::{AppResponse, CreateAppRequest},
+            deploy::DeployRequest,
+            sources::{
+                AssembleSourcesRequest, MissingSourcesRequest,
+                MissingSourcesResponse, SourceEntryRequest,
+            },
+            stage::{StageRequest, StageResponse},
+            upload::UploadResponse,
+            uploads::{InitiateUploadRequest, UploadProgressResponse},
+        },
+        auth::{LoginRequest, RefreshRequest, TokenResponse},
+        info::Info,
+        me::Me,
+        user::{ChangePasswordRequest, CreateUserRequest, UserResponse},
+    },
+};
+
+/// OpenAPI 3 document of `/api/v1`, served at `/api/openapi.json` and
+/// checked in as `openapi.json` for generating clients
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "PaaStel API"),
+    paths(
+        crate::router::v1::info::get,
+        crate::router::v1::me::get,
+        crate::router::v1::auth::login,
+        crate::router::v1::auth::refresh,
+        crate::router::v1::user::list_users,
+        crate::router::v1::user::create_user,
+        crate::router::v1::user::change_password,
+        crate::router::v1::user::delete_user,
+        crate::router::v1::application::create::create_app,
+        crate::router::v1::application::show::show_app,
+        crate::router::v1::application::manifest::apply_manifest,
+        crate::router::v1::application::deploy::deploy_app,
+        crate::router::v1::application::upload::upload_app,
+        crate::router::v1::application::uploads::initiate_upload,
+        crate::router::v1::application::uploads::show_upload,
+        crate::router::v1::application::uploads::upload_part,
+        crate::router::v1::application::uploads::complete_upload,
+        crate::router::v1::application::sources::missing_sources,
+        crate::router::v1::application::sources::upload_source,
+        crate::router::v1::application::sources::assemble_sources,
+        crate::router::v1::application::stage::stage_app,
+        crate::router::v1::application::stage::show_stage,
+        crate::router::v1::application::stage::stage_logs,
+    ),
+    components(schemas(
+        Problem,
+        Info,
+        Me,
+        LoginRequest,
+        RefreshRequest,
+        TokenResponse,
+        CreateUserRequest,
+        ChangePasswordRequest,
+        UserResponse,
+        CreateAppRequest,
+        AppResponse,
+        DeployRequest,
+        UploadResponse,
+        InitiateUploadRequest,
+        UploadProgressResponse,
+        MissingSourcesRequest,
+        MissingSourcesResponse,
+        SourceEntryRequest,
+        AssembleSourcesRequest,
+        StageRequest,
+        StageResponse,
+    )),
+    modifiers(&SecuritySchemes),
+)]
+pub(crate) struct ApiDoc;
+
+/// Basic credentials or bearer access token of `/auth/login`
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components =
+            openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "basic",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Basic)),
+        );
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+    }
+}
+
+pub(crate) async fn get() -> impl IntoResponse {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use axum::{
+        body::Body,
+        http::{Method, Request, StatusCode},
+    };
+    use paastel_app::{AppApplication, MemoryCluster};
+    use paastel_auth::{
+        AuthApplication, SecretLabel, UserSecret, UserSecrets, Username,
+    };
+    use paastel_hash::{Argon2Adapter, Hs256Adapter};
+    use paastel_storage::{archive::ArchiveAdapter, local::LocalAdapter};
+    use tower::ServiceExt;
+    use utoipa::openapi::PathItemType;
+
+    use super::*;
+    use crate::{config::ServerConfig, router, state::AppState};
+
+    /// Rewrites checked in document instead of comparing
+    const UPDATE_ENV: &str = "PAASTEL_UPDATE_OPENAPI";
+
+    /// Sources registering `/api/v1` routes, path below `src`
+    const ROUTE_SOURCES: &[(&str, &str)] = &[
+        ("router/v1/mod.rs", include_str!("router/v1/mod.rs")),
+        ("router/v1/auth.rs", include_str!("router/v1/auth.rs")),
+        ("router/v1/user.rs", include_str!("router/v1/user.rs")),
+        (
+            "router/v1/application/mod.rs",
+            include_str!("router/v1/application/mod.rs"),
+        ),
+    ];
+
+    /// No users, requests of test are never authenticated
+    struct NoSecrets;
+
+    #[async_trait]
+    impl paastel_auth::OutgoingKubernetesPort for NoSecrets {
+        async fn find_secrets_by_label(
+            &self,
+            _label: &SecretLabel,
+        ) -> paastel_auth::Result<UserSecrets> {
+            Ok(UserSecrets::new(vec![]))
+        }
+
+        async fn create_secret(
+            &self,
+            _label: &SecretLabel,
+            _user_secret: &UserSecret,
+        ) -> paastel_auth::Result<()> {
+            Ok(())
+        }
+
+        async fn update_secret(
+            &self,
+            _label: &SecretLabel,
+            _user_secret: &UserSecret,
+        ) -> paastel_auth::Result<()> {
+            Err(paastel_auth::Error::UserNotFound)
+        }
+
+        async fn delete_secret(
+            &self,
+            _label: &SecretLabel,
+            _username: &Username,
+        ) -> paastel_auth::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Rust sources below `dir`, recursively
+    fn sources(dir: &Path) -> Vec<PathBuf> {
+        let mut sources = vec![];
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                sources.extend(self::sources(&path));
+            } else if path.extension().is_some_and(|ext| ext == "rs") {
+                sources.push(path);
+            }
+        }
+        sources
+    }
+
+    const METHODS: &[&str] = &["get", "post", "put", "delete", "patch"];
+
+    /// Method and OpenAPI path of each `.route(path, method(handler))`
+    fn routes(source: &str) -> BTreeSet<(String, String)> {
+        let mut routes = BTreeSet::new();
+        for call in source.split(".route(").skip(1) {
+            let Some(rest) = call.trim_start().strip_prefix('"') else {
+                continue;
+            };
+            let (path, rest) = rest.split_once('"').unwrap();
+            let path = path
+                .split('/')
+                .map(|part| match part.strip_prefix(':') {
+                    Some(param) => format!("{{{param}}}"),
+                    None => part.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+
+            let mut depth = 1;
+            let end = rest
+                .find(|c| {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0
+                })
+                .unwrap();
+            let handlers = &rest[..end];
+            for method in METHODS {
+                let called = handlers.match_indices(&format!("{method}(")).any(
+                    |(i, _)| {
+                        !handlers[..i].ends_with(|c: char| {
+                            c.is_alphanumeric() || c == '_'
+                        })
+                    },
+                );
+                if called {
+                    routes
+                        .insert((method.to_string(), format!("/api/v1{path}")));
+                }
+            }
+        }
+        routes
+    }
+
+    fn method_name(method: &PathItemType) -> String {
+        match method {
+            PathItemType::Get => "get",
+            PathItemType::Post => "post",
+            PathItemType::Put => "put",
+            PathItemType::Delete => "delete",
+            PathItemType::Patch => "patch",
+            PathItemType::Options => "options",
+            PathItemType::Head => "head",
+            PathItemType::Trace => "trace",
+            PathItemType::Connect => "connect",
+        }
+        .to_string()
+    }
+
+    #[test]
+    fn routes_documented() {
+        let routed: BTreeSet<_> = ROUTE_SOURCES
+            .iter()
+            .flat_map(|(_, source)| routes(source))
+            .collect();
+        let documented: BTreeSet<_> = ApiDoc::openapi()
+            .paths
+            .paths
+            .iter()
+            .flat_map(|(path, item)| {
+                item.operations
+                    .keys()
+                    .map(|method| (method_name(method), path.clone()))
+            })
+            .collect();
+
+        assert!(routed.len() > 20);
+        let undocumented: Vec<_> = routed.difference(&documented).collect();
+        assert!(
+            undocumented.is_empty(),
+            "undocumented routes {undocumented:?}"
+        );
+    }
+
+    #[test]
+    fn route_sources_listed() {
+        let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let unlisted: Vec<_> = sources(&src.join("router/v1"))
+            .into_iter()
+            .filter(|path| {
+                let source = std::fs::read_to_string(path).unwrap();
+                let path = path.strip_prefix(&src).unwrap();
+                source.contains(".route(")
+                    && !ROUTE_SOURCES
+                        .iter()
+                        .any(|(listed, _)| path == Path::new(listed))
+            })
+            .collect();
+        assert!(
+            unlisted.is_empty(),
+            "routes registered on {unlisted:?}, add them to ROUTE_SOURCES"
+        );
+    }
+
+    #[tokio::test]
+    async fn documented_routes_served() {
+        let storage = tempfile::tempdir().unwrap();
+        let local = LocalAdapter::new(storage.path()).await.unwrap();
+        let application = AppApplication::new(
+            Box::<MemoryCluster>::default(),
+            Box::new(local.clone()),
+            Box::new(ArchiveAdapter::default()),
+            Box::new(local),
+            "registry.paastel.local",
+        );
+        let credential = AuthApplication::new(
+            Box::new(NoSecrets),
+            Box::new(Argon2Adapter::default()),
+            Box::new(Hs256Adapter::default()),
+        );
+        let state =
+            AppState::new(Arc::new(credential), Arc::new(application), None);
+        let app = router::make_app(state, &ServerConfig::default());
+
+        let mut unserved = vec![];
+        for (path, item) in ApiDoc::openapi().paths.paths {
+            let uri = path.replace(['{', '}'], "");
+            for method in item.operations.keys() {
+                let method = method_name(method).to_uppercase();
+                let request = Request::builder()
+                    .method(Method::from_bytes(method.as_bytes()).unwrap())
+                    .uri(&uri)
+                    .body(Body::empty())
+                    .unwrap();
+                // NOTE: without credentials, matched routes answer 401 or
+                // reject the empty body, only unmatched ones are 404 or 405
+                let status =
+                    app.clone().oneshot(request).await.unwrap().status();
+                if matches!(
+                    status,
+                    StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED
+                ) {
+                    unserved.push(format!("{method} {path} {status}"));
+                }
+            }
+        }
+        assert!(
+            unserved.is_empty(),
+            "documented but not served {unserved:?}"
+        );
+    }
+
+    #[test]
+    fn document_checked_in() {
+        let document = ApiDoc::openapi().to_pretty_json().unwrap() + "\n";
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("openapi.json");
+        if std::env::var_os(UPDATE_ENV).is_some() {
+            std::fs::write(&path, &document).unwrap();
+        }
+        let checked_in = std::fs::read_to_string(&path).unwrap_or_default();
+        assert!(
+            checked_in == document,
+            "{} is outdated, run `{UPDATE_ENV}=1 cargo test -p paastel_rest \
+            openapi`",
+            path.display()
+        );
+    }
+}
@@ -21,34 +21,31 @@ use axum::{
 use metrics_exporter_prometheus::{
     Matcher, PrometheusBuilder, PrometheusHandle,
 };
-use std::{future::ready, time::Instant};
+use std::{future::ready, net::SocketAddr, time::Instant};
 
-use crate::utils;
+use crate::{error::Error, utils};
 
-fn metrics_app() -> Router {
-    let recorder_handle = setup_metrics_recorder();
-    Router::new()
-        .route("/metrics", get(move || ready(recorder_handle.render())))
+fn metrics_app() -> Result<Router, Error> {
+    let recorder_handle = setup_metrics_recorder()?;
+    Ok(Router::new()
+        .route("/metrics", get(move || ready(recorder_handle.render()))))
 }
 
-pub(crate) async fn start_metrics_server() {
-    let app = metrics_app();
+pub(crate) async fn start_metrics_server(
+    addr: SocketAddr,
+) -> Result<(), Error> {
+    let app = metrics_app()?;
 
     // NOTE: expose metrics endpoint on a different port
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3001")
-        .await
-        .unwrap();
-    tracing::info!(
-        "listening metrics server on {}",
-        listener.local_addr().unwrap()
-    );
+    let listener = utils::bind(addr).await?;
+    tracing::info!("listening metrics server on http://{addr}");
     axum::serve(listener, app)
         .with_graceful_shutdown(utils::shutdown_signal())
         .await
-        .unwrap();
+        .map_err(Error::Serve)
 }
 
-fn setup_metrics_recorder() -> PrometheusHandle {
+fn setup_metrics_recorder() -> Result<PrometheusHandle, Error> {
     const EXPONENTIAL_SECONDS: &[f64] = &[
         0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
     ];
@@ -58,9 +55,9 @@ fn setup_metrics_recorder() -> PrometheusHandle {
             Matcher::Full("http_requests_duration_seconds".to_string()),
             EXPONENTIAL_SECONDS,
         )
-        .unwrap()
+        .map_err(|e| Error::Metrics(e.to_string()))?
         .install_recorder()
-        .unwrap()
+        .map_err(|e| Error::Metrics(e.to_string()))
 }
 
 pub(crate) async fn track_metrics(
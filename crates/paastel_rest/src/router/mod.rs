@@ -12,10 +12,15 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-use std::time::Duration;
-
-use axum::{http::HeaderName, response::IntoResponse, routing::get, Router};
+use axum::{
+    extract::State,
+    http::{HeaderName, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
 use tower_http::{
+    catch_panic::CatchPanicLayer,
     limit::RequestBodyLimitLayer,
     propagate_header::PropagateHeaderLayer,
     request_id::{MakeRequestUuid, SetRequestIdLayer},
@@ -23,27 +28,33 @@ use tower_http::{
     trace::TraceLayer,
 };
 
-use crate::{prometheus, state::AppState};
+use crate::{
+    config::ServerConfig, middleware, openapi, prometheus, state::AppState,
+};
 
 pub(crate) mod v1;
 
-const MAX_PUBLISH_CONTENT_LENGTH: usize = 128 * 1024 * 1024; // 128 MB
-
-pub(crate) fn make_app(state: AppState) -> Router<()> {
-    let v1_route = v1::make_route(state.clone());
+pub(crate) fn make_app(state: AppState, config: &ServerConfig) -> Router<()> {
+    // NOTE: v1 routes set their own timeouts, uploads take longer
+    let v1_route = v1::make_route(state.clone(), config);
+    let request_timeout = TimeoutLayer::new(config.request_timeout());
     let ver_route: Router<AppState> = Router::new()
+        .route("/openapi.json", get(openapi::get))
+        .route_layer(request_timeout)
         .nest("/v1", v1_route)
         .with_state(state.clone());
 
     Router::new()
         .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route_layer(request_timeout)
         .nest("/api", ver_route)
         .route_layer(axum::middleware::from_fn(prometheus::track_metrics))
-        .layer(RequestBodyLimitLayer::new(MAX_PUBLISH_CONTENT_LENGTH))
-        .layer((
-            TraceLayer::new_for_http(),
-            TimeoutLayer::new(Duration::from_secs(20)),
-        ))
+        .layer(RequestBodyLimitLayer::new(config.body_limit()))
+        .layer((TraceLayer::new_for_http(), CatchPanicLayer::new()))
+        // NOTE: outside of timeout and panic layers, so their responses
+        // become problem details too, inside of request id layer
+        .layer(axum::middleware::from_fn(middleware::problem))
         .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
         .layer(PropagateHeaderLayer::new(HeaderName::from_static(
             "x-request-id",
@@ -55,3 +66,19 @@ pub(crate) fn make_app(state: AppState) -> Router<()> {
 async fn healthz() -> impl IntoResponse {
     "ok"
 }
+
+/// Ready once the credentials cache has listed the user secrets
+async fn readyz(State(state): State<AppState>) -> impl IntoResponse {
+    if state
+        .secrets_cache
+        .as_ref()
+        .map_or(true, |cache| cache.is_ready())
+    {
+        (StatusCode::OK, "ok")
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "credentials cache not ready",
+        )
+    }
+}
@@ -14,35 +14,95 @@
 
 use axum::{
     extract::{Path, State},
-    response::{Html, IntoResponse},
+    http::StatusCode,
+    response::IntoResponse,
     Extension, Json,
 };
-// use paastel::{CreateAppCommand, Name};
+use paastel_app::{AppName, Application, Namespace, NewApp};
 use serde::{Deserialize, Serialize};
 use tracing::info;
+use utoipa::ToSchema;
 
-use crate::{middleware, state::AppState};
+use crate::{error::ApiError, middleware, state::AppState};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct CreateAppRequest {
     name: String,
 }
 
-// pub(crate) async fn crete_app(
-//     State(AppState {
-//         create_app_usecase, ..
-//     }): State<AppState>,
-//     Extension(current_user): Extension<middleware::CurrentUser>,
-//     Path(namespace): Path<String>,
-//     Json(CreateAppRequest { name }): Json<CreateAppRequest>,
-// ) -> impl IntoResponse {
-//     info!("requesting creating app");
-
-//     let command =
-//         CreateAppCommand::new(Name::new(name.clone()), namespace.clone());
-//     create_app_usecase.create(&command).await.unwrap();
-
-//     Html(format!(
-//         "<h1>Hello, {current_user:?} create {name} on {namespace}</h1>"
-//     ))
-// }
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AppResponse {
+    name: String,
+    namespace: String,
+    created_by: Option<String>,
+    /// Image deployed, none until a stage is deployed
+    image: Option<String>,
+    instances: Option<i32>,
+    routes: Vec<String>,
+    configurations: Vec<String>,
+    /// Builder of stages requested without one
+    builder: Option<String>,
+    /// All replicas of deployed image are ready
+    ready: bool,
+    ready_replicas: i32,
+    /// Route application is reachable from
+    url: Option<String>,
+    /// Why application is not ready
+    message: Option<String>,
+}
+
+impl From<&Application> for AppResponse {
+    fn from(app: &Application) -> Self {
+        let config = app.config();
+        let status = app.status();
+        Self {
+            name: app.name().to_string(),
+            namespace: app.namespace().to_string(),
+            created_by: app.created_by().map(ToString::to_string),
+            image: app.image().map(ToString::to_string),
+            instances: config.instances(),
+            routes: config.routes().to_vec(),
+            configurations: config.configurations().to_vec(),
+            builder: config.builder().map(|b| b.to_string()),
+            ready: status.is_ready(),
+            ready_replicas: status.ready_replicas(),
+            url: status.url().map(ToString::to_string),
+            message: status.message().map(ToString::to_string),
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/namespaces/{namespace}/applications",
+    tag = "applications",
+    params(
+        ("namespace" = String, Path, description = "Namespace of application")
+    ),
+    request_body = CreateAppRequest,
+    responses(
+        (status = 201, description = "Created application", body = AppResponse),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Conflicts with current state", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn create_app(
+    State(AppState { application, .. }): State<AppState>,
+    Extension(current_user): Extension<middleware::CurrentUser>,
+    Path(namespace): Path<String>,
+    Json(CreateAppRequest { name }): Json<CreateAppRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting create app");
+
+    let new_app = NewApp::new(
+        name.parse::<AppName>()?,
+        namespace.parse::<Namespace>()?,
+        current_user.username,
+    );
+    let app = application.create_app.create_app(&new_app).await?;
+
+    Ok((StatusCode::CREATED, Json(AppResponse::from(&app))))
+}
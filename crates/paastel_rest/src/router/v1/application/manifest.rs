@@ -0,0 +1,94 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use paastel_app::{AppConfig, AppName, Builder, ConfigureApp, Namespace};
+use paastel_manifest::Manifest;
+use tracing::info;
+
+use crate::{error::ApiError, middleware, state::AppState};
+
+use super::create::AppResponse;
+
+/// Manifest (`paastel.yml`) sent as YAML or JSON body, application is
+/// created when missing
+#[utoipa::path(
+    put,
+    path = "/api/v1/namespaces/{namespace}/applications/{app}/manifest",
+    tag = "applications",
+    params(
+        ("namespace" = String, Path, description = "Namespace of application"),
+        ("app" = String, Path, description = "Name of application")
+    ),
+    request_body(content = String, description = "Manifest `paastel.yml` as YAML or JSON", content_type = "application/yaml"),
+    responses(
+        (status = 200, description = "Configured application", body = AppResponse),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn apply_manifest(
+    State(AppState { application, .. }): State<AppState>,
+    Extension(current_user): Extension<middleware::CurrentUser>,
+    Path((namespace, app)): Path<(String, String)>,
+    body: String,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting apply manifest");
+
+    let manifest = body.parse::<Manifest>().map_err(|e| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_manifest",
+            e.to_string(),
+        )
+    })?;
+    let same_namespace = manifest
+        .namespace()
+        .map_or(true, |manifest_namespace| manifest_namespace == namespace);
+    if manifest.name() != app || !same_namespace {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_manifest",
+            "manifest does not match application on path",
+        ));
+    }
+
+    let builder = manifest
+        .builder()
+        .map(|b| b.as_ref().parse::<Builder>())
+        .transpose()?;
+    let configure = ConfigureApp::new(
+        app.parse::<AppName>()?,
+        namespace.parse::<Namespace>()?,
+        current_user.username,
+        AppConfig::new(
+            manifest.instances(),
+            manifest.routes().to_vec(),
+            manifest.env().clone(),
+            manifest.configurations().to_vec(),
+        )
+        .with_builder(builder),
+    );
+    let app = application.configure_app.configure_app(&configure).await?;
+
+    Ok(Json(AppResponse::from(&app)))
+}
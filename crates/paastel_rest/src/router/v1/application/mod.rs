@@ -12,22 +12,105 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-use axum::{routing::post, Router};
+use axum::{
+    routing::{get, post, put},
+    Router,
+};
+use paastel_auth::Action;
 
-use crate::state::AppState;
+use crate::{middleware, state::AppState};
 
 pub(crate) mod create;
+pub(crate) mod deploy;
+pub(crate) mod manifest;
+pub(crate) mod show;
+pub(crate) mod sources;
+pub(crate) mod stage;
 pub(crate) mod upload;
+pub(crate) mod uploads;
 
 pub(crate) fn make_route(state: AppState) -> Router<AppState> {
+    let read_route = Router::new()
+        .route(
+            "/namespaces/:namespace/applications/:app",
+            get(show::show_app),
+        )
+        .route(
+            "/namespaces/:namespace/applications/:app/uploads/:upload",
+            get(uploads::show_upload),
+        )
+        .route(
+            "/namespaces/:namespace/stages/:stage",
+            get(stage::show_stage),
+        )
+        .route(
+            "/namespaces/:namespace/stages/:stage/logs",
+            get(stage::stage_logs),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            Action::Read,
+            middleware::authorize,
+        ));
+
+    Router::new()
+        .route(
+            "/namespaces/:namespace/applications",
+            post(create::create_app),
+        )
+        .route(
+            "/namespaces/:namespace/applications/:app/uploads",
+            post(uploads::initiate_upload),
+        )
+        .route(
+            "/namespaces/:namespace/applications/:app/sources/missing",
+            post(sources::missing_sources),
+        )
+        .route(
+            "/namespaces/:namespace/applications/:app/stage",
+            post(stage::stage_app),
+        )
+        .route(
+            "/namespaces/:namespace/applications/:app/manifest",
+            put(manifest::apply_manifest),
+        )
+        .route(
+            "/namespaces/:namespace/applications/:app/deploy",
+            put(deploy::deploy_app),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            Action::Write,
+            middleware::authorize,
+        ))
+        .merge(read_route)
+        .with_state(state)
+}
+
+/// Routes reading or writing whole sources, they take longer than others
+pub(crate) fn make_transfer_route(state: AppState) -> Router<AppState> {
     Router::new()
-        // .route(
-        //     "/namespaces/:namespace/applications",
-        //     post(create::crete_app),
-        // )
-        // .route(
-        //     "/namespaces/:namespace/applications/:app/store",
-        //     post(upload::upload_app),
-        // )
+        .route(
+            "/namespaces/:namespace/applications/:app/store",
+            post(upload::upload_app),
+        )
+        .route(
+            "/namespaces/:namespace/applications/:app/uploads/:upload/parts/:offset",
+            put(uploads::upload_part),
+        )
+        .route(
+            "/namespaces/:namespace/applications/:app/uploads/:upload/complete",
+            post(uploads::complete_upload),
+        )
+        .route(
+            "/namespaces/:namespace/applications/:app/sources",
+            post(sources::assemble_sources),
+        )
+        .route(
+            "/namespaces/:namespace/applications/:app/sources/:digest",
+            put(sources::upload_source),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            Action::Write,
+            middleware::authorize,
+        ))
         .with_state(state)
 }
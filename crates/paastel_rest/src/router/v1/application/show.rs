@@ -0,0 +1,56 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use paastel_app::{AppName, Namespace};
+use tracing::info;
+
+use crate::{error::ApiError, state::AppState};
+
+use super::create::AppResponse;
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/namespaces/{namespace}/applications/{app}",
+    tag = "applications",
+    params(
+        ("namespace" = String, Path, description = "Namespace of application"),
+        ("app" = String, Path, description = "Name of application")
+    ),
+    responses(
+        (status = 200, description = "Application", body = AppResponse),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn show_app(
+    State(AppState { application, .. }): State<AppState>,
+    Path((namespace, app)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting show app");
+
+    let app = application
+        .show_app
+        .show_app(&namespace.parse::<Namespace>()?, &app.parse::<AppName>()?)
+        .await?;
+
+    Ok(Json(AppResponse::from(&app)))
+}
@@ -0,0 +1,208 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use futures::TryStreamExt;
+use paastel_app::{
+    AppName, Digest, Namespace, NewSources, SourceEntry, SourceKind, SourcePath,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::{error::ApiError, state::AppState};
+
+use super::upload::UploadResponse;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MissingSourcesRequest {
+    /// SHA-256 of source files
+    digests: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MissingSourcesResponse {
+    /// Digests to upload before assembling sources
+    missing: Vec<String>,
+}
+
+/// Regular file when `digest` is given, symbolic link when `target` is
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SourceEntryRequest {
+    path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    digest: Option<String>,
+    #[serde(default)]
+    executable: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+}
+
+impl TryFrom<SourceEntryRequest> for SourceEntry {
+    type Error = paastel_app::Error;
+
+    fn try_from(entry: SourceEntryRequest) -> Result<Self, Self::Error> {
+        let path = entry.path.parse::<SourcePath>()?;
+        let kind = match (entry.digest, entry.target) {
+            (Some(digest), None) => SourceKind::File {
+                digest: digest.parse::<Digest>()?,
+                executable: entry.executable,
+            },
+            (None, Some(target)) => SourceKind::Symlink { target },
+            _ => {
+                return Err(paastel_app::Error::DomainError(format!(
+                    "`{path}` needs either digest or target"
+                )))
+            }
+        };
+        Ok(SourceEntry::new(path, kind))
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AssembleSourcesRequest {
+    entries: Vec<SourceEntryRequest>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/namespaces/{namespace}/applications/{app}/sources/missing",
+    tag = "uploads",
+    params(
+        ("namespace" = String, Path, description = "Namespace of application"),
+        ("app" = String, Path, description = "Name of application")
+    ),
+    request_body = MissingSourcesRequest,
+    responses(
+        (status = 200, description = "Source files not stored yet", body = MissingSourcesResponse),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn missing_sources(
+    State(AppState { application, .. }): State<AppState>,
+    Path((namespace, app)): Path<(String, String)>,
+    Json(MissingSourcesRequest { digests }): Json<MissingSourcesRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting missing sources");
+
+    let digests = digests
+        .iter()
+        .map(|digest| digest.parse::<Digest>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let missing = application
+        .missing_sources
+        .missing_sources(
+            &namespace.parse::<Namespace>()?,
+            &app.parse::<AppName>()?,
+            &digests,
+        )
+        .await?;
+
+    Ok(Json(MissingSourcesResponse {
+        missing: missing.iter().map(ToString::to_string).collect(),
+    }))
+}
+
+/// Source file is sent as raw request body, addressed by its SHA-256
+#[utoipa::path(
+    put,
+    path = "/api/v1/namespaces/{namespace}/applications/{app}/sources/{digest}",
+    tag = "uploads",
+    params(
+        ("namespace" = String, Path, description = "Namespace of application"),
+        ("app" = String, Path, description = "Name of application"),
+        ("digest" = String, Path, description = "Hex encoded SHA-256 of source file")
+    ),
+    request_body(content = Vec<u8>, description = "Content of source file", content_type = "application/octet-stream"),
+    responses(
+        (status = 201, description = "Stored source file", body = UploadResponse),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Digest mismatch or invalid archive", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn upload_source(
+    State(AppState { application, .. }): State<AppState>,
+    Path((namespace, app, digest)): Path<(String, String, String)>,
+    body: Body,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting upload source");
+
+    let body = body.into_data_stream().map_err(std::io::Error::other);
+    let blob = application
+        .upload_source
+        .upload_source(
+            &namespace.parse::<Namespace>()?,
+            &app.parse::<AppName>()?,
+            &digest.parse::<Digest>()?,
+            Box::pin(body),
+        )
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(UploadResponse::from(&blob))))
+}
+
+/// Archive of entries is built from source files, staged as an upload
+#[utoipa::path(
+    post,
+    path = "/api/v1/namespaces/{namespace}/applications/{app}/sources",
+    tag = "uploads",
+    params(
+        ("namespace" = String, Path, description = "Namespace of application"),
+        ("app" = String, Path, description = "Name of application")
+    ),
+    request_body = AssembleSourcesRequest,
+    responses(
+        (status = 201, description = "Archive assembled from stored sources", body = UploadResponse),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Conflicts with current state", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn assemble_sources(
+    State(AppState { application, .. }): State<AppState>,
+    Path((namespace, app)): Path<(String, String)>,
+    Json(AssembleSourcesRequest { entries }): Json<AssembleSourcesRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting assemble sources");
+
+    let entries = entries
+        .into_iter()
+        .map(SourceEntry::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    let sources = NewSources::new(
+        app.parse::<AppName>()?,
+        namespace.parse::<Namespace>()?,
+        entries,
+    );
+    let blob = application
+        .assemble_sources
+        .assemble_sources(&sources)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(UploadResponse::from(&blob))))
+}
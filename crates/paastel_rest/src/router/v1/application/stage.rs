@@ -0,0 +1,167 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use paastel_app::{AppName, Builder, Namespace, NewStage, Stage, StageId};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::{error::ApiError, state::AppState};
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct StageRequest {
+    blob_id: String,
+    /// `buildpack` or `dockerfile`, default builder of application or
+    /// `buildpack`
+    #[serde(default)]
+    builder: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct StageResponse {
+    id: String,
+    app: String,
+    namespace: String,
+    blob_id: String,
+    builder: String,
+    phase: String,
+    /// Image reference, only when build succeeded
+    image: Option<String>,
+    /// Why build failed
+    reason: Option<String>,
+}
+
+impl From<&Stage> for StageResponse {
+    fn from(stage: &Stage) -> Self {
+        Self {
+            id: stage.id().to_string(),
+            app: stage.name().to_string(),
+            namespace: stage.namespace().to_string(),
+            blob_id: stage.blob_id().to_string(),
+            builder: stage.builder().to_string(),
+            phase: stage.phase().to_string(),
+            image: stage.built_image().map(ToString::to_string),
+            reason: stage.reason().map(ToString::to_string),
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/namespaces/{namespace}/applications/{app}/stage",
+    tag = "stages",
+    params(
+        ("namespace" = String, Path, description = "Namespace of application"),
+        ("app" = String, Path, description = "Name of application")
+    ),
+    request_body = StageRequest,
+    responses(
+        (status = 201, description = "Started build", body = StageResponse),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn stage_app(
+    State(AppState { application, .. }): State<AppState>,
+    Path((namespace, app)): Path<(String, String)>,
+    Json(StageRequest { blob_id, builder }): Json<StageRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting stage app");
+
+    let builder = builder.map(|b| b.parse::<Builder>()).transpose()?;
+    let new_stage = NewStage::new(
+        app.parse::<AppName>()?,
+        namespace.parse::<Namespace>()?,
+        blob_id.parse()?,
+        builder,
+    );
+    let stage = application.stage_app.stage_app(&new_stage).await?;
+
+    Ok((StatusCode::CREATED, Json(StageResponse::from(&stage))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/namespaces/{namespace}/stages/{stage}",
+    tag = "stages",
+    params(
+        ("namespace" = String, Path, description = "Namespace of application"),
+        ("stage" = String, Path, description = "Id of stage")
+    ),
+    responses(
+        (status = 200, description = "Stage", body = StageResponse),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn show_stage(
+    State(AppState { application, .. }): State<AppState>,
+    Path((namespace, id)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting show stage");
+
+    let stage = application
+        .show_stage
+        .show_stage(&namespace.parse::<Namespace>()?, &id.parse::<StageId>()?)
+        .await?;
+
+    Ok(Json(StageResponse::from(&stage)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/namespaces/{namespace}/stages/{stage}/logs",
+    tag = "stages",
+    params(
+        ("namespace" = String, Path, description = "Namespace of application"),
+        ("stage" = String, Path, description = "Id of stage")
+    ),
+    responses(
+        (status = 200, description = "Build logs written so far, all of them once build ended", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn stage_logs(
+    State(AppState { application, .. }): State<AppState>,
+    Path((namespace, id)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting stage logs");
+
+    let logs = application
+        .stage_logs
+        .stage_logs(&namespace.parse::<Namespace>()?, &id.parse::<StageId>()?)
+        .await?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        Body::from_stream(logs),
+    ))
+}
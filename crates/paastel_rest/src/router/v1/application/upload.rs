@@ -13,38 +13,102 @@
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
 use axum::{
-    extract::{Multipart, Path, State},
-    response::{Html, IntoResponse},
-    Extension,
+    body::Body,
+    extract::{Path, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
 };
+use futures::TryStreamExt;
+use paastel_app::{AppName, AppUpload, ArchiveFormat, Blob, Digest, Namespace};
+use serde::{Deserialize, Serialize};
 use tracing::info;
+use utoipa::ToSchema;
 
-use crate::{middleware, state::AppState};
-
-// pub(crate) async fn upload_app(
-//     State(AppState {
-//         create_app_usecase: _,
-//         ..
-//     }): State<AppState>,
-//     Extension(current_user): Extension<middleware::CurrentUser>,
-//     Path((namespace, app)): Path<(String, String)>,
-//     mut multipart: Multipart,
-// ) -> impl IntoResponse {
-//     info!("requesting uploading app");
-
-//     while let Some(field) = multipart.next_field().await.unwrap() {
-//         let name = field.name().unwrap().to_string();
-//         let file_name = field.file_name().unwrap().to_string();
-//         let content_type = field.content_type().unwrap().to_string();
-//         let data = field.bytes().await.unwrap();
-
-//         println!(
-//             "Length of `{name}` (`{file_name}`: `{content_type}`) is {} bytes",
-//             data.len()
-//         );
-//     }
-
-//     Html(format!(
-//         "<h1>Hello, {current_user:?} upload {app} on {namespace}</h1>"
-//     ))
-// }
+use crate::{error::ApiError, state::AppState};
+
+/// Header with hex encoded SHA-256 of uploaded archive
+pub(crate) const CONTENT_SHA256_HEADER: &str = "x-paastel-content-sha256";
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UploadResponse {
+    blob_id: String,
+    digest: String,
+    size: u64,
+}
+
+impl From<&Blob> for UploadResponse {
+    fn from(blob: &Blob) -> Self {
+        Self {
+            blob_id: blob.id().to_string(),
+            digest: blob.digest().to_string(),
+            size: blob.size(),
+        }
+    }
+}
+
+/// Archive is sent as raw request body and streamed to blob store, its
+/// format is given by content type
+#[utoipa::path(
+    post,
+    path = "/api/v1/namespaces/{namespace}/applications/{app}/store",
+    tag = "uploads",
+    params(
+        ("namespace" = String, Path, description = "Namespace of application"),
+        ("app" = String, Path, description = "Name of application"),
+        ("x-paastel-content-sha256" = String, Header, description = "Hex encoded SHA-256 of body")
+    ),
+    request_body(content = Vec<u8>, description = "Archive, `application/zip`, `application/gzip` or `application/zstd`", content_type = "application/octet-stream"),
+    responses(
+        (status = 201, description = "Stored archive", body = UploadResponse),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json"),
+        (status = 415, description = "Unsupported archive format", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Digest mismatch or invalid archive", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn upload_app(
+    State(AppState { application, .. }): State<AppState>,
+    Path((namespace, app)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting uploading app");
+
+    let digest = headers
+        .get(CONTENT_SHA256_HEADER)
+        .and_then(|digest| digest.to_str().ok())
+        .ok_or_else(|| {
+            ApiError::bad_request(format!("missing {CONTENT_SHA256_HEADER}"))
+        })?
+        .parse::<Digest>()?;
+    let format = headers
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(|content_type| {
+            ArchiveFormat::from_media_type(content_type).ok()
+        })
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_archive_format",
+                "content type is not a supported archive format",
+            )
+        })?;
+    let upload = AppUpload::new(
+        app.parse::<AppName>()?,
+        namespace.parse::<Namespace>()?,
+        digest,
+        format,
+    );
+
+    let body = body.into_data_stream().map_err(std::io::Error::other);
+    let blob = application
+        .upload_app
+        .upload_app(&upload, Box::pin(body))
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(UploadResponse::from(&blob))))
+}
@@ -0,0 +1,234 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use futures::TryStreamExt;
+use paastel_app::{
+    AppName, ArchiveFormat, Digest, Namespace, NewUpload, Upload, UploadId,
+    UploadPart,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::{error::ApiError, state::AppState};
+
+use super::upload::{UploadResponse, CONTENT_SHA256_HEADER};
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct InitiateUploadRequest {
+    /// SHA-256 of whole archive
+    digest: String,
+    size: u64,
+    /// `zip`, `tar.gz` or `tar.zst`
+    format: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UploadProgressResponse {
+    id: String,
+    size: u64,
+    /// Offset of next part
+    received: u64,
+}
+
+impl From<&Upload> for UploadProgressResponse {
+    fn from(upload: &Upload) -> Self {
+        Self {
+            id: upload.id().to_string(),
+            size: upload.size(),
+            received: upload.received(),
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/namespaces/{namespace}/applications/{app}/uploads",
+    tag = "uploads",
+    params(
+        ("namespace" = String, Path, description = "Namespace of application"),
+        ("app" = String, Path, description = "Name of application")
+    ),
+    request_body = InitiateUploadRequest,
+    responses(
+        (status = 201, description = "Started resumable upload", body = UploadProgressResponse),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn initiate_upload(
+    State(AppState { application, .. }): State<AppState>,
+    Path((namespace, app)): Path<(String, String)>,
+    Json(request): Json<InitiateUploadRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting initiate upload");
+
+    let new_upload = NewUpload::new(
+        app.parse::<AppName>()?,
+        namespace.parse::<Namespace>()?,
+        request.digest.parse::<Digest>()?,
+        request.format.parse::<ArchiveFormat>()?,
+        request.size,
+    );
+    let upload = application
+        .initiate_upload
+        .initiate_upload(&new_upload)
+        .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(UploadProgressResponse::from(&upload)),
+    ))
+}
+
+/// Part is sent as raw request body with its own SHA-256, it must start at
+/// bytes received so far
+#[utoipa::path(
+    put,
+    path = "/api/v1/namespaces/{namespace}/applications/{app}/uploads/{upload}/parts/{offset}",
+    tag = "uploads",
+    params(
+        ("namespace" = String, Path, description = "Namespace of application"),
+        ("app" = String, Path, description = "Name of application"),
+        ("upload" = String, Path, description = "Id of upload"),
+        ("offset" = u64, Path, description = "Offset of part, bytes received so far"),
+        ("x-paastel-content-sha256" = String, Header, description = "Hex encoded SHA-256 of body")
+    ),
+    request_body(content = Vec<u8>, description = "Part of archive", content_type = "application/octet-stream"),
+    responses(
+        (status = 200, description = "Progress of upload", body = UploadProgressResponse),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Conflicts with current state", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Digest mismatch or invalid archive", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn upload_part(
+    State(AppState { application, .. }): State<AppState>,
+    Path((namespace, app, upload, offset)): Path<(String, String, String, u64)>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting upload part");
+
+    let digest = headers
+        .get(CONTENT_SHA256_HEADER)
+        .and_then(|digest| digest.to_str().ok())
+        .ok_or_else(|| {
+            ApiError::bad_request(format!("missing {CONTENT_SHA256_HEADER}"))
+        })?
+        .parse::<Digest>()?;
+    let part = UploadPart::new(
+        app.parse::<AppName>()?,
+        namespace.parse::<Namespace>()?,
+        upload.parse::<UploadId>()?,
+        offset,
+        digest,
+    );
+
+    let body = body.into_data_stream().map_err(std::io::Error::other);
+    let upload = application
+        .upload_part
+        .upload_part(&part, Box::pin(body))
+        .await?;
+
+    Ok(Json(UploadProgressResponse::from(&upload)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/namespaces/{namespace}/applications/{app}/uploads/{upload}",
+    tag = "uploads",
+    params(
+        ("namespace" = String, Path, description = "Namespace of application"),
+        ("app" = String, Path, description = "Name of application"),
+        ("upload" = String, Path, description = "Id of upload")
+    ),
+    responses(
+        (status = 200, description = "Progress of upload", body = UploadProgressResponse),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn show_upload(
+    State(AppState { application, .. }): State<AppState>,
+    Path((namespace, app, upload)): Path<(String, String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting show upload");
+
+    let upload = application
+        .show_upload
+        .show_upload(
+            &namespace.parse::<Namespace>()?,
+            &app.parse::<AppName>()?,
+            &upload.parse::<UploadId>()?,
+        )
+        .await?;
+
+    Ok(Json(UploadProgressResponse::from(&upload)))
+}
+
+/// Parts are assembled into a blob, same response of a single upload
+#[utoipa::path(
+    post,
+    path = "/api/v1/namespaces/{namespace}/applications/{app}/uploads/{upload}/complete",
+    tag = "uploads",
+    params(
+        ("namespace" = String, Path, description = "Namespace of application"),
+        ("app" = String, Path, description = "Name of application"),
+        ("upload" = String, Path, description = "Id of upload")
+    ),
+    responses(
+        (status = 201, description = "Stored archive", body = UploadResponse),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Conflicts with current state", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Digest mismatch or invalid archive", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn complete_upload(
+    State(AppState { application, .. }): State<AppState>,
+    Path((namespace, app, upload)): Path<(String, String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting complete upload");
+
+    let blob = application
+        .complete_upload
+        .complete_upload(
+            &namespace.parse::<Namespace>()?,
+            &app.parse::<AppName>()?,
+            &upload.parse::<UploadId>()?,
+        )
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(UploadResponse::from(&blob))))
+}
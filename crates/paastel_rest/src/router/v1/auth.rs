@@ -0,0 +1,121 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use axum::{
+    extract::State, http::StatusCode, response::IntoResponse, routing::post,
+    Json, Router,
+};
+use paastel_auth::{Credential, SignedToken, TokenPair};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::{error::ApiError, state::AppState};
+
+/// Type of token returned, clients must send `Authorization: Bearer`
+const TOKEN_TYPE: &str = "Bearer";
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    refresh_token: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TokenResponse {
+    access_token: String,
+    expiry: u64,
+    refresh_token: String,
+    token_type: String,
+}
+
+impl From<TokenPair> for TokenResponse {
+    fn from(pair: TokenPair) -> Self {
+        Self {
+            access_token: pair.access_token().as_ref().to_string(),
+            expiry: pair.expires_at(),
+            refresh_token: pair.refresh_token().as_ref().to_string(),
+            token_type: TOKEN_TYPE.to_string(),
+        }
+    }
+}
+
+pub(crate) fn make_route(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/auth/login", post(login))
+        .route("/auth/refresh", post(refresh))
+        .with_state(state)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Access and refresh tokens", body = TokenResponse),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json")
+    )
+)]
+pub(crate) async fn login(
+    State(AppState { credential, .. }): State<AppState>,
+    Json(LoginRequest { username, password }): Json<LoginRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting login");
+
+    let cred = Credential::new(&username, &password)
+        .map_err(|_| ApiError::invalid_credentials())?;
+    let pair =
+        credential.login.login(&cred).await.map_err(
+            |e| match ApiError::from(e) {
+                e if e.status().is_server_error() => e,
+                _ => ApiError::invalid_credentials(),
+            },
+        )?;
+
+    Ok(Json(TokenResponse::from(pair)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/refresh",
+    tag = "auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "New access and refresh tokens", body = TokenResponse),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json")
+    )
+)]
+pub(crate) async fn refresh(
+    State(AppState { credential, .. }): State<AppState>,
+    Json(RefreshRequest { refresh_token }): Json<RefreshRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting refresh token");
+
+    let token = refresh_token.parse::<SignedToken>().map_err(|_| {
+        ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "invalid_token",
+            "malformed refresh token",
+        )
+    })?;
+    let pair = credential.refresh_token.refresh(&token).await?;
+
+    Ok(Json(TokenResponse::from(pair)))
+}
@@ -0,0 +1,45 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use axum::{response::IntoResponse, Json};
+use paastel_app::ArchiveFormat;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Server capabilities, read by clients before pushing
+#[derive(Serialize, Deserialize, ToSchema)]
+pub(crate) struct Info {
+    version: String,
+    /// Accepted source archives, preferred first
+    archive_formats: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/info",
+    operation_id = "info",
+    tag = "info",
+    responses(
+        (status = 200, description = "Server capabilities", body = Info)
+    )
+)]
+pub(crate) async fn get() -> impl IntoResponse {
+    Json(Info {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        archive_formats: ArchiveFormat::ALL
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+    })
+}
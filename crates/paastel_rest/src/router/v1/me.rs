@@ -12,26 +12,41 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-use axum::{
-    response::{Html, IntoResponse},
-    Extension, Json,
-};
+use axum::{response::IntoResponse, Extension, Json};
 use serde::{Deserialize, Serialize};
 use tracing::info;
+use utoipa::ToSchema;
 
 use crate::middleware;
 
-#[derive(Serialize, Deserialize)]
-struct Me {
+#[derive(Serialize, Deserialize, ToSchema)]
+pub(crate) struct Me {
     username: String,
+    roles: Vec<String>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/me",
+    operation_id = "me",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Authenticated user", body = Me),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
 pub(crate) async fn get(
     Extension(current_user): Extension<middleware::CurrentUser>,
 ) -> impl IntoResponse {
     info!("requesting me");
     let me = Me {
         username: current_user.username,
+        roles: current_user
+            .roles
+            .iter()
+            .map(|grant| grant.to_string())
+            .collect(),
     };
     Json(me)
 }
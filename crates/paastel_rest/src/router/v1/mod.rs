@@ -13,19 +13,41 @@
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
 use axum::Router;
+use tower_http::timeout::TimeoutLayer;
 
-use crate::{middleware, state::AppState};
+use crate::{config::ServerConfig, middleware, state::AppState};
 
 pub mod application;
+pub(crate) mod auth;
+pub(crate) mod info;
 pub(crate) mod me;
+pub(crate) mod user;
+
+pub(crate) fn make_route(
+    state: AppState,
+    config: &ServerConfig,
+) -> Router<AppState> {
+    let request_timeout = TimeoutLayer::new(config.request_timeout());
+    // NOTE: uploads of sources are bound by their own timeout, larger than
+    // the one of other requests
+    let transfer_route = application::make_transfer_route(state.clone())
+        .route_layer(TimeoutLayer::new(config.transfer_timeout()));
+    // NOTE: token and info routes must be reachable without
+    // authentication
+    let public_route = auth::make_route(state.clone())
+        .route("/info", axum::routing::get(info::get))
+        .route_layer(request_timeout);
 
-pub(crate) fn make_route(state: AppState) -> Router<AppState> {
     Router::new()
         .route("/me", axum::routing::get(me::get))
         .merge(application::make_route(state.clone()))
+        .merge(user::make_route(state.clone()))
+        .route_layer(request_timeout)
+        .merge(transfer_route)
         .route_layer(axum::middleware::from_fn_with_state(
             state.clone(),
             middleware::auth,
         ))
+        .merge(public_route)
         .with_state(state)
 }
@@ -0,0 +1,185 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, get, put},
+    Json, Router,
+};
+use paastel_auth::{Action, Credential, NewUser, Roles, UserSecret, Username};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::{error::ApiError, middleware, state::AppState};
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CreateUserRequest {
+    username: String,
+    password: String,
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ChangePasswordRequest {
+    password: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UserResponse {
+    username: String,
+    roles: Vec<String>,
+}
+
+impl From<&UserSecret> for UserResponse {
+    fn from(user_secret: &UserSecret) -> Self {
+        Self {
+            username: user_secret.username().to_string(),
+            roles: user_secret
+                .roles()
+                .iter()
+                .map(|grant| grant.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Users management is cluster wide, only unscoped admins are allowed
+pub(crate) fn make_route(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/users", get(list_users).post(create_user))
+        .route("/users/:username", delete(delete_user))
+        .route("/users/:username/password", put(change_password))
+        .route_layer(axum::middleware::from_fn_with_state(
+            Action::Manage,
+            middleware::authorize,
+        ))
+        .with_state(state)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users",
+    tag = "users",
+    responses(
+        (status = 200, description = "Users", body = [UserResponse]),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn list_users(
+    State(AppState { credential, .. }): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting list users");
+
+    let users = credential.list_users.list_users().await?;
+    let users: Vec<UserResponse> =
+        users.iter().map(UserResponse::from).collect();
+    Ok(Json(users))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/users",
+    tag = "users",
+    request_body = CreateUserRequest,
+    responses(
+        (status = 201, description = "Created user", body = UserResponse),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Conflicts with current state", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn create_user(
+    State(AppState { credential, .. }): State<AppState>,
+    Json(CreateUserRequest {
+        username,
+        password,
+        roles,
+    }): Json<CreateUserRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting create user");
+
+    let new_user = NewUser::new(
+        Credential::new(&username, &password)?,
+        roles.join(",").parse::<Roles>()?,
+    );
+    let user_secret = credential.create_user.create_user(&new_user).await?;
+
+    Ok((StatusCode::CREATED, Json(UserResponse::from(&user_secret))))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/users/{username}/password",
+    tag = "users",
+    params(
+        ("username" = String, Path, description = "Username")
+    ),
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 204, description = "Password changed"),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn change_password(
+    State(AppState { credential, .. }): State<AppState>,
+    Path(username): Path<String>,
+    Json(ChangePasswordRequest { password }): Json<ChangePasswordRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting change password");
+
+    let cred = Credential::new(&username, &password)?;
+    credential.change_password.change_password(&cred).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/users/{username}",
+    tag = "users",
+    params(
+        ("username" = String, Path, description = "Username")
+    ),
+    responses(
+        (status = 204, description = "User deleted"),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Not allowed on namespace", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json")
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+pub(crate) async fn delete_user(
+    State(AppState { credential, .. }): State<AppState>,
+    Path(username): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("requesting delete user");
+
+    let username = username.parse::<Username>()?;
+    credential.delete_user.delete_user(&username).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
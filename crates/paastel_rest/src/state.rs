@@ -15,9 +15,14 @@
 use std::sync::Arc;
 
 use derive_new::new;
+use paastel_app::AppApplication;
 use paastel_auth::AuthApplication;
+use paastel_kube::cache::SecretsCache;
 
 #[derive(new, Clone)]
 pub(crate) struct AppState {
     pub(crate) credential: Arc<AuthApplication>,
+    pub(crate) application: Arc<AppApplication>,
+    /// Cache of user secrets, none when users are not read from cluster
+    pub(crate) secrets_cache: Option<SecretsCache>,
 }
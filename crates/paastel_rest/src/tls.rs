@@ -0,0 +1,393 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{
+    collections::BTreeSet,
+    io::BufReader,
+    path::Path,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use axum::{extract::Request, Router};
+use hyper::body::Incoming;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::{conn::auto, graceful::GracefulShutdown},
+};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use rustls::{
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore,
+};
+use tokio::{net::TcpListener, time::Duration};
+use tokio_rustls::TlsAcceptor;
+use tower::ServiceExt;
+
+use crate::{config::TlsConfig, error::Error, utils};
+
+/// Time a client has to finish TLS handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Common name of a verified client certificate subject, set on requests
+/// of mutual TLS connections and used as username
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ClientIdentity(pub(crate) String);
+
+/// TLS settings of HTTPS listener, rebuilt when certificate, key or client
+/// CA change so renewals need no restart
+#[derive(Clone)]
+pub(crate) struct TlsReloader {
+    tls: TlsConfig,
+    current: Arc<RwLock<Arc<rustls::ServerConfig>>>,
+}
+
+impl TlsReloader {
+    pub(crate) fn new(tls: &TlsConfig) -> Result<Self, Error> {
+        Ok(Self {
+            tls: tls.clone(),
+            current: Arc::new(RwLock::new(server_config(tls)?)),
+        })
+    }
+
+    /// Acceptor of latest settings, open connections keep previous ones
+    pub(crate) fn acceptor(&self) -> TlsAcceptor {
+        let current =
+            self.current.read().unwrap_or_else(PoisonError::into_inner);
+        TlsAcceptor::from(current.clone())
+    }
+
+    /// Rebuilds settings from files, previous ones are kept on error
+    pub(crate) fn reload(&self) -> Result<(), Error> {
+        let config = server_config(&self.tls)?;
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = config;
+        Ok(())
+    }
+
+    /// Watches directories of TLS files and reloads on any change there,
+    /// kubernetes replaces mounted secrets by swapping a symlink
+    pub(crate) fn watch(&self) -> Result<RecommendedWatcher, Error> {
+        let reloader = self.clone();
+        let mut watcher = notify::recommended_watcher(
+            move |event: notify::Result<notify::Event>| match event {
+                Ok(event) if event.kind.is_access() => {}
+                Ok(_) => match reloader.reload() {
+                    Ok(()) => tracing::info!("reloaded tls certificate"),
+                    Err(e) => {
+                        tracing::warn!("keep previous tls certificate, {e}")
+                    }
+                },
+                Err(e) => tracing::warn!("watch tls files {e}"),
+            },
+        )
+        .map_err(|e| Error::tls(self.tls.cert(), e))?;
+
+        let paths = [
+            Some(self.tls.cert()),
+            Some(self.tls.key()),
+            self.tls.client_ca(),
+        ];
+        let dirs: BTreeSet<&Path> = paths
+            .into_iter()
+            .flatten()
+            .map(|path| match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            })
+            .collect();
+        for dir in dirs {
+            watcher
+                .watch(dir, RecursiveMode::NonRecursive)
+                .map_err(|e| Error::tls(dir, e))?;
+        }
+        Ok(watcher)
+    }
+}
+
+/// Settings of HTTPS listener, negotiates HTTP/2 or HTTP/1.1 over ALPN.
+/// Client certificates are optional so password and token users keep
+/// working on mutual TLS.
+fn server_config(tls: &TlsConfig) -> Result<Arc<rustls::ServerConfig>, Error> {
+    let certs = load_certs(tls.cert())?;
+    let key = load_key(tls.key())?;
+    // NOTE: ring and aws-lc-rs are both linked, provider must be explicit
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| Error::tls(tls.cert(), e))?;
+    let builder = match tls.client_ca() {
+        Some(path) => {
+            builder.with_client_cert_verifier(client_verifier(path, provider)?)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| Error::tls(tls.key(), e))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+fn client_verifier(
+    path: &Path,
+    provider: Arc<CryptoProvider>,
+) -> Result<Arc<dyn rustls::server::danger::ClientCertVerifier>, Error> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(cert).map_err(|e| Error::tls(path, e))?;
+    }
+    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+        .allow_unauthenticated()
+        .build()
+        .map_err(|e| Error::tls(path, e))
+}
+
+/// Identity of verified client certificate, `None` without common name
+fn client_identity(cert: &CertificateDer<'_>) -> Option<ClientIdentity> {
+    let (_, cert) = x509_parser::parse_x509_certificate(cert.as_ref()).ok()?;
+    let common_name = cert.subject().iter_common_name().next()?;
+    Some(ClientIdentity(common_name.as_str().ok()?.to_string()))
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, Error> {
+    let file = std::fs::File::open(path).map_err(|e| Error::tls(path, e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::tls(path, e))?;
+    if certs.is_empty() {
+        return Err(Error::tls(path, "no certificate found"));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>, Error> {
+    let file = std::fs::File::open(path).map_err(|e| Error::tls(path, e))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| Error::tls(path, e))?
+        .ok_or_else(|| Error::tls(path, "no private key found"))
+}
+
+/// Serves `app` over TLS until shutdown signal, then waits open
+/// connections to finish
+pub(crate) async fn serve(
+    listener: TcpListener,
+    app: Router,
+    reloader: TlsReloader,
+) -> Result<(), Error> {
+    let _watcher = reloader.watch()?;
+    let graceful = GracefulShutdown::new();
+    let shutdown = utils::shutdown_signal();
+    tokio::pin!(shutdown);
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    // NOTE: ex: too many open files, keep serving others
+                    tracing::warn!("accept connection {e}");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+
+        let acceptor = reloader.acceptor();
+        let app = app.clone();
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            let stream = match tokio::time::timeout(
+                HANDSHAKE_TIMEOUT,
+                acceptor.accept(stream),
+            )
+            .await
+            {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    tracing::debug!(%peer, "tls handshake {e}");
+                    return;
+                }
+                Err(_) => {
+                    tracing::debug!(%peer, "tls handshake timeout");
+                    return;
+                }
+            };
+            let identity = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .and_then(client_identity);
+            let service = hyper::service::service_fn(
+                move |mut request: Request<Incoming>| {
+                    if let Some(identity) = &identity {
+                        request.extensions_mut().insert(identity.clone());
+                    }
+                    app.clone().oneshot(request)
+                },
+            );
+            let builder = auto::Builder::new(TokioExecutor::new());
+            let conn = builder
+                .serve_connection_with_upgrades(TokioIo::new(stream), service);
+            if let Err(e) = watcher.watch(conn.into_owned()).await {
+                tracing::debug!(%peer, "serve connection {e}");
+            }
+        });
+    }
+
+    graceful.shutdown().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{routing::get, Extension};
+    use rcgen::{
+        BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose,
+        IsCa, KeyPair,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    fn params(common_name: &str) -> CertificateParams {
+        let mut params =
+            CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, common_name);
+        params
+    }
+
+    fn write_self_signed(dir: &Path, common_name: &str) -> TlsConfig {
+        let key = KeyPair::generate().unwrap();
+        let cert = params(common_name).self_signed(&key).unwrap();
+        let (cert_path, key_path) = (dir.join("tls.crt"), dir.join("tls.key"));
+        std::fs::write(&cert_path, cert.pem()).unwrap();
+        std::fs::write(&key_path, key.serialize_pem()).unwrap();
+        TlsConfig::new(cert_path, key_path)
+    }
+
+    fn current(reloader: &TlsReloader) -> Arc<rustls::ServerConfig> {
+        reloader.current.read().unwrap().clone()
+    }
+
+    #[test]
+    fn reload_keeps_previous_on_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let tls = write_self_signed(dir.path(), "paastel");
+        let reloader = TlsReloader::new(&tls).unwrap();
+        let first = current(&reloader);
+
+        write_self_signed(dir.path(), "paastel");
+        reloader.reload().unwrap();
+        let second = current(&reloader);
+        assert!(!Arc::ptr_eq(&first, &second));
+
+        std::fs::write(tls.key(), "").unwrap();
+        assert!(matches!(
+            reloader.reload(),
+            Err(Error::Tls { ref path, .. }) if path == tls.key()
+        ));
+        assert!(Arc::ptr_eq(&second, &current(&reloader)));
+    }
+
+    #[test]
+    fn missing_files_report_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert = dir.path().join("tls.crt");
+        let key = dir.path().join("tls.key");
+
+        let missing = TlsReloader::new(&TlsConfig::new(&cert, &key));
+        assert!(
+            matches!(missing, Err(Error::Tls { ref path, .. }) if path == &cert)
+        );
+
+        std::fs::write(&cert, "not a certificate").unwrap();
+        let empty = TlsReloader::new(&TlsConfig::new(&cert, &key));
+        assert!(matches!(
+            empty,
+            Err(Error::Tls { ref path, ref reason })
+                if path == &cert && reason == "no certificate found"
+        ));
+    }
+
+    #[tokio::test]
+    async fn client_certificate_identity() {
+        let dir = tempfile::tempdir().unwrap();
+        let tls = write_self_signed(dir.path(), "paastel");
+
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = params("paastel ca");
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        let ca_path = dir.path().join("ca.crt");
+        std::fs::write(&ca_path, ca.pem()).unwrap();
+
+        let client_key = KeyPair::generate().unwrap();
+        let mut client_params = params("ci-agent");
+        client_params.extended_key_usages =
+            vec![ExtendedKeyUsagePurpose::ClientAuth];
+        let client =
+            client_params.signed_by(&client_key, &ca, &ca_key).unwrap();
+
+        let reloader =
+            TlsReloader::new(&tls.clone().with_client_ca(&ca_path)).unwrap();
+        let app = Router::new().route(
+            "/whoami",
+            get(|identity: Option<Extension<ClientIdentity>>| async move {
+                identity
+                    .map(|Extension(identity)| identity.0)
+                    .unwrap_or_default()
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, app, reloader));
+
+        let mut roots = RootCertStore::empty();
+        roots.add_parsable_certificates(load_certs(tls.cert()).unwrap());
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_client_auth_cert(
+                vec![client.der().clone()],
+                PrivateKeyDer::try_from(client_key.serialize_der()).unwrap(),
+            )
+            .unwrap();
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut stream = tokio_rustls::TlsConnector::from(Arc::new(config))
+            .connect(
+                rustls::pki_types::ServerName::try_from("localhost").unwrap(),
+                stream,
+            )
+            .await
+            .unwrap();
+        stream
+            .write_all(
+                b"GET /whoami HTTP/1.1\r\nhost: localhost\r\n\
+                connection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("ci-agent"));
+    }
+}
@@ -12,7 +12,17 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-use tokio::signal;
+use std::net::SocketAddr;
+
+use tokio::{net::TcpListener, signal};
+
+use crate::error::Error;
+
+pub(crate) async fn bind(addr: SocketAddr) -> Result<TcpListener, Error> {
+    TcpListener::bind(addr)
+        .await
+        .map_err(|source| Error::Bind { addr, source })
+}
 
 pub(crate) async fn shutdown_signal() {
     let ctrl_c = async {
@@ -0,0 +1,119 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::path::{Path, PathBuf};
+
+use derive_new::new;
+use serde::{Deserialize, Serialize};
+
+use crate::{CredentialRef, Namespace};
+
+/// PaaStel server used by cli, ex: staging or production cluster
+#[derive(new, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Context {
+    /// Url of PaaStel api, ex: `https://paastel.example.com`
+    api: String,
+
+    /// Url of PaaStel websockets, ex: `wss://paastel.example.com`
+    #[new(default)]
+    wss: Option<String>,
+
+    /// Username of credentials
+    #[new(default)]
+    username: Option<String>,
+
+    /// Reference to tokens issued on login
+    #[new(default)]
+    credential: Option<CredentialRef>,
+
+    /// Namespace of applications when not given
+    #[new(default)]
+    #[serde(default)]
+    namespace: Namespace,
+
+    /// PEM bundle of certificate authorities trusted for api besides the
+    /// system ones
+    #[new(default)]
+    ca_bundle: Option<PathBuf>,
+}
+
+impl Context {
+    /// Return api url
+    pub fn api(&self) -> &str {
+        self.api.as_str()
+    }
+
+    /// Return websockets url
+    pub fn wss(&self) -> Option<&str> {
+        self.wss.as_deref()
+    }
+
+    /// Return username of credentials
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+
+    /// Return reference to tokens issued on login
+    pub fn credential(&self) -> Option<&CredentialRef> {
+        self.credential.as_ref()
+    }
+
+    /// Return namespace
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    /// Return CA bundle trusted for api
+    pub fn ca_bundle(&self) -> Option<&Path> {
+        self.ca_bundle.as_deref()
+    }
+
+    pub fn with_api(mut self, api: impl Into<String>) -> Self {
+        self.api = api.into();
+        self
+    }
+
+    pub fn with_wss(mut self, wss: impl Into<String>) -> Self {
+        self.wss = Some(wss.into());
+        self
+    }
+
+    pub fn with_namespace(mut self, namespace: Namespace) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    pub fn with_ca_bundle(mut self, ca_bundle: PathBuf) -> Self {
+        self.ca_bundle = Some(ca_bundle);
+        self
+    }
+
+    /// Set username without tokens, ex: settings from before login issued
+    /// tokens
+    pub fn with_username(mut self, username: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self
+    }
+
+    /// Set username authenticated by tokens on `credential`
+    pub fn with_login(
+        mut self,
+        username: impl Into<String>,
+        credential: CredentialRef,
+    ) -> Self {
+        self.username = Some(username.into());
+        self.credential = Some(credential);
+        self
+    }
+}
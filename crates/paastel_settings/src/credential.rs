@@ -0,0 +1,77 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{fmt::Display, str::FromStr};
+
+use derive_new::new;
+use serde::{Deserialize, Serialize};
+
+/// Store keeping secrets of settings, ex: tokens issued on login
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretStore {
+    /// Keyring of operating system, ex: Secret Service on linux
+    Keyring,
+    /// File encrypted with a passphrase, used when there is no keyring
+    File,
+}
+
+impl Display for SecretStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Keyring => write!(f, "keyring"),
+            Self::File => write!(f, "file"),
+        }
+    }
+}
+
+impl FromStr for SecretStore {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "keyring" => Ok(Self::Keyring),
+            "file" => Ok(Self::File),
+            _ => Err(format!("unknown store `{value}`, keyring or file")),
+        }
+    }
+}
+
+/// Reference to a secret, settings never hold secret itself
+#[derive(new, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CredentialRef {
+    /// Store holding the secret
+    store: SecretStore,
+
+    /// Key of secret on store, ex: `admin@paastel.example.com`
+    key: String,
+}
+
+impl CredentialRef {
+    /// Return store holding the secret
+    pub fn store(&self) -> SecretStore {
+        self.store
+    }
+
+    /// Return key of secret on store
+    pub fn key(&self) -> &str {
+        self.key.as_str()
+    }
+}
+
+impl Display for CredentialRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.store, self.key)
+    }
+}
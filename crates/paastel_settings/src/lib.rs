@@ -12,11 +12,14 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 use std::{
+    collections::BTreeMap,
     fmt::{Debug, Display},
+    io::Write,
     path::Path,
+    sync::OnceLock,
 };
 
-use config::{Config, ConfigError, Environment, File};
+use config::{Config, Environment, File, FileFormat};
 use serde::{Deserialize, Serialize};
 use tracing::info;
 
@@ -26,25 +29,158 @@ pub use namespace::*;
 pub mod location;
 pub use location::*;
 
+pub mod context;
+pub use context::*;
+
+pub mod credential;
+pub use credential::*;
+
+pub mod error;
+pub use error::*;
+
+mod migration;
+
+pub mod token;
+pub use token::*;
+
+/// Version of settings written by this crate, older files are migrated on
+/// load
+pub const SETTINGS_VERSION: u32 = 2;
+
+/// Context holding server of settings written before contexts
+const LEGACY_CONTEXT: &str = "default";
+
+/// Permissions of settings file, it holds credentials
+#[cfg(unix)]
+const SETTINGS_MODE: u32 = 0o600;
+
 /// Represent PaaStel settings
 #[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
-    /// Currently namespace default is `workspace`
-    namespace: Namespace,
+    /// Version of settings format, see [`SETTINGS_VERSION`]
+    version: u32,
+
+    /// Name of context in use
+    current_context: Option<String>,
+
+    /// Servers known by name, ex: `staging` and `production`
+    contexts: BTreeMap<String, Context>,
+
+    /// Namespace of settings written before contexts
+    #[serde(skip_serializing)]
+    namespace: Option<Namespace>,
+
+    /// Url of api of settings written before contexts
+    #[serde(skip_serializing)]
+    api: Option<String>,
+
+    /// Username of settings written before contexts
+    #[serde(skip_serializing)]
+    username: Option<String>,
+
+    /// Websockets url of settings written before contexts
+    #[serde(skip_serializing)]
+    wss: Option<String>,
 
     /// Origin of data, now from memory or file
-    // #[serde(skip_serializing)]
+    #[serde(skip_serializing)]
     location: Location,
 }
 
 impl Settings {
-    /// Return namespace
+    /// Return name of context in use
+    pub fn current_context(&self) -> Option<&str> {
+        self.current_context.as_deref()
+    }
+
+    /// Return context in use
+    pub fn context(&self) -> Option<&Context> {
+        self.current_context
+            .as_ref()
+            .and_then(|name| self.contexts.get(name))
+    }
+
+    /// Return contexts by name
+    pub fn contexts(&self) -> &BTreeMap<String, Context> {
+        &self.contexts
+    }
+
+    /// Add context, replacing one with same name
+    pub fn set_context(&mut self, name: impl Into<String>, context: Context) {
+        self.contexts.insert(name.into(), context);
+    }
+
+    /// Use context on next commands, false when there is no such context
+    pub fn use_context(&mut self, name: &str) -> bool {
+        if !self.contexts.contains_key(name) {
+            return false;
+        }
+        self.current_context = Some(name.to_string());
+        true
+    }
+
+    /// Remove context, when in use no context is used after
+    pub fn remove_context(&mut self, name: &str) -> Option<Context> {
+        if self.current_context.as_deref() == Some(name) {
+            self.current_context = None;
+        }
+        self.contexts.remove(name)
+    }
+
+    /// Return namespace of context in use
     pub fn namespace(&self) -> &Namespace {
-        &self.namespace
+        static DEFAULT: OnceLock<Namespace> = OnceLock::new();
+        self.context()
+            .map(Context::namespace)
+            .unwrap_or_else(|| DEFAULT.get_or_init(Namespace::default))
+    }
+
+    /// Return api url of context in use
+    pub fn api(&self) -> Option<&str> {
+        self.context().map(Context::api)
+    }
+
+    /// Return websockets url of context in use
+    pub fn wss(&self) -> Option<&str> {
+        self.context().and_then(Context::wss)
+    }
+
+    /// Return username of context in use
+    pub fn username(&self) -> Option<&str> {
+        self.context().and_then(Context::username)
+    }
+
+    /// Return reference to tokens of context in use
+    pub fn credential(&self) -> Option<&CredentialRef> {
+        self.context().and_then(Context::credential)
+    }
+
+    /// Return CA bundle trusted by context in use
+    pub fn ca_bundle(&self) -> Option<&Path> {
+        self.context().and_then(Context::ca_bundle)
     }
 
-    pub fn namespace_mut(&mut self) -> &mut Namespace {
-        &mut self.namespace
+    /// Move server of settings written before contexts into a context
+    fn migrate_legacy(&mut self) {
+        let api = self.api.take();
+        let username = self.username.take();
+        let wss = self.wss.take();
+        let namespace = self.namespace.take();
+        let Some(api) = api.filter(|_| self.contexts.is_empty()) else {
+            return;
+        };
+
+        let mut context =
+            Context::new(api).with_namespace(namespace.unwrap_or_default());
+        if let Some(username) = username {
+            context = context.with_username(username);
+        }
+        if let Some(wss) = wss {
+            context = context.with_wss(wss);
+        }
+        self.set_context(LEGACY_CONTEXT, context);
+        self.use_context(LEGACY_CONTEXT);
     }
 
     /// Return location
@@ -57,42 +193,143 @@ impl Settings {
         &mut self.location
     }
 
-    fn from_location(loc: &Location) -> Result<Self, ConfigError> {
-        match loc.exists() {
-            true => Self::from_path(loc),
-            false => match loc.is_default_path() {
-                true => Ok(Self::default()),
-                false => Ok(Self {
-                    location: loc.clone(),
-                    ..Default::default()
-                }),
-            },
-        }
+    fn from_location(loc: &Location) -> Result<Self> {
+        let mut settings = match loc {
+            Location::File(path) if path.exists() => Self::from_path(path)?,
+            Location::File(_) | Location::Memory => Self::default(),
+            Location::Env => Self::from_env(None)?,
+        };
+        settings.location = loc.clone();
+        Ok(settings)
+    }
+
+    /// Loads PaaStel settings only from `PAASTEL_*` variables, or from
+    /// `vars` when given, nested keys are separated by `__`, ex:
+    /// `PAASTEL_CONTEXTS__PROD__API`
+    fn from_env(vars: Option<config::Map<String, String>>) -> Result<Self> {
+        tracing::debug!("load config from environment");
+
+        let s = Config::builder()
+            .add_source(environment().source(vars))
+            .build()?;
+        let mut settings: Self = s.try_deserialize()?;
+        settings.migrate_legacy();
+        settings.validate()?;
+        Ok(settings)
     }
-    /// Loads PaaStel settings from the specific location
-    fn from_path<P: AsRef<Path> + Debug>(p: P) -> Result<Self, ConfigError> {
+
+    /// Loads PaaStel settings from the specific location, older versions are
+    /// migrated
+    fn from_path<P: AsRef<Path> + Debug>(p: P) -> Result<Self> {
         let path = p.as_ref();
-        let location: Location = path.into();
 
         tracing::debug!(?path, "load config from file");
 
+        let text =
+            std::fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        let mut table: toml::Table =
+            toml::from_str(&text).map_err(|e| Error::Parse {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            })?;
+        migration::migrate(&mut table)?;
+        let text = toml::to_string(&table).map_err(|e| Error::Parse {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+
         let s = Config::builder()
-            // Required file path
-            .add_source(
-                File::with_name(
-                    path.to_str()
-                        .expect("failed convert path of settings to str"),
-                )
-                .required(true),
-            )
+            .add_source(File::from_str(&text, FileFormat::Toml))
             // Try loading fields from PAASTEL_ env prefix
-            .add_source(Environment::with_prefix("paastel"))
-            .set_override("location.file", location)?
+            .add_source(environment())
             .build()?;
 
         info!("Loaded from {p:?}");
 
-        s.try_deserialize()
+        let mut settings: Self = s.try_deserialize()?;
+        settings.migrate_legacy();
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Check values deserialized, errors point to offending key
+    pub fn validate(&self) -> Result<()> {
+        if let Some(name) = self.current_context.as_deref() {
+            if !self.contexts.contains_key(name) {
+                return Err(Error::invalid(
+                    "current_context",
+                    format!("names missing context `{name}`"),
+                ));
+            }
+        }
+        for (name, context) in &self.contexts {
+            let key = |field: &str| format!("contexts.{name}.{field}");
+            check_url(&key("api"), context.api(), &["http", "https"])?;
+            if let Some(wss) = context.wss() {
+                check_url(&key("wss"), wss, &["ws", "wss"])?;
+            }
+            if paastel_manifest::check_dns_label(
+                &key("namespace"),
+                context.namespace().as_ref(),
+            )
+            .is_err()
+            {
+                return Err(Error::invalid(
+                    key("namespace"),
+                    "must be lowercase alphanumeric or `-`, at most 63 \
+                    characters",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves PaaStel settings to its location atomically, file is only
+    /// readable by owner
+    pub fn save(&self) -> Result<()> {
+        self.validate()?;
+        let path = match &self.location {
+            Location::File(path) => path.as_path(),
+            Location::Memory => {
+                tracing::debug!("settings on memory, nothing saved");
+                return Ok(());
+            }
+            Location::Env => {
+                return Err(Error::ReadOnly(self.location.to_string()))
+            }
+        };
+
+        tracing::debug!(?path, "save config to file");
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        std::fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+        let mut settings = self.clone();
+        settings.version = SETTINGS_VERSION;
+        let text = toml::to_string(&settings).map_err(|e| Error::Parse {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+
+        // NOTE: rename over settings, readers never see a partial file
+        let partial =
+            path.with_extension(format!("tmp-{}", std::process::id()));
+        let mut options = std::fs::OpenOptions::new();
+        options.create(true).truncate(true).write(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, SETTINGS_MODE);
+        let result = options.open(&partial).and_then(|mut file| {
+            file.write_all(text.as_bytes())?;
+            file.sync_all()
+        });
+        let result = result.and_then(|()| std::fs::rename(&partial, path));
+        if let Err(e) = result {
+            let _ = std::fs::remove_file(&partial);
+            return Err(Error::io(path, e));
+        }
+
+        info!("Saved to {path:?}");
+
+        Ok(())
     }
 
     // /// Loads PaaStel settings from default file path
@@ -102,15 +339,191 @@ impl Settings {
 }
 
 impl TryFrom<&Location> for Settings {
-    type Error = ConfigError;
+    type Error = Error;
 
-    fn try_from(location: &Location) -> Result<Self, Self::Error> {
+    fn try_from(location: &Location) -> Result<Self> {
         Self::from_location(location)
     }
 }
 
+/// Variables overriding settings, ex: `PAASTEL_API`
+fn environment() -> Environment {
+    Environment::with_prefix("paastel")
+        .prefix_separator("_")
+        .separator("__")
+}
+
+fn check_url(key: &str, value: &str, schemes: &[&str]) -> Result<()> {
+    let url = url::Url::parse(value)
+        .map_err(|e| Error::invalid(key, format!("`{value}` {e}")))?;
+    if !schemes.contains(&url.scheme()) {
+        return Err(Error::invalid(
+            key,
+            format!("`{value}` scheme must be one of {}", schemes.join(", ")),
+        ));
+    }
+    Ok(())
+}
+
 impl Display for Settings {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "load from `{}` location", self.location())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let location: Location =
+            dir.path().join("paastel/settings.toml").into();
+        let mut settings = Settings::try_from(&location).unwrap();
+        let credential =
+            CredentialRef::new(SecretStore::Keyring, "admin@example".into());
+        let context = Context::new("https://paastel.example.com/".into())
+            .with_wss("wss://paastel.example.com/")
+            .with_login("admin", credential.clone());
+        settings.set_context("example", context);
+        assert!(settings.use_context("example"));
+        assert!(!settings.use_context("missing"));
+
+        settings.save().unwrap();
+
+        let loaded = Settings::try_from(&location).unwrap();
+        assert_eq!(loaded.api(), Some("https://paastel.example.com/"));
+        assert_eq!(loaded.wss(), Some("wss://paastel.example.com/"));
+        assert_eq!(loaded.username(), Some("admin"));
+        assert_eq!(loaded.credential(), Some(&credential));
+        assert_eq!(loaded.current_context(), Some("example"));
+        let text = std::fs::read_to_string(location.path().unwrap()).unwrap();
+        assert!(text.contains("store = \"keyring\""));
+        assert!(text.starts_with("version = 2"));
+        let files = std::fs::read_dir(dir.path().join("paastel")).unwrap();
+        assert_eq!(files.count(), 1);
+        assert_eq!(loaded.location(), &location);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let metadata = std::fs::metadata(location.path().unwrap()).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o777, SETTINGS_MODE);
+        }
+    }
+
+    #[test]
+    fn legacy_server_into_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+        std::fs::write(
+            &path,
+            "namespace = \"team\"\napi = \"http://localhost:8080\"\n\
+            username = \"admin\"\npassword = \"secret\"\n",
+        )
+        .unwrap();
+
+        let mut settings = Settings::try_from(&Location::from(&path)).unwrap();
+
+        assert_eq!(settings.current_context(), Some(LEGACY_CONTEXT));
+        assert_eq!(settings.api(), Some("http://localhost:8080"));
+        assert_eq!(settings.namespace().as_ref(), "team");
+        assert_eq!(settings.username(), Some("admin"));
+
+        settings.remove_context(LEGACY_CONTEXT);
+        assert_eq!(settings.current_context(), None);
+        assert_eq!(settings.api(), None);
+        assert!(settings.namespace() == &Namespace::default());
+    }
+
+    #[test]
+    fn legacy_cli_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+        std::fs::write(
+            &path,
+            "api = \"https://paastel.example.com\"\nnamespace = \"team\"\n\
+            password = \"c2VjcmV0\"\nusername = \"admin\"\nwss = \"\"\n\
+            location = \"/tmp/settings.toml\"\n",
+        )
+        .unwrap();
+        let location = Location::from(&path);
+
+        let settings = Settings::try_from(&location).unwrap();
+        settings.save().unwrap();
+        let settings = Settings::try_from(&location).unwrap();
+
+        assert_eq!(settings.current_context(), Some(LEGACY_CONTEXT));
+        assert_eq!(settings.username(), Some("admin"));
+        assert_eq!(settings.credential(), None);
+        assert_eq!(settings.wss(), None);
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("password"), "{text}");
+        assert!(!text.contains("c2VjcmV0"), "{text}");
+    }
+
+    #[test]
+    fn invalid_key_of_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+        std::fs::write(
+            &path,
+            "version = 2\ncurrent_context = \"prod\"\n\
+            [contexts.prod]\napi = \"ftp://paastel.example.com\"\n",
+        )
+        .unwrap();
+
+        let Err(e) = Settings::try_from(&Location::from(&path)) else {
+            panic!("invalid api accepted");
+        };
+
+        assert!(
+            matches!(&e, Error::Invalid { key, .. } if key == "contexts.prod.api"),
+            "{e}"
+        );
+    }
+
+    #[test]
+    fn env_only_settings() {
+        let vars = [
+            ("PAASTEL_CURRENT_CONTEXT", "ci"),
+            ("PAASTEL_CONTEXTS__CI__API", "https://paastel.example.com"),
+            ("PAASTEL_CONTEXTS__CI__NAMESPACE", "team"),
+            ("PAASTEL_CONTEXTS__CI__USERNAME", "deployer"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+
+        let mut settings = Settings::from_env(Some(vars)).unwrap();
+        settings.location = Location::Env;
+
+        assert_eq!(settings.api(), Some("https://paastel.example.com"));
+        assert_eq!(settings.namespace().as_ref(), "team");
+        assert_eq!(settings.username(), Some("deployer"));
+        assert!(matches!(settings.save(), Err(Error::ReadOnly(_))));
+
+        let legacy = [("PAASTEL_API", "http://localhost:8080")]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let settings = Settings::from_env(Some(legacy)).unwrap();
+        assert_eq!(settings.current_context(), Some(LEGACY_CONTEXT));
+    }
+
+    #[test]
+    fn memory_settings_not_saved() {
+        let mut settings = Settings::try_from(&Location::Memory).unwrap();
+        settings.set_context(
+            "local",
+            Context::new("http://localhost:8080".to_string()),
+        );
+        assert!(settings.use_context("local"));
+
+        settings.save().unwrap();
+
+        assert_eq!(settings.location(), &Location::Memory);
+        assert_eq!(settings.api(), Some("http://localhost:8080"));
+    }
+}
@@ -22,6 +22,12 @@ use serde::{Deserialize, Serialize};
 
 const DEFAULT_SETTINGS_PATH: &str = "paastel/settings.toml";
 
+/// Prefix of `--settings-file` reading settings only from environment
+const ENV_SCHEME: &str = "env:";
+
+/// Prefix of `--settings-file` using settings only on memory
+const MEMORY_SCHEME: &str = "memory:";
+
 /// Define where settings from
 #[derive(
     Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord,
@@ -29,6 +35,11 @@ const DEFAULT_SETTINGS_PATH: &str = "paastel/settings.toml";
 pub enum Location {
     /// When save or another irect with settings this is location
     File(PathBuf),
+    /// Settings only on memory, save does nothing, ex: tests
+    Memory,
+    /// Settings only from `PAASTEL_*` variables, can not be saved, ex: CI
+    /// jobs
+    Env,
 }
 
 impl Location {
@@ -43,12 +54,22 @@ impl Location {
     pub fn is_default_path(&self) -> bool {
         match self {
             Self::File(loc) => loc == &Self::default_path(),
+            Self::Memory | Self::Env => false,
         }
     }
 
     pub fn exists(&self) -> bool {
         match self {
             Self::File(loc) => loc.exists(),
+            Self::Memory | Self::Env => false,
+        }
+    }
+
+    /// Return path of file, `None` when settings are not on a file
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::File(loc) => Some(loc.as_path()),
+            Self::Memory | Self::Env => None,
         }
     }
 }
@@ -63,14 +84,8 @@ impl Display for Location {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::File(loc) => write!(f, "{}", loc.as_path().display()),
-        }
-    }
-}
-
-impl AsRef<Path> for Location {
-    fn as_ref(&self) -> &Path {
-        match self {
-            Self::File(loc) => loc.as_path(),
+            Self::Memory => write!(f, "{MEMORY_SCHEME}"),
+            Self::Env => write!(f, "{ENV_SCHEME}"),
         }
     }
 }
@@ -86,7 +101,11 @@ impl From<Location> for Value {
 
 impl From<String> for Location {
     fn from(value: String) -> Self {
-        Self::File(Path::new(&value).to_path_buf())
+        match value.as_str() {
+            ENV_SCHEME => Self::Env,
+            MEMORY_SCHEME => Self::Memory,
+            _ => Self::File(Path::new(&value).to_path_buf()),
+        }
     }
 }
 
@@ -0,0 +1,118 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use toml::{Table, Value};
+
+use crate::{Error, Result, SETTINGS_VERSION};
+
+/// Keys of legacy cli settings that may be written empty
+const LEGACY_CLI_KEYS: [&str; 5] =
+    ["api", "namespace", "password", "username", "wss"];
+
+/// Upgrade settings of older versions to current version in place, files
+/// without version are from before versioning
+pub(crate) fn migrate(table: &mut Table) -> Result<()> {
+    let version = match table.get("version") {
+        None => 1,
+        Some(Value::Integer(version)) if *version > 0 => *version as u32,
+        Some(_) => {
+            return Err(Error::invalid("version", "must be a positive integer"))
+        }
+    };
+    if version > SETTINGS_VERSION {
+        return Err(Error::invalid(
+            "version",
+            format!(
+                "{version} is newer than supported {SETTINGS_VERSION}, \
+                upgrade paastel"
+            ),
+        ));
+    }
+
+    if version == 1 && table.contains_key("location") {
+        migrate_legacy_cli(table);
+    }
+    // NOTE: settings never hold secrets, passwords of version 1 are not
+    // kept anywhere and login issues tokens kept on a credential store
+    if version == 1 && table.remove("password").is_some() {
+        tracing::warn!(
+            "password of legacy settings dropped, run `paastel login`"
+        );
+    }
+    // NOTE: servers on top level of version 1 move into a context on load
+    table.insert("version".to_string(), Value::from(SETTINGS_VERSION));
+    Ok(())
+}
+
+/// Settings written by cli before `paastel_settings`, empty strings for
+/// unset keys and tokens inline
+fn migrate_legacy_cli(table: &mut Table) {
+    tracing::debug!("migrate legacy cli settings");
+
+    table.remove("location");
+    if table.remove("token").is_some() {
+        tracing::warn!("tokens of legacy settings dropped, login again");
+    }
+    for key in LEGACY_CLI_KEYS {
+        if matches!(table.get(key), Some(Value::String(value)) if value.trim().is_empty())
+        {
+            table.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_cli_password_dropped() {
+        let mut table: Table = toml::from_str(
+            r#"
+            api = "http://localhost:8080"
+            namespace = "workspace"
+            password = "c2VjcmV0"
+            username = "admin"
+            wss = ""
+            location = "/home/user/.config/paastel/settings.toml"
+
+            [token]
+            access_token = "access"
+            expiry = 0
+            refresh_token = "refresh"
+            token_type = "Bearer"
+            "#,
+        )
+        .unwrap();
+
+        migrate(&mut table).unwrap();
+
+        assert!(!table.contains_key("password"));
+        assert_eq!(table["version"].as_integer(), Some(2));
+        assert!(!table.contains_key("token"));
+        assert!(!table.contains_key("location"));
+        assert!(!table.contains_key("wss"));
+    }
+
+    #[test]
+    fn invalid_versions() {
+        let mut table: Table = toml::from_str("version = 99").unwrap();
+        let e = migrate(&mut table).unwrap_err();
+        assert!(matches!(e, Error::Invalid { key, .. } if key == "version"));
+
+        let mut table: Table = toml::from_str("version = 0").unwrap();
+        let e = migrate(&mut table).unwrap_err();
+        assert!(matches!(e, Error::Invalid { key, .. } if key == "version"));
+    }
+}
@@ -0,0 +1,68 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use derive_new::new;
+use serde::{Deserialize, Serialize};
+
+/// Tokens issued by PaaStel api on login
+#[derive(new, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Token {
+    /// Sent as `Authorization: Bearer` on requests
+    access_token: String,
+
+    /// Seconds since unix epoch when access token expires
+    expiry: u64,
+
+    /// Exchanged for a new access token once expired
+    refresh_token: String,
+
+    /// Type of access token, ex: `Bearer`
+    token_type: String,
+}
+
+impl Token {
+    /// Return access token
+    pub fn access_token(&self) -> &str {
+        self.access_token.as_str()
+    }
+
+    /// Return seconds since unix epoch when access token expires
+    pub fn expiry(&self) -> u64 {
+        self.expiry
+    }
+
+    /// Return refresh token
+    pub fn refresh_token(&self) -> &str {
+        self.refresh_token.as_str()
+    }
+
+    /// Return type of access token
+    pub fn token_type(&self) -> &str {
+        self.token_type.as_str()
+    }
+
+    /// Whether access token expires before `now`, seconds since unix epoch
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expiry
+    }
+}
+
+impl std::fmt::Debug for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Token")
+            .field("expiry", &self.expiry)
+            .field("token_type", &self.token_type)
+            .finish_non_exhaustive()
+    }
+}
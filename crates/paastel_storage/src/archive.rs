@@ -0,0 +1,868 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use derive_new::new;
+use futures::TryStreamExt;
+use paastel_app::{
+    ArchiveFormat, ArchiveInfo, ArchiveWriter, BlobStream, OutgoingArchivePort,
+    SourcePath,
+};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+
+/// Unix file type bits of a symbolic link, as stored on zip entries
+const S_IFLNK: u32 = 0o120000;
+
+/// Mask of unix file type bits
+const S_IFMT: u32 = 0o170000;
+
+/// Longest link target read from a zip entry
+const MAX_LINK_LENGTH: u64 = 4096;
+
+/// Size of tar blocks, entries are padded to it
+const TAR_BLOCK_SIZE: u64 = 512;
+
+/// Compression ratio is only checked beyond this unpacked size
+const RATIO_MIN_SIZE: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error("io error {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Invalid(String),
+}
+
+impl From<ArchiveError> for paastel_app::Error {
+    fn from(e: ArchiveError) -> Self {
+        match e {
+            ArchiveError::Io(e) => paastel_app::Error::Storage(e.to_string()),
+            ArchiveError::Invalid(e) => paastel_app::Error::InvalidArchive(e),
+        }
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> ArchiveError {
+    ArchiveError::Invalid(reason.into())
+}
+
+/// Bounds on content of an archive, protect against zip bombs
+#[derive(Debug, Clone, Copy, new)]
+pub struct ArchiveLimits {
+    max_entries: u64,
+    /// Bytes of all entries once unpacked
+    max_unpacked_size: u64,
+    /// Unpacked size divided by archive size
+    max_ratio: u64,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 100_000,
+            max_unpacked_size: 2 * 1024 * 1024 * 1024,
+            max_ratio: 200,
+        }
+    }
+}
+
+/// Check archives by reading every entry, nothing is written to disk but a
+/// temporary copy of archive
+#[derive(Debug, Clone, Default, new)]
+pub struct ArchiveAdapter {
+    limits: ArchiveLimits,
+}
+
+#[async_trait]
+impl OutgoingArchivePort for ArchiveAdapter {
+    async fn inspect(
+        &self,
+        format: ArchiveFormat,
+        mut body: BlobStream,
+    ) -> paastel_app::Result<ArchiveInfo> {
+        // NOTE: zip central directory is at the end, archive is spooled
+        let file = tempfile::tempfile().map_err(ArchiveError::from)?;
+        let mut file = tokio::fs::File::from_std(file);
+        while let Some(chunk) =
+            body.try_next().await.map_err(ArchiveError::from)?
+        {
+            tokio::io::AsyncWriteExt::write_all(&mut file, &chunk)
+                .await
+                .map_err(ArchiveError::from)?;
+        }
+        let file = file.into_std().await;
+
+        let limits = self.limits;
+        let info =
+            tokio::task::spawn_blocking(move || inspect(format, file, limits))
+                .await
+                .map_err(|e| paastel_app::Error::Storage(e.to_string()))??;
+        Ok(info)
+    }
+
+    async fn create_writer(
+        &self,
+    ) -> paastel_app::Result<Box<dyn ArchiveWriter>> {
+        Ok(Box::new(TarGzWriter::new()?))
+    }
+}
+
+/// Build tar.gz into a temporary file, compressed bytes are buffered in
+/// memory only until entry is appended
+pub struct TarGzWriter {
+    builder: Option<tar::Builder<flate2::write::GzEncoder<Vec<u8>>>>,
+    file: tokio::fs::File,
+}
+
+impl TarGzWriter {
+    pub fn new() -> Result<Self, ArchiveError> {
+        let encoder = flate2::GzBuilder::new()
+            .mtime(0)
+            .write(Vec::new(), flate2::Compression::default());
+        Ok(Self {
+            builder: Some(tar::Builder::new(encoder)),
+            file: tokio::fs::File::from_std(tempfile::tempfile()?),
+        })
+    }
+
+    fn builder(
+        &mut self,
+    ) -> Result<
+        &mut tar::Builder<flate2::write::GzEncoder<Vec<u8>>>,
+        ArchiveError,
+    > {
+        self.builder
+            .as_mut()
+            .ok_or_else(|| invalid("archive already finished"))
+    }
+
+    /// Move compressed bytes to file
+    async fn flush(&mut self) -> Result<(), ArchiveError> {
+        let buffer = std::mem::take(self.builder()?.get_mut().get_mut());
+        self.file.write_all(&buffer).await?;
+        Ok(())
+    }
+
+    /// Header of entry without owners nor timestamps
+    fn header(entry_type: tar::EntryType, mode: u32, size: u64) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_mode(mode);
+        header.set_size(size);
+        header.set_mtime(0);
+        header.set_uid(0);
+        header.set_gid(0);
+        header
+    }
+}
+
+#[async_trait]
+impl ArchiveWriter for TarGzWriter {
+    fn format(&self) -> ArchiveFormat {
+        ArchiveFormat::TarGz
+    }
+
+    async fn append_file(
+        &mut self,
+        path: &SourcePath,
+        executable: bool,
+        size: u64,
+        mut body: BlobStream,
+    ) -> paastel_app::Result<()> {
+        let mode = if executable { 0o755 } else { 0o644 };
+        let mut header = Self::header(tar::EntryType::Regular, mode, size);
+        // NOTE: header only, content is streamed after it
+        self.builder()?
+            .append_data(&mut header, path.as_ref(), std::io::empty())
+            .map_err(ArchiveError::from)?;
+
+        let mut written = 0;
+        while let Some(chunk) =
+            body.try_next().await.map_err(ArchiveError::from)?
+        {
+            written += chunk.len() as u64;
+            if written > size {
+                break;
+            }
+            self.builder()?
+                .get_mut()
+                .write_all(&chunk)
+                .map_err(ArchiveError::from)?;
+            self.flush().await?;
+        }
+        if written != size {
+            return Err(ArchiveError::Io(std::io::Error::other(format!(
+                "{path} has {written} bytes, expected {size}"
+            )))
+            .into());
+        }
+
+        let padding = (TAR_BLOCK_SIZE - size % TAR_BLOCK_SIZE) % TAR_BLOCK_SIZE;
+        self.builder()?
+            .get_mut()
+            .write_all(&vec![0; padding as usize])
+            .map_err(ArchiveError::from)?;
+        Ok(self.flush().await?)
+    }
+
+    async fn append_symlink(
+        &mut self,
+        path: &SourcePath,
+        target: &str,
+    ) -> paastel_app::Result<()> {
+        let mut header = Self::header(tar::EntryType::Symlink, 0o777, 0);
+        self.builder()?
+            .append_link(&mut header, path.as_ref(), target)
+            .map_err(ArchiveError::from)?;
+        Ok(self.flush().await?)
+    }
+
+    async fn finish(&mut self) -> paastel_app::Result<BlobStream> {
+        let builder = self
+            .builder
+            .take()
+            .ok_or_else(|| invalid("archive already finished"))?;
+        let rest = builder
+            .into_inner()
+            .and_then(|encoder| encoder.finish())
+            .map_err(ArchiveError::from)?;
+        self.file
+            .write_all(&rest)
+            .await
+            .map_err(ArchiveError::from)?;
+        self.file.flush().await.map_err(ArchiveError::from)?;
+
+        let mut file =
+            self.file.try_clone().await.map_err(ArchiveError::from)?;
+        file.rewind().await.map_err(ArchiveError::from)?;
+        Ok(Box::pin(ReaderStream::new(file)))
+    }
+}
+
+/// Read whole archive and check each entry against limits
+fn inspect(
+    format: ArchiveFormat,
+    mut file: File,
+    limits: ArchiveLimits,
+) -> Result<ArchiveInfo, ArchiveError> {
+    file.flush()?;
+    let packed_size = file.seek(SeekFrom::End(0))?;
+    file.rewind()?;
+
+    let mut counter = Counter::new(limits, packed_size);
+    let mut tree = Tree::default();
+    match format {
+        ArchiveFormat::Zip => inspect_zip(file, &mut counter, &mut tree)?,
+        ArchiveFormat::TarGz => inspect_tar(
+            flate2::read::GzDecoder::new(file),
+            &mut counter,
+            &mut tree,
+        )?,
+        ArchiveFormat::TarZst => {
+            let decoder = zstd::stream::read::Decoder::new(file)
+                .map_err(|e| invalid(format!("not a tar.zst archive {e}")))?;
+            inspect_tar(decoder, &mut counter, &mut tree)?
+        }
+    }
+    tree.check()?;
+
+    Ok(ArchiveInfo::new(
+        format,
+        counter.entries,
+        counter.unpacked_size,
+    ))
+}
+
+/// Entries and unpacked bytes seen so far
+#[derive(Debug)]
+struct Counter {
+    limits: ArchiveLimits,
+    packed_size: u64,
+    entries: u64,
+    unpacked_size: u64,
+}
+
+impl Counter {
+    fn new(limits: ArchiveLimits, packed_size: u64) -> Self {
+        Self {
+            limits,
+            packed_size,
+            entries: 0,
+            unpacked_size: 0,
+        }
+    }
+
+    fn entry(&mut self) -> Result<(), ArchiveError> {
+        self.entries += 1;
+        if self.entries > self.limits.max_entries {
+            return Err(invalid(format!(
+                "more than {} entries",
+                self.limits.max_entries
+            )));
+        }
+        Ok(())
+    }
+
+    /// Read content of entry, sizes declared on headers are not trusted
+    fn content(&mut self, reader: impl Read) -> Result<(), ArchiveError> {
+        let remaining = self.limits.max_unpacked_size - self.unpacked_size;
+        let read = std::io::copy(
+            &mut reader.take(remaining + 1),
+            &mut std::io::sink(),
+        )?;
+        self.unpacked_size += read;
+        if read > remaining {
+            return Err(invalid(format!(
+                "unpacked size beyond {} bytes",
+                self.limits.max_unpacked_size
+            )));
+        }
+
+        let max_size = self.packed_size.saturating_mul(self.limits.max_ratio);
+        if self.unpacked_size > RATIO_MIN_SIZE && self.unpacked_size > max_size
+        {
+            return Err(invalid(format!(
+                "compression ratio beyond {}",
+                self.limits.max_ratio
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Entry path stays inside root once unpacked
+fn check_path(path: &Path) -> Result<(), ArchiveError> {
+    let escapes = path.components().any(|c| {
+        matches!(
+            c,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_)
+        )
+    });
+    if escapes || path.as_os_str().is_empty() {
+        return Err(invalid(format!("entry {} escapes root", path.display())));
+    }
+    Ok(())
+}
+
+/// Link target, relative to directory of entry, stays inside root
+fn check_link(path: &Path, target: &Path) -> Result<(), ArchiveError> {
+    let mut depth = path
+        .parent()
+        .map(|parent| {
+            parent
+                .components()
+                .filter(|c| matches!(c, Component::Normal(_)))
+                .count()
+        })
+        .unwrap_or_default();
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => {
+                return Err(invalid(format!(
+                    "link {} to {} escapes root",
+                    path.display(),
+                    target.display()
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Paths of entries and symbolic links, checked once whole archive is
+/// read since a link may come before or after entries going through it
+#[derive(Debug, Default)]
+struct Tree {
+    /// Entries and targets of hard links
+    paths: Vec<PathBuf>,
+    /// Symbolic links by path, with their target
+    links: BTreeMap<PathBuf, PathBuf>,
+}
+
+impl Tree {
+    fn entry(&mut self, path: &Path) {
+        self.paths.push(normalize(path));
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) {
+        self.links.insert(normalize(path), target.to_path_buf());
+    }
+
+    /// Links are checked one by one against root, but once unpacked a
+    /// link to a directory moves whatever goes through it, ex: `a/l1 -> ..`
+    /// then `a/l1/l2 -> ..` escapes, so no path may go through a link
+    fn check(&self) -> Result<(), ArchiveError> {
+        let through_link = |path: &Path| {
+            path.ancestors()
+                .skip(1)
+                .find(|dir| self.links.contains_key(*dir))
+                .map(Path::to_path_buf)
+        };
+        for path in self.paths.iter().chain(self.links.keys()) {
+            if let Some(link) = through_link(path) {
+                return Err(invalid(format!(
+                    "entry {} goes through link {}",
+                    path.display(),
+                    link.display()
+                )));
+            }
+        }
+
+        for (path, target) in &self.links {
+            let mut resolved =
+                path.parent().map(Path::to_path_buf).unwrap_or_default();
+            let mut components = target.components().peekable();
+            while let Some(component) = components.next() {
+                match component {
+                    Component::Normal(name) => resolved.push(name),
+                    Component::ParentDir => {
+                        resolved.pop();
+                    }
+                    _ => continue,
+                }
+                if components.peek().is_some()
+                    && self.links.contains_key(&resolved)
+                {
+                    return Err(invalid(format!(
+                        "link {} to {} goes through link {}",
+                        path.display(),
+                        target.display(),
+                        resolved.display()
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Path without `.` components, `..` and root are rejected before
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect()
+}
+
+fn inspect_zip(
+    reader: impl Read + Seek,
+    counter: &mut Counter,
+    tree: &mut Tree,
+) -> Result<(), ArchiveError> {
+    let mut zip = zip::ZipArchive::new(reader)
+        .map_err(|e| invalid(format!("not a zip archive {e}")))?;
+    for i in 0..zip.len() {
+        let mut entry = zip
+            .by_index(i)
+            .map_err(|e| invalid(format!("invalid zip entry {e}")))?;
+        counter.entry()?;
+        let path =
+            entry
+                .enclosed_name()
+                .map(Path::to_path_buf)
+                .ok_or_else(|| {
+                    invalid(format!("entry {} escapes root", entry.name()))
+                })?;
+        check_path(&path)?;
+
+        let is_link = entry
+            .unix_mode()
+            .is_some_and(|mode| mode & S_IFMT == S_IFLNK);
+        if is_link {
+            let mut target = String::new();
+            (&mut entry)
+                .take(MAX_LINK_LENGTH)
+                .read_to_string(&mut target)?;
+            check_link(&path, Path::new(&target))?;
+            tree.symlink(&path, Path::new(&target));
+        } else {
+            counter.content(&mut entry)?;
+            tree.entry(&path);
+        }
+    }
+    Ok(())
+}
+
+fn inspect_tar(
+    reader: impl Read,
+    counter: &mut Counter,
+    tree: &mut Tree,
+) -> Result<(), ArchiveError> {
+    let mut tar = tar::Archive::new(reader);
+    let entries = tar
+        .entries()
+        .map_err(|e| invalid(format!("not a tar archive {e}")))?;
+    for entry in entries {
+        let entry =
+            entry.map_err(|e| invalid(format!("invalid tar entry {e}")))?;
+        counter.entry()?;
+        let path = entry
+            .path()
+            .map_err(|e| invalid(format!("invalid tar entry path {e}")))?
+            .to_path_buf();
+        check_path(&path)?;
+
+        match entry.header().entry_type() {
+            tar::EntryType::Regular
+            | tar::EntryType::Continuous
+            | tar::EntryType::Directory => {
+                counter.content(entry)?;
+                tree.entry(&path);
+            }
+            tar::EntryType::Symlink | tar::EntryType::Link => {
+                let target = entry
+                    .link_name()
+                    .map_err(|e| invalid(format!("invalid link {e}")))?
+                    .ok_or_else(|| {
+                        invalid(format!(
+                            "link {} without target",
+                            path.display()
+                        ))
+                    })?;
+                // NOTE: hard links are relative to root, not to entry
+                match entry.header().entry_type() {
+                    tar::EntryType::Link => {
+                        check_path(&target)?;
+                        tree.entry(&path);
+                        tree.entry(&target);
+                    }
+                    _ => {
+                        check_link(&path, &target)?;
+                        tree.symlink(&path, &target);
+                    }
+                }
+            }
+            entry_type => {
+                return Err(invalid(format!(
+                    "entry {} of type {entry_type:?} not allowed",
+                    path.display()
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use bytes::Bytes;
+    use futures::stream;
+    use zip::write::FileOptions;
+
+    use super::*;
+
+    fn body(content: Vec<u8>) -> BlobStream {
+        Box::pin(stream::iter(vec![Ok(Bytes::from(content))]))
+    }
+
+    fn tar_gz(build: impl FnOnce(&mut tar::Builder<Vec<u8>>)) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        build(&mut builder);
+        let tar = builder.into_inner().unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(&tar).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn file(builder: &mut tar::Builder<Vec<u8>>, path: &str, content: &[u8]) {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o755);
+        // NOTE: raw name, builder refuses `..` on set_path
+        header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+        header.set_cksum();
+        builder.append(&header, content).unwrap();
+    }
+
+    fn symlink(builder: &mut tar::Builder<Vec<u8>>, path: &str, target: &str) {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder.append_link(&mut header, path, target).unwrap();
+    }
+
+    async fn inspect_with(
+        limits: ArchiveLimits,
+        format: ArchiveFormat,
+        content: Vec<u8>,
+    ) -> paastel_app::Result<ArchiveInfo> {
+        ArchiveAdapter::new(limits)
+            .inspect(format, body(content))
+            .await
+    }
+
+    #[tokio::test]
+    async fn tar_gz_ok() -> paastel_app::Result<()> {
+        let archive = tar_gz(|builder| {
+            file(builder, "bin/start", b"#!/bin/sh");
+            symlink(builder, "bin/run", "start");
+            symlink(builder, "current", "bin/../bin/start");
+        });
+        let info = inspect_with(
+            ArchiveLimits::default(),
+            ArchiveFormat::TarGz,
+            archive,
+        )
+        .await?;
+        assert_eq!(info.entries(), 3);
+        assert_eq!(info.unpacked_size(), 9);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tar_zst_ok() -> paastel_app::Result<()> {
+        let mut builder = tar::Builder::new(Vec::new());
+        file(&mut builder, "main.rs", b"fn main() {}");
+        let tar = builder.into_inner().map_err(ArchiveError::from)?;
+        let archive = zstd::encode_all(Cursor::new(tar), 3)
+            .map_err(ArchiveError::from)?;
+
+        let info = inspect_with(
+            ArchiveLimits::default(),
+            ArchiveFormat::TarZst,
+            archive,
+        )
+        .await?;
+        assert_eq!(info.format(), ArchiveFormat::TarZst);
+        assert_eq!(info.unpacked_size(), 12);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tar_path_traversal() {
+        let archive = tar_gz(|builder| file(builder, "../evil", b"x"));
+        let result = inspect_with(
+            ArchiveLimits::default(),
+            ArchiveFormat::TarGz,
+            archive,
+        )
+        .await;
+        assert!(matches!(result, Err(paastel_app::Error::InvalidArchive(_))));
+    }
+
+    #[tokio::test]
+    async fn tar_symlink_escapes() {
+        for target in ["../../etc/passwd", "/etc/passwd"] {
+            let archive =
+                tar_gz(|builder| symlink(builder, "app/link", target));
+            let result = inspect_with(
+                ArchiveLimits::default(),
+                ArchiveFormat::TarGz,
+                archive,
+            )
+            .await;
+            assert!(
+                matches!(result, Err(paastel_app::Error::InvalidArchive(_))),
+                "{target}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn tar_through_symlink_escapes() {
+        let cases: [&[(&str, &str)]; 3] = [
+            &[("a/l1", ".."), ("a/l1/l2", "..")],
+            &[("a/l1/l2", ".."), ("a/l1", "..")],
+            &[("a/l1", ".."), ("b", "a/l1/..")],
+        ];
+        for links in cases {
+            let archive = tar_gz(|builder| {
+                for (path, target) in links {
+                    symlink(builder, path, target);
+                }
+            });
+            let result = inspect_with(
+                ArchiveLimits::default(),
+                ArchiveFormat::TarGz,
+                archive,
+            )
+            .await;
+            assert!(
+                matches!(result, Err(paastel_app::Error::InvalidArchive(_))),
+                "{links:?}"
+            );
+        }
+
+        let archive = tar_gz(|builder| {
+            symlink(builder, "static", "public");
+            file(builder, "static/index.html", b"<html>");
+        });
+        let result = inspect_with(
+            ArchiveLimits::default(),
+            ArchiveFormat::TarGz,
+            archive,
+        )
+        .await;
+        assert!(matches!(result, Err(paastel_app::Error::InvalidArchive(_))));
+    }
+
+    #[tokio::test]
+    async fn wrong_format() {
+        let archive = tar_gz(|builder| file(builder, "main.rs", b"x"));
+        let result =
+            inspect_with(ArchiveLimits::default(), ArchiveFormat::Zip, archive)
+                .await;
+        assert!(matches!(result, Err(paastel_app::Error::InvalidArchive(_))));
+    }
+
+    fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[tokio::test]
+    async fn zip_ok_and_limits() -> paastel_app::Result<()> {
+        let archive = zip(&[("a.txt", b"hello"), ("b.txt", b"world")]);
+
+        let info = inspect_with(
+            ArchiveLimits::default(),
+            ArchiveFormat::Zip,
+            archive.clone(),
+        )
+        .await?;
+        assert_eq!(info.entries(), 2);
+        assert_eq!(info.unpacked_size(), 10);
+
+        let too_many = inspect_with(
+            ArchiveLimits::new(1, 1024, 10),
+            ArchiveFormat::Zip,
+            archive.clone(),
+        )
+        .await;
+        assert!(matches!(
+            too_many,
+            Err(paastel_app::Error::InvalidArchive(_))
+        ));
+
+        let too_big = inspect_with(
+            ArchiveLimits::new(10, 8, 10),
+            ArchiveFormat::Zip,
+            archive,
+        )
+        .await;
+        assert!(matches!(
+            too_big,
+            Err(paastel_app::Error::InvalidArchive(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn zip_bomb_ratio() {
+        let zeros = vec![0; RATIO_MIN_SIZE as usize + 1];
+        let archive = zip(&[("zeros", &zeros)]);
+        let result =
+            inspect_with(ArchiveLimits::default(), ArchiveFormat::Zip, archive)
+                .await;
+        assert!(matches!(result, Err(paastel_app::Error::InvalidArchive(_))));
+    }
+
+    #[tokio::test]
+    async fn tar_gz_writer() -> paastel_app::Result<()> {
+        let mut writer = ArchiveAdapter::default().create_writer().await?;
+        let long_path = format!("{}/main.rs", "src".repeat(50));
+        writer
+            .append_file(
+                &"bin/start".parse()?,
+                true,
+                9,
+                body(b"#!/bin/sh".to_vec()),
+            )
+            .await?;
+        writer
+            .append_file(&long_path.parse()?, false, 0, body(Vec::new()))
+            .await?;
+        writer
+            .append_symlink(&"start".parse()?, "bin/start")
+            .await?;
+        let archive: Vec<bytes::Bytes> = writer
+            .finish()
+            .await?
+            .try_collect()
+            .await
+            .map_err(ArchiveError::from)?;
+        let archive = archive.concat();
+
+        let info = inspect_with(
+            ArchiveLimits::default(),
+            ArchiveFormat::TarGz,
+            archive.clone(),
+        )
+        .await?;
+        assert_eq!(info.entries(), 3);
+
+        let decoder = flate2::read::GzDecoder::new(archive.as_slice());
+        let entries: Vec<(String, u32)> = tar::Archive::new(decoder)
+            .entries()
+            .map_err(ArchiveError::from)?
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let path = entry.path().unwrap().display().to_string();
+                (path, entry.header().mode().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("bin/start".to_string(), 0o755),
+                (long_path, 0o644),
+                ("start".to_string(), 0o777)
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tar_gz_writer_size_mismatch() -> paastel_app::Result<()> {
+        let mut writer = TarGzWriter::new()?;
+        let result = writer
+            .append_file(
+                &"main.rs".parse()?,
+                false,
+                3,
+                body(b"fn main".to_vec()),
+            )
+            .await;
+        assert!(matches!(result, Err(paastel_app::Error::Storage(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn link_targets() {
+        let path = Path::new("a/b/link");
+        assert!(check_link(path, Path::new("../c")).is_ok());
+        assert!(check_link(path, Path::new("../../c")).is_ok());
+        assert!(check_link(path, Path::new("../../../c")).is_err());
+        assert!(check_link(path, Path::new("/c")).is_err());
+    }
+}
@@ -0,0 +1,496 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use async_trait::async_trait;
+use futures::{StreamExt, TryStreamExt};
+use paastel_app::{
+    BlobId, BlobInfo, BlobStream, OutgoingBlobStorePort,
+    OutgoingUploadStorePort, Upload, UploadId,
+};
+use tokio::{fs, io::AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+
+use crate::upload::{self, UploadRecord, UPLOAD_FILE};
+
+/// Prefix of partially written blobs, renamed once complete
+const PARTIAL_PREFIX: &str = ".partial-";
+
+/// Directory of resumable uploads, one directory per upload with its parts
+const UPLOADS_DIR: &str = "uploads";
+
+#[derive(Debug, thiserror::Error)]
+pub enum LocalError {
+    #[error("io error {0}")]
+    Io(#[from] std::io::Error),
+    #[error("json error {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl From<LocalError> for paastel_app::Error {
+    fn from(e: LocalError) -> Self {
+        match e {
+            LocalError::Io(e) if e.kind() == ErrorKind::NotFound => {
+                paastel_app::Error::BlobNotFound
+            }
+            e => paastel_app::Error::Storage(e.to_string()),
+        }
+    }
+}
+
+/// Store blobs as files in a local directory, for single node installs and
+/// tests without a S3 compatible server
+#[derive(Debug, Clone)]
+pub struct LocalAdapter {
+    root: PathBuf,
+}
+
+impl LocalAdapter {
+    /// Create root directory when missing
+    pub async fn new(root: impl Into<PathBuf>) -> Result<Self, LocalError> {
+        let root = root.into();
+        fs::create_dir_all(&root).await?;
+        let root = fs::canonicalize(root).await?;
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &PathBuf {
+        &self.root
+    }
+
+    fn path(&self, id: &BlobId) -> PathBuf {
+        self.root.join(id.as_ref())
+    }
+
+    fn upload_dir(&self, id: &UploadId) -> PathBuf {
+        self.root.join(UPLOADS_DIR).join(id.as_ref())
+    }
+
+    /// Write body to a partial file next to path, renamed once complete
+    async fn write(
+        &self,
+        path: PathBuf,
+        mut body: BlobStream,
+    ) -> Result<u64, LocalError> {
+        // NOTE: readers never see a partially written blob, each write has
+        // its own partial so parallel writes of same path do not mix
+        let (dir, name) = match (path.parent(), path.file_name()) {
+            (Some(dir), Some(name)) => (dir, name.to_string_lossy()),
+            _ => return Err(std::io::Error::other("invalid path").into()),
+        };
+        // NOTE: created with default permissions, staging jobs read blobs
+        let (file, partial) = tempfile::Builder::new()
+            .prefix(&format!("{PARTIAL_PREFIX}{name}-"))
+            .make_in(dir, |path| {
+                std::fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(path)
+            })?
+            .into_parts();
+        let mut file = fs::File::from_std(file);
+        let mut size = 0;
+        let written: Result<(), LocalError> = async {
+            while let Some(chunk) = body.try_next().await? {
+                file.write_all(&chunk).await?;
+                size += chunk.len() as u64;
+            }
+            file.sync_all().await?;
+            Ok(())
+        }
+        .await;
+
+        if let Err(e) = written {
+            if let Err(e) = partial.close() {
+                tracing::warn!(?path, "failed remove partial blob {e}");
+            }
+            return Err(e);
+        }
+
+        partial.persist(&path).map_err(|e| e.error)?;
+        Ok(size)
+    }
+
+    /// Offset and size of parts of upload, `None` when upload is missing
+    async fn read_upload(
+        &self,
+        id: &UploadId,
+    ) -> Result<Option<(Vec<u8>, Vec<(u64, u64)>)>, LocalError> {
+        let dir = self.upload_dir(id);
+        let json = match fs::read(dir.join(UPLOAD_FILE)).await {
+            Ok(json) => json,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut parts = Vec::new();
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(offset) =
+                entry.file_name().to_str().and_then(upload::part_offset)
+            {
+                parts.push((offset, entry.metadata().await?.len()));
+            }
+        }
+        Ok(Some((json, parts)))
+    }
+
+    /// Last write on upload directory or any of its files
+    async fn upload_modified(
+        &self,
+        dir: &Path,
+    ) -> Result<SystemTime, LocalError> {
+        let mut modified = fs::metadata(dir).await?.modified()?;
+        let mut entries = fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            modified = modified.max(entry.metadata().await?.modified()?);
+        }
+        Ok(modified)
+    }
+
+    async fn read_dir(&self) -> Result<Vec<BlobInfo>, LocalError> {
+        let mut blobs = Vec::new();
+        let mut entries = fs::read_dir(&self.root).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            let Some(id) = entry
+                .file_name()
+                .to_str()
+                .filter(|_| metadata.is_file())
+                .and_then(|name| name.parse::<BlobId>().ok())
+            else {
+                continue;
+            };
+            blobs.push(BlobInfo::new(id, metadata.len()));
+        }
+        blobs.sort_by(|a, b| a.id().cmp(b.id()));
+        Ok(blobs)
+    }
+}
+
+#[async_trait]
+impl OutgoingBlobStorePort for LocalAdapter {
+    async fn put(
+        &self,
+        id: &BlobId,
+        body: BlobStream,
+    ) -> paastel_app::Result<u64> {
+        Ok(self.write(self.path(id), body).await?)
+    }
+
+    async fn get(&self, id: &BlobId) -> paastel_app::Result<BlobStream> {
+        let file = fs::File::open(self.path(id))
+            .await
+            .map_err(LocalError::from)?;
+        Ok(Box::pin(ReaderStream::new(file)))
+    }
+
+    async fn head(&self, id: &BlobId) -> paastel_app::Result<BlobInfo> {
+        let metadata = fs::metadata(self.path(id))
+            .await
+            .map_err(LocalError::from)?;
+        Ok(BlobInfo::new(id.clone(), metadata.len()))
+    }
+
+    async fn delete(&self, id: &BlobId) -> paastel_app::Result<()> {
+        fs::remove_file(self.path(id))
+            .await
+            .map_err(LocalError::from)?;
+        Ok(())
+    }
+
+    async fn list(&self) -> paastel_app::Result<Vec<BlobInfo>> {
+        Ok(self.read_dir().await?)
+    }
+
+    /// File url, staging jobs must mount root directory on same path
+    async fn source_url(&self, id: &BlobId) -> paastel_app::Result<String> {
+        Ok(format!("file://{}", self.path(id).display()))
+    }
+}
+
+#[async_trait]
+impl OutgoingUploadStorePort for LocalAdapter {
+    async fn create_upload(&self, upload: &Upload) -> paastel_app::Result<()> {
+        let dir = self.upload_dir(upload.id());
+        let json = UploadRecord::to_json(upload).map_err(LocalError::from)?;
+        fs::create_dir_all(&dir).await.map_err(LocalError::from)?;
+        fs::write(dir.join(UPLOAD_FILE), json)
+            .await
+            .map_err(LocalError::from)?;
+        Ok(())
+    }
+
+    async fn find_upload(&self, id: &UploadId) -> paastel_app::Result<Upload> {
+        let (json, parts) = self
+            .read_upload(id)
+            .await?
+            .ok_or(paastel_app::Error::UploadNotFound)?;
+        UploadRecord::from_json(&json, &parts)
+    }
+
+    async fn put_part(
+        &self,
+        id: &UploadId,
+        offset: u64,
+        body: BlobStream,
+    ) -> paastel_app::Result<u64> {
+        let dir = self.upload_dir(id);
+        if !fs::try_exists(dir.join(UPLOAD_FILE))
+            .await
+            .map_err(LocalError::from)?
+        {
+            return Err(paastel_app::Error::UploadNotFound);
+        }
+        Ok(self
+            .write(dir.join(upload::part_name(offset)), body)
+            .await?)
+    }
+
+    async fn read_parts(
+        &self,
+        id: &UploadId,
+    ) -> paastel_app::Result<BlobStream> {
+        let (_, parts) = self
+            .read_upload(id)
+            .await?
+            .ok_or(paastel_app::Error::UploadNotFound)?;
+        let dir = self.upload_dir(id);
+        let paths: Vec<_> = upload::contiguous(parts)
+            .into_iter()
+            .map(|(offset, _)| dir.join(upload::part_name(offset)))
+            .collect();
+        let body = futures::stream::iter(paths)
+            .then(|path| async move {
+                fs::File::open(path).await.map(ReaderStream::new)
+            })
+            .try_flatten();
+        Ok(Box::pin(body))
+    }
+
+    async fn delete_upload(&self, id: &UploadId) -> paastel_app::Result<()> {
+        match fs::remove_dir_all(self.upload_dir(id)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                Err(paastel_app::Error::UploadNotFound)
+            }
+            Err(e) => Err(LocalError::from(e).into()),
+        }
+    }
+
+    async fn stale_uploads(
+        &self,
+        before: SystemTime,
+    ) -> paastel_app::Result<Vec<UploadId>> {
+        let mut stale = Vec::new();
+        let mut entries = match fs::read_dir(self.root.join(UPLOADS_DIR)).await
+        {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(stale),
+            Err(e) => return Err(LocalError::from(e).into()),
+        };
+        while let Some(entry) =
+            entries.next_entry().await.map_err(LocalError::from)?
+        {
+            let Some(id) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse::<UploadId>().ok())
+            else {
+                continue;
+            };
+            if self.upload_modified(&entry.path()).await? < before {
+                stale.push(id);
+            }
+        }
+        Ok(stale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures::stream;
+
+    use super::*;
+
+    fn body(chunks: &[&'static [u8]]) -> BlobStream {
+        let chunks: Vec<std::io::Result<Bytes>> =
+            chunks.iter().map(|c| Ok(Bytes::from_static(c))).collect();
+        Box::pin(stream::iter(chunks))
+    }
+
+    #[tokio::test]
+    async fn put_get_head_delete() -> paastel_app::Result<()> {
+        let dir = tempfile::tempdir().map_err(LocalError::from)?;
+        let store = LocalAdapter::new(dir.path()).await?;
+        let id = BlobId::generate();
+
+        let size = store.put(&id, body(&[b"hello ", b"world"])).await?;
+        assert_eq!(size, 11);
+        assert_eq!(store.head(&id).await?.size(), 11);
+
+        let content: Vec<Bytes> = store
+            .get(&id)
+            .await?
+            .try_collect()
+            .await
+            .map_err(LocalError::from)?;
+        assert_eq!(content.concat(), b"hello world");
+
+        assert_eq!(store.list().await?, vec![BlobInfo::new(id.clone(), 11)]);
+        assert!(store.source_url(&id).await?.starts_with("file:///"));
+
+        store.delete(&id).await?;
+        assert!(matches!(
+            store.head(&id).await,
+            Err(paastel_app::Error::BlobNotFound)
+        ));
+        assert!(matches!(
+            store.get(&id).await,
+            Err(paastel_app::Error::BlobNotFound)
+        ));
+        assert!(store.list().await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn put_failed_body() -> paastel_app::Result<()> {
+        let dir = tempfile::tempdir().map_err(LocalError::from)?;
+        let store = LocalAdapter::new(dir.path()).await?;
+        let id = BlobId::generate();
+
+        let body: BlobStream = Box::pin(stream::iter(vec![
+            Ok(Bytes::from_static(b"hello")),
+            Err(std::io::Error::other("connection reset")),
+        ]));
+        assert!(store.put(&id, body).await.is_err());
+        assert!(store.list().await?.is_empty());
+        let mut entries = fs::read_dir(dir.path()).await.unwrap();
+        assert!(entries.next_entry().await.unwrap().is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resumable_upload() -> paastel_app::Result<()> {
+        let dir = tempfile::tempdir().map_err(LocalError::from)?;
+        let store = LocalAdapter::new(dir.path()).await?;
+        let upload = Upload::new(
+            UploadId::generate(),
+            "my-app".parse()?,
+            "paastel-space".parse()?,
+            "a".repeat(64).parse()?,
+            paastel_app::ArchiveFormat::TarGz,
+            11,
+        );
+        let id = upload.id();
+        store.create_upload(&upload).await?;
+
+        assert_eq!(store.put_part(id, 0, body(&[b"hello "])).await?, 6);
+        let failed: BlobStream = Box::pin(stream::iter(vec![
+            Ok(Bytes::from_static(b"wor")),
+            Err(std::io::Error::other("connection reset")),
+        ]));
+        assert!(store.put_part(id, 6, failed).await.is_err());
+        assert_eq!(store.find_upload(id).await?.received(), 6);
+
+        store.put_part(id, 6, body(&[b"world"])).await?;
+        let found = store.find_upload(id).await?;
+        assert_eq!(found, upload.clone().with_received(11));
+
+        let content: Vec<Bytes> = store
+            .read_parts(id)
+            .await?
+            .try_collect()
+            .await
+            .map_err(LocalError::from)?;
+        assert_eq!(content.concat(), b"hello world");
+        // NOTE: uploads are not blobs
+        assert!(store.list().await?.is_empty());
+
+        store.delete_upload(id).await?;
+        assert!(matches!(
+            store.find_upload(id).await,
+            Err(paastel_app::Error::UploadNotFound)
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn parallel_parts_same_offset() -> paastel_app::Result<()> {
+        let dir = tempfile::tempdir().map_err(LocalError::from)?;
+        let store = LocalAdapter::new(dir.path()).await?;
+        let upload = Upload::new(
+            UploadId::generate(),
+            "my-app".parse()?,
+            "paastel-space".parse()?,
+            "a".repeat(64).parse()?,
+            paastel_app::ArchiveFormat::TarGz,
+            11,
+        );
+        let id = upload.id();
+        store.create_upload(&upload).await?;
+
+        let (first, second) = tokio::join!(
+            store.put_part(id, 0, body(&[b"hello ", b"world"])),
+            store.put_part(id, 0, body(&[b"hello ", b"world"])),
+        );
+        assert_eq!((first?, second?), (11, 11));
+        assert_eq!(store.find_upload(id).await?.received(), 11);
+        let content: Vec<Bytes> = store
+            .read_parts(id)
+            .await?
+            .try_collect()
+            .await
+            .map_err(LocalError::from)?;
+        assert_eq!(content.concat(), b"hello world");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stale_uploads() -> paastel_app::Result<()> {
+        let dir = tempfile::tempdir().map_err(LocalError::from)?;
+        let store = LocalAdapter::new(dir.path()).await?;
+        assert!(store.stale_uploads(SystemTime::now()).await?.is_empty());
+
+        let upload = Upload::new(
+            UploadId::generate(),
+            "my-app".parse()?,
+            "paastel-space".parse()?,
+            "a".repeat(64).parse()?,
+            paastel_app::ArchiveFormat::TarGz,
+            11,
+        );
+        store.create_upload(&upload).await?;
+        store.put_part(upload.id(), 0, body(&[b"hello "])).await?;
+
+        let written = SystemTime::now() - std::time::Duration::from_secs(60);
+        assert!(store.stale_uploads(written).await?.is_empty());
+        let later = SystemTime::now() + std::time::Duration::from_secs(60);
+        assert_eq!(
+            store.stale_uploads(later).await?,
+            vec![upload.id().clone()]
+        );
+
+        Ok(())
+    }
+}
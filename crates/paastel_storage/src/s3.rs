@@ -12,17 +12,70 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-// use async_trait::async_trait;
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
 use aws_sdk_s3::{
-    error::SdkError, operation::put_object::PutObjectError,
-    primitives::ByteStream, Client,
+    error::{DisplayErrorContext, SdkError},
+    presigning::PresigningConfig,
+    primitives::ByteStream,
+    types::{CompletedMultipartUpload, CompletedPart, Object},
+    Client,
 };
-use aws_smithy_runtime_api::client::orchestrator::HttpResponse;
 use derive_new::new;
+use futures::{StreamExt, TryStreamExt};
+use paastel_app::{
+    BlobId, BlobInfo, BlobStream, OutgoingBlobStorePort,
+    OutgoingUploadStorePort, Upload, UploadId,
+};
+
+use crate::upload::{self, UploadRecord, UPLOAD_FILE};
+
+/// Size of multipart upload parts, S3 requires at least 5 MB except last one
+const PART_SIZE: usize = 8 * 1024 * 1024; // 8 MB
+
+/// Prefix of blob keys on bucket
+const BLOB_PREFIX: &str = "blobs";
+
+/// Prefix of resumable uploads, `uploads/{id}/` holds metadata and parts
+const UPLOAD_PREFIX: &str = "uploads";
+
+/// Lifetime of presigned urls given to staging jobs
+const SOURCE_URL_EXPIRES_IN: Duration = Duration::from_secs(60 * 60);
 
 #[derive(Debug, thiserror::Error)]
-pub enum S3Error {}
+pub enum S3Error {
+    #[error("s3 request failed {0}")]
+    Request(String),
+    #[error("read body failed {0}")]
+    Body(#[from] std::io::Error),
+    #[error("missing multipart upload id")]
+    MissingUploadId,
+    #[error("json error {0}")]
+    Json(#[from] serde_json::Error),
+}
 
+impl<E, R> From<SdkError<E, R>> for S3Error
+where
+    E: std::error::Error + 'static,
+    R: Debug,
+{
+    fn from(e: SdkError<E, R>) -> Self {
+        Self::Request(DisplayErrorContext(e).to_string())
+    }
+}
+
+impl From<S3Error> for paastel_app::Error {
+    fn from(e: S3Error) -> Self {
+        paastel_app::Error::Storage(e.to_string())
+    }
+}
+
+#[derive(Clone)]
 pub struct S3Adapter {
     bucket: String,
     object: S3Object,
@@ -36,6 +89,17 @@ impl S3Adapter {
         }
     }
 
+    /// Build client from standard aws environment (`AWS_ENDPOINT_URL`,
+    /// `AWS_REGION`, credentials), path style addressing is forced to keep
+    /// MinIO compatible
+    pub async fn from_env(bucket: impl Into<String>) -> Self {
+        let sdk_config = aws_config::load_from_env().await;
+        let config = aws_sdk_s3::config::Builder::from(&sdk_config)
+            .force_path_style(true)
+            .build();
+        Self::new(Client::from_conf(config), bucket)
+    }
+
     pub fn bucket(&self) -> &str {
         self.bucket.as_str()
     }
@@ -43,9 +107,191 @@ impl S3Adapter {
     pub fn bucket_mut(&mut self) -> &mut str {
         self.bucket.as_mut_str()
     }
+
+    fn key(id: &BlobId) -> String {
+        format!("{BLOB_PREFIX}/{id}")
+    }
+
+    fn upload_prefix(id: &UploadId) -> String {
+        format!("{UPLOAD_PREFIX}/{id}/")
+    }
+
+    /// Offset and size of stored parts of upload
+    async fn upload_parts(
+        &self,
+        id: &UploadId,
+    ) -> Result<Vec<(u64, u64)>, S3Error> {
+        let prefix = Self::upload_prefix(id);
+        let objects = self.object.list(&self.bucket, &prefix).await?;
+        Ok(objects
+            .into_iter()
+            .filter_map(|(key, size)| {
+                let name = key.strip_prefix(&prefix)?;
+                Some((upload::part_offset(name)?, size))
+            })
+            .collect())
+    }
+
+    /// Reverse of `key`, objects outside blob prefix are ignored
+    fn id(key: &str) -> Option<BlobId> {
+        key.strip_prefix(BLOB_PREFIX)?
+            .strip_prefix('/')?
+            .parse::<BlobId>()
+            .ok()
+    }
 }
 
-#[derive(new)]
+#[async_trait]
+impl OutgoingBlobStorePort for S3Adapter {
+    async fn put(
+        &self,
+        id: &BlobId,
+        body: BlobStream,
+    ) -> paastel_app::Result<u64> {
+        let size = self
+            .object
+            .save_stream(&self.bucket, &Self::key(id), body)
+            .await?;
+        Ok(size)
+    }
+
+    async fn get(&self, id: &BlobId) -> paastel_app::Result<BlobStream> {
+        let body = self
+            .object
+            .get(&self.bucket, &Self::key(id))
+            .await?
+            .ok_or(paastel_app::Error::BlobNotFound)?;
+        Ok(byte_stream_to_blob(body))
+    }
+
+    async fn head(&self, id: &BlobId) -> paastel_app::Result<BlobInfo> {
+        let size = self
+            .object
+            .head(&self.bucket, &Self::key(id))
+            .await?
+            .ok_or(paastel_app::Error::BlobNotFound)?;
+        Ok(BlobInfo::new(id.clone(), size))
+    }
+
+    async fn delete(&self, id: &BlobId) -> paastel_app::Result<()> {
+        self.object.delete(&self.bucket, &Self::key(id)).await?;
+        Ok(())
+    }
+
+    async fn list(&self) -> paastel_app::Result<Vec<BlobInfo>> {
+        let objects = self.object.list(&self.bucket, BLOB_PREFIX).await?;
+        let blobs = objects
+            .into_iter()
+            .filter_map(|(key, size)| {
+                Some(BlobInfo::new(Self::id(&key)?, size))
+            })
+            .collect();
+        Ok(blobs)
+    }
+
+    /// Presigned url, staging jobs do not need bucket credentials
+    async fn source_url(&self, id: &BlobId) -> paastel_app::Result<String> {
+        let url = self
+            .object
+            .presign(&self.bucket, &Self::key(id), SOURCE_URL_EXPIRES_IN)
+            .await?;
+        Ok(url)
+    }
+}
+
+#[async_trait]
+impl OutgoingUploadStorePort for S3Adapter {
+    async fn create_upload(&self, upload: &Upload) -> paastel_app::Result<()> {
+        let json = UploadRecord::to_json(upload).map_err(S3Error::from)?;
+        let key = format!("{}{UPLOAD_FILE}", Self::upload_prefix(upload.id()));
+        self.object.save(&self.bucket, &key, json).await?;
+        Ok(())
+    }
+
+    async fn find_upload(&self, id: &UploadId) -> paastel_app::Result<Upload> {
+        let key = format!("{}{UPLOAD_FILE}", Self::upload_prefix(id));
+        let body = self
+            .object
+            .get(&self.bucket, &key)
+            .await?
+            .ok_or(paastel_app::Error::UploadNotFound)?;
+        let json = body
+            .collect()
+            .await
+            .map_err(|e| S3Error::Request(e.to_string()))?
+            .into_bytes();
+        let parts = self.upload_parts(id).await?;
+        UploadRecord::from_json(&json, &parts)
+    }
+
+    /// Body fully read before object is written, failed parts are not kept
+    async fn put_part(
+        &self,
+        id: &UploadId,
+        offset: u64,
+        body: BlobStream,
+    ) -> paastel_app::Result<u64> {
+        let key =
+            format!("{}{}", Self::upload_prefix(id), upload::part_name(offset));
+        let size = self.object.save_stream(&self.bucket, &key, body).await?;
+        Ok(size)
+    }
+
+    async fn read_parts(
+        &self,
+        id: &UploadId,
+    ) -> paastel_app::Result<BlobStream> {
+        let prefix = Self::upload_prefix(id);
+        let keys: Vec<_> = upload::contiguous(self.upload_parts(id).await?)
+            .into_iter()
+            .map(|(offset, _)| format!("{prefix}{}", upload::part_name(offset)))
+            .collect();
+        // NOTE: parts are requested one after another, once previous is read
+        let object = self.object.clone();
+        let bucket = self.bucket.clone();
+        let body = futures::stream::iter(keys)
+            .then(move |key| {
+                let object = object.clone();
+                let bucket = bucket.clone();
+                async move {
+                    let body = object
+                        .get(&bucket, &key)
+                        .await
+                        .map_err(std::io::Error::other)?
+                        .ok_or_else(|| {
+                            std::io::Error::other(format!("missing part {key}"))
+                        })?;
+                    Ok::<_, std::io::Error>(byte_stream_to_blob(body))
+                }
+            })
+            .try_flatten();
+        Ok(Box::pin(body))
+    }
+
+    async fn delete_upload(&self, id: &UploadId) -> paastel_app::Result<()> {
+        let objects = self
+            .object
+            .list(&self.bucket, &Self::upload_prefix(id))
+            .await?;
+        for (key, _) in objects {
+            self.object.delete(&self.bucket, &key).await?;
+        }
+        Ok(())
+    }
+
+    async fn stale_uploads(
+        &self,
+        before: SystemTime,
+    ) -> paastel_app::Result<Vec<UploadId>> {
+        let objects = self
+            .object
+            .list_modified(&self.bucket, &format!("{UPLOAD_PREFIX}/"))
+            .await?;
+        Ok(stale_uploads(objects, before))
+    }
+}
+
+#[derive(Clone, new)]
 pub struct S3Object {
     client: Client,
 }
@@ -56,7 +302,7 @@ impl S3Object {
         bucket: &str,
         key: &str,
         body: Vec<u8>,
-    ) -> Result<(), SdkError<PutObjectError, HttpResponse>> {
+    ) -> Result<(), S3Error> {
         self.client
             .put_object()
             .bucket(bucket)
@@ -66,4 +312,339 @@ impl S3Object {
             .await?;
         Ok(())
     }
+
+    /// Save body part by part, only one part is kept in memory
+    async fn save_stream(
+        &self,
+        bucket: &str,
+        key: &str,
+        mut body: BlobStream,
+    ) -> Result<u64, S3Error> {
+        let first = read_part(&mut body).await?;
+        if first.len() < PART_SIZE {
+            let size = first.len() as u64;
+            self.save(bucket, key, first).await?;
+            return Ok(size);
+        }
+
+        let upload = self
+            .client
+            .create_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await?;
+        let upload_id = upload
+            .upload_id()
+            .ok_or(S3Error::MissingUploadId)?
+            .to_string();
+
+        let result = self
+            .save_parts(bucket, key, &upload_id, first, &mut body)
+            .await;
+        if result.is_err() {
+            if let Err(e) = self
+                .client
+                .abort_multipart_upload()
+                .bucket(bucket)
+                .key(key)
+                .upload_id(&upload_id)
+                .send()
+                .await
+            {
+                tracing::warn!(key, "failed abort multipart upload {e}");
+            }
+        }
+        result
+    }
+
+    async fn save_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        first: Vec<u8>,
+        body: &mut BlobStream,
+    ) -> Result<u64, S3Error> {
+        let mut completed = Vec::new();
+        let mut size = 0;
+        let mut part = first;
+        let mut part_number = 1;
+
+        while !part.is_empty() {
+            let last = part.len() < PART_SIZE;
+            size += part.len() as u64;
+
+            let output = self
+                .client
+                .upload_part()
+                .bucket(bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(ByteStream::from(part))
+                .send()
+                .await?;
+            completed.push(
+                CompletedPart::builder()
+                    .set_e_tag(output.e_tag)
+                    .part_number(part_number)
+                    .build(),
+            );
+
+            if last {
+                break;
+            }
+            part_number += 1;
+            part = read_part(body).await?;
+        }
+
+        self.client
+            .complete_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(completed))
+                    .build(),
+            )
+            .send()
+            .await?;
+        Ok(size)
+    }
+
+    async fn get(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> Result<Option<ByteStream>, S3Error> {
+        match self
+            .client
+            .get_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(output) => Ok(Some(output.body)),
+            Err(e)
+                if e.as_service_error().is_some_and(|e| e.is_no_such_key()) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Return size of object
+    async fn head(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> Result<Option<u64>, S3Error> {
+        match self
+            .client
+            .head_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(output) => {
+                Ok(Some(output.content_length().unwrap_or_default() as u64))
+            }
+            Err(e)
+                if e.as_service_error().is_some_and(|e| e.is_not_found()) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Return key and size of objects under prefix
+    async fn list(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, u64)>, S3Error> {
+        Ok(self
+            .list_objects(bucket, prefix)
+            .await?
+            .into_iter()
+            .filter_map(|object| {
+                let size = object.size().unwrap_or_default() as u64;
+                Some((object.key?, size))
+            })
+            .collect())
+    }
+
+    /// Return key and last modification of objects under prefix
+    async fn list_modified(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, SystemTime)>, S3Error> {
+        Ok(self
+            .list_objects(bucket, prefix)
+            .await?
+            .into_iter()
+            .filter_map(|object| {
+                let modified = object.last_modified?.try_into().ok()?;
+                Some((object.key?, modified))
+            })
+            .collect())
+    }
+
+    async fn list_objects(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Vec<Object>, S3Error> {
+        let mut objects = Vec::new();
+        let mut pages = self
+            .client
+            .list_objects_v2()
+            .bucket(bucket)
+            .prefix(prefix)
+            .into_paginator()
+            .send();
+        while let Some(page) = pages.next().await {
+            objects.extend(page?.contents.unwrap_or_default());
+        }
+        Ok(objects)
+    }
+
+    async fn presign(
+        &self,
+        bucket: &str,
+        key: &str,
+        expires_in: Duration,
+    ) -> Result<String, S3Error> {
+        let config = PresigningConfig::expires_in(expires_in)
+            .map_err(|e| S3Error::Request(e.to_string()))?;
+        let request = self
+            .client
+            .get_object()
+            .bucket(bucket)
+            .key(key)
+            .presigned(config)
+            .await?;
+        Ok(request.uri().to_string())
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<(), S3Error> {
+        self.client
+            .delete_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await?;
+        Ok(())
+    }
+}
+
+/// Uploads whose objects were all modified before, from upload keys
+fn stale_uploads(
+    objects: Vec<(String, SystemTime)>,
+    before: SystemTime,
+) -> Vec<UploadId> {
+    let mut modified = BTreeMap::<String, SystemTime>::new();
+    for (key, time) in objects {
+        let Some((id, _)) = key
+            .strip_prefix(UPLOAD_PREFIX)
+            .and_then(|key| key.strip_prefix('/'))
+            .and_then(|key| key.split_once('/'))
+        else {
+            continue;
+        };
+        let last = modified.entry(id.to_string()).or_insert(time);
+        *last = (*last).max(time);
+    }
+    modified
+        .into_iter()
+        .filter(|(_, time)| *time < before)
+        .filter_map(|(id, _)| id.parse().ok())
+        .collect()
+}
+
+fn byte_stream_to_blob(body: ByteStream) -> BlobStream {
+    Box::pin(futures::stream::try_unfold(body, |mut body| async move {
+        match body.next().await {
+            Some(Ok(chunk)) => Ok(Some((chunk, body))),
+            Some(Err(e)) => Err(std::io::Error::other(e)),
+            None => Ok(None),
+        }
+    }))
+}
+
+/// Read chunks until part size is reached or body ends
+async fn read_part(body: &mut BlobStream) -> Result<Vec<u8>, std::io::Error> {
+    let mut part = Vec::with_capacity(PART_SIZE);
+    while part.len() < PART_SIZE {
+        match body.try_next().await? {
+            Some(chunk) => part.extend_from_slice(&chunk),
+            None => break,
+        }
+    }
+    Ok(part)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn read_part_split_body() -> Result<(), std::io::Error> {
+        let chunk = bytes::Bytes::from(vec![0; PART_SIZE / 2 + 1]);
+        let mut body: BlobStream = Box::pin(stream::iter(
+            (0..3).map(|_| Ok(chunk.clone())).collect::<Vec<_>>(),
+        ));
+
+        assert_eq!(read_part(&mut body).await?.len(), PART_SIZE + 2);
+        assert_eq!(read_part(&mut body).await?.len(), chunk.len());
+        assert!(read_part(&mut body).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn blob_key() {
+        let id = BlobId::generate();
+        assert_eq!(S3Adapter::id(&S3Adapter::key(&id)), Some(id));
+        assert_eq!(S3Adapter::id("other/key"), None);
+    }
+
+    #[test]
+    fn upload_prefix_outside_blobs() {
+        let id = UploadId::generate();
+        let key = format!(
+            "{}{}",
+            S3Adapter::upload_prefix(&id),
+            upload::part_name(0)
+        );
+        assert_eq!(S3Adapter::id(&key), None);
+    }
+
+    #[test]
+    fn stale_uploads_by_last_part() {
+        let now = SystemTime::now();
+        let old = now - Duration::from_secs(60);
+        let (stale, active) = (UploadId::generate(), UploadId::generate());
+        let key = |id: &UploadId, name: &str| {
+            format!("{}{name}", S3Adapter::upload_prefix(id))
+        };
+        let objects = vec![
+            (key(&stale, UPLOAD_FILE), old),
+            (key(&stale, &upload::part_name(0)), old),
+            (key(&active, UPLOAD_FILE), old),
+            (key(&active, &upload::part_name(0)), now),
+            (format!("{UPLOAD_PREFIX}/orphan"), old),
+        ];
+
+        assert_eq!(stale_uploads(objects, now), vec![stale]);
+    }
 }
@@ -0,0 +1,113 @@
+// Copyright (c) 2024 Murilo Ijanc' <mbsd@m0x.ru>
+
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use paastel_app::Upload;
+use serde::{Deserialize, Serialize};
+
+/// Name of upload metadata, next to its parts
+pub(crate) const UPLOAD_FILE: &str = "upload.json";
+
+/// Upload metadata as stored, bytes received are computed from parts
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct UploadRecord {
+    id: String,
+    name: String,
+    namespace: String,
+    digest: String,
+    format: String,
+    size: u64,
+}
+
+impl UploadRecord {
+    pub fn to_json(upload: &Upload) -> Result<Vec<u8>, serde_json::Error> {
+        serde_json::to_vec(&Self {
+            id: upload.id().to_string(),
+            name: upload.name().to_string(),
+            namespace: upload.namespace().to_string(),
+            digest: upload.digest().to_string(),
+            format: upload.format().to_string(),
+            size: upload.size(),
+        })
+    }
+
+    pub fn from_json(
+        json: &[u8],
+        parts: &[(u64, u64)],
+    ) -> paastel_app::Result<Upload> {
+        let record: Self = serde_json::from_slice(json)
+            .map_err(|e| paastel_app::Error::Storage(e.to_string()))?;
+        Ok(Upload::new(
+            record.id.parse()?,
+            record.name.parse()?,
+            record.namespace.parse()?,
+            record.digest.parse()?,
+            record.format.parse()?,
+            record.size,
+        )
+        .with_received(received(parts)))
+    }
+}
+
+/// Name of part starting at offset, zero padded so names sort by offset
+pub(crate) fn part_name(offset: u64) -> String {
+    format!("{offset:020}")
+}
+
+/// Reverse of `part_name`
+pub(crate) fn part_offset(name: &str) -> Option<u64> {
+    name.parse().ok().filter(|_| name.len() == 20)
+}
+
+/// Offset and size of parts following each other from start, sorted
+pub(crate) fn contiguous(mut parts: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    parts.sort();
+    let mut end = 0;
+    parts
+        .into_iter()
+        .take_while(|(offset, size)| {
+            let next = *offset == end;
+            end += size;
+            next
+        })
+        .collect()
+}
+
+/// Bytes received, only parts following each other from start count
+pub(crate) fn received(parts: &[(u64, u64)]) -> u64 {
+    contiguous(parts.to_vec())
+        .iter()
+        .map(|(_, size)| size)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn part_names_sort_by_offset() {
+        assert_eq!(part_name(42), "00000000000000000042");
+        assert_eq!(part_offset(&part_name(42)), Some(42));
+        assert_eq!(part_offset("42"), None);
+        assert!(part_name(9) < part_name(10));
+    }
+
+    #[test]
+    fn received_contiguous_parts() {
+        assert_eq!(received(&[]), 0);
+        assert_eq!(received(&[(5, 5), (0, 5)]), 10);
+        assert_eq!(received(&[(0, 5), (8, 5)]), 5);
+        assert_eq!(received(&[(3, 5)]), 0);
+    }
+}